@@ -0,0 +1,773 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use crate::animator::interpolate::{interpolate, AnimatedValue};
+use crate::animator::timing::TimingFunction;
+use crate::common::document::document::Document;
+use crate::common::document::node::{NodeId, NodeType};
+use crate::common::document::style::{Keyframe, StyleProperty, StylePropertyList, StyleValue};
+
+pub mod interpolate;
+pub mod timing;
+
+// The animator runs CSS transitions and keyframe animations. Every frame, `tick()` returns the current value of
+// every animated property. Compositor friendly properties (opacity and transform) are applied to the layer of the
+// element, which is promoted for as long as it animates, so the tiles don't need to be painted again. All other
+// values are written into the styles of the document, which then goes through the regular relayout and repaint path.
+
+/// Source of the current time of the animator, in milliseconds
+pub trait Clock: Send + Sync {
+    fn now(&self) -> f64;
+}
+
+/// Clock that follows the wall clock, starting at 0 when created
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+/// Clock that only moves when told to. This makes animations deterministic, which is what tests need.
+pub struct ManualClock {
+    time: RwLock<f64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self { time: RwLock::new(0.0) }
+    }
+
+    /// Moves the clock forward by the given number of milliseconds
+    pub fn advance(&self, ms: f64) {
+        *self.time.write().expect("Failed to lock clock") += ms;
+    }
+
+    /// Sets the clock to the given time in milliseconds
+    pub fn set(&self, ms: f64) {
+        *self.time.write().expect("Failed to lock clock") = ms;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        *self.time.read().expect("Failed to lock clock")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationDirection {
+    Normal,
+    Reverse,
+    Alternate,
+    AlternateReverse,
+}
+
+impl AnimationDirection {
+    fn parse(value: &str) -> Self {
+        match value.trim() {
+            "reverse" => AnimationDirection::Reverse,
+            "alternate" => AnimationDirection::Alternate,
+            "alternate-reverse" => AnimationDirection::AlternateReverse,
+            _ => AnimationDirection::Normal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillMode {
+    None,
+    Forwards,
+    Backwards,
+    Both,
+}
+
+impl FillMode {
+    fn parse(value: &str) -> Self {
+        match value.trim() {
+            "forwards" => FillMode::Forwards,
+            "backwards" => FillMode::Backwards,
+            "both" => FillMode::Both,
+            _ => FillMode::None,
+        }
+    }
+
+    fn forwards(&self) -> bool {
+        matches!(self, FillMode::Forwards | FillMode::Both)
+    }
+
+    fn backwards(&self) -> bool {
+        matches!(self, FillMode::Backwards | FillMode::Both)
+    }
+}
+
+/// Current value of an animated property of an element
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimatedProperty {
+    pub node_id: NodeId,
+    pub property: StyleProperty,
+    pub value: AnimatedValue,
+}
+
+/// All animated values of a single frame
+#[derive(Clone, Debug, Default)]
+pub struct AnimationFrame {
+    pub values: Vec<AnimatedProperty>,
+}
+
+impl AnimationFrame {
+    /// Returns true when any value must be written into the styles of the document
+    pub fn has_style_values(&self) -> bool {
+        self.values.iter().any(|v| !v.value.is_composited())
+    }
+}
+
+/// Keyframes of a single property within an animation
+struct Track {
+    property: StyleProperty,
+    /// Keyframe offsets (0.0 - 1.0) with their value, sorted on offset
+    keyframes: Vec<(f64, StyleValue)>,
+    /// Value of the property when the animation is not running. None when the element does not have the style.
+    base: Option<StyleValue>,
+}
+
+struct Animation {
+    node_id: NodeId,
+    name: String,
+    tracks: Vec<Track>,
+    start: f64,
+    duration: f64,
+    delay: f64,
+    /// Number of iterations, infinite for `animation-iteration-count: infinite`
+    iterations: f64,
+    direction: AnimationDirection,
+    fill_mode: FillMode,
+    timing: TimingFunction,
+    finished: bool,
+}
+
+impl Animation {
+    /// Returns the directed progress (0.0 - 1.0) within the keyframes at the given time, or None when the
+    /// animation has no effect at that time.
+    fn progress(&self, now: f64) -> Option<f64> {
+        let elapsed = now - self.start - self.delay;
+        let active_duration = self.duration * self.iterations;
+
+        let (iteration, progress) = if elapsed < 0.0 {
+            if !self.fill_mode.backwards() {
+                return None;
+            }
+            (0.0, 0.0)
+        } else if elapsed >= active_duration {
+            if !self.fill_mode.forwards() {
+                return None;
+            }
+            // An animation with a whole number of iterations ends at the end of its last iteration
+            if self.iterations.fract() == 0.0 {
+                (self.iterations - 1.0, 1.0)
+            } else {
+                (self.iterations.floor(), self.iterations.fract())
+            }
+        } else if self.duration <= 0.0 {
+            (0.0, 1.0)
+        } else {
+            let iteration = (elapsed / self.duration).floor();
+            (iteration, elapsed / self.duration - iteration)
+        };
+
+        let odd = iteration as u64 % 2 == 1;
+        let reversed = match self.direction {
+            AnimationDirection::Normal => false,
+            AnimationDirection::Reverse => true,
+            AnimationDirection::Alternate => odd,
+            AnimationDirection::AlternateReverse => !odd,
+        };
+
+        Some(if reversed { 1.0 - progress } else { progress })
+    }
+
+    fn is_done(&self, now: f64) -> bool {
+        now - self.start - self.delay >= self.duration * self.iterations
+    }
+
+    /// Returns the values of all tracks at the given progress. The timing function applies to each interval
+    /// between two keyframes.
+    fn sample(&self, progress: f64) -> Vec<AnimatedProperty> {
+        self.tracks.iter().filter_map(|track| {
+            let next = track.keyframes.iter().position(|(offset, _)| *offset > progress)
+                .unwrap_or(track.keyframes.len() - 1)
+                .max(1);
+            let (from_offset, from) = track.keyframes.get(next - 1)?;
+            let (to_offset, to) = track.keyframes.get(next)?;
+
+            let local = if to_offset > from_offset {
+                ((progress - from_offset) / (to_offset - from_offset)).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+
+            Some(AnimatedProperty {
+                node_id: self.node_id,
+                property: track.property.clone(),
+                value: interpolate(&track.property, from, to, self.timing.apply(local)),
+            })
+        }).collect()
+    }
+
+    /// Returns the values the element has without the animation
+    fn base_values(&self) -> Vec<AnimatedProperty> {
+        self.tracks.iter().filter_map(|track| {
+            let base = track.base.as_ref()?;
+            Some(AnimatedProperty {
+                node_id: self.node_id,
+                property: track.property.clone(),
+                value: interpolate(&track.property, base, base, 0.0),
+            })
+        }).collect()
+    }
+}
+
+struct Transition {
+    node_id: NodeId,
+    property: StyleProperty,
+    from: StyleValue,
+    to: StyleValue,
+    start: f64,
+    duration: f64,
+    delay: f64,
+    timing: TimingFunction,
+}
+
+impl Transition {
+    fn value(&self, now: f64) -> AnimatedValue {
+        let elapsed = (now - self.start - self.delay).max(0.0);
+        let progress = if self.duration > 0.0 { (elapsed / self.duration).min(1.0) } else { 1.0 };
+        interpolate(&self.property, &self.from, &self.to, self.timing.apply(progress))
+    }
+
+    fn is_done(&self, now: f64) -> bool {
+        now - self.start - self.delay >= self.duration
+    }
+}
+
+/// Properties that can be transitioned, by their CSS name
+const TRANSITIONABLE: &[(&str, StyleProperty)] = &[
+    ("opacity", StyleProperty::Opacity),
+    ("transform", StyleProperty::Transform),
+    ("color", StyleProperty::Color),
+    ("background-color", StyleProperty::BackgroundColor),
+    ("width", StyleProperty::Width),
+    ("height", StyleProperty::Height),
+    ("font-size", StyleProperty::FontSize),
+    ("margin-top", StyleProperty::MarginTop),
+    ("margin-right", StyleProperty::MarginRight),
+    ("margin-bottom", StyleProperty::MarginBottom),
+    ("margin-left", StyleProperty::MarginLeft),
+    ("padding-top", StyleProperty::PaddingTop),
+    ("padding-right", StyleProperty::PaddingRight),
+    ("padding-bottom", StyleProperty::PaddingBottom),
+    ("padding-left", StyleProperty::PaddingLeft),
+];
+
+/// Runs the transitions and keyframe animations of a document
+pub struct Animator {
+    clock: Arc<dyn Clock>,
+    animations: Vec<Animation>,
+    transitions: Vec<Transition>,
+}
+
+impl Animator {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            animations: Vec::new(),
+            transitions: Vec::new(),
+        }
+    }
+
+    /// Returns true when there are animations or transitions that change over time, so new frames are needed.
+    /// Finished animations that only hold their end value (fill mode forwards) don't count.
+    pub fn is_active(&self) -> bool {
+        let now = self.clock.now();
+        !self.transitions.is_empty() || self.animations.iter().any(|a| !a.finished && !a.is_done(now))
+    }
+
+    /// Returns the elements with an animation or transition of opacity or transform. These elements need their own
+    /// layer, so the animated values can be applied by the compositor.
+    pub fn composited_nodes(&self) -> HashSet<NodeId> {
+        let animations = self.animations.iter()
+            .filter(|a| a.tracks.iter().any(|t| is_composited(&t.property)))
+            .map(|a| a.node_id);
+        let transitions = self.transitions.iter()
+            .filter(|t| is_composited(&t.property))
+            .map(|t| t.node_id);
+
+        animations.chain(transitions).collect()
+    }
+
+    /// Starts the animations (animation-name) of all elements in the document that are not running yet. Running
+    /// animations whose name is no longer in the styles of their element are stopped.
+    pub fn start_animations(&mut self, doc: &Document) {
+        let now = self.clock.now();
+
+        let mut node_ids: Vec<NodeId> = doc.arena.keys().copied().collect();
+        node_ids.sort_by_key(|id| id.to_u64());
+
+        let mut wanted: Vec<(NodeId, String)> = Vec::new();
+        for node_id in node_ids {
+            let Some(NodeType::Element(data)) = doc.get_node_by_id(node_id).map(|n| &n.node_type) else {
+                continue;
+            };
+
+            let names = list(&data.styles, StyleProperty::AnimationName);
+            for (index, name) in names.iter().enumerate() {
+                if name == "none" || name.is_empty() {
+                    continue;
+                }
+                wanted.push((node_id, name.clone()));
+                if self.animations.iter().any(|a| a.node_id == node_id && &a.name == name) {
+                    continue;
+                }
+                let Some(keyframes) = doc.keyframes.get(name) else {
+                    log::warn!("No @keyframes found for animation {}", name);
+                    continue;
+                };
+
+                // Lists of the other animation properties are matched by index, and repeated when shorter
+                let nth = |prop: StyleProperty| {
+                    let values = list(&data.styles, prop);
+                    match values.is_empty() {
+                        true => None,
+                        false => Some(values[index % values.len()].clone()),
+                    }
+                };
+
+                let iterations = match nth(StyleProperty::AnimationIterationCount).as_deref() {
+                    Some("infinite") => f64::INFINITY,
+                    Some(count) => count.parse::<f64>().unwrap_or(1.0).max(0.0),
+                    None => 1.0,
+                };
+
+                self.animations.push(Animation {
+                    node_id,
+                    name: name.clone(),
+                    tracks: tracks(keyframes, &data.styles),
+                    start: now,
+                    duration: nth(StyleProperty::AnimationDuration).map_or(0.0, |d| parse_time(&d)),
+                    delay: nth(StyleProperty::AnimationDelay).map_or(0.0, |d| parse_time(&d)),
+                    iterations,
+                    direction: nth(StyleProperty::AnimationDirection).map_or(AnimationDirection::Normal, |d| AnimationDirection::parse(&d)),
+                    fill_mode: nth(StyleProperty::AnimationFillMode).map_or(FillMode::None, |f| FillMode::parse(&f)),
+                    timing: nth(StyleProperty::AnimationTimingFunction).map_or(TimingFunction::EASE, |t| TimingFunction::parse(&t)),
+                    finished: false,
+                });
+            }
+        }
+
+        // Animations that are removed from the styles end right away and restore their base values on the next tick
+        for animation in &mut self.animations {
+            if !wanted.iter().any(|(node_id, name)| *node_id == animation.node_id && *name == animation.name) {
+                animation.fill_mode = FillMode::None;
+                animation.iterations = 0.0;
+                animation.delay = 0.0;
+            }
+        }
+    }
+
+    /// Starts transitions for all transitionable properties that differ between the old and the new styles of
+    /// the document. A running transition that gets a new end value continues from its current value.
+    pub fn start_transitions(&mut self, old: &Document, new: &Document) {
+        let now = self.clock.now();
+
+        let mut node_ids: Vec<NodeId> = new.arena.keys().copied().collect();
+        node_ids.sort_by_key(|id| id.to_u64());
+
+        for node_id in node_ids {
+            let Some(NodeType::Element(new_data)) = new.get_node_by_id(node_id).map(|n| &n.node_type) else {
+                continue;
+            };
+            let Some(NodeType::Element(old_data)) = old.get_node_by_id(node_id).map(|n| &n.node_type) else {
+                continue;
+            };
+
+            let properties = list(&new_data.styles, StyleProperty::TransitionProperty);
+            for (index, name) in properties.iter().enumerate() {
+                let nth = |prop: StyleProperty| {
+                    let values = list(&new_data.styles, prop);
+                    match values.is_empty() {
+                        true => None,
+                        false => Some(values[index % values.len()].clone()),
+                    }
+                };
+
+                let duration = nth(StyleProperty::TransitionDuration).map_or(0.0, |d| parse_time(&d));
+                if duration <= 0.0 {
+                    continue;
+                }
+                let delay = nth(StyleProperty::TransitionDelay).map_or(0.0, |d| parse_time(&d));
+                let timing = nth(StyleProperty::TransitionTimingFunction).map_or(TimingFunction::EASE, |t| TimingFunction::parse(&t));
+
+                for (_, property) in TRANSITIONABLE.iter().filter(|(n, _)| name == "all" || name == n) {
+                    // Animations override transitions of the same property
+                    let is_animated = self.animations.iter()
+                        .any(|a| a.node_id == node_id && a.tracks.iter().any(|t| t.property == *property));
+                    if is_animated {
+                        continue;
+                    }
+
+                    let Some(to) = new_data.styles.get_property(property.clone()) else {
+                        continue;
+                    };
+
+                    let running = self.transitions.iter().position(|t| t.node_id == node_id && t.property == *property);
+                    let from = match running {
+                        Some(index) => {
+                            if self.transitions[index].to == *to {
+                                continue;
+                            }
+                            let transition = self.transitions.remove(index);
+                            transition.value(now).to_style_value()
+                        }
+                        None => match old_data.styles.get_property(property.clone()) {
+                            Some(from) if from != to => from.clone(),
+                            _ => continue,
+                        },
+                    };
+
+                    self.transitions.push(Transition {
+                        node_id,
+                        property: property.clone(),
+                        from,
+                        to: to.clone(),
+                        start: now,
+                        duration,
+                        delay,
+                        timing,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns the current values of all animated properties. Animations and transitions that have ended emit
+    /// their final value once, and are then removed, except animations that keep their end value (fill mode
+    /// forwards).
+    pub fn tick(&mut self) -> AnimationFrame {
+        let now = self.clock.now();
+        let mut frame = AnimationFrame::default();
+
+        for animation in &mut self.animations {
+            let done = animation.is_done(now);
+            match animation.progress(now) {
+                Some(progress) => frame.values.extend(animation.sample(progress)),
+                None if done || animation.fill_mode.backwards() => frame.values.extend(animation.base_values()),
+                None => {}
+            }
+            animation.finished = done;
+        }
+        self.animations.retain(|a| !a.finished || a.fill_mode.forwards());
+
+        for transition in &self.transitions {
+            frame.values.push(AnimatedProperty {
+                node_id: transition.node_id,
+                property: transition.property.clone(),
+                value: transition.value(now),
+            });
+        }
+        self.transitions.retain(|t| !t.is_done(now));
+
+        frame
+    }
+}
+
+/// Creates a track for every property in the keyframes. Keyframes at 0% and 100% that are missing are filled in
+/// with the value the element has without the animation.
+fn tracks(keyframes: &[Keyframe], styles: &StylePropertyList) -> Vec<Track> {
+    let mut tracks: Vec<Track> = Vec::new();
+    for keyframe in keyframes {
+        let mut properties: Vec<(&StyleProperty, &StyleValue)> = keyframe.styles.properties.iter().collect();
+        properties.sort_by_key(|(prop, _)| format!("{:?}", prop));
+
+        for (prop, value) in properties {
+            let offset = keyframe.offset as f64;
+            match tracks.iter_mut().find(|t| t.property == *prop) {
+                Some(track) => track.keyframes.push((offset, value.clone())),
+                None => tracks.push(Track {
+                    property: prop.clone(),
+                    keyframes: vec![(offset, value.clone())],
+                    base: styles.get_property(prop.clone()).cloned().or_else(|| initial_value(prop)),
+                }),
+            }
+        }
+    }
+
+    for track in &mut tracks {
+        track.keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let first = track.keyframes[0].1.clone();
+        let last = track.keyframes[track.keyframes.len() - 1].1.clone();
+
+        if track.keyframes[0].0 > 0.0 {
+            track.keyframes.insert(0, (0.0, track.base.clone().unwrap_or(first)));
+        }
+        if track.keyframes[track.keyframes.len() - 1].0 < 1.0 {
+            track.keyframes.push((1.0, track.base.clone().unwrap_or(last)));
+        }
+    }
+
+    tracks
+}
+
+/// Initial value of the composited properties, which are often not in the styles of an element
+fn initial_value(prop: &StyleProperty) -> Option<StyleValue> {
+    match prop {
+        StyleProperty::Opacity => Some(StyleValue::Number(1.0)),
+        StyleProperty::Transform => Some(StyleValue::Keyword("none".to_string())),
+        _ => None,
+    }
+}
+
+/// Returns the comma separated list of a keyword style, like "opacity, transform"
+fn list(styles: &StylePropertyList, prop: StyleProperty) -> Vec<String> {
+    match styles.get_property(prop) {
+        Some(StyleValue::Keyword(value)) => split_list(value),
+        _ => Vec::new(),
+    }
+}
+
+/// Splits a comma separated list, but not on the commas inside functions like cubic-bezier()
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+/// Returns true when the property can be animated by the compositor
+fn is_composited(prop: &StyleProperty) -> bool {
+    matches!(prop, StyleProperty::Opacity | StyleProperty::Transform)
+}
+
+/// Parses a CSS time ("0.5s" or "200ms") into milliseconds
+fn parse_time(value: &str) -> f64 {
+    let value = value.trim();
+    if let Some(ms) = value.strip_suffix("ms") {
+        return ms.parse().unwrap_or(0.0);
+    }
+    if let Some(s) = value.strip_suffix('s') {
+        return s.parse::<f64>().map_or(0.0, |s| s * 1000.0);
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::style::Unit;
+
+    fn keyword(value: &str) -> StyleValue {
+        StyleValue::Keyword(value.to_string())
+    }
+
+    fn styles(props: &[(StyleProperty, StyleValue)]) -> StylePropertyList {
+        let mut styles = StylePropertyList::new();
+        for (prop, value) in props {
+            styles.set_property(prop.clone(), value.clone());
+        }
+        styles
+    }
+
+    /// Document with a single div with the given styles and a "fade" animation from opacity 0 to 1
+    fn document(props: &[(StyleProperty, StyleValue)]) -> (Document, NodeId) {
+        let mut doc = Document::new("https://example.com");
+        let root_id = doc.new_element(None, "DocumentRoot", None, false, None);
+        let div_id = doc.new_element(Some(root_id), "div", None, false, Some(styles(props)));
+        doc.add_child(root_id, div_id);
+        doc.set_root(root_id);
+
+        doc.keyframes.insert("fade".to_string(), vec![
+            Keyframe { offset: 0.0, styles: styles(&[(StyleProperty::Opacity, StyleValue::Number(0.0))]) },
+            Keyframe { offset: 1.0, styles: styles(&[(StyleProperty::Opacity, StyleValue::Number(1.0))]) },
+        ]);
+
+        (doc, div_id)
+    }
+
+    fn opacity(frame: &AnimationFrame) -> Option<f32> {
+        frame.values.iter().find_map(|v| match v.value {
+            AnimatedValue::Opacity(opacity) => Some(opacity),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_animation() {
+        let clock = Arc::new(ManualClock::new());
+        let mut animator = Animator::new(clock.clone());
+
+        let (doc, _) = document(&[
+            (StyleProperty::AnimationName, keyword("fade")),
+            (StyleProperty::AnimationDuration, keyword("1s")),
+            (StyleProperty::AnimationTimingFunction, keyword("linear")),
+            (StyleProperty::AnimationIterationCount, keyword("2")),
+            (StyleProperty::AnimationDirection, keyword("alternate")),
+        ]);
+        animator.start_animations(&doc);
+        assert!(animator.is_active());
+
+        clock.set(250.0);
+        assert_eq!(opacity(&animator.tick()), Some(0.25));
+
+        // The second iteration runs backwards
+        clock.set(1250.0);
+        assert_eq!(opacity(&animator.tick()), Some(0.75));
+
+        // Starting again does not restart a running animation
+        animator.start_animations(&doc);
+        assert_eq!(opacity(&animator.tick()), Some(0.75));
+
+        // Without a fill mode the base value is restored once when done
+        clock.set(2500.0);
+        assert_eq!(opacity(&animator.tick()), Some(1.0));
+        assert!(!animator.is_active());
+        assert!(animator.tick().values.is_empty());
+    }
+
+    #[test]
+    fn test_fill_mode() {
+        let clock = Arc::new(ManualClock::new());
+        let mut animator = Animator::new(clock.clone());
+
+        let (doc, _) = document(&[
+            (StyleProperty::Opacity, StyleValue::Number(0.5)),
+            (StyleProperty::AnimationName, keyword("fade")),
+            (StyleProperty::AnimationDuration, keyword("100ms")),
+            (StyleProperty::AnimationDelay, keyword("100ms")),
+            (StyleProperty::AnimationTimingFunction, keyword("linear")),
+            (StyleProperty::AnimationDirection, keyword("reverse")),
+            (StyleProperty::AnimationFillMode, keyword("both")),
+        ]);
+        animator.start_animations(&doc);
+
+        // Backwards fill holds the first value during the delay, which is the last keyframe when reversed
+        assert_eq!(opacity(&animator.tick()), Some(1.0));
+
+        clock.set(175.0);
+        assert_eq!(opacity(&animator.tick()), Some(0.25));
+
+        // Forwards fill keeps the end value instead of the base value of 0.5
+        clock.set(500.0);
+        assert_eq!(opacity(&animator.tick()), Some(0.0));
+        assert!(!animator.is_active());
+        assert_eq!(opacity(&animator.tick()), Some(0.0));
+    }
+
+    #[test]
+    fn test_transition() {
+        let clock = Arc::new(ManualClock::new());
+        let mut animator = Animator::new(clock.clone());
+
+        let transition = [
+            (StyleProperty::TransitionProperty, keyword("all")),
+            (StyleProperty::TransitionDuration, keyword("200ms")),
+            (StyleProperty::TransitionTimingFunction, keyword("linear")),
+        ];
+        let width = |w: f32| (StyleProperty::Width, StyleValue::Unit(w, Unit::Px));
+
+        let (old, div_id) = document(&[transition[0].clone(), transition[1].clone(), transition[2].clone(), width(100.0)]);
+        let mut new = old.clone();
+        new.set_style(div_id, StyleProperty::Width, StyleValue::Unit(200.0, Unit::Px));
+
+        animator.start_transitions(&old, &old);
+        assert!(!animator.is_active());
+
+        animator.start_transitions(&old, &new);
+        assert!(animator.is_active());
+
+        clock.set(50.0);
+        let frame = animator.tick();
+        assert!(frame.has_style_values());
+        assert_eq!(frame.values[0].value, AnimatedValue::Style(StyleValue::Unit(125.0, Unit::Px)));
+
+        // Going back starts from the current value
+        clock.set(100.0);
+        animator.start_transitions(&new, &old);
+        clock.set(200.0);
+        assert_eq!(animator.tick().values[0].value, AnimatedValue::Style(StyleValue::Unit(125.0, Unit::Px)));
+
+        clock.set(300.0);
+        assert_eq!(animator.tick().values[0].value, AnimatedValue::Style(StyleValue::Unit(100.0, Unit::Px)));
+        assert!(!animator.is_active());
+    }
+
+    #[test]
+    fn test_composited_nodes() {
+        let clock = Arc::new(ManualClock::new());
+        let mut animator = Animator::new(clock.clone());
+
+        let (old, div_id) = document(&[
+            (StyleProperty::TransitionProperty, keyword("all")),
+            (StyleProperty::TransitionDuration, keyword("200ms")),
+            (StyleProperty::Opacity, StyleValue::Number(1.0)),
+            (StyleProperty::Width, StyleValue::Unit(100.0, Unit::Px)),
+        ]);
+
+        // Only transitions of opacity and transform need a layer
+        let mut wider = old.clone();
+        wider.set_style(div_id, StyleProperty::Width, StyleValue::Unit(200.0, Unit::Px));
+        animator.start_transitions(&old, &wider);
+        assert!(animator.composited_nodes().is_empty());
+
+        let mut faded = wider.clone();
+        faded.set_style(div_id, StyleProperty::Opacity, StyleValue::Number(0.5));
+        animator.start_transitions(&wider, &faded);
+        assert_eq!(animator.composited_nodes(), HashSet::from([div_id]));
+
+        clock.set(300.0);
+        animator.tick();
+        assert!(animator.composited_nodes().is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_time("0.5s"), 500.0);
+        assert_eq!(parse_time("200ms"), 200.0);
+        assert_eq!(split_list("opacity 1s cubic-bezier(0, 0, 1, 1), width"), vec!["opacity 1s cubic-bezier(0, 0, 1, 1)", "width"]);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::common::document::style::{Color as StyleColor, StyleProperty, StyleValue};
+use crate::common::geo::Transform;
+use crate::painter::commands::color::Color;
+
+/// Value of an animated property at a certain moment
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimatedValue {
+    /// Opacity, which can be animated by the compositor
+    Opacity(f32),
+    /// Transform in CSS pixels (without the transform origin), which can be animated by the compositor
+    Transform(Transform),
+    /// Any other property. These are written into the styles of the element.
+    Style(StyleValue),
+}
+
+impl AnimatedValue {
+    /// Returns true when the compositor can animate the value without painting the element again
+    pub fn is_composited(&self) -> bool {
+        matches!(self, AnimatedValue::Opacity(_) | AnimatedValue::Transform(_))
+    }
+
+    /// Returns the value as it would be found in the styles of an element
+    pub fn to_style_value(&self) -> StyleValue {
+        match self {
+            AnimatedValue::Opacity(opacity) => StyleValue::Number(*opacity),
+            AnimatedValue::Transform(t) => StyleValue::Keyword(
+                format!("matrix({}, {}, {}, {}, {}, {})", t.a, t.b, t.c, t.d, t.e, t.f)
+            ),
+            AnimatedValue::Style(value) => value.clone(),
+        }
+    }
+}
+
+/// Returns the value of the property at progress t (0.0 is the from value, 1.0 is the to value). Values that
+/// cannot be interpolated, like keywords, flip halfway (discrete animation).
+pub fn interpolate(property: &StyleProperty, from: &StyleValue, to: &StyleValue, t: f64) -> AnimatedValue {
+    match property {
+        StyleProperty::Opacity => {
+            let from = number(from).unwrap_or(1.0);
+            let to = number(to).unwrap_or(1.0);
+            AnimatedValue::Opacity(lerp(from, to, t).clamp(0.0, 1.0) as f32)
+        }
+        StyleProperty::Transform => {
+            let from = parse_transform(&keyword(from)).unwrap_or_default();
+            let to = parse_transform(&keyword(to)).unwrap_or_default();
+            AnimatedValue::Transform(interpolate_transform(&from, &to, t))
+        }
+        _ => AnimatedValue::Style(interpolate_style(from, to, t)),
+    }
+}
+
+fn interpolate_style(from: &StyleValue, to: &StyleValue, t: f64) -> StyleValue {
+    match (from, to) {
+        (StyleValue::Unit(a, unit_a), StyleValue::Unit(b, unit_b)) if unit_a == unit_b => {
+            StyleValue::Unit(lerp(*a as f64, *b as f64, t) as f32, unit_a.clone())
+        }
+        (StyleValue::Number(a), StyleValue::Number(b)) => StyleValue::Number(lerp(*a as f64, *b as f64, t) as f32),
+        (StyleValue::Percentage(a), StyleValue::Percentage(b)) => {
+            StyleValue::Percentage(lerp(*a as f64, *b as f64, t) as f32)
+        }
+        (StyleValue::Color(a), StyleValue::Color(b)) => {
+            let (a, b) = (to_color(a), to_color(b));
+            let channel = |a: f32, b: f32| lerp(a as f64, b as f64, t).clamp(0.0, 1.0);
+            StyleValue::Color(StyleColor::Rgba(
+                (channel(a.r(), b.r()) * 255.0).round() as u8,
+                (channel(a.g(), b.g()) * 255.0).round() as u8,
+                (channel(a.b(), b.b()) * 255.0).round() as u8,
+                channel(a.a(), b.a()) as f32,
+            ))
+        }
+        _ => if t < 0.5 { from.clone() } else { to.clone() },
+    }
+}
+
+fn to_color(color: &StyleColor) -> Color {
+    match color {
+        StyleColor::Named(name) => Color::from_css(name),
+        StyleColor::Rgb(r, g, b) => Color::from_rgb8(*r, *g, *b),
+        StyleColor::Rgba(r, g, b, a) => Color::from_rgba8(*r, *g, *b, (*a * 255.0) as u8),
+    }
+}
+
+fn number(value: &StyleValue) -> Option<f64> {
+    match value {
+        StyleValue::Number(n) => Some(*n as f64),
+        StyleValue::Keyword(k) => k.parse().ok(),
+        _ => None,
+    }
+}
+
+fn keyword(value: &StyleValue) -> String {
+    match value {
+        StyleValue::Keyword(k) => k.clone(),
+        _ => "none".to_string(),
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Parses a CSS transform list like "translate(10px, 20px) rotate(45deg)" or the "matrix(...)" form of
+/// computed styles. Percentages are not supported, as they depend on the size of the element.
+pub fn parse_transform(value: &str) -> Option<Transform> {
+    let value = value.trim();
+    if value.is_empty() || value == "none" {
+        return Some(Transform::IDENTITY);
+    }
+
+    let mut transform = Transform::IDENTITY;
+    let mut rest = value;
+    while !rest.trim().is_empty() {
+        let (name, tail) = rest.split_once('(')?;
+        let (args, tail) = tail.split_once(')')?;
+        rest = tail;
+
+        let args: Vec<&str> = args.split(',').flat_map(|a| a.split_whitespace()).collect();
+        let length = |i: usize| args.get(i).and_then(|a| parse_length(a));
+        let num = |i: usize| args.get(i).and_then(|a| a.parse::<f64>().ok());
+        let angle = |i: usize| args.get(i).and_then(|a| parse_angle(a));
+
+        let function = match name.trim() {
+            "matrix" => Transform {
+                a: num(0)?, b: num(1)?, c: num(2)?, d: num(3)?, e: num(4)?, f: num(5)?,
+            },
+            "translate" => Transform::translate(length(0)?, length(1).unwrap_or(0.0)),
+            "translateX" => Transform::translate(length(0)?, 0.0),
+            "translateY" => Transform::translate(0.0, length(0)?),
+            "scale" => Transform::scale(num(0)?, num(1).or(num(0))?),
+            "scaleX" => Transform::scale(num(0)?, 1.0),
+            "scaleY" => Transform::scale(1.0, num(0)?),
+            "rotate" => Transform::rotate(angle(0)?),
+            "skewX" => Transform { c: angle(0)?.tan(), ..Transform::IDENTITY },
+            "skewY" => Transform { b: angle(0)?.tan(), ..Transform::IDENTITY },
+            _ => return None,
+        };
+
+        // The last function in the list is applied to the element first
+        transform = function.then(&transform);
+    }
+
+    Some(transform)
+}
+
+fn parse_length(value: &str) -> Option<f64> {
+    let value = value.trim();
+    value.strip_suffix("px").unwrap_or(value).parse().ok()
+}
+
+fn parse_angle(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Some(deg) = value.strip_suffix("deg") {
+        return deg.parse::<f64>().ok().map(f64::to_radians);
+    }
+    if let Some(grad) = value.strip_suffix("grad") {
+        return grad.parse::<f64>().ok().map(|g| g * std::f64::consts::PI / 200.0);
+    }
+    if let Some(rad) = value.strip_suffix("rad") {
+        return rad.parse().ok();
+    }
+    if let Some(turn) = value.strip_suffix("turn") {
+        return turn.parse::<f64>().ok().map(|t| t * std::f64::consts::TAU);
+    }
+    // Only a unitless zero is allowed
+    value.parse::<f64>().ok().filter(|v| *v == 0.0)
+}
+
+/// A 2D transform broken up in its parts, so it can be interpolated without distorting rotations
+#[derive(Clone, Copy, Debug)]
+struct Decomposed {
+    translate_x: f64,
+    translate_y: f64,
+    scale_x: f64,
+    scale_y: f64,
+    skew: f64,
+    angle: f64,
+}
+
+/// Decomposes the transform as translate * rotate * skew * scale (CSS transforms, "unmatrix" for 2D)
+fn decompose(t: &Transform) -> Decomposed {
+    let (mut col0, mut col1) = ((t.a, t.b), (t.c, t.d));
+
+    let mut scale_x = col0.0.hypot(col0.1);
+    if scale_x != 0.0 {
+        col0 = (col0.0 / scale_x, col0.1 / scale_x);
+    }
+
+    // A mirrored transform is stored as a negative horizontal scale
+    if t.a * t.d - t.b * t.c < 0.0 {
+        scale_x = -scale_x;
+        col0 = (-col0.0, -col0.1);
+    }
+
+    let mut skew = col0.0 * col1.0 + col0.1 * col1.1;
+    col1 = (col1.0 - col0.0 * skew, col1.1 - col0.1 * skew);
+
+    let scale_y = col1.0.hypot(col1.1);
+    if scale_y != 0.0 {
+        skew /= scale_y;
+    }
+
+    Decomposed {
+        translate_x: t.e,
+        translate_y: t.f,
+        scale_x,
+        scale_y,
+        skew,
+        angle: col0.1.atan2(col0.0),
+    }
+}
+
+fn recompose(d: &Decomposed) -> Transform {
+    Transform::scale(d.scale_x, d.scale_y)
+        .then(&Transform { c: d.skew, ..Transform::IDENTITY })
+        .then(&Transform::rotate(d.angle))
+        .then(&Transform::translate(d.translate_x, d.translate_y))
+}
+
+fn interpolate_transform(from: &Transform, to: &Transform, t: f64) -> Transform {
+    let (a, b) = (decompose(from), decompose(to));
+    recompose(&Decomposed {
+        translate_x: lerp(a.translate_x, b.translate_x, t),
+        translate_y: lerp(a.translate_y, b.translate_y, t),
+        scale_x: lerp(a.scale_x, b.scale_x, t),
+        scale_y: lerp(a.scale_y, b.scale_y, t),
+        skew: lerp(a.skew, b.skew, t),
+        angle: lerp(a.angle, b.angle, t),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::style::Unit;
+    use crate::common::geo::Coordinate;
+
+    fn assert_transform_eq(a: &Transform, b: &Transform) {
+        let (a, b) = ([a.a, a.b, a.c, a.d, a.e, a.f], [b.a, b.b, b.c, b.d, b.e, b.f]);
+        assert!(a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-9), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_parse_transform() {
+        assert_eq!(parse_transform("none"), Some(Transform::IDENTITY));
+        assert_eq!(parse_transform("matrix(1, 0, 0, 1, 10, 20)"), Some(Transform::translate(10.0, 20.0)));
+
+        // The rotation is applied first, the translation after it
+        let transform = parse_transform("translateX(10px) rotate(90deg)").unwrap();
+        let coord = transform.apply(Coordinate::new(1.0, 0.0));
+        assert!((coord.x - 10.0).abs() < 1e-9 && (coord.y - 1.0).abs() < 1e-9);
+
+        assert_transform_eq(&parse_transform("rotate(0.25turn)").unwrap(), &parse_transform("rotate(90deg)").unwrap());
+        assert_eq!(parse_transform("translate(50%)"), None);
+    }
+
+    #[test]
+    fn test_decompose() {
+        let transforms = [
+            parse_transform("translate(10px, 20px) rotate(30deg) scale(2, 3)").unwrap(),
+            parse_transform("skewX(20deg) scale(-1, 1)").unwrap(),
+            Transform::IDENTITY,
+        ];
+        for transform in transforms {
+            assert_transform_eq(&recompose(&decompose(&transform)), &transform);
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let from = StyleValue::Keyword("rotate(0deg)".to_string());
+        let to = StyleValue::Keyword("rotate(180deg)".to_string());
+        // Halfway a rotation is a quarter turn, and not a matrix that is scaled to zero
+        let AnimatedValue::Transform(half) = interpolate(&StyleProperty::Transform, &from, &to, 0.5) else {
+            panic!("expected a transform");
+        };
+        assert_transform_eq(&half, &Transform::rotate(std::f64::consts::FRAC_PI_2));
+
+        let opacity = interpolate(&StyleProperty::Opacity, &StyleValue::Number(0.0), &StyleValue::Number(1.0), 0.25);
+        assert_eq!(opacity, AnimatedValue::Opacity(0.25));
+
+        let width = interpolate(&StyleProperty::Width, &StyleValue::Unit(100.0, Unit::Px), &StyleValue::Unit(200.0, Unit::Px), 0.5);
+        assert_eq!(width, AnimatedValue::Style(StyleValue::Unit(150.0, Unit::Px)));
+
+        let black = StyleValue::Color(StyleColor::Named("black".to_string()));
+        let white = StyleValue::Color(StyleColor::Rgb(255, 255, 255));
+        let grey = interpolate(&StyleProperty::Color, &black, &white, 0.5);
+        assert_eq!(grey, AnimatedValue::Style(StyleValue::Color(StyleColor::Rgba(128, 128, 128, 1.0))));
+
+        // Keywords are discrete
+        let from = StyleValue::Keyword("block".to_string());
+        let to = StyleValue::Keyword("none".to_string());
+        assert_eq!(interpolate(&StyleProperty::Float, &from, &to, 0.4), AnimatedValue::Style(from.clone()));
+        assert_eq!(interpolate(&StyleProperty::Float, &from, &to, 0.6), AnimatedValue::Style(to.clone()));
+    }
+}
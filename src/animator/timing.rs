@@ -0,0 +1,157 @@
+/// Easing function that maps the progress of an animation (0.0 - 1.0) onto the progress of its value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimingFunction {
+    Linear,
+    CubicBezier(f64, f64, f64, f64),
+    Steps(u32, StepPosition),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepPosition {
+    JumpStart,
+    JumpEnd,
+    JumpNone,
+    JumpBoth,
+}
+
+impl TimingFunction {
+    pub const EASE: TimingFunction = TimingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0);
+    pub const EASE_IN: TimingFunction = TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0);
+    pub const EASE_OUT: TimingFunction = TimingFunction::CubicBezier(0.0, 0.0, 0.58, 1.0);
+    pub const EASE_IN_OUT: TimingFunction = TimingFunction::CubicBezier(0.42, 0.0, 0.58, 1.0);
+
+    /// Parses a single CSS easing function. Unknown functions fall back to `ease`, which is the CSS default.
+    pub fn parse(value: &str) -> TimingFunction {
+        let value = value.trim();
+        match value {
+            "linear" => return TimingFunction::Linear,
+            "ease" => return TimingFunction::EASE,
+            "ease-in" => return TimingFunction::EASE_IN,
+            "ease-out" => return TimingFunction::EASE_OUT,
+            "ease-in-out" => return TimingFunction::EASE_IN_OUT,
+            "step-start" => return TimingFunction::Steps(1, StepPosition::JumpStart),
+            "step-end" => return TimingFunction::Steps(1, StepPosition::JumpEnd),
+            _ => {}
+        }
+
+        let Some((name, args)) = value.strip_suffix(')').and_then(|v| v.split_once('(')) else {
+            return TimingFunction::EASE;
+        };
+        let args: Vec<&str> = args.split(',').map(|a| a.trim()).collect();
+
+        match name.trim() {
+            "cubic-bezier" => {
+                let nums: Vec<f64> = args.iter().filter_map(|a| a.parse().ok()).collect();
+                match nums.as_slice() {
+                    [x1, y1, x2, y2] => TimingFunction::CubicBezier(x1.clamp(0.0, 1.0), *y1, x2.clamp(0.0, 1.0), *y2),
+                    _ => TimingFunction::EASE,
+                }
+            }
+            "steps" => {
+                let Some(steps) = args.first().and_then(|a| a.parse::<u32>().ok()).filter(|s| *s > 0) else {
+                    return TimingFunction::EASE;
+                };
+                let position = match args.get(1).copied() {
+                    Some("jump-start" | "start") => StepPosition::JumpStart,
+                    Some("jump-none") => StepPosition::JumpNone,
+                    Some("jump-both") => StepPosition::JumpBoth,
+                    _ => StepPosition::JumpEnd,
+                };
+                TimingFunction::Steps(steps, position)
+            }
+            _ => TimingFunction::EASE,
+        }
+    }
+
+    /// Returns the eased progress for the given progress (0.0 - 1.0)
+    pub fn apply(&self, t: f64) -> f64 {
+        match *self {
+            TimingFunction::Linear => t,
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => {
+                if t <= 0.0 || t >= 1.0 {
+                    return t.clamp(0.0, 1.0);
+                }
+                let s = solve_bezier_x(x1, x2, t);
+                bezier(y1, y2, s)
+            }
+            TimingFunction::Steps(steps, position) => {
+                let steps = steps as i64;
+                let jumps = match position {
+                    StepPosition::JumpStart | StepPosition::JumpEnd => steps,
+                    StepPosition::JumpNone => (steps - 1).max(1),
+                    StepPosition::JumpBoth => steps + 1,
+                };
+
+                let mut step = (t * steps as f64).floor() as i64;
+                if matches!(position, StepPosition::JumpStart | StepPosition::JumpBoth) {
+                    step += 1;
+                }
+                step.clamp(0, jumps) as f64 / jumps as f64
+            }
+        }
+    }
+}
+
+/// One dimension of a cubic bezier from (0,0) to (1,1) with the given control points
+fn bezier(p1: f64, p2: f64, s: f64) -> f64 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+}
+
+/// Finds the bezier parameter for which the x of the curve equals x
+fn solve_bezier_x(x1: f64, x2: f64, x: f64) -> f64 {
+    // Newton's method converges fast for most curves
+    let mut s = x;
+    for _ in 0..8 {
+        let error = bezier(x1, x2, s) - x;
+        if error.abs() < 1e-7 {
+            return s;
+        }
+        let derivative = 3.0 * (1.0 - s) * (1.0 - s) * x1 + 6.0 * (1.0 - s) * s * (x2 - x1) + 3.0 * s * s * (1.0 - x2);
+        if derivative.abs() < 1e-6 {
+            break;
+        }
+        s -= error / derivative;
+    }
+
+    // Fall back to bisection, as x is monotonic in s
+    let (mut low, mut high) = (0.0, 1.0);
+    s = x;
+    while high - low > 1e-7 {
+        if bezier(x1, x2, s) < x {
+            low = s;
+        } else {
+            high = s;
+        }
+        s = (low + high) / 2.0;
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_functions() {
+        assert_eq!(TimingFunction::parse("linear").apply(0.3), 0.3);
+
+        let ease = TimingFunction::parse("ease");
+        assert_eq!(ease.apply(0.0), 0.0);
+        assert_eq!(ease.apply(1.0), 1.0);
+        // ease is well past the middle halfway through
+        assert!((ease.apply(0.5) - 0.8024).abs() < 0.001);
+
+        // A linear bezier is linear
+        let bezier = TimingFunction::parse("cubic-bezier(0.25, 0.25, 0.75, 0.75)");
+        assert!((bezier.apply(0.4) - 0.4).abs() < 1e-5);
+
+        let steps = TimingFunction::parse("steps(4, end)");
+        assert_eq!(steps.apply(0.3), 0.25);
+        assert_eq!(steps.apply(1.0), 1.0);
+        assert_eq!(TimingFunction::parse("steps(4, jump-start)").apply(0.3), 0.5);
+        assert_eq!(TimingFunction::parse("step-start").apply(0.1), 1.0);
+
+        assert_eq!(TimingFunction::parse("bogus(1)"), TimingFunction::EASE);
+    }
+}
@@ -0,0 +1,31 @@
+#[cfg(not(feature = "backend_skia"))]
+compile_error!("This binary can only be used with the feature 'backend_skia' enabled");
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use poc_pipeline::common;
+use poc_pipeline::layouter::pagination::PageSetup;
+use poc_pipeline::rasterizer::skia::pdf::document_to_pdf;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <document.json> <output.pdf> [base-url] [title]", args[0]);
+        std::process::exit(1);
+    }
+
+    let base_url = args.get(3).map_or("https://example.com", |s| s.as_str());
+    let doc = common::document::parser::document_from_json(base_url, &args[1]);
+
+    // The @page rules of the document override the default A4 page
+    let page = PageSetup::from_document(&doc, PageSetup::new(PageSetup::A4));
+
+    let file = File::create(&args[2]).expect("Failed to create output file");
+    let mut writer = BufWriter::new(file);
+    // The title on the command line overrides the <title> of the document
+    let title = args.get(4).cloned().or_else(|| doc.title()).unwrap_or_default();
+    document_to_pdf(&doc, &page, &title, &mut writer);
+
+    println!("Written {}", args[2]);
+}
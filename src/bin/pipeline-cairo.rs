@@ -10,24 +10,33 @@ use gtk4::{
     glib, Adjustment, Application, ApplicationWindow, DrawingArea, EventControllerMotion,
     ScrolledWindow,
 };
+use poc_pipeline::animator::{Animator, SystemClock};
 use poc_pipeline::common;
 use poc_pipeline::common::browser_state::{
     get_browser_state, init_browser_state, BrowserState, WireframeState,
 };
+use poc_pipeline::common::document::media_query::ColorScheme;
 use poc_pipeline::common::geo::{Dimension, Rect};
 use poc_pipeline::compositor::cairo::{CairoCompositor, CairoCompositorConfig};
 use poc_pipeline::compositor::Composable;
-use poc_pipeline::layering::layer::{LayerId, LayerList};
+use poc_pipeline::layering::layer::LayerList;
+use poc_pipeline::layering::policy::{CombinedLayerPolicy, DefaultLayerPolicy, ImageLayerPolicy};
 use poc_pipeline::layouter::taffy::TaffyLayouter;
 use poc_pipeline::layouter::CanLayout;
+use poc_pipeline::painter::display_list::DisplayList;
 use poc_pipeline::painter::Painter;
 use poc_pipeline::rasterizer::cairo::CairoRasterizer;
 use poc_pipeline::rasterizer::Rasterable;
 use poc_pipeline::rendertree_builder::RenderTree;
-use poc_pipeline::tiler::{TileList, TileState};
-use std::sync::RwLock;
+use poc_pipeline::tiler::scheduler::{TileScheduler, TileTask};
+use poc_pipeline::tiler::{TileId, TileList, TileState};
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
 
 const TILE_DIMENSION: f64 = 256.0;
+/// Time spent on painting and rasterizing tiles each frame. Tiles that do not fit are done in the next frames.
+const FRAME_BUDGET: Duration = Duration::from_millis(8);
 
 const WINDOW_WIDTH: f64 = 1024.0;
 const WINDOW_HEIGHT: f64 = 768.0;
@@ -36,16 +45,15 @@ fn main() {
     // --------------------------------------------------------------------
     // Generate a DOM tree
     // let doc = common::document::create_document();
-    // let doc = common::document::parser::document_from_json("tables.json");
-    // let doc = common::document::parser::document_from_json("news.ycombinator.com.json");
-    let doc = common::document::parser::document_from_json("cm.json");
+    // let doc = common::document::parser::document_from_json("https://news.ycombinator.com", "news.ycombinator.com.json");
+    let doc = Arc::new(common::document::parser::document_from_json("https://codemusings.nl", "cm.json"));
     let mut output = String::new();
     doc.print_tree(&mut output).expect("");
     println!("{}", output);
 
     // --------------------------------------------------------------------
     // Convert the DOM tree into a render-tree that has all the non-visible elements removed
-    let mut render_tree = RenderTree::new(doc);
+    let mut render_tree = RenderTree::new(doc.clone());
     render_tree.parse();
     // render_tree.print();
 
@@ -61,7 +69,9 @@ fn main() {
 
     // -------------------------------------------------------------------  -
     // Generate render layers
-    let layer_list = LayerList::new(layout_tree);
+    // Images get their own layer, so the layer toggles have something to show
+    let policy = CombinedLayerPolicy::new(vec![Box::new(DefaultLayerPolicy::default()), Box::new(ImageLayerPolicy)]);
+    let layer_list = LayerList::with_policy(layout_tree, &policy);
     // for (layer_id, layer) in layer_list.layers.read().expect("").iter() {
     //     println!("Layer: {} (order: {})", layer_id, layer.order);
     //     for element in layer.elements.iter() {
@@ -85,15 +95,24 @@ fn main() {
         .build();
 
     let browser_state = BrowserState {
-        visible_layer_list: vec![true; 10],
+        visible_layer_list: vec![],
         wireframed: WireframeState::None,
         debug_hover: false,
         current_hovered_element: None,
-        tile_list: RwLock::new(tile_list),
         show_tilegrid: true,
         viewport: Rect::ZERO,
+        device_pixel_ratio: 1.0,
+        zoom: 1.0,
+        document: doc,
+        animated_styles: vec![],
+        animated_document: None,
+        tile_list: None,
+        color_scheme: ColorScheme::Light,
+        animator: Animator::new(Arc::new(SystemClock::new())),
+        display_list: DisplayList::new(),
     };
     init_browser_state(browser_state);
+    get_browser_state().write().unwrap().set_tile_list(tile_list);
 
     app.connect_activate(move |app| {
         build_ui(app);
@@ -130,33 +149,29 @@ fn build_ui(app: &Application) {
     let state = binding.read().unwrap();
     let dim = state
         .tile_list
-        .read()
-        .unwrap()
-        .layer_list
-        .layout_tree
-        .clone()
-        .root_dimension
-        .clone();
+        .as_ref()
+        .map(|tile_list| tile_list.read().unwrap().layer_list.layout_tree.root_dimension)
+        .unwrap_or(Dimension::ZERO);
+    drop(state);
 
     let area = DrawingArea::new();
     area.set_content_width(dim.width as i32);
     area.set_content_height(dim.height as i32);
-    area.set_draw_func(move |_area, cr, _width, _height| {
-        let binding = get_browser_state();
-        let state = binding.read().unwrap();
-        let vis_layers = state.visible_layer_list.clone();
-        drop(state);
-
-        if vis_layers[0] {
-            do_paint(LayerId::new(0));
-            do_rasterize(LayerId::new(0));
-        }
-        if vis_layers[1] {
-            do_paint(LayerId::new(1));
-            do_rasterize(LayerId::new(1));
-        }
+    let scheduler = RefCell::new(TileScheduler::new().with_frame_budget(FRAME_BUDGET));
+    area.set_draw_func(move |area, cr, _width, _height| {
+        // Visible tiles first, then offscreen tiles are prefetched with the time left in this frame
+        let mut scheduler = scheduler.borrow_mut();
+        let tasks = schedule_tiles(&mut scheduler);
+        let pending = scheduler.run(tasks, |task| {
+            do_paint(task.tile_id);
+            do_rasterize(task.tile_id);
+        });
 
         CairoCompositor::compose(CairoCompositorConfig { cr: cr.clone() });
+
+        if !pending.is_empty() {
+            area.queue_draw();
+        }
     });
 
     // When we move the mouse, we can detect which element is currently hovered upon
@@ -166,89 +181,33 @@ fn build_ui(app: &Application) {
     let area_clone = area.clone();
     motion_controller.connect_motion(move |_, x, y| {
         let binding = get_browser_state();
-        let state = binding.read().expect("Failed to get browser state");
-        let el_id = state
-            .tile_list
-            .read()
-            .unwrap()
-            .layer_list
-            .find_element_at(x, y)
-            .clone();
-        let che = state.current_hovered_element.clone();
-
-        let mut tile_ids = vec![];
-        match (che, el_id) {
-            (Some(current_id), Some(new_id)) if current_id != new_id => {
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(current_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(new_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-            }
-            (None, Some(new_id)) => {
-                let mut tile_ids = vec![];
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(new_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-            }
-            (Some(current_id), None) => {
-                let mut tile_ids = vec![];
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(current_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-            }
-            _ => {}
-        }
-        drop(state);
-
         let mut state = binding.write().expect("Failed to get browser state");
-        if state.current_hovered_element != el_id {
-            if el_id.is_some() {
-                let binding = state.tile_list.read().unwrap();
-                let layout_element = binding
-                    .layer_list
-                    .layout_tree
-                    .get_node_by_id(el_id.unwrap())
-                    .unwrap();
-                println!("Hovered element id:");
-                println!("   Layout ID : {:?}", el_id);
-                println!("   DOM ID    : {:?}", layout_element.dom_node_id);
-                drop(binding);
-            }
+        let Some(ref tile_list) = state.tile_list else {
+            log::error!("No tile list found");
+            return;
+        };
 
-            for tile_id in &tile_ids {
-                // It's ok when we have double tiles in the list. We just set the tile to dirty again.
-                state.tile_list.write().unwrap().invalidate_tile(*tile_id);
-            }
+        let el_id = tile_list.read().unwrap().find_element_at(x, y);
+        if state.current_hovered_element == el_id {
+            return;
+        }
 
-            state.current_hovered_element = el_id;
-            area_clone.queue_draw();
+        if let Some(el_id) = el_id {
+            let binding = tile_list.read().unwrap();
+            let layout_element = binding
+                .layer_list
+                .layout_tree
+                .get_node_by_id(el_id)
+                .unwrap();
+            println!("Hovered element id:");
+            println!("   Layout ID : {:?}", el_id);
+            println!("   DOM ID    : {:?}", layout_element.dom_node_id);
+            drop(binding);
         }
+
+        // Only the tiles of the previous and the new hovered element are painted again
+        state.set_hovered_element(el_id);
+        area_clone.queue_draw();
     });
     area.add_controller(motion_controller);
 
@@ -270,44 +229,54 @@ fn build_ui(app: &Application) {
         match keyval {
             // numeric keys triggers the visibility of the layers
             key if key == gtk4::gdk::Key::_1 => {
-                state.visible_layer_list[0] = !state.visible_layer_list[0];
-                area.queue_draw();
+                if state.toggle_layer(0) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_2 => {
-                state.visible_layer_list[1] = !state.visible_layer_list[1];
-                area.queue_draw();
+                if state.toggle_layer(1) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_3 => {
-                state.visible_layer_list[2] = !state.visible_layer_list[2];
-                area.queue_draw();
+                if state.toggle_layer(2) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_4 => {
-                state.visible_layer_list[3] = !state.visible_layer_list[3];
-                area.queue_draw();
+                if state.toggle_layer(3) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_5 => {
-                state.visible_layer_list[4] = !state.visible_layer_list[4];
-                area.queue_draw();
+                if state.toggle_layer(4) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_6 => {
-                state.visible_layer_list[5] = !state.visible_layer_list[5];
-                area.queue_draw();
+                if state.toggle_layer(5) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_7 => {
-                state.visible_layer_list[6] = !state.visible_layer_list[6];
-                area.queue_draw();
+                if state.toggle_layer(6) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_8 => {
-                state.visible_layer_list[7] = !state.visible_layer_list[7];
-                area.queue_draw();
+                if state.toggle_layer(7) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_9 => {
-                state.visible_layer_list[8] = !state.visible_layer_list[8];
-                area.queue_draw();
+                if state.toggle_layer(8) {
+                    area.queue_draw();
+                }
             }
             key if key == gtk4::gdk::Key::_0 => {
-                state.visible_layer_list[9] = !state.visible_layer_list[9];
-                area.queue_draw();
+                if state.toggle_layer(9) {
+                    area.queue_draw();
+                }
             }
             // toggle wireframed elements
             key if key == gtk4::gdk::Key::w => {
@@ -316,21 +285,17 @@ fn build_ui(app: &Application) {
                     WireframeState::Only => state.wireframed = WireframeState::Both,
                     WireframeState::Both => state.wireframed = WireframeState::None,
                 }
-                state
-                    .tile_list
-                    .write()
-                    .expect("Failed to get tile list")
-                    .invalidate_all();
+                if let Some(ref tile_list) = state.tile_list {
+                    tile_list
+                        .write()
+                        .expect("Failed to get tile list")
+                        .invalidate_all();
+                }
                 area.queue_draw();
             }
             // toggle displaying only the hovered element
             key if key == gtk4::gdk::Key::d => {
-                state.debug_hover = !state.debug_hover;
-                state
-                    .tile_list
-                    .write()
-                    .expect("Failed to get tile list")
-                    .invalidate_all();
+                state.toggle_debug_hover();
                 area.queue_draw();
             }
             // toggle tile grid
@@ -349,73 +314,72 @@ fn build_ui(app: &Application) {
     window.show();
 }
 
-/// Paint all the dirty tiles that are in view
-fn do_paint(layer_id: LayerId) {
+/// Returns the dirty tiles of the visible layers in the order they should be painted and rasterized
+fn schedule_tiles(scheduler: &mut TileScheduler) -> Vec<TileTask> {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
-    let painter = Painter::new(state.tile_list.read().unwrap().layer_list.clone());
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return vec![];
+    };
 
-    let tile_ids = state
-        .tile_list
-        .read()
-        .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
-    for tile_id in tile_ids {
-        // get tile
-        let mut binding = state.tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
+    let layer_ids = state.visible_layers();
+    let mut tile_list = tile_list.write().expect("Failed to get tile list");
+    scheduler.schedule(&mut tile_list, &layer_ids, state.viewport)
+}
 
-        // if not dirty, no need to render and continue
-        if tile.state == TileState::Clean {
-            continue;
-        }
+/// Paint the tile when it is dirty
+fn do_paint(tile_id: TileId) {
+    let binding = get_browser_state();
+    let state = binding.read().unwrap();
 
-        // Paint all the elements in each tile
-        for tiled_layout_element in &mut tile.elements {
-            tiled_layout_element.paint_commands = painter.paint(tiled_layout_element);
-        }
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return;
+    };
+
+    let painter = Painter::new(tile_list.read().unwrap().layer_list.clone());
+
+    let mut binding = tile_list.write().expect("Failed to get tile list");
+    let Some(tile) = binding.get_tile_mut(tile_id) else {
+        log::warn!("Tile not found: {:?}", tile_id);
+        return;
+    };
+
+    // if not dirty, no need to render
+    if tile.state != TileState::Dirty {
+        return;
     }
+
+    // Paint all the elements in the tile
+    painter.paint_tile(tile);
 }
 
-fn do_rasterize(layer_id: LayerId) {
+fn do_rasterize(tile_id: TileId) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
-    let tile_ids = state
-        .tile_list
-        .read()
-        .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
-    for tile_id in tile_ids {
-        // get tile
-        let mut binding = state.tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
-
-        // if not dirty, no need to render and continue
-        if tile.state == TileState::Clean {
-            continue;
-        }
-
-        // Rasterize the tile into a texture
-        // println!("Generating painting commands for tile");
-        let rasterizer = CairoRasterizer::new();
-        let texture_id = rasterizer.rasterize(tile);
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return;
+    };
 
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
+    let mut binding = tile_list.write().expect("Failed to get tile list");
+    let Some(tile) = binding.get_tile(tile_id) else {
+        log::warn!("Tile not found: {:?}", tile_id);
+        return;
+    };
 
-        tile.texture_id = Some(texture_id);
-        tile.state = TileState::Clean;
+    // if not dirty, no need to render
+    if tile.state != TileState::Dirty {
+        return;
     }
+
+    // Rasterize the tile into a texture. Empty tiles and tiles with the same content as another tile are not
+    // rasterized again.
+    let rasterizer = CairoRasterizer::new();
+    binding.rasterize_tile(tile_id, |tile| rasterizer.rasterize(tile));
 }
 
 // Function to set up viewport event listeners
@@ -475,11 +439,12 @@ fn on_viewport_changed(area: &DrawingArea, hadj: &Adjustment, vadj: &Adjustment)
 
     // If we changed the viewport size, we need to invalidate all tiles
     if width != state.viewport.width || height != state.viewport.height {
-        state
-            .tile_list
-            .write()
-            .expect("Failed to get tile list")
-            .invalidate_all();
+        if let Some(ref tile_list) = state.tile_list {
+            tile_list
+                .write()
+                .expect("Failed to get tile list")
+                .invalidate_all();
+        }
     }
 
     state.viewport = Rect::new(x, y, width, height);
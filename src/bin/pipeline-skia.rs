@@ -16,29 +16,34 @@ use log::info;
 use raw_window_handle::HasWindowHandle;
 use std::ffi::CString;
 use std::num::NonZeroU32;
-use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use winit::{
     event::WindowEvent,
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
+use poc_pipeline::animator::{Animator, SystemClock};
 use poc_pipeline::common;
 use poc_pipeline::common::browser_state::{
     get_browser_state, init_browser_state, BrowserState, WireframeState,
 };
+use poc_pipeline::common::document::media_query::ColorScheme;
 use poc_pipeline::common::geo::{Dimension, Rect};
 use poc_pipeline::compositor::skia::{SkiaCompositor, SkiaCompositorConfig};
 use poc_pipeline::compositor::Composable;
-use poc_pipeline::layering::layer::{LayerId, LayerList};
+use poc_pipeline::layering::layer::LayerList;
 use poc_pipeline::layouter::taffy::TaffyLayouter;
 use poc_pipeline::layouter::CanLayout;
+use poc_pipeline::layouter::text::text_cache_stats;
+use poc_pipeline::painter::display_list::DisplayList;
 use poc_pipeline::painter::Painter;
 use poc_pipeline::rasterizer::skia::SkiaRasterizer;
 use poc_pipeline::rasterizer::Rasterable;
 use poc_pipeline::rendertree_builder::RenderTree;
-use poc_pipeline::tiler::{TileList, TileState};
+use poc_pipeline::tiler::scheduler::{TileScheduler, TileTask};
+use poc_pipeline::tiler::{TileId, TileList, TileState};
 use skia_safe::{
     gpu::{self, backend_render_targets, gl::FramebufferInfo, SurfaceOrigin},
     Color, ColorType, Surface,
@@ -51,6 +56,8 @@ use winit::keyboard::PhysicalKey::Code;
 use winit::window::{WindowAttributes, WindowId};
 
 const TILE_DIMENSION: f64 = 256.0;
+/// Time spent on painting and rasterizing tiles each frame. Tiles that do not fit are done in the next frames.
+const FRAME_BUDGET: Duration = Duration::from_millis(8);
 
 fn main() {
     // let doc = common::document::parser::document_from_json("https://codemusings.nl","cm.json");
@@ -68,7 +75,7 @@ fn main() {
     let viewport_dimension = Dimension::new(1024.0, 768.0);
 
     let browser_state = BrowserState {
-        visible_layer_list: vec![true; 10],
+        visible_layer_list: vec![],
         wireframed: WireframeState::None,
         debug_hover: false,
         current_hovered_element: None,
@@ -80,10 +87,23 @@ fn main() {
             viewport_dimension.height,
         ),
         document: Arc::new(doc),
+        animated_styles: vec![],
+        animated_document: None,
         tile_list: None,
+        color_scheme: ColorScheme::Light,
+        device_pixel_ratio: 1.0,
+        zoom: 1.0,
+        animator: Animator::new(Arc::new(SystemClock::new())),
+        display_list: DisplayList::new(),
     };
     init_browser_state(browser_state);
 
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    let doc = state.document.clone();
+    state.animator.start_animations(&doc);
+    drop(state);
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
@@ -94,9 +114,16 @@ fn main() {
 // This will reflow EVERYTHING. This is not efficient, but it's good enough for now.
 fn reflow() {
     let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    // Styles depend on the viewport through media queries, so these need to be up to date before layout
+    if state.update_media() {
+        info!("media queries changed, styles are recomputed");
+    }
+    drop(state);
+
     let state = binding.read().unwrap();
 
-    let mut render_tree = RenderTree::new(state.document.clone());
+    let mut render_tree = RenderTree::new(state.render_document());
     render_tree.parse();
 
     let mut layouter = TaffyLayouter::new();
@@ -104,20 +131,42 @@ fn reflow() {
         render_tree,
         Some(Dimension::new(state.viewport.width, state.viewport.height)),
     );
+    log::debug!("Text layout cache: {}", text_cache_stats());
 
-    let layer_list = LayerList::new(layout_tree);
+    // Images and elements with opacity or transform animations get their own layer
+    let layer_list = LayerList::with_policy(layout_tree, &state.layer_policy());
 
     let mut tile_list = TileList::new(layer_list, Dimension::new(TILE_DIMENSION, TILE_DIMENSION));
+    tile_list.set_device_pixel_ratio(state.device_pixel_ratio);
+    tile_list.set_zoom(state.zoom);
     tile_list.generate();
 
     drop(state);
 
     let binding = get_browser_state();
     let mut state = binding.write().unwrap();
-    state.tile_list = Some(RwLock::new(tile_list));
+    state.set_tile_list(tile_list);
 }
 
 // Application environment. Mostly OpenGL stuff.
+/// Advances the transitions and animations. Opacity and transform of elements with their own layer are set on
+/// the layer, other animated styles need a reflow. Returns true when more frames are needed.
+fn animate() -> bool {
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    let frame = state.animator.tick();
+    let needs_reflow = state.apply_animation_styles(&frame);
+    drop(state);
+
+    if needs_reflow {
+        reflow();
+    }
+
+    let state = binding.read().unwrap();
+    state.apply_animation_layers(&frame);
+    state.animator.is_active()
+}
+
 struct Env {
     pub surface: Surface,
     pub gl_surface: GlutinSurface<WindowSurface>,
@@ -153,6 +202,8 @@ struct App {
     ///
     window_size: Dimension,
     window_title: String,
+    /// Decides which tiles are painted and rasterized each frame
+    scheduler: TileScheduler,
 }
 
 impl App {
@@ -164,6 +215,7 @@ impl App {
             fps: 0.0,
             window_size,
             window_title: window_title.to_string(),
+            scheduler: TileScheduler::new().with_frame_budget(FRAME_BUDGET),
         }
     }
 }
@@ -181,6 +233,12 @@ impl ApplicationHandler for App {
             &self.window_title,
             self.window_size,
         ));
+
+        if let Some(env) = &self.env {
+            let binding = get_browser_state();
+            let mut state = binding.write().unwrap();
+            state.set_device_pixel_ratio(env.window.scale_factor());
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
@@ -208,13 +266,37 @@ impl ApplicationHandler for App {
                     NonZeroU32::new(height.max(1)).unwrap(),
                 );
 
+                // The window size is in device pixels, our viewport is in (zoomed) CSS pixels
                 let binding = get_browser_state();
                 let mut state = binding.write().unwrap();
-                state.viewport = Rect::new(0.0, 0.0, width as f64, height as f64);
+                let scale = state.device_scale();
+                state.viewport = Rect::new(0.0, 0.0, width as f64 / scale, height as f64 / scale);
                 drop(state);
 
                 reflow();
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                // The cursor position is in device pixels, the layout in (zoomed) CSS pixels
+                let binding = get_browser_state();
+                let mut state = binding.write().unwrap();
+                let scale = state.device_scale();
+                let x = state.viewport.x + position.x / scale;
+                let y = state.viewport.y + position.y / scale;
+
+                let element_id = state.tile_list.as_ref()
+                    .and_then(|tile_list| tile_list.read().unwrap().find_element_at(x, y));
+
+                // Only the tiles of the previous and the new hovered element are painted again
+                if state.set_hovered_element(element_id) {
+                    env.window.request_redraw();
+                }
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // A resize with the new window size follows, which will take care of the viewport
+                let binding = get_browser_state();
+                let mut state = binding.write().unwrap();
+                state.set_device_pixel_ratio(scale_factor);
+            }
             WindowEvent::RedrawRequested => {
                 self.frame += 1;
 
@@ -223,28 +305,27 @@ impl ApplicationHandler for App {
                 self.pfs = Instant::now();
                 // println!("FPS: {:.2}", self.fps);
 
+                let animating = animate();
+
                 let canvas = env.surface.canvas();
                 canvas.clear(Color::WHITE);
 
-                let binding = get_browser_state();
-                let state = binding.read().unwrap();
-                let vis_layers = state.visible_layer_list.clone();
-                drop(state);
-
-                if vis_layers[0] {
-                    do_paint(LayerId::new(0));
-                    do_rasterize(LayerId::new(0));
-                }
-                if vis_layers[1] {
-                    do_paint(LayerId::new(1));
-                    do_rasterize(LayerId::new(1));
-                }
+                // Visible tiles first, then offscreen tiles are prefetched with the time left in this frame
+                let tasks = schedule_tiles(&mut self.scheduler);
+                let pending = self.scheduler.run(tasks, |task| {
+                    do_paint(task.tile_id);
+                    do_rasterize(task.tile_id);
+                });
 
                 let canvas = env.surface.canvas();
                 let _surface = SkiaCompositor::compose(SkiaCompositorConfig { canvas });
 
                 env.gr_context.flush_and_submit();
                 env.gl_surface.swap_buffers(&env.gl_context).unwrap();
+
+                if animating || !pending.is_empty() {
+                    env.window.request_redraw();
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -269,7 +350,7 @@ impl ApplicationHandler for App {
                     let binding = get_browser_state();
                     let mut state = binding.write().unwrap();
 
-                    let layer_id = match physical_key {
+                    let layer_index = match physical_key {
                         Code(KeyCode::Digit1) => 0,
                         Code(KeyCode::Digit2) => 1,
                         Code(KeyCode::Digit3) => 2,
@@ -282,8 +363,9 @@ impl ApplicationHandler for App {
                         Code(KeyCode::Digit0) => 9,
                         _ => unreachable!(),
                     };
-                    state.visible_layer_list[layer_id] = !state.visible_layer_list[layer_id];
-                    env.window.request_redraw();
+                    if state.toggle_layer(layer_index) {
+                        env.window.request_redraw();
+                    }
                 }
 
                 if logical_key == "w" {
@@ -301,6 +383,8 @@ impl ApplicationHandler for App {
                         return;
                     };
 
+                    // Wireframes change the painting of every element, so this is the one case where all tiles
+                    // are invalidated. Other changes use invalidate_rect() or invalidate_element().
                     tile_list
                         .write()
                         .expect("Failed to get tile list")
@@ -312,7 +396,7 @@ impl ApplicationHandler for App {
                     let binding = get_browser_state();
                     let mut state = binding.write().unwrap();
 
-                    state.debug_hover = !state.debug_hover;
+                    state.toggle_debug_hover();
                     env.window.request_redraw();
                 }
 
@@ -323,6 +407,17 @@ impl ApplicationHandler for App {
                     state.show_tilegrid = !state.show_tilegrid;
                     env.window.request_redraw();
                 }
+
+                if logical_key == "+" || logical_key == "=" || logical_key == "-" {
+                    let binding = get_browser_state();
+                    let mut state = binding.write().unwrap();
+
+                    // The current tiles are shown scaled until the tiles of the new zoom level are rendered
+                    let factor = if logical_key == "-" { 1.0 / 1.25 } else { 1.25 };
+                    let zoom = state.zoom * factor;
+                    state.set_zoom(zoom);
+                    env.window.request_redraw();
+                }
             }
             _ => (),
         }
@@ -483,7 +578,22 @@ fn create_surface(
     .expect("Failed to create surface")
 }
 
-fn do_paint(layer_id: LayerId) {
+/// Returns the dirty tiles of the visible layers in the order they should be painted and rasterized
+fn schedule_tiles(scheduler: &mut TileScheduler) -> Vec<TileTask> {
+    let binding = get_browser_state();
+    let state = binding.read().unwrap();
+
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return vec![];
+    };
+
+    let layer_ids = state.visible_layers();
+    let mut tile_list = tile_list.write().expect("Failed to get tile list");
+    scheduler.schedule(&mut tile_list, &layer_ids, state.viewport)
+}
+
+fn do_paint(tile_id: TileId) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
@@ -494,31 +604,22 @@ fn do_paint(layer_id: LayerId) {
 
     let painter = Painter::new(tile_list.read().unwrap().layer_list.clone());
 
-    let tile_ids = tile_list
-        .read()
-        .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
-    for tile_id in tile_ids {
-        // get tile
-        let mut binding = tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
-
-        // if not dirty, no need to render and continue
-        if tile.state == TileState::Clean {
-            continue;
-        }
+    let mut binding = tile_list.write().expect("Failed to get tile list");
+    let Some(tile) = binding.get_tile_mut(tile_id) else {
+        log::warn!("Tile not found: {:?}", tile_id);
+        return;
+    };
 
-        // Paint all the elements in each tile
-        for tiled_layout_element in &mut tile.elements {
-            tiled_layout_element.paint_commands = painter.paint(tiled_layout_element);
-        }
+    // if not dirty, no need to render
+    if tile.state != TileState::Dirty {
+        return;
     }
+
+    // Paint all the elements in the tile
+    painter.paint_tile(tile);
 }
 
-fn do_rasterize(layer_id: LayerId) {
+fn do_rasterize(tile_id: TileId) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
@@ -527,33 +628,19 @@ fn do_rasterize(layer_id: LayerId) {
         return;
     };
 
-    let tile_ids = tile_list
-        .read()
-        .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
-    for tile_id in tile_ids {
-        // get tile
-        let mut binding = tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
-
-        // if not dirty, no need to render and continue
-        if tile.state == TileState::Clean {
-            continue;
-        }
-
-        // Rasterize the tile into a texture
-        let rasterizer = SkiaRasterizer::new();
-        let texture_id = rasterizer.rasterize(tile);
-
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
+    let mut binding = tile_list.write().expect("Failed to get tile list");
+    let Some(tile) = binding.get_tile(tile_id) else {
+        log::warn!("Tile not found: {:?}", tile_id);
+        return;
+    };
 
-        tile.texture_id = Some(texture_id);
-        tile.state = TileState::Clean;
+    // if not dirty, no need to render
+    if tile.state != TileState::Dirty {
+        return;
     }
+
+    // Rasterize the tile into a texture. Empty tiles and tiles with the same content as another tile are not
+    // rasterized again.
+    let rasterizer = SkiaRasterizer::new();
+    binding.rasterize_tile(tile_id, |tile| rasterizer.rasterize(tile));
 }
@@ -1,26 +1,30 @@
 #[cfg(not(feature = "backend_vello"))]
 compile_error!("This binary can only be used with the feature 'backend_vello' enabled");
 
+use poc_pipeline::animator::{Animator, SystemClock};
 use poc_pipeline::common;
 use poc_pipeline::common::browser_state::{
     get_browser_state, init_browser_state, BrowserState, WireframeState,
 };
+use poc_pipeline::common::document::media_query::ColorScheme;
 use poc_pipeline::common::geo::{Dimension, Rect};
 use poc_pipeline::compositor::vello::{VelloCompositor, VelloCompositorConfig};
 use poc_pipeline::compositor::Composable;
-use poc_pipeline::layering::layer::{LayerId, LayerList};
+use poc_pipeline::layering::layer::LayerList;
 use poc_pipeline::layouter::taffy::TaffyLayouter;
 use poc_pipeline::layouter::CanLayout;
+use poc_pipeline::layouter::text::text_cache_stats;
+use poc_pipeline::painter::display_list::DisplayList;
 use poc_pipeline::painter::Painter;
 use poc_pipeline::rasterizer::vello::VelloRasterizer;
 use poc_pipeline::rasterizer::Rasterable;
 use poc_pipeline::rendertree_builder::RenderTree;
-use poc_pipeline::tiler::{TileList, TileState};
+use poc_pipeline::tiler::scheduler::{TileScheduler, TileTask};
+use poc_pipeline::tiler::{TileId, TileList, TileState};
 use std::cell::RefCell;
 use std::fmt::Formatter;
 use std::sync::Arc;
-use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use vello::peniko::color;
 use vello::util::{DeviceHandle, RenderContext, RenderSurface};
 use vello::{wgpu, AaConfig, AaSupport, RenderParams, Renderer, RendererOptions};
@@ -33,6 +37,8 @@ use winit::keyboard::PhysicalKey::Code;
 use winit::window::{Window, WindowId};
 
 const TILE_DIMENSION: f64 = 256.0;
+/// Time spent on painting and rasterizing tiles each frame. Tiles that do not fit are done in the next frames.
+const FRAME_BUDGET: Duration = Duration::from_millis(8);
 
 fn main() {
     // --------------------------------------------------------------------
@@ -43,8 +49,7 @@ fn main() {
     let viewport_dimension = Dimension::new(1024.0, 768.0);
 
     let browser_state = BrowserState {
-        // visible_layer_list: vec![true; 10],
-        visible_layer_list: vec![true, false, false, false, false, false, false, false, false, false],
+        visible_layer_list: vec![],
         wireframed: WireframeState::None,
         debug_hover: false,
         current_hovered_element: None,
@@ -56,10 +61,23 @@ fn main() {
             viewport_dimension.height,
         ),
         document: Arc::new(doc),
+        animated_styles: vec![],
+        animated_document: None,
         tile_list: None,
+        color_scheme: ColorScheme::Light,
+        device_pixel_ratio: 1.0,
+        zoom: 1.0,
+        animator: Animator::new(Arc::new(SystemClock::new())),
+        display_list: DisplayList::new(),
     };
     init_browser_state(browser_state);
 
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    let doc = state.document.clone();
+    state.animator.start_animations(&doc);
+    drop(state);
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
@@ -69,11 +87,18 @@ fn main() {
 
 fn reflow() {
     let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    // Styles depend on the viewport through media queries, so these need to be up to date before layout
+    if state.update_media() {
+        log::info!("media queries changed, styles are recomputed");
+    }
+    drop(state);
+
     let state = binding.read().unwrap();
 
     println!("reflowing to dimension: {:?}", state.viewport);
 
-    let mut render_tree = RenderTree::new(state.document.clone());
+    let mut render_tree = RenderTree::new(state.render_document());
     render_tree.parse();
 
     let mut layouter = TaffyLayouter::new();
@@ -81,17 +106,39 @@ fn reflow() {
         render_tree,
         Some(Dimension::new(state.viewport.width, state.viewport.height)),
     );
+    log::debug!("Text layout cache: {}", text_cache_stats());
 
-    let layer_list = LayerList::new(layout_tree);
+    // Images and elements with opacity or transform animations get their own layer
+    let layer_list = LayerList::with_policy(layout_tree, &state.layer_policy());
 
     let mut tile_list = TileList::new(layer_list, Dimension::new(TILE_DIMENSION, TILE_DIMENSION));
+    tile_list.set_device_pixel_ratio(state.device_pixel_ratio);
+    tile_list.set_zoom(state.zoom);
     tile_list.generate();
 
     drop(state);
 
     let binding = get_browser_state();
     let mut state = binding.write().unwrap();
-    state.tile_list = Some(RwLock::new(tile_list));
+    state.set_tile_list(tile_list);
+}
+
+/// Advances the transitions and animations. Opacity and transform of elements with their own layer are set on
+/// the layer, other animated styles need a reflow. Returns true when more frames are needed.
+fn animate() -> bool {
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    let frame = state.animator.tick();
+    let needs_reflow = state.apply_animation_styles(&frame);
+    drop(state);
+
+    if needs_reflow {
+        reflow();
+    }
+
+    let state = binding.read().unwrap();
+    state.apply_animation_layers(&frame);
+    state.animator.is_active()
 }
 
 struct Env<'s> {
@@ -116,6 +163,8 @@ struct App<'s> {
     fps: f32,
     window_size: Dimension,
     window_title: String,
+    /// Decides which tiles are painted and rasterized each frame
+    scheduler: TileScheduler,
 }
 
 impl App<'_> {
@@ -127,6 +176,7 @@ impl App<'_> {
             fps: 0.0,
             window_size,
             window_title: window_title.to_string(),
+            scheduler: TileScheduler::new().with_frame_budget(FRAME_BUDGET),
         }
     }
 }
@@ -145,6 +195,12 @@ impl ApplicationHandler for App<'_> {
             self.window_size
         ));
 
+        if let Some(window) = self.env.as_ref().and_then(|env| env.window.as_ref()) {
+            let binding = get_browser_state();
+            let mut state = binding.write().unwrap();
+            state.set_device_pixel_ratio(window.scale_factor());
+        }
+
         reflow();
     }
 
@@ -166,35 +222,61 @@ impl ApplicationHandler for App<'_> {
                     height,
                 );
 
+                // The window size is in device pixels, our viewport is in (zoomed) CSS pixels
                 let binding = get_browser_state();
                 let mut state = binding.write().unwrap();
-                state.viewport = Rect::new(0.0, 0.0, width as f64, height as f64);
+                let scale = state.device_scale();
+                state.viewport = Rect::new(0.0, 0.0, width as f64 / scale, height as f64 / scale);
+                let media_changed = state.media_changed();
                 drop(state);
 
-                // reflow();
+                // Only a crossed breakpoint changes the styles, so we don't need to reflow on every resize
+                if media_changed {
+                    reflow();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // The cursor position is in device pixels, the layout in (zoomed) CSS pixels
+                let binding = get_browser_state();
+                let mut state = binding.write().unwrap();
+                let scale = state.device_scale();
+                let x = state.viewport.x + position.x / scale;
+                let y = state.viewport.y + position.y / scale;
+
+                let element_id = state.tile_list.as_ref()
+                    .and_then(|tile_list| tile_list.read().unwrap().find_element_at(x, y));
+
+                // Only the tiles of the previous and the new hovered element are painted again
+                if state.set_hovered_element(element_id) {
+                    if let Some(window) = &env.window {
+                        window.request_redraw();
+                    }
+                }
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // A resize with the new window size follows, which will take care of the viewport
+                let binding = get_browser_state();
+                let mut state = binding.write().unwrap();
+                state.set_device_pixel_ratio(scale_factor);
             }
             WindowEvent::RedrawRequested => {
                 self.frame += 1;
                 self.pfs = Instant::now();
                 println!("Redraw requested: framecount: {}", self.frame);
 
+                let animating = animate();
+
                 let surface = env.surface.as_ref().unwrap();
                 let dev_id = surface.dev_id;
                 let DeviceHandle { device, queue, .. } = &env.render_ctx.devices[dev_id];
 
-                let binding = get_browser_state();
-                let state = binding.read().unwrap();
-                let vis_layers = state.visible_layer_list.clone();
-                drop(state);
-
-                let renderer = &mut env.renderer.as_mut().unwrap();
-
-                for i in 0..10 {
-                    if vis_layers[i] {
-                        do_paint(LayerId::new(i as u64));
-                        do_rasterize(device, queue, renderer.clone(), LayerId::new(i as u64));
-                    }
-                }
+                // Visible tiles first, then offscreen tiles are prefetched with the time left in this frame
+                let tasks = schedule_tiles(&mut self.scheduler);
+                let renderer = env.renderer.clone().unwrap();
+                let pending = self.scheduler.run(tasks, |task| {
+                    do_paint(task.tile_id);
+                    do_rasterize(device, queue, renderer.clone(), task.tile_id);
+                });
 
                 let surface_texture = surface
                     .surface
@@ -205,10 +287,12 @@ impl ApplicationHandler for App<'_> {
                 let binding = get_browser_state();
                 let state = binding.read().unwrap();
 
+                // The surface is in device pixels
+                let device_viewport = state.device_viewport();
                 let render_params = RenderParams {
                     base_color: color::palette::css::DARK_MAGENTA,
-                    width: state.viewport.width as u32,
-                    height: state.viewport.height as u32,
+                    width: device_viewport.width as u32,
+                    height: device_viewport.height as u32,
                     // width: self.window_size.width as u32,
                     // height: self.window_size.height as u32,
                     antialiasing_method: AaConfig::Msaa16,
@@ -227,6 +311,12 @@ impl ApplicationHandler for App<'_> {
                 );
 
                 surface_texture.present();
+
+                if animating || !pending.is_empty() {
+                    if let Some(window) = &env.window {
+                        window.request_redraw();
+                    }
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -255,7 +345,7 @@ impl ApplicationHandler for App<'_> {
                     let binding = get_browser_state();
                     let mut state = binding.write().unwrap();
 
-                    let layer_id = match physical_key {
+                    let layer_index = match physical_key {
                         Code(KeyCode::Digit1) => 0,
                         Code(KeyCode::Digit2) => 1,
                         Code(KeyCode::Digit3) => 2,
@@ -268,8 +358,9 @@ impl ApplicationHandler for App<'_> {
                         Code(KeyCode::Digit0) => 9,
                         _ => unreachable!(),
                     };
-                    state.visible_layer_list[layer_id] = !state.visible_layer_list[layer_id];
-                    window.request_redraw();
+                    if state.toggle_layer(layer_index) {
+                        window.request_redraw();
+                    }
                 }
 
                 if logical_key == "w" {
@@ -287,6 +378,8 @@ impl ApplicationHandler for App<'_> {
                         return;
                     };
 
+                    // Wireframes change the painting of every element, so this is the one case where all tiles
+                    // are invalidated. Other changes use invalidate_rect() or invalidate_element().
                     tile_list
                         .write()
                         .expect("Failed to get tile list")
@@ -298,7 +391,7 @@ impl ApplicationHandler for App<'_> {
                     let binding = get_browser_state();
                     let mut state = binding.write().unwrap();
 
-                    state.debug_hover = !state.debug_hover;
+                    state.toggle_debug_hover();
                     window.request_redraw();
                 }
 
@@ -310,6 +403,17 @@ impl ApplicationHandler for App<'_> {
                     window.request_redraw();
                 }
 
+                if logical_key == "+" || logical_key == "=" || logical_key == "-" {
+                    let binding = get_browser_state();
+                    let mut state = binding.write().unwrap();
+
+                    // The current tiles are shown scaled until the tiles of the new zoom level are rendered
+                    let factor = if logical_key == "-" { 1.0 / 1.25 } else { 1.25 };
+                    let zoom = state.zoom * factor;
+                    state.set_zoom(zoom);
+                    window.request_redraw();
+                }
+
             }
             _ => (),
         }
@@ -363,7 +467,22 @@ fn create_window_env<'s>(el: &ActiveEventLoop, title: &str, size: Dimension) ->
 }
 
 
-fn do_paint(layer_id: LayerId) {
+/// Returns the dirty tiles of the visible layers in the order they should be painted and rasterized
+fn schedule_tiles(scheduler: &mut TileScheduler) -> Vec<TileTask> {
+    let binding = get_browser_state();
+    let state = binding.read().unwrap();
+
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return vec![];
+    };
+
+    let layer_ids = state.visible_layers();
+    let mut tile_list = tile_list.write().expect("Failed to get tile list");
+    scheduler.schedule(&mut tile_list, &layer_ids, state.viewport)
+}
+
+fn do_paint(tile_id: TileId) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
@@ -374,35 +493,26 @@ fn do_paint(layer_id: LayerId) {
 
     let painter = Painter::new(tile_list.read().unwrap().layer_list.clone());
 
-    let tile_ids = tile_list
-        .read()
-        .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
-    for tile_id in tile_ids {
-        // get tile
-        let mut binding = tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
-
-        // if not dirty, no need to render and continue
-        if tile.state == TileState::Clean {
-            continue;
-        }
+    let mut binding = tile_list.write().expect("Failed to get tile list");
+    let Some(tile) = binding.get_tile_mut(tile_id) else {
+        log::warn!("Tile not found: {:?}", tile_id);
+        return;
+    };
 
-        // Paint all the elements in each tile
-        for tiled_layout_element in &mut tile.elements {
-            tiled_layout_element.paint_commands = painter.paint(tiled_layout_element);
-        }
+    // if not dirty, no need to render
+    if tile.state != TileState::Dirty {
+        return;
     }
+
+    // Paint all the elements in the tile
+    painter.paint_tile(tile);
 }
 
 fn do_rasterize(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     renderer: Arc<RefCell<Renderer>>,
-    layer_id: LayerId,
+    tile_id: TileId,
 ) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
@@ -412,33 +522,19 @@ fn do_rasterize(
         return;
     };
 
-    let tile_ids = tile_list
-        .read()
-        .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
-    for tile_id in tile_ids {
-        // get tile
-        let mut binding = tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
-
-        // if not dirty, no need to render and continue
-        if tile.state == TileState::Clean {
-            continue;
-        }
-
-        // Rasterize the tile into a texture
-        let rasterizer = VelloRasterizer::new(device, queue, &renderer);
-        let texture_id = rasterizer.rasterize(tile);
-
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
-            log::warn!("Tile not found: {:?}", tile_id);
-            continue;
-        };
+    let mut binding = tile_list.write().expect("Failed to get tile list");
+    let Some(tile) = binding.get_tile(tile_id) else {
+        log::warn!("Tile not found: {:?}", tile_id);
+        return;
+    };
 
-        tile.texture_id = Some(texture_id);
-        tile.state = TileState::Clean;
+    // if not dirty, no need to render
+    if tile.state != TileState::Dirty {
+        return;
     }
+
+    // Rasterize the tile into a texture. Empty tiles and tiles with the same content as another tile are not
+    // rasterized again.
+    let rasterizer = VelloRasterizer::new(device, queue, &renderer);
+    binding.rasterize_tile(tile_id, |tile| rasterizer.rasterize(tile));
 }
@@ -6,7 +6,7 @@ pub mod browser_state;
 pub mod font;
 
 mod texture_store;
-mod hash;
+pub mod hash;
 
 pub use texture_store::get_texture_store;
 pub use media::get_media_store;
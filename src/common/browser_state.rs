@@ -1,9 +1,17 @@
 use std::fmt::Debug;
 use std::sync::{Arc, OnceLock, RwLock};
+use crate::animator::{AnimationFrame, Animator};
+use crate::animator::interpolate::AnimatedValue;
 use crate::common::document::document::Document;
-use crate::common::geo::Rect;
+use crate::common::document::node::{NodeId, NodeType};
+use crate::common::document::media_query::{ColorScheme, MediaEnvironment};
+use crate::common::document::style::{StyleProperty, StyleValue};
+use crate::common::geo::{Dimension, Rect};
+use crate::layering::layer::LayerId;
+use crate::layering::policy::{AnimatedLayerPolicy, CombinedLayerPolicy, DefaultLayerPolicy, ImageLayerPolicy};
 use crate::layouter::LayoutElementId;
-use crate::tiler::TileList;
+use crate::painter::display_list::DisplayList;
+use crate::tiler::{TileList, MAX_ZOOM, MIN_ZOOM};
 
 #[derive(Debug)]
 pub enum WireframeState {
@@ -15,7 +23,7 @@ pub enum WireframeState {
 /// Things that can change in the browser is stored in this structure. It keeps the current rendering pipeline (in the form of a layer_list),
 /// and some things that we can control, or is controlled by the user (like current_hovered_element).
 pub struct BrowserState {
-    /// List of layers that will be visible are set to true
+    /// Visibility of each layer, in layer order (from bottom to top). Layers without an entry are visible.
     pub visible_layer_list: Vec<bool>,
     /// Defines if we need to draw wireframes, or the actual content, or both
     pub wireframed: WireframeState,
@@ -25,12 +33,246 @@ pub struct BrowserState {
     pub show_tilegrid: bool,
     /// When set, this is the element that is currently hovered upon
     pub current_hovered_element: Option<LayoutElementId>,
-    /// Current viewport offset + size
+    /// Current viewport offset + size in CSS pixels
     pub viewport: Rect,
-    /// Main document that is currently being rendered
+    /// Number of device pixels per CSS pixel (2.0 on most HiDPI screens). Layout is done in CSS pixels, only
+    /// the tiles are rendered and composited at device resolution.
+    pub device_pixel_ratio: f64,
+    /// Zoom factor of the page (pinch zoom). Zooming does not reflow the page, it only changes the scale the tiles
+    /// are rendered and composited at.
+    pub zoom: f64,
+    /// Main document that is currently being rendered, without any animated styles. Media queries and transitions
+    /// are based on this document.
     pub document: Arc<Document>,
+    /// Animated styles that cannot be handled by the compositor, and must be laid out and painted
+    pub animated_styles: Vec<(NodeId, StyleProperty, StyleValue)>,
+    /// Copy of the document with the animated styles applied. None when no styles are animated.
+    pub animated_document: Option<Arc<Document>>,
     /// LayerList that is currently being rendered
     pub tile_list: Option<RwLock<TileList>>,
+    /// Color scheme the user prefers (prefers-color-scheme media queries)
+    pub color_scheme: ColorScheme,
+    /// Runs the transitions and animations of the document
+    pub animator: Animator,
+    /// Retained paint commands of the layout elements
+    pub display_list: DisplayList,
+}
+
+impl BrowserState {
+    /// Returns the environment media queries are evaluated against
+    pub fn media_environment(&self) -> MediaEnvironment {
+        MediaEnvironment {
+            resolution: self.device_pixel_ratio,
+            color_scheme: self.color_scheme,
+            ..MediaEnvironment::new(self.viewport.width, self.viewport.height)
+        }
+    }
+
+    /// Number of device pixels per CSS pixel on screen, which includes the zoom
+    pub fn device_scale(&self) -> f64 {
+        self.device_pixel_ratio * self.zoom
+    }
+
+    /// Size of the viewport in device pixels
+    pub fn device_viewport(&self) -> Dimension {
+        Dimension::new(
+            (self.viewport.width * self.device_scale()).round(),
+            (self.viewport.height * self.device_scale()).round(),
+        )
+    }
+
+    /// Sets the zoom factor. The viewport keeps its size on screen, so it covers less (or more) of the page. Tiles
+    /// of the previous zoom level are shown scaled until the tiles of the new zoom level are rendered.
+    pub fn set_zoom(&mut self, zoom: f64) {
+        let device_viewport = self.device_viewport();
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        if let Some(tile_list) = &self.tile_list {
+            tile_list.write().expect("Failed to get tile list").set_zoom(self.zoom);
+        }
+
+        self.viewport.width = device_viewport.width / self.device_scale();
+        self.viewport.height = device_viewport.height / self.device_scale();
+    }
+
+    /// Sets the device pixel ratio, for instance when the window is moved to another screen. The layout stays
+    /// the same, but all tiles need to be rendered again at the new resolution.
+    pub fn set_device_pixel_ratio(&mut self, device_pixel_ratio: f64) {
+        self.device_pixel_ratio = device_pixel_ratio;
+        if let Some(tile_list) = &self.tile_list {
+            tile_list.write().expect("Failed to get tile list").set_device_pixel_ratio(device_pixel_ratio);
+        }
+    }
+
+    /// Replaces the tile list, for instance after a reflow. The layer visibility toggles are resized to the
+    /// number of layers, where new layers are visible.
+    pub fn set_tile_list(&mut self, tile_list: TileList) {
+        let layer_count = tile_list.layer_list.layer_ids.read().expect("Failed to lock layer IDs").len();
+        self.visible_layer_list.resize(layer_count, true);
+        // Paint commands of elements that did not change can be used for the new tiles as well
+        self.display_list.retain_unchanged(&tile_list.layer_list.layout_tree);
+        self.tile_list = Some(RwLock::new(tile_list));
+    }
+
+    /// Returns the layers that are visible, ordered from bottom to top
+    pub fn visible_layers(&self) -> Vec<LayerId> {
+        let Some(tile_list) = &self.tile_list else {
+            return Vec::new();
+        };
+        let tile_list = tile_list.read().expect("Failed to get tile list");
+
+        tile_list.layer_list.ordered_layer_ids()
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| self.visible_layer_list.get(*index).copied().unwrap_or(true))
+            .map(|(_, layer_id)| layer_id)
+            .collect()
+    }
+
+    /// Toggles the visibility of the layer at the given position in the layer order. Returns false when there is
+    /// no layer at that position.
+    pub fn toggle_layer(&mut self, index: usize) -> bool {
+        match self.visible_layer_list.get_mut(index) {
+            Some(visible) => {
+                *visible = !*visible;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the element the mouse is over. When the box model of the hovered element is shown, only the tiles of
+    /// the previous and the new hovered element are invalidated. Returns true when the screen needs a redraw.
+    pub fn set_hovered_element(&mut self, element_id: Option<LayoutElementId>) -> bool {
+        if self.current_hovered_element == element_id {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut self.current_hovered_element, element_id);
+        if !self.debug_hover {
+            return false;
+        }
+
+        if let Some(tile_list) = &self.tile_list {
+            let mut tile_list = tile_list.write().expect("Failed to get tile list");
+            for element_id in [previous, element_id].into_iter().flatten() {
+                tile_list.invalidate_element(element_id);
+            }
+        }
+        true
+    }
+
+    /// Toggles showing the box model of the hovered element. Only the tiles of the hovered element are invalidated.
+    pub fn toggle_debug_hover(&mut self) {
+        self.debug_hover = !self.debug_hover;
+
+        if let (Some(tile_list), Some(element_id)) = (&self.tile_list, self.current_hovered_element) {
+            tile_list.write().expect("Failed to get tile list").invalidate_element(element_id);
+        }
+    }
+
+    /// Returns true when the styles of the document need to be recomputed for the current viewport
+    pub fn media_changed(&self) -> bool {
+        self.document.media_changed(&self.media_environment())
+    }
+
+    /// Recomputes the styles of the document when a media query matches differently for the current viewport,
+    /// for instance when a breakpoint is crossed. Returns true when the document has changed and needs a reflow.
+    pub fn update_media(&mut self) -> bool {
+        if !self.media_changed() {
+            return false;
+        }
+
+        let env = self.media_environment();
+        let mut doc = (*self.document).clone();
+        doc.apply_media(&env);
+
+        self.set_document(doc);
+        true
+    }
+
+    /// Replaces the document with a new version of it, for instance after its styles have changed. Transitions are
+    /// started for the styles that differ from the current document, and new animations are started.
+    pub fn set_document(&mut self, doc: Document) {
+        self.animator.start_transitions(&self.document, &doc);
+        self.animator.start_animations(&doc);
+        self.document = Arc::new(doc);
+        self.animated_document = self.animate_document();
+    }
+
+    /// Returns the document that must be laid out and painted: the document with the animated styles applied
+    pub fn render_document(&self) -> Arc<Document> {
+        self.animated_document.clone().unwrap_or_else(|| self.document.clone())
+    }
+
+    /// Returns the policy that decides which elements get their own layer: the default policy, every image (so the
+    /// layer toggles have something to show), and every element with a running opacity or transform animation.
+    pub fn layer_policy(&self) -> CombinedLayerPolicy {
+        CombinedLayerPolicy::new(vec![
+            Box::new(DefaultLayerPolicy::default()),
+            Box::new(ImageLayerPolicy),
+            Box::new(AnimatedLayerPolicy::new(self.animator.composited_nodes())),
+        ])
+    }
+
+    /// Applies the animated values that cannot be handled by the compositor to a copy of the document, which is
+    /// used for layout and paint (see `render_document`). The document itself keeps its own styles. Opacity and
+    /// transform are never applied, as these are set on the layer of the element (see `apply_animation_layers`).
+    /// Returns true when the animated styles have changed and need a reflow.
+    pub fn apply_animation_styles(&mut self, frame: &AnimationFrame) -> bool {
+        let animated_styles: Vec<_> = frame.values.iter()
+            .filter(|animated| !animated.value.is_composited())
+            .map(|animated| (animated.node_id, animated.property.clone(), animated.value.to_style_value()))
+            .filter(|(node_id, property, value)| {
+                let current = self.document.get_node_by_id(*node_id).and_then(|node| match &node.node_type {
+                    NodeType::Element(data) => data.get_style(property.clone()),
+                    _ => None,
+                });
+                current != Some(value)
+            })
+            .collect();
+
+        if animated_styles == self.animated_styles {
+            return false;
+        }
+
+        self.animated_styles = animated_styles;
+        self.animated_document = self.animate_document();
+        true
+    }
+
+    /// Returns a copy of the document with the animated styles applied, or None when there are none
+    fn animate_document(&self) -> Option<Arc<Document>> {
+        if self.animated_styles.is_empty() {
+            return None;
+        }
+
+        let mut doc = (*self.document).clone();
+        for (node_id, property, value) in &self.animated_styles {
+            doc.set_style(*node_id, property.clone(), value.clone());
+        }
+        Some(Arc::new(doc))
+    }
+
+    /// Applies the animated opacity and transform of elements with their own layer to that layer. Only the
+    /// compositor needs to run again afterward.
+    pub fn apply_animation_layers(&self, frame: &AnimationFrame) {
+        let Some(tile_list) = &self.tile_list else {
+            return;
+        };
+        let tile_list = tile_list.read().expect("Failed to get tile list");
+
+        for animated in &frame.values {
+            match &animated.value {
+                AnimatedValue::Opacity(opacity) => {
+                    tile_list.layer_list.set_element_opacity(animated.node_id, *opacity);
+                }
+                AnimatedValue::Transform(transform) => {
+                    tile_list.layer_list.set_element_transform(animated.node_id, *transform);
+                }
+                AnimatedValue::Style(_) => {}
+            }
+        }
+    }
 }
 
 impl Debug for BrowserState {
@@ -42,6 +284,9 @@ impl Debug for BrowserState {
             .field("show_tilegrid", &self.show_tilegrid)
             .field("current_hovered_element", &self.current_hovered_element)
             .field("viewport", &self.viewport)
+            .field("device_pixel_ratio", &self.device_pixel_ratio)
+            .field("zoom", &self.zoom)
+            .field("color_scheme", &self.color_scheme)
             .finish()
     }
 }
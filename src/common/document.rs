@@ -1,4 +1,5 @@
 pub mod node;
 pub mod style;
 pub mod document;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod media_query;
\ No newline at end of file
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use crate::common::document::node::{Node, NodeType, NodeId, AttrMap};
-use crate::common::document::style::StylePropertyList;
+use crate::common::document::media_query::MediaEnvironment;
+use crate::common::document::style::{Keyframe, StyleProperty, StylePropertyList, StyleValue};
+use crate::common::geo::{Dimension, Rect};
 
 /// Main DOM document structure
 #[derive(Clone)]
@@ -11,6 +13,17 @@ pub struct Document {
     pub root_id: Option<NodeId>,
 
     pub base_url: String,
+
+    /// Border boxes of the elements as laid out by the browser the document was captured with (soupertoo
+    /// --bbox). Only used to compare our layout against a reference browser.
+    pub reference_boxes: HashMap<NodeId, Rect>,
+    /// Viewport the reference boxes were captured with
+    pub reference_viewport: Option<Dimension>,
+
+    /// Styles from the @page rules (size and margins), used when the document is paginated
+    pub page_style: StylePropertyList,
+    /// Keyframes of the @keyframes rules, by animation name
+    pub keyframes: HashMap<String, Vec<Keyframe>>,
 }
 
 impl Document {
@@ -20,6 +33,10 @@ impl Document {
             root_id: None,
             next_node_id: Arc::new(RwLock::new(NodeId::new(1))),
             base_url: base_url.to_string(),
+            reference_boxes: HashMap::new(),
+            reference_viewport: None,
+            page_style: StylePropertyList::new(),
+            keyframes: HashMap::new(),
         }
     }
 
@@ -96,6 +113,87 @@ impl Document {
         *nid += 1;
         id
     }
+
+    /// Sets a style of an element, for instance the current value of an animation. The text nodes of the element
+    /// copy its styles, so they are updated as well. Returns false when the node is not an element.
+    pub fn set_style(&mut self, node_id: NodeId, prop: StyleProperty, value: StyleValue) -> bool {
+        let Some(node) = self.arena.get_mut(&node_id) else {
+            return false;
+        };
+        let NodeType::Element(data) = &mut node.node_type else {
+            return false;
+        };
+        data.styles.set_property(prop.clone(), value.clone());
+
+        for child_id in node.children.clone() {
+            if let Some(NodeType::Text(_, text_styles)) = self.arena.get_mut(&child_id).map(|n| &mut n.node_type) {
+                text_styles.set_property(prop.clone(), value.clone());
+            }
+        }
+
+        true
+    }
+
+    /// Returns true when a media query of any element matches differently in the given environment than the last
+    /// time the styles were computed, which means the styles (and layout) need to be recomputed.
+    pub fn media_changed(&self, env: &MediaEnvironment) -> bool {
+        self.arena.values().any(|node| match &node.node_type {
+            NodeType::Element(data) => data.media.as_ref().is_some_and(|media| media.changed(env)),
+            _ => false,
+        })
+    }
+
+    /// Recomputes the styles of all elements for the given environment. Returns true when any media query matched
+    /// differently than before.
+    pub fn apply_media(&mut self, env: &MediaEnvironment) -> bool {
+        if !self.media_changed(env) {
+            return false;
+        }
+
+        if let Some(root_id) = self.root_id {
+            self.cascade_media(root_id, None, env);
+        }
+
+        true
+    }
+
+    /// Computes the styles of the node for the given environment, and then those of its children. Inherited
+    /// properties the element does not declare itself get the value of the parent, and text decorations are
+    /// propagated from the parent again. Text nodes get the styles of their parent.
+    fn cascade_media(&mut self, node_id: NodeId, parent_styles: Option<&StylePropertyList>, env: &MediaEnvironment) {
+        let Some(node) = self.arena.get_mut(&node_id) else {
+            return;
+        };
+
+        let styles = match &mut node.node_type {
+            NodeType::Element(data) => {
+                if let Some(media) = data.media.as_mut() {
+                    let mut styles = media.compute(env);
+                    if let Some(parent_styles) = parent_styles {
+                        for (prop, value) in &parent_styles.properties {
+                            if prop.is_inherited() && !media.declares(prop) {
+                                styles.set_property(prop.clone(), value.clone());
+                            }
+                        }
+                        styles.propagate_text_decoration(parent_styles);
+                    }
+                    data.styles = styles;
+                }
+                data.styles.clone()
+            }
+            NodeType::Text(_, text_styles) => {
+                if let Some(parent_styles) = parent_styles {
+                    *text_styles = parent_styles.clone();
+                }
+                return;
+            }
+            NodeType::Comment(_) => return,
+        };
+
+        for child_id in node.children.clone() {
+            self.cascade_media(child_id, Some(&styles), env);
+        }
+    }
 }
 
 #[allow(unused)]
@@ -109,6 +207,30 @@ impl Document {
         &self.base_url
     }
 
+    /// Returns the text of the first `<title>` element, with its whitespace collapsed, or None when the document
+    /// has no (non-empty) title.
+    pub fn title(&self) -> Option<String> {
+        let mut node_ids: Vec<&NodeId> = self.arena.keys().collect();
+        node_ids.sort_by_key(|id| id.to_u64());
+
+        let title = node_ids.into_iter()
+            .filter_map(|id| self.get_node_by_id(*id))
+            .find(|node| matches!(&node.node_type, NodeType::Element(data) if data.tag_name.eq_ignore_ascii_case("title")))?;
+
+        let text: Vec<&str> = title.children.iter()
+            .filter_map(|id| match self.get_node_by_id(*id).map(|n| &n.node_type) {
+                Some(NodeType::Text(text, _)) => Some(text.as_str()),
+                _ => None,
+            })
+            .flat_map(|text| text.split_whitespace())
+            .collect();
+
+        match text.is_empty() {
+            true => None,
+            false => Some(text.join(" ")),
+        }
+    }
+
     #[allow(unused)]
     pub fn walk_depth_first<F>(&self, node_id: NodeId, cb: &mut F)
     where
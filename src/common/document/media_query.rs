@@ -0,0 +1,747 @@
+use std::collections::HashMap;
+use crate::common::document::style::{StyleProperty, StylePropertyList};
+
+/// Font size used for em and rem units in media queries. These are always relative to the initial font size.
+const INITIAL_FONT_SIZE: f64 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaType {
+    #[default]
+    Screen,
+    Print,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// Everything media queries are evaluated against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaEnvironment {
+    pub media_type: MediaType,
+    /// Width of the viewport in CSS pixels
+    pub width: f64,
+    /// Height of the viewport in CSS pixels
+    pub height: f64,
+    /// Device pixels per CSS pixel
+    pub resolution: f64,
+    pub color_scheme: ColorScheme,
+}
+
+impl MediaEnvironment {
+    /// Screen environment with the given viewport size
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            media_type: MediaType::Screen,
+            width,
+            height,
+            resolution: 1.0,
+            color_scheme: ColorScheme::Light,
+        }
+    }
+}
+
+/// Position of a declaration in the cascade. Declarations that sort later win: important declarations win from
+/// normal ones, inline styles from style rules, then the rule with the highest specificity, and then the rule that
+/// comes last in the stylesheets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct CascadeOrder {
+    pub important: bool,
+    pub inline: bool,
+    /// Number of ids, classes and types in the selector
+    pub specificity: (u32, u32, u32),
+    /// Index of the rule in the stylesheets
+    pub source_order: usize,
+}
+
+/// Styles from a `@media` rule that apply to an element when the media query matches
+#[derive(Debug, Clone)]
+pub struct MediaStyle {
+    pub query: MediaQueryList,
+    pub styles: StylePropertyList,
+    pub order: CascadeOrder,
+}
+
+/// Styles of an element that depend on media queries
+#[derive(Debug, Clone)]
+pub struct ElementMediaStyles {
+    /// Styles without any `@media` rules applied
+    pub base: StylePropertyList,
+    /// Cascade order of the properties the element declares itself in the base styles. Properties that are not
+    /// declared have their inherited or initial value.
+    pub declared: HashMap<StyleProperty, CascadeOrder>,
+    /// Media styles in the order they are found in the stylesheets
+    pub rules: Vec<MediaStyle>,
+    /// Which rules matched when the styles were last computed
+    matched: Vec<bool>,
+}
+
+impl ElementMediaStyles {
+    pub fn new(base: StylePropertyList, declared: HashMap<StyleProperty, CascadeOrder>, rules: Vec<MediaStyle>) -> Self {
+        let matched = vec![false; rules.len()];
+        Self { base, declared, rules, matched }
+    }
+
+    /// Returns true when the rules match differently in the given environment than the last time the styles were
+    /// computed
+    pub fn changed(&self, env: &MediaEnvironment) -> bool {
+        self.rules.iter().zip(&self.matched).any(|(rule, matched)| rule.query.matches(env) != *matched)
+    }
+
+    /// Computes the styles for the given environment: for each property, the declaration of the base styles or of
+    /// a matching media style that comes last in the cascade
+    pub fn compute(&mut self, env: &MediaEnvironment) -> StylePropertyList {
+        let mut styles = self.base.clone();
+        let mut winners = self.declared.clone();
+        for (rule, matched) in self.rules.iter().zip(self.matched.iter_mut()) {
+            *matched = rule.query.matches(env);
+            if !*matched {
+                continue;
+            }
+
+            for (prop, value) in &rule.styles.properties {
+                if winners.get(prop).is_none_or(|order| rule.order >= *order) {
+                    styles.set_property(prop.clone(), value.clone());
+                    winners.insert(prop.clone(), rule.order);
+                }
+            }
+        }
+        styles
+    }
+
+    /// Returns true when the element declares the property itself, in its base styles or in a media style that
+    /// matched when the styles were last computed
+    pub fn declares(&self, prop: &StyleProperty) -> bool {
+        self.declared.contains_key(prop) || self.rules.iter().zip(&self.matched)
+            .any(|(rule, matched)| *matched && rule.styles.properties.contains_key(prop))
+    }
+}
+
+/// A comma separated list of media queries, as found in `@media <list> { ... }`. The list matches when any of the
+/// queries match. An empty list always matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQueryList {
+    queries: Vec<MediaQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MediaQuery {
+    /// `not screen and (...)` negates the whole query
+    negated: bool,
+    /// None when only a condition is given, which is the same as `all`
+    media_type: Option<QueryMediaType>,
+    condition: Option<MediaCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryMediaType {
+    All,
+    Type(MediaType),
+    /// Media types we don't know (tv, speech etc) never match
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+    /// Anything we cannot parse. This never matches, like browsers do with invalid queries.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MediaFeature {
+    name: String,
+    /// None for boolean features like `(color)`
+    comparison: Option<(Comparison, MediaValue)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    /// Flips the comparison for `value < feature` notations
+    fn flip(self) -> Self {
+        match self {
+            Comparison::Equal => Comparison::Equal,
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessEqual => Comparison::GreaterEqual,
+            Comparison::Greater => Comparison::Less,
+            Comparison::GreaterEqual => Comparison::LessEqual,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MediaValue {
+    /// Numbers, lengths (in px), ratios and resolutions (in dppx) are all compared as numbers
+    Number(f64),
+    Ident(String),
+}
+
+impl MediaQueryList {
+    pub fn parse(input: &str) -> Self {
+        let input = input.trim().to_ascii_lowercase();
+        let queries = split_top_level(&input, ',')
+            .iter()
+            .map(|query| query.trim())
+            .filter(|query| !query.is_empty())
+            .map(parse_query)
+            .collect();
+
+        Self { queries }
+    }
+
+    pub fn matches(&self, env: &MediaEnvironment) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|query| query.matches(env))
+    }
+}
+
+impl MediaQuery {
+    fn matches(&self, env: &MediaEnvironment) -> bool {
+        let type_matches = match &self.media_type {
+            None | Some(QueryMediaType::All) => true,
+            Some(QueryMediaType::Type(media_type)) => *media_type == env.media_type,
+            Some(QueryMediaType::Unknown) => false,
+        };
+        let condition_matches = self.condition.as_ref().is_none_or(|c| c.matches(env));
+
+        (type_matches && condition_matches) != self.negated
+    }
+}
+
+impl MediaCondition {
+    fn matches(&self, env: &MediaEnvironment) -> bool {
+        match self {
+            MediaCondition::Feature(feature) => feature.matches(env),
+            MediaCondition::Not(condition) => !condition.matches(env),
+            MediaCondition::And(conditions) => conditions.iter().all(|c| c.matches(env)),
+            MediaCondition::Or(conditions) => conditions.iter().any(|c| c.matches(env)),
+            MediaCondition::Unknown => false,
+        }
+    }
+}
+
+impl MediaFeature {
+    fn matches(&self, env: &MediaEnvironment) -> bool {
+        let Some(actual) = feature_value(&self.name, env) else {
+            return false;
+        };
+
+        let Some((comparison, expected)) = &self.comparison else {
+            // Boolean context: the feature matches when it is not zero or none
+            return match actual {
+                MediaValue::Number(n) => n != 0.0,
+                MediaValue::Ident(ident) => ident != "none",
+            };
+        };
+
+        match (&actual, expected) {
+            (MediaValue::Number(a), MediaValue::Number(e)) => match comparison {
+                Comparison::Equal => (a - e).abs() < f64::EPSILON,
+                Comparison::Less => a < e,
+                Comparison::LessEqual => a <= e,
+                Comparison::Greater => a > e,
+                Comparison::GreaterEqual => a >= e,
+            },
+            (MediaValue::Ident(a), MediaValue::Ident(e)) => *comparison == Comparison::Equal && a == e,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the value of a media feature in the given environment, or None when we don't know the feature
+fn feature_value(name: &str, env: &MediaEnvironment) -> Option<MediaValue> {
+    let value = match name {
+        "width" | "device-width" => MediaValue::Number(env.width),
+        "height" | "device-height" => MediaValue::Number(env.height),
+        "aspect-ratio" | "device-aspect-ratio" => MediaValue::Number(env.width / env.height.max(1.0)),
+        "orientation" => MediaValue::Ident(if env.height >= env.width { "portrait" } else { "landscape" }.to_string()),
+        "resolution" => MediaValue::Number(env.resolution),
+        "prefers-color-scheme" => MediaValue::Ident(match env.color_scheme {
+            ColorScheme::Light => "light".to_string(),
+            ColorScheme::Dark => "dark".to_string(),
+        }),
+        "color" => MediaValue::Number(8.0),
+        "monochrome" => MediaValue::Number(0.0),
+        "hover" | "any-hover" => MediaValue::Ident(match env.media_type {
+            MediaType::Screen => "hover".to_string(),
+            MediaType::Print => "none".to_string(),
+        }),
+        "pointer" | "any-pointer" => MediaValue::Ident(match env.media_type {
+            MediaType::Screen => "fine".to_string(),
+            MediaType::Print => "none".to_string(),
+        }),
+        "prefers-reduced-motion" => MediaValue::Ident("no-preference".to_string()),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn parse_query(query: &str) -> MediaQuery {
+    let mut rest = query;
+    let mut negated = false;
+
+    if let Some(r) = strip_keyword(rest, "not") {
+        if !r.starts_with('(') {
+            negated = true;
+            rest = r;
+        }
+    } else if let Some(r) = strip_keyword(rest, "only") {
+        rest = r;
+    }
+
+    if rest.starts_with('(') || rest.starts_with("not") {
+        return MediaQuery { negated, media_type: None, condition: Some(parse_condition(rest)) };
+    }
+
+    // A media type, optionally followed by "and <condition>"
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let media_type = match &rest[..end] {
+        "all" => QueryMediaType::All,
+        "screen" => QueryMediaType::Type(MediaType::Screen),
+        "print" => QueryMediaType::Type(MediaType::Print),
+        _ => QueryMediaType::Unknown,
+    };
+
+    let rest = rest[end..].trim();
+    let condition = if rest.is_empty() {
+        None
+    } else {
+        match strip_keyword(rest, "and") {
+            Some(condition) => Some(parse_condition(condition)),
+            None => Some(MediaCondition::Unknown),
+        }
+    };
+
+    MediaQuery { negated, media_type: Some(media_type), condition }
+}
+
+/// Parses `(a) and (b)`, `(a) or (b)`, `not (a)` and nested conditions
+fn parse_condition(input: &str) -> MediaCondition {
+    let input = input.trim();
+
+    if let Some(rest) = strip_keyword(input, "not") {
+        return MediaCondition::Not(Box::new(parse_condition(rest)));
+    }
+
+    let mut terms = Vec::new();
+    let mut operator: Option<&str> = None;
+    let mut rest = input;
+
+    loop {
+        let Some(end) = matching_paren(rest) else {
+            return MediaCondition::Unknown;
+        };
+        terms.push(parse_in_parens(&rest[1..end]));
+
+        rest = rest[end + 1..].trim();
+        if rest.is_empty() {
+            break;
+        }
+
+        let (op, r) = match (strip_keyword(rest, "and"), strip_keyword(rest, "or")) {
+            (Some(r), _) => ("and", r),
+            (_, Some(r)) => ("or", r),
+            _ => return MediaCondition::Unknown,
+        };
+        // Mixing and/or without parentheses is not allowed
+        if operator.is_some_and(|o| o != op) {
+            return MediaCondition::Unknown;
+        }
+        operator = Some(op);
+        rest = r;
+    }
+
+    match (operator, terms.len()) {
+        (_, 1) => terms.remove(0),
+        (Some("or"), _) => MediaCondition::Or(terms),
+        _ => MediaCondition::And(terms),
+    }
+}
+
+/// Parses the inside of parentheses: a nested condition, a feature, or a range
+fn parse_in_parens(input: &str) -> MediaCondition {
+    let input = input.trim();
+    if input.starts_with('(') || input.starts_with("not ") {
+        return parse_condition(input);
+    }
+
+    // (name: value), with min- and max- prefixes
+    if let Some((name, value)) = input.split_once(':') {
+        let name = name.trim();
+        let Some(value) = parse_value(value.trim()) else {
+            return MediaCondition::Unknown;
+        };
+
+        let (comparison, name) = if let Some(name) = name.strip_prefix("min-") {
+            (Comparison::GreaterEqual, name)
+        } else if let Some(name) = name.strip_prefix("max-") {
+            (Comparison::LessEqual, name)
+        } else {
+            (Comparison::Equal, name)
+        };
+
+        return MediaCondition::Feature(MediaFeature { name: name.to_string(), comparison: Some((comparison, value)) });
+    }
+
+    // Range syntax: (width >= 600px), (400px < width <= 700px)
+    let tokens = split_comparisons(input);
+    match tokens.as_slice() {
+        [single] => MediaCondition::Feature(MediaFeature { name: single.to_string(), comparison: None }),
+        [left, op, right] => parse_range(left, op, right).unwrap_or(MediaCondition::Unknown),
+        [low, op1, name, op2, high] => {
+            match (parse_range(low, op1, name), parse_range(name, op2, high)) {
+                (Some(a), Some(b)) => MediaCondition::And(vec![a, b]),
+                _ => MediaCondition::Unknown,
+            }
+        }
+        _ => MediaCondition::Unknown,
+    }
+}
+
+fn parse_range(left: &str, op: &str, right: &str) -> Option<MediaCondition> {
+    let comparison = match op {
+        "=" => Comparison::Equal,
+        "<" => Comparison::Less,
+        "<=" => Comparison::LessEqual,
+        ">" => Comparison::Greater,
+        ">=" => Comparison::GreaterEqual,
+        _ => return None,
+    };
+
+    // Either "feature op value" or "value op feature"
+    let is_name = |s: &str| s.chars().next().is_some_and(|c| c.is_ascii_alphabetic());
+    let (name, comparison, value) = if is_name(left) {
+        (left, comparison, right)
+    } else {
+        (right, comparison.flip(), left)
+    };
+
+    Some(MediaCondition::Feature(MediaFeature {
+        name: name.to_string(),
+        comparison: Some((comparison, parse_value(value)?)),
+    }))
+}
+
+/// Parses a length (in px), a ratio, a resolution (in dppx), a number or an identifier
+fn parse_value(value: &str) -> Option<MediaValue> {
+    if let Some((num, den)) = value.split_once('/') {
+        let num = num.trim().parse::<f64>().ok()?;
+        let den = den.trim().parse::<f64>().ok()?;
+        return (den != 0.0).then_some(MediaValue::Number(num / den));
+    }
+
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(split);
+
+    let Ok(num) = num.parse::<f64>() else {
+        return Some(MediaValue::Ident(value.to_string()));
+    };
+
+    let value = match unit.trim() {
+        "" | "px" | "dppx" | "x" => num,
+        "em" | "rem" => num * INITIAL_FONT_SIZE,
+        "in" => num * 96.0,
+        "cm" => num * 96.0 / 2.54,
+        "mm" => num * 96.0 / 25.4,
+        "pt" => num * 96.0 / 72.0,
+        "dpi" => num / 96.0,
+        "dpcm" => num * 2.54 / 96.0,
+        _ => return None,
+    };
+
+    Some(MediaValue::Number(value))
+}
+
+/// Splits "400px <= width < 700px" into values and comparison operators
+fn split_comparisons(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if matches!(c, '<' | '>' | '=') {
+            if !current.trim().is_empty() {
+                tokens.push(current.trim().to_string());
+            }
+            current.clear();
+
+            let mut op = c.to_string();
+            if c != '=' && chars.peek() == Some(&'=') {
+                op.push('=');
+                chars.next();
+            }
+            tokens.push(op);
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        tokens.push(current.trim().to_string());
+    }
+
+    tokens
+}
+
+/// Returns the input without the keyword when it starts with it (followed by whitespace or a parenthesis)
+fn strip_keyword<'a>(input: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = input.strip_prefix(keyword)?;
+    if rest.starts_with(|c: char| c.is_whitespace() || c == '(') {
+        Some(rest.trim_start())
+    } else {
+        None
+    }
+}
+
+/// Returns the index of the parenthesis closing the one the input starts with
+fn matching_paren(input: &str) -> Option<usize> {
+    if !input.starts_with('(') {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn split_top_level(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, width: f64, height: f64) -> bool {
+        MediaQueryList::parse(query).matches(&MediaEnvironment::new(width, height))
+    }
+
+    #[test]
+    fn test_media_types() {
+        assert!(matches("", 800.0, 600.0));
+        assert!(matches("all", 800.0, 600.0));
+        assert!(matches("screen", 800.0, 600.0));
+        assert!(!matches("print", 800.0, 600.0));
+        assert!(matches("not print", 800.0, 600.0));
+        assert!(matches("only screen and (min-width: 600px)", 800.0, 600.0));
+        assert!(matches("print, screen", 800.0, 600.0));
+        assert!(!matches("tv", 800.0, 600.0));
+
+        let mut env = MediaEnvironment::new(800.0, 600.0);
+        env.media_type = MediaType::Print;
+        assert!(MediaQueryList::parse("print and (orientation: landscape)").matches(&env));
+    }
+
+    #[test]
+    fn test_width_and_height() {
+        assert!(matches("(min-width: 600px)", 800.0, 600.0));
+        assert!(!matches("(max-width: 600px)", 800.0, 600.0));
+        assert!(matches("(max-width: 50em)", 800.0, 600.0));
+        assert!(matches("screen and (min-width: 600px) and (max-height: 700px)", 800.0, 600.0));
+        assert!(!matches("(min-width: 600px) and (min-height: 700px)", 800.0, 600.0));
+        assert!(matches("(min-width: 900px) or (min-height: 500px)", 800.0, 600.0));
+        assert!(matches("not (min-width: 900px)", 800.0, 600.0));
+        assert!(matches("(width)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_range_syntax() {
+        assert!(matches("(width >= 600px)", 800.0, 600.0));
+        assert!(!matches("(width < 600px)", 800.0, 600.0));
+        assert!(matches("(400px <= width <= 800px)", 800.0, 600.0));
+        assert!(!matches("(400px < width < 800px)", 800.0, 600.0));
+        assert!(matches("(600px < width)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_other_features() {
+        assert!(matches("(orientation: landscape)", 800.0, 600.0));
+        assert!(matches("(orientation: portrait)", 600.0, 800.0));
+        assert!(matches("(min-aspect-ratio: 4/3)", 800.0, 600.0));
+        assert!(!matches("(min-aspect-ratio: 16/9)", 800.0, 600.0));
+        assert!(matches("(prefers-color-scheme: light)", 800.0, 600.0));
+        assert!(!matches("(min-resolution: 2dppx)", 800.0, 600.0));
+        assert!(matches("(min-resolution: 96dpi)", 800.0, 600.0));
+
+        let mut env = MediaEnvironment::new(800.0, 600.0);
+        env.color_scheme = ColorScheme::Dark;
+        env.resolution = 2.0;
+        assert!(MediaQueryList::parse("(prefers-color-scheme: dark)").matches(&env));
+        assert!(MediaQueryList::parse("(min-resolution: 2x)").matches(&env));
+    }
+
+    #[test]
+    fn test_invalid_queries_never_match() {
+        assert!(!matches("(unknown-feature: 10px)", 800.0, 600.0));
+        assert!(!matches("(min-width: 10foo)", 800.0, 600.0));
+        assert!(!matches("screen and", 800.0, 600.0));
+        assert!(!matches("(min-width: 1px) and (min-height: 1px) or (color)", 800.0, 600.0));
+    }
+
+    #[test]
+    fn test_element_media_styles() {
+        use crate::common::document::style::{StyleProperty, StyleValue, Unit};
+
+        let width = |px: f32| {
+            let mut styles = StylePropertyList::new();
+            styles.set_property(StyleProperty::Width, StyleValue::Unit(px, Unit::Px));
+            styles
+        };
+        let mut media = ElementMediaStyles::new(width(100.0), HashMap::new(), vec![
+            MediaStyle { query: MediaQueryList::parse("(min-width: 600px)"), styles: width(200.0), order: CascadeOrder::default() },
+            MediaStyle { query: MediaQueryList::parse("(min-width: 1000px)"), styles: width(300.0), order: CascadeOrder::default() },
+        ]);
+
+        let narrow = MediaEnvironment::new(400.0, 600.0);
+        let wide = MediaEnvironment::new(1200.0, 600.0);
+
+        // Nothing matches yet, so the base styles are still valid
+        assert!(!media.changed(&narrow));
+        assert!(media.changed(&wide));
+
+        let styles = media.compute(&wide);
+        assert!(matches!(styles.get_property(StyleProperty::Width), Some(StyleValue::Unit(w, _)) if *w == 300.0));
+        assert!(media.declares(&StyleProperty::Width));
+        assert!(!media.changed(&MediaEnvironment::new(1100.0, 600.0)));
+        assert!(media.changed(&MediaEnvironment::new(800.0, 600.0)));
+
+        let styles = media.compute(&narrow);
+        assert!(matches!(styles.get_property(StyleProperty::Width), Some(StyleValue::Unit(w, _)) if *w == 100.0));
+        assert!(!media.declares(&StyleProperty::Width));
+    }
+
+    #[test]
+    fn test_media_styles_cascade() {
+        use crate::common::document::style::{StyleProperty, StyleValue, Unit};
+
+        let width = |px: f32| {
+            let mut styles = StylePropertyList::new();
+            styles.set_property(StyleProperty::Width, StyleValue::Unit(px, Unit::Px));
+            styles
+        };
+        let order = |specificity, source_order| CascadeOrder { specificity, source_order, ..Default::default() };
+        let computed_width = |media: &mut ElementMediaStyles| match media.compute(&MediaEnvironment::new(800.0, 600.0)).get_property(StyleProperty::Width) {
+            Some(StyleValue::Unit(w, _)) => *w,
+            _ => 0.0,
+        };
+
+        // `#id { width: 100px }` comes after `@media { .a { width: 200px } }`, and wins by its specificity and order
+        let id_rule = HashMap::from([(StyleProperty::Width, order((1, 0, 0), 2))]);
+        let mut media = ElementMediaStyles::new(width(100.0), id_rule.clone(), vec![
+            MediaStyle { query: MediaQueryList::parse("screen"), styles: width(200.0), order: order((0, 1, 0), 1) },
+        ]);
+        assert_eq!(computed_width(&mut media), 100.0);
+
+        // A media rule with the same specificity later in the stylesheets wins
+        let mut media = ElementMediaStyles::new(width(100.0), id_rule.clone(), vec![
+            MediaStyle { query: MediaQueryList::parse("screen"), styles: width(200.0), order: order((1, 0, 0), 3) },
+            MediaStyle { query: MediaQueryList::parse("screen"), styles: width(300.0), order: order((0, 2, 0), 4) },
+        ]);
+        assert_eq!(computed_width(&mut media), 200.0);
+
+        // Inline styles win from any rule, unless it is important
+        let inline = HashMap::from([(StyleProperty::Width, CascadeOrder { inline: true, ..Default::default() })]);
+        let mut media = ElementMediaStyles::new(width(100.0), inline, vec![
+            MediaStyle { query: MediaQueryList::parse("screen"), styles: width(200.0), order: order((1, 0, 0), 3) },
+        ]);
+        assert_eq!(computed_width(&mut media), 100.0);
+        media.rules[0].order.important = true;
+        assert_eq!(computed_width(&mut media), 200.0);
+    }
+
+    #[test]
+    fn test_apply_media_inheritance() {
+        use crate::common::document::document::Document;
+        use crate::common::document::node::NodeType;
+        use crate::common::document::style::{Color, StyleProperty, StyleValue};
+
+        let color = |name: &str| {
+            let mut styles = StylePropertyList::new();
+            styles.set_property(StyleProperty::Color, StyleValue::Color(Color::Named(name.to_string())));
+            styles
+        };
+        let declares_color = || HashMap::from([(StyleProperty::Color, CascadeOrder::default())]);
+
+        // <div> (red, blue on wide screens) > <p> (inherits) > text, and <div> > <span> (green)
+        let mut doc = Document::new("https://example.com");
+        let element = |doc: &mut Document, parent, styles: StylePropertyList, declared, rules| {
+            let node_id = doc.new_element(parent, "div", None, false, Some(styles.clone()));
+            if let Some(NodeType::Element(data)) = doc.arena.get_mut(&node_id).map(|n| &mut n.node_type) {
+                data.media = Some(ElementMediaStyles::new(styles, declared, rules));
+            }
+            if let Some(parent) = parent {
+                doc.add_child(parent, node_id);
+            }
+            node_id
+        };
+        let div = element(&mut doc, None, color("red"), declares_color(), vec![
+            MediaStyle { query: MediaQueryList::parse("(min-width: 1000px)"), styles: color("blue"), order: CascadeOrder::default() },
+        ]);
+        let p = element(&mut doc, Some(div), color("red"), HashMap::new(), vec![]);
+        let span = element(&mut doc, Some(div), color("green"), declares_color(), vec![]);
+        let text = doc.new_text(Some(p), "text", Some(color("red")));
+        doc.add_child(p, text);
+        doc.set_root(div);
+
+        let color_of = |doc: &Document, node_id| match doc.get_node_by_id(node_id).map(|n| &n.node_type) {
+            Some(NodeType::Element(data)) => data.styles.get_property(StyleProperty::Color).cloned(),
+            Some(NodeType::Text(_, styles)) => styles.get_property(StyleProperty::Color).cloned(),
+            _ => None,
+        };
+        let named = |name: &str| Some(StyleValue::Color(Color::Named(name.to_string())));
+
+        assert!(doc.apply_media(&MediaEnvironment::new(1200.0, 800.0)));
+        assert_eq!(color_of(&doc, div), named("blue"));
+        assert_eq!(color_of(&doc, p), named("blue"));
+        assert_eq!(color_of(&doc, text), named("blue"));
+        assert_eq!(color_of(&doc, span), named("green"));
+
+        assert!(doc.apply_media(&MediaEnvironment::new(800.0, 600.0)));
+        assert_eq!(color_of(&doc, p), named("red"));
+        assert_eq!(color_of(&doc, text), named("red"));
+    }
+}
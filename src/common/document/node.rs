@@ -2,6 +2,7 @@ use std::cmp::PartialEq;
 use std::collections::HashMap;
 use std::ops::AddAssign;
 use crate::common::document::document::Document;
+use crate::common::document::media_query::ElementMediaStyles;
 use crate::common::document::style::{StylePropertyList, StyleValue, StyleProperty, Display};
 use crate::rendertree_builder::RenderNodeId;
 
@@ -60,6 +61,9 @@ pub struct ElementData {
     pub self_closing: bool,
     /// Element styles (color, font-size etc)
     pub styles: StylePropertyList,
+    /// Cascaded styles of the element, including those of `@media` rules. When set, `styles` is computed from these
+    /// for the media environment.
+    pub media: Option<ElementMediaStyles>,
 }
 
 impl ElementData {
@@ -69,6 +73,7 @@ impl ElementData {
             attributes: attributes.unwrap_or(AttrMap::new()),
             self_closing: is_self_closing,
             styles: styles.unwrap_or(StylePropertyList::new()),
+            media: None,
         }
     }
 
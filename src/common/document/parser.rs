@@ -3,11 +3,16 @@ use regex::Regex;
 use serde::Deserialize;
 use std::collections::HashMap;
 use crate::common::document::document::Document;
+use crate::common::document::media_query::{CascadeOrder, ElementMediaStyles, MediaEnvironment, MediaQueryList, MediaStyle};
 use crate::common::document::node::{AttrMap, NodeId, NodeType};
-use crate::common::document::style::{Color, Display, FontWeight, StyleProperty, StylePropertyList, StyleValue, TextWrap, Unit};
+use crate::common::geo::{Dimension, Rect};
+use crate::common::document::style::{AspectRatio, Color, Direction, Display, FontStyle, Keyframe, FontWeight, StyleProperty, StylePropertyList, StyleValue, TextDecorationLine, TextDecorationStyle, TextWrap, UnicodeBidi, Unit, WordBreak, OverflowWrap, Hyphens, LineBreak, WhiteSpace, TextOverflow, PageBreak};
 // This parses uses the tools/souper.py to load a JSON file and create a DOM from it. This allows us to render
 // a webpage with minimal effort, and without connecting a whole html5 and css parser to it.
 
+/// Viewport soupertoo captures pages with, used when the captured document does not tell us
+pub const CAPTURE_VIEWPORT: Dimension = Dimension { width: 1280.0, height: 1144.0 };
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DomNode {
@@ -25,6 +30,58 @@ struct DomNode {
     styles: HashMap<String, String>,
     #[serde(default)]
     children: Vec<DomNode>,
+    /// Bounding box as computed by the browser (only when captured with --bbox)
+    #[serde(default)]
+    bbox: Option<BoundingBox>,
+    /// Styles from @media rules that apply to this element when the media query matches
+    #[serde(default, rename = "media_styles")]
+    media_styles: Vec<DomMediaStyle>,
+    /// Cascade order of the properties the element declares itself (not captured by older versions of soupertoo)
+    #[serde(default)]
+    cascade: Option<HashMap<String, DomCascadeOrder>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomMediaStyle {
+    media: String,
+    #[serde(default)]
+    styles: HashMap<String, String>,
+    #[serde(flatten)]
+    order: DomCascadeOrder,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DomCascadeOrder {
+    important: bool,
+    inline: bool,
+    specificity: (u32, u32, u32),
+    order: usize,
+}
+
+impl From<&DomCascadeOrder> for CascadeOrder {
+    fn from(order: &DomCascadeOrder) -> Self {
+        CascadeOrder {
+            important: order.important,
+            inline: order.inline,
+            specificity: order.specificity,
+            source_order: order.order,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BoundingBox {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Viewport {
+    width: f64,
+    height: f64,
 }
 
 #[allow(unused)]
@@ -36,6 +93,21 @@ struct DomRoot {
     #[serde(default)]
     styles: HashMap<String, String>,
     children: Vec<DomNode>,
+    #[serde(default)]
+    viewport: Option<Viewport>,
+    /// Declarations of the @page rules
+    #[serde(default)]
+    page: HashMap<String, String>,
+    /// Keyframes of the @keyframes rules, by animation name
+    #[serde(default)]
+    keyframes: HashMap<String, Vec<DomKeyframe>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomKeyframe {
+    offset: f32,
+    #[serde(default)]
+    styles: HashMap<String, String>,
 }
 
 // Text is "as-is" from the JSON, but we don't want text with multiple spaces and newlines.
@@ -71,8 +143,36 @@ fn create_dom_from_json(doc: &mut Document, node: &DomNode, parent_id: Option<No
     };
 
 
-    let style = get_style_from_node(node);
-    let node_id = doc.new_element(parent_id, &tag, Some(attrs), node.self_closing, Some(style.clone()));
+    let base = parse_styles(&node.styles);
+    let mut style = base.clone();
+    let parent_media = parent_id.and_then(|parent_id| match doc.get_node_by_id(parent_id).map(|n| &n.node_type) {
+        Some(NodeType::Element(parent)) => {
+            style.propagate_text_decoration(&parent.styles);
+            parent.media.as_ref()
+        }
+        _ => None,
+    });
+    let declared = match &node.cascade {
+        Some(cascade) => declared_properties(cascade),
+        // Without the cascade, we assume the element declares everything it does not inherit from its parent
+        None => base.properties.keys()
+            .filter(|prop| !prop.is_inherited() || parent_media.is_none_or(|parent| parent.base.get_property((*prop).clone()) != base.get_property((*prop).clone())))
+            .map(|prop| (prop.clone(), CascadeOrder::default()))
+            .collect(),
+    };
+    let rules = node.media_styles.iter().map(|m| MediaStyle {
+        query: MediaQueryList::parse(&m.media),
+        styles: parse_styles(&m.styles),
+        order: CascadeOrder::from(&m.order),
+    }).collect();
+
+    let node_id = doc.new_element(parent_id, &tag, Some(attrs), node.self_closing, Some(style));
+    if let Some(NodeType::Element(data)) = doc.arena.get_mut(&node_id).map(|n| &mut n.node_type) {
+        data.media = Some(ElementMediaStyles::new(base, declared, rules));
+    }
+    if let Some(bbox) = &node.bbox {
+        doc.reference_boxes.insert(node_id, Rect::new(bbox.x, bbox.y, bbox.width, bbox.height));
+    }
 
     // if node_id.is_greater_than(24) {
     //     return None
@@ -88,13 +188,49 @@ fn create_dom_from_json(doc: &mut Document, node: &DomNode, parent_id: Option<No
     Some(node_id)
 }
 
-fn get_style_from_node(node: &DomNode) -> StylePropertyList {
+/// Returns the style properties the element declares, with the cascade order of the declaration that won. A
+/// declaration can set multiple properties, like the longhands of a shorthand.
+fn declared_properties(cascade: &HashMap<String, DomCascadeOrder>) -> HashMap<StyleProperty, CascadeOrder> {
+    let mut declared = HashMap::new();
+    for (name, order) in cascade {
+        let order = CascadeOrder::from(order);
+        for prop in parse_styles(&HashMap::from([(name.clone(), String::new())])).properties.into_keys() {
+            let winner = declared.entry(prop).or_insert(order);
+            *winner = order.max(*winner);
+        }
+    }
+    declared
+}
+
+fn parse_styles(styles: &HashMap<String, String>) -> StylePropertyList {
     let mut style = StylePropertyList::new();
 
-    for (key, value) in &node.styles {
+    // The styles are not ordered, so the text-decoration shorthand is applied first and the longhands override it
+    if let Some(value) = styles.get("text-decoration") {
+        parse_text_decoration(value, &mut style);
+    }
+
+    for (key, value) in styles {
         match key.as_str() {
             "display" => style.set_property(StyleProperty::Display, parse_display(value)),
             "position" => style.set_property(StyleProperty::Position, parse_position(value)),
+            "z-index" => style.set_property(StyleProperty::ZIndex, parse_style_num(value)),
+            "opacity" => style.set_property(StyleProperty::Opacity, parse_style_num(value)),
+            "transform" => style.set_property(StyleProperty::Transform, parse_style_str(value)),
+            "isolation" => style.set_property(StyleProperty::Isolation, parse_style_str(value)),
+            "float" => style.set_property(StyleProperty::Float, parse_style_str(value)),
+            "will-change" => style.set_property(StyleProperty::WillChange, parse_style_str(value)),
+            "animation-name" => style.set_property(StyleProperty::AnimationName, parse_style_str(value)),
+            "animation-duration" => style.set_property(StyleProperty::AnimationDuration, parse_style_str(value)),
+            "animation-timing-function" => style.set_property(StyleProperty::AnimationTimingFunction, parse_style_str(value)),
+            "animation-delay" => style.set_property(StyleProperty::AnimationDelay, parse_style_str(value)),
+            "animation-iteration-count" => style.set_property(StyleProperty::AnimationIterationCount, parse_style_str(value)),
+            "animation-direction" => style.set_property(StyleProperty::AnimationDirection, parse_style_str(value)),
+            "animation-fill-mode" => style.set_property(StyleProperty::AnimationFillMode, parse_style_str(value)),
+            "transition-property" => style.set_property(StyleProperty::TransitionProperty, parse_style_str(value)),
+            "transition-duration" => style.set_property(StyleProperty::TransitionDuration, parse_style_str(value)),
+            "transition-timing-function" => style.set_property(StyleProperty::TransitionTimingFunction, parse_style_str(value)),
+            "transition-delay" => style.set_property(StyleProperty::TransitionDelay, parse_style_str(value)),
 
             "width" => style.set_property(StyleProperty::Width, parse_style_value(value)),
             "height" => style.set_property(StyleProperty::Height, parse_style_value(value)),
@@ -124,8 +260,10 @@ fn get_style_from_node(node: &DomNode) -> StylePropertyList {
 
             "color" => style.set_property(StyleProperty::Color, StyleValue::Color(Color::Named(value.to_string()))),
             "background-color" => style.set_property(StyleProperty::BackgroundColor, StyleValue::Color(Color::Named(value.to_string()))),
+            "background-image" => style.set_property(StyleProperty::BackgroundImage, parse_style_str(value)),
 
             "font-weight" => style.set_property(StyleProperty::FontWeight, parse_font_weight(value)),
+            "font-style" => style.set_property(StyleProperty::FontStyle, parse_font_style(value)),
             "font-size" => style.set_property(StyleProperty::FontSize, parse_style_value(value)),
             "font-family" => style.set_property(StyleProperty::FontFamily, StyleValue::Keyword(value.to_string())),
 
@@ -135,14 +273,33 @@ fn get_style_from_node(node: &DomNode) -> StylePropertyList {
             "flex-shrink" => style.set_property(StyleProperty::FlexShrink, parse_style_num(value)),
             "flex-wrap" => style.set_property(StyleProperty::FlexWrap, parse_style_str(value)),
 
-            "aspect-ratio" => style.set_property(StyleProperty::AspectRatio, parse_style_num(value)),
+            "aspect-ratio" => style.set_property(StyleProperty::AspectRatio, parse_aspect_ratio(value)),
             "gap" => style.set_property(StyleProperty::Gap, parse_style_value(value)),
             "align-items" => style.set_property(StyleProperty::AlignItems, parse_style_str(value)),
             "align-self" => style.set_property(StyleProperty::AlignSelf, parse_style_str(value)),
             "align-content" => style.set_property(StyleProperty::AlignContent, parse_style_str(value)),
             "text-align" => style.set_property(StyleProperty::TextAlign, parse_text_align(value)),
+            "direction" => style.set_property(StyleProperty::Direction, parse_direction(value)),
+            "unicode-bidi" => style.set_property(StyleProperty::UnicodeBidi, parse_unicode_bidi(value)),
             "line-height" => style.set_property(StyleProperty::LineHeight, parse_style_value(value)),
             "text-wrap" => style.set_property(StyleProperty::TextWrap, parse_text_wrap(value)),
+            "word-break" => style.set_property(StyleProperty::WordBreak, parse_word_break(value)),
+            // word-wrap is the legacy name of overflow-wrap
+            "overflow-wrap" | "word-wrap" => style.set_property(StyleProperty::OverflowWrap, parse_overflow_wrap(value)),
+            "hyphens" => style.set_property(StyleProperty::Hyphens, parse_hyphens(value)),
+            "line-break" => style.set_property(StyleProperty::LineBreak, parse_line_break(value)),
+            "white-space" => style.set_property(StyleProperty::WhiteSpace, parse_white_space(value)),
+            "text-overflow" => style.set_property(StyleProperty::TextOverflow, parse_text_overflow(value)),
+            "line-clamp" | "-webkit-line-clamp" => style.set_property(StyleProperty::LineClamp, parse_style_num(value)),
+            "text-decoration-line" => style.set_property(StyleProperty::TextDecorationLine, parse_text_decoration_line(value)),
+            "text-decoration-color" => style.set_property(StyleProperty::TextDecorationColor, StyleValue::Color(Color::Named(value.to_string()))),
+            "text-decoration-style" => style.set_property(StyleProperty::TextDecorationStyle, parse_text_decoration_style(value)),
+
+            // The page-break-* properties are the legacy names of break-*
+            "break-before" | "page-break-before" => style.set_property(StyleProperty::BreakBefore, parse_page_break(value)),
+            "break-after" | "page-break-after" => style.set_property(StyleProperty::BreakAfter, parse_page_break(value)),
+            "break-inside" | "page-break-inside" => style.set_property(StyleProperty::BreakInside, parse_page_break(value)),
+            "size" => style.set_property(StyleProperty::Size, parse_style_str(value)),
 
             "inset-block-end" => style.set_property(StyleProperty::InsetBlockEnd, parse_style_value(value)),
             "inset-block-start" => style.set_property(StyleProperty::InsetBlockStart, parse_style_value(value)),
@@ -153,6 +310,14 @@ fn get_style_from_node(node: &DomNode) -> StylePropertyList {
             "justify-self" => style.set_property(StyleProperty::JustifySelf, parse_style_str(value)),
             "justify-content" => style.set_property(StyleProperty::JustifyContent, parse_style_str(value)),
 
+            "overflow" => {
+                // One value for both axis, or separate values for x and y
+                let mut values = value.split_whitespace();
+                let x = values.next().unwrap_or(value);
+                let y = values.next().unwrap_or(x);
+                style.set_property(StyleProperty::OverflowX, parse_style_str(x));
+                style.set_property(StyleProperty::OverflowY, parse_style_str(y));
+            }
             "overflow-x" => style.set_property(StyleProperty::OverflowX, parse_style_str(value)),
             "overflow-y" => style.set_property(StyleProperty::OverflowY, parse_style_str(value)),
             "box-sizing" => style.set_property(StyleProperty::BoxSizing, parse_style_str(value)),
@@ -180,6 +345,67 @@ fn parse_text_wrap(value: &str) -> StyleValue {
     }
 }
 
+fn parse_word_break(value: &str) -> StyleValue {
+    match value {
+        "break-all" => StyleValue::WordBreak(WordBreak::BreakAll),
+        "keep-all" => StyleValue::WordBreak(WordBreak::KeepAll),
+        "break-word" => StyleValue::WordBreak(WordBreak::BreakWord),
+        _ => StyleValue::WordBreak(WordBreak::Normal),
+    }
+}
+
+fn parse_overflow_wrap(value: &str) -> StyleValue {
+    match value {
+        "anywhere" => StyleValue::OverflowWrap(OverflowWrap::Anywhere),
+        "break-word" => StyleValue::OverflowWrap(OverflowWrap::BreakWord),
+        _ => StyleValue::OverflowWrap(OverflowWrap::Normal),
+    }
+}
+
+fn parse_hyphens(value: &str) -> StyleValue {
+    match value {
+        "none" => StyleValue::Hyphens(Hyphens::None),
+        "auto" => StyleValue::Hyphens(Hyphens::Auto),
+        _ => StyleValue::Hyphens(Hyphens::Manual),
+    }
+}
+
+fn parse_line_break(value: &str) -> StyleValue {
+    match value {
+        "loose" => StyleValue::LineBreak(LineBreak::Loose),
+        "normal" => StyleValue::LineBreak(LineBreak::Normal),
+        "strict" => StyleValue::LineBreak(LineBreak::Strict),
+        "anywhere" => StyleValue::LineBreak(LineBreak::Anywhere),
+        _ => StyleValue::LineBreak(LineBreak::Auto),
+    }
+}
+
+fn parse_white_space(value: &str) -> StyleValue {
+    match value {
+        "nowrap" => StyleValue::WhiteSpace(WhiteSpace::NoWrap),
+        "pre" => StyleValue::WhiteSpace(WhiteSpace::Pre),
+        "pre-wrap" => StyleValue::WhiteSpace(WhiteSpace::PreWrap),
+        "pre-line" => StyleValue::WhiteSpace(WhiteSpace::PreLine),
+        "break-spaces" => StyleValue::WhiteSpace(WhiteSpace::BreakSpaces),
+        _ => StyleValue::WhiteSpace(WhiteSpace::Normal),
+    }
+}
+
+fn parse_text_overflow(value: &str) -> StyleValue {
+    match value {
+        "ellipsis" => StyleValue::TextOverflow(TextOverflow::Ellipsis),
+        _ => StyleValue::TextOverflow(TextOverflow::Clip),
+    }
+}
+
+fn parse_page_break(value: &str) -> StyleValue {
+    match value {
+        "page" | "always" | "left" | "right" | "recto" | "verso" => StyleValue::PageBreak(PageBreak::Page),
+        "avoid" | "avoid-page" => StyleValue::PageBreak(PageBreak::Avoid),
+        _ => StyleValue::PageBreak(PageBreak::Auto),
+    }
+}
+
 fn parse_position(position: &str) -> StyleValue {
     StyleValue::Keyword(position.to_string())
 }
@@ -190,8 +416,8 @@ fn parse_style_str(val: &str) -> StyleValue {
 
 fn parse_text_align(val: &str) -> StyleValue {
     match val {
-        "left" => StyleValue::TextAlign(TextAlign::Start),
-        "right" => StyleValue::TextAlign(TextAlign::End),
+        "left" => StyleValue::TextAlign(TextAlign::Left),
+        "right" => StyleValue::TextAlign(TextAlign::Right),
         "start" => StyleValue::TextAlign(TextAlign::Start),
         "end" => StyleValue::TextAlign(TextAlign::End),
         "center" => StyleValue::TextAlign(TextAlign::Center),
@@ -200,6 +426,24 @@ fn parse_text_align(val: &str) -> StyleValue {
     }
 }
 
+fn parse_direction(val: &str) -> StyleValue {
+    match val {
+        "rtl" => StyleValue::Direction(Direction::Rtl),
+        _ => StyleValue::Direction(Direction::Ltr),
+    }
+}
+
+fn parse_unicode_bidi(val: &str) -> StyleValue {
+    match val {
+        "embed" => StyleValue::UnicodeBidi(UnicodeBidi::Embed),
+        "isolate" => StyleValue::UnicodeBidi(UnicodeBidi::Isolate),
+        "bidi-override" => StyleValue::UnicodeBidi(UnicodeBidi::BidiOverride),
+        "isolate-override" => StyleValue::UnicodeBidi(UnicodeBidi::IsolateOverride),
+        "plaintext" => StyleValue::UnicodeBidi(UnicodeBidi::Plaintext),
+        _ => StyleValue::UnicodeBidi(UnicodeBidi::Normal),
+    }
+}
+
 fn parse_style_num(val: &str) -> StyleValue {
     if let Ok(num) = val.parse::<f32>() {
         StyleValue::Number(num)
@@ -208,6 +452,26 @@ fn parse_style_num(val: &str) -> StyleValue {
     }
 }
 
+/// Parses aspect-ratio values like "1.5", "16 / 9" and "auto 16 / 9". With "auto", replaced elements prefer their
+/// natural ratio when they have one.
+fn parse_aspect_ratio(val: &str) -> StyleValue {
+    let val = val.trim();
+    let auto = val.starts_with("auto") || val.ends_with("auto");
+    let ratio = val.trim_start_matches("auto").trim_end_matches("auto").trim();
+    if ratio.is_empty() {
+        return StyleValue::Keyword(val.to_string());
+    }
+
+    let mut parts = ratio.splitn(2, '/').map(|p| p.trim().parse::<f32>());
+    let ratio = match (parts.next(), parts.next()) {
+        (Some(Ok(w)), None) if w > 0.0 => w,
+        (Some(Ok(w)), Some(Ok(h))) if w > 0.0 && h > 0.0 => w / h,
+        _ => return StyleValue::Keyword(val.to_string()),
+    };
+
+    StyleValue::AspectRatio(AspectRatio { auto, ratio })
+}
+
 fn parse_display(value: &String) -> StyleValue {
     match value.as_str() {
         "block" => StyleValue::Display(Display::Block),
@@ -236,7 +500,8 @@ fn parse_style_value(value: &str) -> StyleValue {
 
 fn parse_font_weight(value: &str) -> StyleValue {
     match value {
-        "bold" | "bolder" => StyleValue::FontWeight(FontWeight::Bolder),
+        "bold" => StyleValue::FontWeight(FontWeight::Bold),
+        "bolder" => StyleValue::FontWeight(FontWeight::Bolder),
         "lighter" => StyleValue::FontWeight(FontWeight::Lighter),
         "normal" => StyleValue::FontWeight(FontWeight::Normal),
         _ => {
@@ -249,12 +514,103 @@ fn parse_font_weight(value: &str) -> StyleValue {
     }
 }
 
+fn parse_font_style(value: &str) -> StyleValue {
+    // Oblique can have an angle ("oblique 10deg"), which we don't support
+    match value.split_whitespace().next() {
+        Some("italic") => StyleValue::FontStyle(FontStyle::Italic),
+        Some("oblique") => StyleValue::FontStyle(FontStyle::Oblique),
+        _ => StyleValue::FontStyle(FontStyle::Normal),
+    }
+}
+
+fn parse_text_decoration_line(value: &str) -> StyleValue {
+    let mut line = TextDecorationLine::NONE;
+    for part in value.split_whitespace() {
+        match part {
+            "underline" => line.underline = true,
+            "overline" => line.overline = true,
+            "line-through" => line.line_through = true,
+            _ => {}
+        }
+    }
+
+    StyleValue::TextDecorationLine(line)
+}
+
+/// Parses the text-decoration shorthand ("underline dotted red") into the line, style and color longhands. Any
+/// value that is not a line or style keyword is taken as the color.
+fn parse_text_decoration(value: &str, style: &mut StylePropertyList) {
+    let mut lines = Vec::new();
+    let mut decoration_style = None;
+    let mut color = None;
+    for part in split_components(value) {
+        match part.as_str() {
+            "none" | "underline" | "overline" | "line-through" => lines.push(part),
+            "solid" | "double" | "dotted" | "dashed" | "wavy" => decoration_style = Some(part),
+            _ => color = Some(part),
+        }
+    }
+
+    style.set_property(StyleProperty::TextDecorationLine, parse_text_decoration_line(&lines.join(" ")));
+    style.set_property(StyleProperty::TextDecorationStyle, parse_text_decoration_style(decoration_style.as_deref().unwrap_or("solid")));
+    // Without a color, the decoration is drawn in the color of the text
+    if let Some(color) = color {
+        style.set_property(StyleProperty::TextDecorationColor, StyleValue::Color(Color::Named(color)));
+    }
+}
+
+/// Splits a value on whitespace, but keeps functions like "rgb(0, 0, 0)" together.
+fn split_components(value: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    parts
+}
+
+fn parse_text_decoration_style(value: &str) -> StyleValue {
+    match value {
+        "double" => StyleValue::TextDecorationStyle(TextDecorationStyle::Double),
+        "dotted" => StyleValue::TextDecorationStyle(TextDecorationStyle::Dotted),
+        "dashed" => StyleValue::TextDecorationStyle(TextDecorationStyle::Dashed),
+        "wavy" => StyleValue::TextDecorationStyle(TextDecorationStyle::Wavy),
+        _ => StyleValue::TextDecorationStyle(TextDecorationStyle::Solid),
+    }
+}
+
 pub fn document_from_json(base_url: &str, path: &str) -> Document {
     let mut doc = Document::new(base_url);
 
     let json_data = std::fs::read_to_string(path).expect("Failed to read JSON file");
     let dom_root: DomRoot = serde_json::from_str(&json_data).expect("Failed to parse JSON");
 
+    doc.reference_viewport = dom_root.viewport.map(|v| Dimension::new(v.width, v.height));
+    doc.page_style = parse_styles(&dom_root.page);
+    for (name, keyframes) in &dom_root.keyframes {
+        let mut keyframes: Vec<Keyframe> = keyframes.iter()
+            .map(|k| Keyframe { offset: k.offset.clamp(0.0, 1.0), styles: parse_styles(&k.styles) })
+            .collect();
+        keyframes.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+        doc.keyframes.insert(name.clone(), keyframes);
+    }
+
     let root_node_id = doc.new_element(None, "DocumentRoot", None, false, None);
     for node in dom_root.children {
         if let Some(child_node_id) = create_dom_from_json(&mut doc, &node, Some(root_node_id)) {
@@ -263,5 +619,11 @@ pub fn document_from_json(base_url: &str, path: &str) -> Document {
     }
 
     doc.set_root(root_node_id);
+
+    // Apply the media styles for the viewport the page was captured with, so the document starts out with the
+    // same styles as in the browser. The viewport of the browser state will apply its own media when it differs.
+    let viewport = doc.reference_viewport.unwrap_or(CAPTURE_VIEWPORT);
+    doc.apply_media(&MediaEnvironment::new(viewport.width, viewport.height));
+
     doc
 }
\ No newline at end of file
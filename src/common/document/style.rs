@@ -4,8 +4,10 @@ use std::collections::HashMap;
 pub enum StyleProperty {
     Color,
     BackgroundColor,
+    BackgroundImage,
     FontSize,
     FontWeight,
+    FontStyle,
     Display,
     Width,
     Height,
@@ -45,6 +47,8 @@ pub enum StyleProperty {
     AlignSelf,
     AlignContent,
     TextAlign,
+    Direction,
+    UnicodeBidi,
 
     InsetBlockEnd,
     InsetBlockStart,
@@ -58,6 +62,38 @@ pub enum StyleProperty {
     BoxSizing,
     LineHeight,
     TextWrap,
+    WordBreak,
+    OverflowWrap,
+    Hyphens,
+    LineBreak,
+    WhiteSpace,
+    TextOverflow,
+    LineClamp,
+    TextDecorationLine,
+    TextDecorationColor,
+    TextDecorationStyle,
+    BreakBefore,
+    BreakAfter,
+    BreakInside,
+    /// Size of the page, only used in @page rules
+    Size,
+    ZIndex,
+    Opacity,
+    Transform,
+    Isolation,
+    Float,
+    WillChange,
+    AnimationName,
+    AnimationDuration,
+    AnimationTimingFunction,
+    AnimationDelay,
+    AnimationIterationCount,
+    AnimationDirection,
+    AnimationFillMode,
+    TransitionProperty,
+    TransitionDuration,
+    TransitionTimingFunction,
+    TransitionDelay,
     GridRow,
     GridColumn,
     GridAutoFlow,
@@ -67,6 +103,18 @@ pub enum StyleProperty {
     GridAutoColumns,
 }
 
+impl StyleProperty {
+    /// Returns true for the properties that elements inherit from their parent when they do not declare them
+    pub fn is_inherited(&self) -> bool {
+        matches!(self,
+            StyleProperty::Color | StyleProperty::FontSize | StyleProperty::FontWeight | StyleProperty::FontStyle |
+            StyleProperty::FontFamily | StyleProperty::TextAlign | StyleProperty::Direction | StyleProperty::LineHeight |
+            StyleProperty::TextWrap | StyleProperty::WordBreak | StyleProperty::OverflowWrap | StyleProperty::Hyphens |
+            StyleProperty::LineBreak | StyleProperty::WhiteSpace
+        )
+    }
+}
+
 #[allow(unused)]
 #[derive(Clone, Debug, PartialEq)]
 pub enum Unit {
@@ -111,6 +159,167 @@ pub enum FontWeight {
     Number(f32),
 }
 
+impl FontWeight {
+    /// Resolves the weight to a numeric value. Bolder and lighter are relative to the inherited weight, as
+    /// defined in the table of CSS Fonts 4, §2.2.1.
+    pub fn resolve(&self, inherited: f32) -> f32 {
+        match self {
+            FontWeight::Normal => 400.0,
+            FontWeight::Bold => 700.0,
+            FontWeight::Number(value) => value.clamp(1.0, 1000.0),
+            FontWeight::Bolder => match inherited {
+                w if w < 350.0 => 400.0,
+                w if w < 550.0 => 700.0,
+                w if w < 900.0 => 900.0,
+                w => w,
+            },
+            FontWeight::Lighter => match inherited {
+                w if w < 100.0 => w,
+                w if w < 550.0 => 100.0,
+                w if w < 750.0 => 400.0,
+                _ => 700.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// Value of the aspect-ratio property (width / height). With `auto`, replaced elements that have a natural ratio use
+/// their natural ratio instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatio {
+    pub auto: bool,
+    pub ratio: f32,
+}
+
+/// Lines that decorate a text. Multiple lines can be set at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextDecorationLine {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+}
+
+impl TextDecorationLine {
+    pub const NONE: TextDecorationLine = TextDecorationLine { underline: false, overline: false, line_through: false };
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextDecorationStyle {
+    #[default]
+    Solid,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
+/// Base direction of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeBidi {
+    #[default]
+    Normal,
+    Embed,
+    Isolate,
+    BidiOverride,
+    IsolateOverride,
+    Plaintext,
+}
+
+/// Where lines may break inside words
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WordBreak {
+    #[default]
+    Normal,
+    BreakAll,
+    KeepAll,
+    /// Deprecated, same as `word-break: normal` with `overflow-wrap: anywhere`
+    BreakWord,
+}
+
+/// Whether words that do not fit on a line may be broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverflowWrap {
+    #[default]
+    Normal,
+    Anywhere,
+    BreakWord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hyphens {
+    None,
+    #[default]
+    Manual,
+    Auto,
+}
+
+/// Strictness of the line breaking rules, mostly for CJK text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineBreak {
+    #[default]
+    Auto,
+    Loose,
+    Normal,
+    Strict,
+    Anywhere,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhiteSpace {
+    #[default]
+    Normal,
+    NoWrap,
+    Pre,
+    PreWrap,
+    PreLine,
+    BreakSpaces,
+}
+
+impl WhiteSpace {
+    /// Returns true when lines may be wrapped at soft wrap opportunities
+    pub fn wraps(&self) -> bool {
+        !matches!(self, WhiteSpace::NoWrap | WhiteSpace::Pre)
+    }
+}
+
+/// How text that overflows its (non-visible overflow) container is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextOverflow {
+    #[default]
+    Clip,
+    Ellipsis,
+}
+
+/// Page break before, after or inside an element when the document is paginated. Column and region breaks are
+/// not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageBreak {
+    #[default]
+    Auto,
+    Avoid,
+    /// Always break. Left, right, recto and verso are also treated as a normal page break
+    Page,
+}
+
 #[allow(unused)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum StyleValue {
@@ -122,8 +331,29 @@ pub enum StyleValue {
     None,
     Display(Display),
     FontWeight(FontWeight),
+    FontStyle(FontStyle),
+    TextDecorationLine(TextDecorationLine),
+    TextDecorationStyle(TextDecorationStyle),
     TextWrap(TextWrap),
     TextAlign(TextAlign),
+    Direction(Direction),
+    UnicodeBidi(UnicodeBidi),
+    WordBreak(WordBreak),
+    OverflowWrap(OverflowWrap),
+    Hyphens(Hyphens),
+    LineBreak(LineBreak),
+    WhiteSpace(WhiteSpace),
+    TextOverflow(TextOverflow),
+    PageBreak(PageBreak),
+    AspectRatio(AspectRatio),
+}
+
+/// A single keyframe of a @keyframes rule
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    /// Offset of the keyframe in the animation (0.0 - 1.0)
+    pub offset: f32,
+    pub styles: StylePropertyList,
 }
 
 #[derive(Debug, Clone)]
@@ -174,6 +404,24 @@ impl StylePropertyList {
     pub fn get_property(&self, prop: StyleProperty) -> Option<&StyleValue> {
         self.properties.get(&prop)
     }
+
+    /// Text decorations are not inherited, but they are still drawn over the text of all descendants. We copy
+    /// the decoration of the parent when the element itself does not have any.
+    pub fn propagate_text_decoration(&mut self, parent: &StylePropertyList) {
+        let has_decoration = |style: &StylePropertyList| matches!(
+            style.get_property(StyleProperty::TextDecorationLine),
+            Some(StyleValue::TextDecorationLine(line)) if !line.is_none()
+        );
+        if has_decoration(self) || !has_decoration(parent) {
+            return;
+        }
+
+        for prop in [StyleProperty::TextDecorationLine, StyleProperty::TextDecorationColor, StyleProperty::TextDecorationStyle] {
+            if let Some(value) = parent.get_property(prop.clone()) {
+                self.set_property(prop, value.clone());
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -189,4 +437,20 @@ mod tests {
 
         assert_eq!(style.get_property(StyleProperty::Color), Some(&val.clone()));
     }
+
+    #[test]
+    fn test_font_weight_resolve() {
+        assert_eq!(FontWeight::Normal.resolve(700.0), 400.0);
+        assert_eq!(FontWeight::Bold.resolve(100.0), 700.0);
+        assert_eq!(FontWeight::Number(550.0).resolve(400.0), 550.0);
+
+        assert_eq!(FontWeight::Bolder.resolve(300.0), 400.0);
+        assert_eq!(FontWeight::Bolder.resolve(400.0), 700.0);
+        assert_eq!(FontWeight::Bolder.resolve(700.0), 900.0);
+        assert_eq!(FontWeight::Bolder.resolve(950.0), 950.0);
+
+        assert_eq!(FontWeight::Lighter.resolve(400.0), 100.0);
+        assert_eq!(FontWeight::Lighter.resolve(700.0), 400.0);
+        assert_eq!(FontWeight::Lighter.resolve(900.0), 700.0);
+    }
 }
\ No newline at end of file
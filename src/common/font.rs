@@ -1,6 +1,8 @@
 #[cfg(not(any(feature = "text_parley", feature = "text_pango", feature = "text_skia")))]
 compile_error!("Either the 'text_parley' 'text_skia' or 'text_pango' feature must be enabled");
 
+pub mod cache;
+
 #[cfg(feature = "text_parley")]
 pub mod parley;
 #[cfg(feature = "text_pango")]
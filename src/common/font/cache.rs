@@ -0,0 +1,181 @@
+use std::collections::{HashMap, VecDeque};
+use crate::layouter::text::TextStyle;
+
+/// Default number of text layouts we keep around
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// Key of a shaped text layout: the text together with its style, which holds every parameter that influences
+/// shaping or line breaking.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextLayoutKey {
+    text: String,
+    style: TextStyle,
+}
+
+impl TextLayoutKey {
+    pub fn new(text: &str, style: &TextStyle) -> Self {
+        Self {
+            text: text.to_string(),
+            style: style.clone(),
+        }
+    }
+}
+
+/// Statistics of a text layout cache
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    /// Number of lookups that returned an already shaped layout
+    pub hits: u64,
+    /// Number of lookups that needed to shape the text
+    pub misses: u64,
+    /// Number of layouts currently in the cache
+    pub entries: usize,
+}
+
+impl std::fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "hits: {}, misses: {}, entries: {}", self.hits, self.misses, self.entries)
+    }
+}
+
+/// Cache for shaped text layouts (skia paragraphs, parley layouts, pango layouts). The layouter measures the same
+/// text many times during a reflow, and the rasterizer needs the exact same layout again when painting. When the
+/// cache is full, the oldest entries are evicted first.
+pub struct TextLayoutCache<T> {
+    entries: HashMap<TextLayoutKey, T>,
+    /// Insertion order of the keys, used for eviction
+    order: VecDeque<TextLayoutKey>,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<T: Clone> TextLayoutCache<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the layout for the given key, or creates (and stores) it with the given function
+    pub fn get_or_insert_with(&mut self, key: TextLayoutKey, f: impl FnOnce() -> T) -> T {
+        if let Some(layout) = self.get(&key) {
+            return layout;
+        }
+
+        let layout = f();
+        self.insert(key, layout.clone());
+
+        layout
+    }
+
+    /// Returns the layout for the given key. Every lookup counts as either a hit or a miss.
+    pub fn get(&mut self, key: &TextLayoutKey) -> Option<T> {
+        match self.entries.get(key) {
+            Some(layout) => {
+                self.hits += 1;
+                Some(layout.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Stores the layout for the given key, evicting the oldest layouts when the cache is full
+    pub fn insert(&mut self, key: TextLayoutKey, layout: T) {
+        if let Some(existing) = self.entries.get_mut(&key) {
+            *existing = layout;
+            return;
+        }
+
+        while self.entries.len() >= self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+
+        self.order.push_back(key.clone());
+        self.entries.insert(key, layout);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+        }
+    }
+
+    /// Removes all layouts. Needed when fonts change, as the cached layouts reference the old fonts.
+    #[allow(unused)]
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+impl<T: Clone> Default for TextLayoutCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::style::{Direction, FontStyle};
+    use crate::layouter::text::{Alignment, TextWrapping};
+
+    fn key(text: &str, max_width: f64) -> TextLayoutKey {
+        TextLayoutKey::new(text, &TextStyle {
+            font_family: "Sans".to_string(),
+            font_size: 16.0,
+            font_weight: 400,
+            font_style: FontStyle::Normal,
+            line_height: 20.0,
+            max_width,
+            alignment: Alignment::Start,
+            direction: Direction::Ltr,
+            wrapping: TextWrapping::default(),
+        })
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = TextLayoutCache::new();
+
+        assert_eq!(cache.get_or_insert_with(key("hello", 100.0), || 1), 1);
+        assert_eq!(cache.get_or_insert_with(key("hello", 100.0), || 2), 1);
+        assert_eq!(cache.get_or_insert_with(key("hello", 200.0), || 3), 3);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 2);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = TextLayoutCache::with_capacity(2);
+
+        cache.get_or_insert_with(key("a", 100.0), || 1);
+        cache.get_or_insert_with(key("b", 100.0), || 2);
+        cache.get_or_insert_with(key("c", 100.0), || 3);
+        assert_eq!(cache.stats().entries, 2);
+
+        // "a" was the oldest entry and must be shaped again
+        assert_eq!(cache.get_or_insert_with(key("a", 100.0), || 4), 4);
+        assert_eq!(cache.get_or_insert_with(key("c", 100.0), || 5), 3);
+    }
+}
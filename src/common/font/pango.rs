@@ -1,10 +1,145 @@
+use std::cell::RefCell;
+use gtk4::cairo::{Context, Format, ImageSurface};
 use gtk4::gio::Settings;
 use gtk4::pango;
-use gtk4::pango::Weight;
+use gtk4::pango::{FontDescription, Layout, Weight, SCALE};
 use gtk4::prelude::{FontFamilyExt, SettingsExt};
+use pangocairo::functions::{context_set_resolution, create_layout};
+use crate::common::font::cache::{CacheStats, TextLayoutCache, TextLayoutKey};
+use crate::common::document::style::{Direction, FontStyle};
+use crate::layouter::text::breaking::breaks_overflowing_words;
+use crate::layouter::text::truncate::truncate_text;
+use crate::layouter::text::{Alignment, TextStyle, TextWrapping};
 
 const DEFAULT_FONT_FAMILY: &str = "sans";
 
+thread_local! {
+    /// Pango layouts are not Send, so each thread keeps its own cache
+    static LAYOUT_CACHE: RefCell<TextLayoutCache<Layout>> = RefCell::new(TextLayoutCache::new());
+}
+
+/// Returns the hit/miss statistics of the pango layout cache of the current thread
+pub fn layout_cache_stats() -> CacheStats {
+    LAYOUT_CACHE.with(|cache| cache.borrow().stats())
+}
+
+/// Returns the (cached) pango layout for the given text. The layout can be shown on any cairo context after
+/// calling `pangocairo::functions::update_layout()`.
+pub fn get_pango_layout(text: &str, style: &TextStyle) -> Result<Layout, gtk4::cairo::Error> {
+    let key = TextLayoutKey::new(text, style);
+
+    if let Some(layout) = LAYOUT_CACHE.with(|cache| cache.borrow_mut().get(&key)) {
+        return Ok(layout);
+    }
+
+    let layout = shape_pango_layout(text, style)?;
+    LAYOUT_CACHE.with(|cache| cache.borrow_mut().insert(key, layout.clone()));
+
+    Ok(layout)
+}
+
+/// Lays out the text and truncates it when it overflows (text-overflow, line-clamp). Words that do not fit are
+/// broken by pango itself.
+fn shape_pango_layout(text: &str, style: &TextStyle) -> Result<Layout, gtk4::cairo::Error> {
+    let layout = build_pango_layout(text, style)?;
+
+    // Byte ranges of the lines, each line ends where the next one starts
+    let mut starts = Vec::new();
+    let mut iter = layout.iter();
+    loop {
+        starts.push(iter.index() as usize);
+        if !iter.next_line() {
+            break;
+        }
+    }
+    let lines: Vec<_> = starts.iter().enumerate().map(|(i, start)| *start..starts.get(i + 1).copied().unwrap_or(text.len())).collect();
+
+    // Width of the text on a single line
+    let single_line = TextStyle {
+        max_width: f64::MAX,
+        alignment: Alignment::Start,
+        wrapping: TextWrapping { nowrap: true, ..TextWrapping::default() },
+        ..style.clone()
+    };
+    let measure = |text: &str| {
+        build_pango_layout(text, &single_line)
+            .map(|layout| layout.extents().1.width() as f64 / SCALE as f64)
+            .unwrap_or(0.0)
+    };
+
+    match truncate_text(text, &style.wrapping, style.max_width, &lines, measure) {
+        Some(truncated) => build_pango_layout(&truncated, style),
+        None => Ok(layout),
+    }
+}
+
+fn build_pango_layout(text: &str, style: &TextStyle) -> Result<Layout, gtk4::cairo::Error> {
+    let TextStyle { font_size, font_weight, font_style, max_width, alignment, direction, wrapping, .. } = *style;
+    let surface = ImageSurface::create(Format::ARgb32, 1, 1)?;
+    let cr = Context::new(&surface)?;
+    let layout = create_layout(&cr);
+
+    // @TODO: I need to set the DPI resolution to 72dpi, otherwise the text will be too large
+    context_set_resolution(&layout.context(), 72.0);
+
+    let selected_family = find_available_font(&style.font_family, &layout.context());
+    let mut font_desc = FontDescription::new();
+    font_desc.set_family(&selected_family);
+    font_desc.set_size((font_size * SCALE as f64) as i32);
+    font_desc.set_weight(to_pango_weight(font_weight));
+    font_desc.set_style(match font_style {
+        FontStyle::Normal => pango::Style::Normal,
+        FontStyle::Italic => pango::Style::Italic,
+        FontStyle::Oblique => pango::Style::Oblique,
+    });
+    layout.set_font_description(Some(&font_desc));
+
+    layout.set_text(text);
+    layout.set_width((max_width * SCALE as f64) as i32);
+
+    // Pango can break inside words by itself when they do not fit on a line
+    if breaks_overflowing_words(wrapping.overflow_wrap, max_width) {
+        layout.set_wrap(pango::WrapMode::WordChar);
+    } else {
+        layout.set_wrap(pango::WrapMode::Word);
+    }
+
+    layout.set_spacing(0);
+    layout.set_line_spacing(0.0);
+
+    // Pango runs the bidi algorithm itself, we only need to set the base direction. With auto-dir disabled, the
+    // alignment is physical, so we resolve start and end here.
+    layout.set_auto_dir(false);
+    layout.context().set_base_dir(match direction {
+        Direction::Ltr => pango::Direction::Ltr,
+        Direction::Rtl => pango::Direction::Rtl,
+    });
+    layout.context_changed();
+
+    let (start, end) = match direction {
+        Direction::Ltr => (pango::Alignment::Left, pango::Alignment::Right),
+        Direction::Rtl => (pango::Alignment::Right, pango::Alignment::Left),
+    };
+    match alignment {
+        Alignment::Start => layout.set_alignment(start),
+        Alignment::Middle => layout.set_alignment(pango::Alignment::Center),
+        Alignment::End => layout.set_alignment(end),
+        Alignment::Justified => {
+            layout.set_alignment(start);
+            layout.set_justify(true);
+        }
+    }
+
+    if wrapping.nowrap {
+        // Lines are only broken at forced breaks. A line that fits is still aligned within the max width.
+        layout.set_width(-1);
+        let width = layout.extents().1.width().max((max_width * SCALE as f64) as i32);
+        layout.set_width(width);
+    }
+
+    Ok(layout)
+}
+
 pub fn find_available_font(families: &str, ctx: &pango::Context) -> String {
     let available_fonts: Vec<String> = ctx.list_families().iter().map(|f| f.name().to_ascii_lowercase()).collect();
 
@@ -1,11 +1,23 @@
 use parley::GenericFamily;
-use crate::layouter::text::Alignment;
+use crate::common::document::style::{Direction, FontStyle};
+use crate::layouter::text::bidi::{LRM, RLM};
+use crate::layouter::text::breaking::{break_overflowing_words, breaks_overflowing_words};
+use crate::layouter::text::truncate::truncate_text;
+use crate::layouter::text::{Alignment, TextStyle};
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Mutex, OnceLock};
 use parley::{AlignmentOptions, Layout};
+use crate::common::font::cache::{CacheStats, TextLayoutCache, TextLayoutKey};
 
 static FONT_CTX: OnceLock<Mutex<parley::FontContext>> = OnceLock::new();
 static LAYOUT_CTX: OnceLock<Mutex<parley::LayoutContext>> = OnceLock::new();
 
+thread_local! {
+    /// Layouts are not Sync, so each thread keeps its own cache
+    static LAYOUT_CACHE: RefCell<TextLayoutCache<Rc<Layout<[u8; 4]>>>> = RefCell::new(TextLayoutCache::new());
+}
+
 pub fn get_font_context() -> std::sync::MutexGuard<'static, parley::FontContext> {
     FONT_CTX
         .get_or_init(|| Mutex::new(parley::FontContext::new()))
@@ -20,12 +32,68 @@ fn get_layout_context() -> std::sync::MutexGuard<'static, parley::LayoutContext>
         .expect("Failed to lock layout context")
 }
 
+/// Returns the hit/miss statistics of the parley layout cache of the current thread
+pub fn layout_cache_stats() -> CacheStats {
+    LAYOUT_CACHE.with(|cache| cache.borrow().stats())
+}
+
+/// Returns the (cached) parley layout for the given text. The same layout is shared between the layouter and
+/// the rasterizer, so text is only shaped once as long as the style is the same.
+pub fn get_parley_layout(text: &str, style: &TextStyle) -> Rc<Layout<[u8; 4]>> {
+    let key = TextLayoutKey::new(text, style);
+    LAYOUT_CACHE.with(|cache| {
+        cache.borrow_mut().get_or_insert_with(key, || Rc::new(shape_parley_layout(text, style)))
+    })
+}
+
+/// Lays out the text, and applies the wrapping options that depend on the laid out text: words that do not fit
+/// are broken (overflow-wrap), and overflowing text is truncated (text-overflow, line-clamp).
+fn shape_parley_layout(text: &str, style: &TextStyle) -> Layout<[u8; 4]> {
+    let (max_width, wrapping) = (style.max_width, style.wrapping);
+    let build = |text: &str| build_parley_layout(text, style, wrapping.nowrap);
+    // Width of the text on a single line
+    let single_line = TextStyle { max_width: f64::MAX, alignment: Alignment::Start, ..style.clone() };
+    let measure = |text: &str| build_parley_layout(text, &single_line, true).width() as f64;
+
+    let mut text = text.to_string();
+    let mut layout = build(&text);
 
-pub fn get_parley_layout(text: &str, font_family: &str, font_size: f64, line_height: f64, max_width: f64, alignment: Alignment) -> Layout<[u8; 4]> {
-    let font_stack = parley::FontStack::from(font_family);
+    // Parley cannot break inside words, so we add break opportunities to the words that do not fit
+    if !wrapping.nowrap && breaks_overflowing_words(wrapping.overflow_wrap, max_width) && layout.width() as f64 > max_width * 1.01 {
+        if let Some(broken) = break_overflowing_words(&text, max_width, measure) {
+            text = broken;
+            layout = build(&text);
+        }
+    }
+
+    // Line ranges include the directional mark in front of the text (LRM and RLM have the same length)
+    let offset = LRM.len_utf8();
+    let lines: Vec<_> = layout.lines().map(|line| {
+        let range = line.text_range();
+        range.start.saturating_sub(offset)..range.end.saturating_sub(offset)
+    }).collect();
+    if let Some(truncated) = truncate_text(&text, &wrapping, max_width, &lines, measure) {
+        layout = build(&truncated);
+    }
+
+    layout
+}
+
+fn build_parley_layout(text: &str, style: &TextStyle, nowrap: bool) -> Layout<[u8; 4]> {
+    let TextStyle { font_size, font_weight, font_style, line_height, alignment, direction, .. } = *style;
+    let font_stack = parley::FontStack::from(style.font_family.as_str());
 
     let display_scale = 1.0;
-    let max_advance = (max_width * display_scale) as f32;
+    let max_advance = (style.max_width * display_scale) as f32;
+
+    // Parley takes the base direction from the first strong character of the text, so we start the text with a
+    // (zero-width) directional mark to force the direction we need.
+    let mark = match direction {
+        Direction::Ltr => LRM,
+        Direction::Rtl => RLM,
+    };
+    let text = format!("{}{}", mark, text);
+    let text = text.as_str();
 
     let mut font_ctx = get_font_context();
     let mut layout_ctx = get_layout_context();
@@ -34,6 +102,12 @@ pub fn get_parley_layout(text: &str, font_family: &str, font_size: f64, line_hei
     builder.push_default(font_stack);
     builder.push_default(parley::StyleProperty::LineHeight(line_height as f32 / font_size as f32));
     builder.push_default(parley::StyleProperty::FontSize(font_size as f32));
+    builder.push_default(parley::StyleProperty::FontWeight(parley::FontWeight::new(font_weight as f32)));
+    builder.push_default(parley::StyleProperty::FontStyle(match font_style {
+        FontStyle::Normal => parley::FontStyle::Normal,
+        FontStyle::Italic => parley::FontStyle::Italic,
+        FontStyle::Oblique => parley::FontStyle::Oblique(None),
+    }));
     builder.push_default(GenericFamily::SystemUi);
 
     let align = match alignment {
@@ -44,8 +118,15 @@ pub fn get_parley_layout(text: &str, font_family: &str, font_size: f64, line_hei
     };
 
     let mut layout: Layout<[u8; 4]> = builder.build(text);
-    layout.break_all_lines(Some(max_advance * 1.01));
-    layout.align(Some(max_advance), align, AlignmentOptions::default());
+    if nowrap {
+        // Lines are only broken at forced breaks. A line that fits is still aligned within the max width.
+        layout.break_all_lines(None);
+        let width = max_advance.max(layout.width());
+        layout.align(Some(width), align, AlignmentOptions::default());
+    } else {
+        layout.break_all_lines(Some(max_advance * 1.01));
+        layout.align(Some(max_advance), align, AlignmentOptions::default());
+    }
 
     layout
 }
\ No newline at end of file
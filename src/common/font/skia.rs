@@ -1,6 +1,14 @@
-use skia_safe::Paint;
-use crate::layouter::text::Alignment;
-use skia_safe::textlayout::{Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle};
+use std::cell::RefCell;
+use std::rc::Rc;
+use skia_safe::{Paint, PaintStyle, Path, PathEffect};
+use skia_safe::font_style::{Slant, Weight, Width};
+use crate::common::document::style::{Direction, FontStyle, TextDecorationStyle};
+use crate::layouter::text::breaking::{break_overflowing_words, breaks_overflowing_words};
+use crate::layouter::text::truncate::truncate_text;
+use crate::painter::commands::text::{dash_pattern, decoration_lines, wavy_points, TextDecoration, TextLineMetrics};
+use crate::common::font::cache::{CacheStats, TextLayoutCache, TextLayoutKey};
+use crate::layouter::text::{Alignment, TextStyle};
+use skia_safe::textlayout::{Paragraph, ParagraphBuilder, ParagraphStyle, TextAlign, TextDirection, TextStyle as SkiaTextStyle};
 
 thread_local! {
     static FC: skia_safe::textlayout::FontCollection = {
@@ -8,10 +16,81 @@ thread_local! {
         fc.set_default_font_manager(skia_safe::FontMgr::new(), None);
         fc
     };
+
+    /// Paragraphs are not Send, so each thread keeps its own cache
+    static PARAGRAPH_CACHE: RefCell<TextLayoutCache<Rc<Paragraph>>> = RefCell::new(TextLayoutCache::new());
+}
+
+/// Returns the hit/miss statistics of the paragraph cache of the current thread
+pub fn paragraph_cache_stats() -> CacheStats {
+    PARAGRAPH_CACHE.with(|cache| cache.borrow().stats())
+}
+
+/// Returns the (cached) skia paragraph for the given text. These paragraphs are shaped without a paint, so
+/// the same paragraph can be used for measuring and for painting with any brush.
+pub fn get_cached_skia_paragraph(text: &str, style: &TextStyle) -> Rc<Paragraph> {
+    let key = TextLayoutKey::new(text, style);
+    PARAGRAPH_CACHE.with(|cache| {
+        cache.borrow_mut().get_or_insert_with(key, || Rc::new(shape_skia_paragraph(text, style, None)))
+    })
+}
+
+/// Returns a skia paragraph for the given text that paints its glyphs with the given paint. These paragraphs are not
+/// cached. Used where the text cannot be painted into a separate layer, like in PDF documents.
+pub fn get_painted_skia_paragraph(text: &str, style: &TextStyle, paint: &Paint) -> Paragraph {
+    shape_skia_paragraph(text, style, Some(paint))
+}
+
+/// Lays out the text, and applies the wrapping options that depend on the laid out text: words that do not fit
+/// are broken (overflow-wrap), and overflowing text is truncated (text-overflow, line-clamp).
+fn shape_skia_paragraph(text: &str, style: &TextStyle, paint: Option<&Paint>) -> Paragraph {
+    let (max_width, wrapping) = (style.max_width, style.wrapping);
+    let build = |text: &str| {
+        let mut paragraph = get_skia_paragraph(text, style, paint);
+        if wrapping.nowrap {
+            // Lines are only broken at forced breaks. A line that fits is still aligned within the max width.
+            let width = paragraph.max_intrinsic_width().ceil().max(max_width as f32);
+            paragraph.layout(width);
+        }
+        paragraph
+    };
+    // Width of the text on a single line
+    let single_line = TextStyle { max_width: f64::MAX, alignment: Alignment::Start, ..style.clone() };
+    let measure = |text: &str| get_skia_paragraph(text, &single_line, None).max_intrinsic_width() as f64;
+
+    let mut text = text.to_string();
+    let mut paragraph = build(&text);
+
+    // Skia cannot break inside words, so we add break opportunities to the words that do not fit
+    if !wrapping.nowrap && breaks_overflowing_words(wrapping.overflow_wrap, max_width) && paragraph.longest_line() as f64 > max_width {
+        if let Some(broken) = break_overflowing_words(&text, max_width, measure) {
+            text = broken;
+            paragraph = build(&text);
+        }
+    }
+
+    let lines: Vec<_> = paragraph.get_line_metrics().iter().map(|line| line.start_index..line.end_index).collect();
+    if let Some(truncated) = truncate_text(&text, &wrapping, max_width, &lines, measure) {
+        paragraph = build(&truncated);
+    }
+
+    paragraph
 }
 
-pub fn get_skia_paragraph(text: &str, font_family: &str, font_size: f64, line_height: f64, max_width: f64, _alignment: Alignment, paint: Option<&Paint>) -> Paragraph {
-    let paragraph_style = ParagraphStyle::new();
+pub fn get_skia_paragraph(text: &str, style: &TextStyle, paint: Option<&Paint>) -> Paragraph {
+    // Skia runs the bidi algorithm itself, we only need to set the base direction. Start and end alignment are
+    // resolved against this direction.
+    let mut paragraph_style = ParagraphStyle::new();
+    paragraph_style.set_text_direction(match style.direction {
+        Direction::Ltr => TextDirection::LTR,
+        Direction::Rtl => TextDirection::RTL,
+    });
+    paragraph_style.set_text_align(match style.alignment {
+        Alignment::Start => TextAlign::Start,
+        Alignment::End => TextAlign::End,
+        Alignment::Middle => TextAlign::Center,
+        Alignment::Justified => TextAlign::Justify,
+    });
     let mut paragraph_builder = ParagraphBuilder::new(&paragraph_style, FC.with(|fc| fc.clone()));
 
     let paint = match paint {
@@ -19,17 +98,74 @@ pub fn get_skia_paragraph(text: &str, font_family: &str, font_size: f64, line_he
         None => Paint::default(),
     };
 
-    let mut ts = TextStyle::new();
+    let mut ts = SkiaTextStyle::new();
     ts.set_foreground_paint(&paint);
-    ts.set_font_size(font_size as f32);
-    ts.set_font_families(&[font_family]);
-    ts.set_height(line_height as f32);
+    ts.set_font_size(style.font_size as f32);
+    ts.set_font_families(&[style.font_family.as_str()]);
+    let slant = match style.font_style {
+        FontStyle::Normal => Slant::Upright,
+        FontStyle::Italic => Slant::Italic,
+        FontStyle::Oblique => Slant::Oblique,
+    };
+    ts.set_font_style(skia_safe::FontStyle::new(Weight::from(style.font_weight as i32), Width::NORMAL, slant));
+    ts.set_height(style.line_height as f32);
 
     paragraph_builder.push_style(&ts);
     paragraph_builder.add_text(text);
 
     let mut paragraph = paragraph_builder.build();
-    paragraph.layout(max_width as f32);
+    paragraph.layout(style.max_width as f32);
 
     paragraph
-}
\ No newline at end of file
+}
+/// Paints the decoration lines of the given paragraph, which has been painted at the origin
+pub fn paint_skia_decorations(canvas: &skia_safe::Canvas, paragraph: &Paragraph, origin: (f32, f32), decoration: &TextDecoration, font_size: f64, paint: &Paint) {
+    for line_metrics in paragraph.get_line_metrics() {
+        let x = origin.0 as f64 + line_metrics.left;
+        let baseline = origin.1 as f64 + line_metrics.baseline;
+
+        let mut metrics = TextLineMetrics::from_font_size(x, line_metrics.width, baseline, font_size);
+        metrics.ascent = line_metrics.ascent;
+
+        // Use the metrics from the font when they are available
+        let style_metrics = line_metrics.get_style_metrics(line_metrics.start_index..line_metrics.end_index);
+        if let Some((_, style)) = style_metrics.first() {
+            let fm = &style.font_metrics;
+            if let (Some(pos), Some(thickness)) = (fm.underline_position(), fm.underline_thickness()) {
+                metrics.underline_offset = pos as f64;
+                metrics.underline_thickness = thickness as f64;
+            }
+            if let (Some(pos), Some(thickness)) = (fm.strikeout_position(), fm.strikeout_thickness()) {
+                metrics.strikethrough_offset = pos as f64;
+                metrics.strikethrough_thickness = thickness as f64;
+            }
+        }
+
+        for line in decoration_lines(decoration, &metrics) {
+            let mut paint = paint.clone();
+            paint.set_style(PaintStyle::Stroke);
+            paint.set_stroke_width(line.thickness as f32);
+            paint.set_anti_alias(true);
+
+            let mut path = Path::new();
+            if decoration.style == TextDecorationStyle::Wavy {
+                for (i, (x, y)) in wavy_points(&line).into_iter().enumerate() {
+                    if i == 0 {
+                        path.move_to((x as f32, y as f32));
+                    } else {
+                        path.line_to((x as f32, y as f32));
+                    }
+                }
+            } else {
+                path.move_to((line.x as f32, line.y as f32));
+                path.line_to(((line.x + line.width) as f32, line.y as f32));
+            }
+
+            if let Some([on, off]) = dash_pattern(decoration.style, line.thickness) {
+                paint.set_path_effect(PathEffect::dash(&[on as f32, off as f32], 0.0));
+            }
+
+            canvas.draw_path(&path, &paint);
+        }
+    }
+}
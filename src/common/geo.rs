@@ -1,5 +1,5 @@
 /// A simple rectangle with a position (x, y) and dimensions (width, height).
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rect {
     pub x: f64,
     pub y: f64,
@@ -40,6 +40,19 @@ impl Rect {
             height: self.height,
         }
     }
+
+    /// Returns the overlapping part of both rectangles, or None when they do not overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(Rect::new(x, y, right - x, bottom - y))
+    }
 }
 
 impl Into<Coordinate> for Rect {
@@ -88,6 +101,99 @@ impl Dimension {
     }
 }
 
+/// A 2D affine transformation. A point is mapped as x' = a*x + c*y + e and y' = b*x + d*y + f, which is the
+/// same layout that skia, cairo and vello use for their matrices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Self { e: x, f: y, ..Self::IDENTITY }
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Self { a: sx, d: sy, ..Self::IDENTITY }
+    }
+
+    /// Rotation in radians, clockwise on screen
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// Returns the transform that applies this transform first, and the other transform after it.
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            a: other.a * self.a + other.c * self.b,
+            b: other.b * self.a + other.d * self.b,
+            c: other.a * self.c + other.c * self.d,
+            d: other.b * self.c + other.d * self.d,
+            e: other.a * self.e + other.c * self.f + other.e,
+            f: other.b * self.e + other.d * self.f + other.f,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// Returns the inverse of the transform, or None when it cannot be inverted (ie: scaled to zero).
+    pub fn invert(&self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        Some(Transform {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    pub fn apply(&self, coord: Coordinate) -> Coordinate {
+        Coordinate::new(
+            self.a * coord.x + self.c * coord.y + self.e,
+            self.b * coord.x + self.d * coord.y + self.f,
+        )
+    }
+
+    /// Returns the bounding box of the transformed rectangle.
+    pub fn apply_rect(&self, rect: Rect) -> Rect {
+        let corners = [
+            self.apply(Coordinate::new(rect.x, rect.y)),
+            self.apply(Coordinate::new(rect.x + rect.width, rect.y)),
+            self.apply(Coordinate::new(rect.x, rect.y + rect.height)),
+            self.apply(Coordinate::new(rect.x + rect.width, rect.y + rect.height)),
+        ];
+
+        let min_x = corners.iter().map(|c| c.x).fold(f64::INFINITY, f64::min);
+        let min_y = corners.iter().map(|c| c.y).fold(f64::INFINITY, f64::min);
+        let max_x = corners.iter().map(|c| c.x).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = corners.iter().map(|c| c.y).fold(f64::NEG_INFINITY, f64::max);
+
+        Rect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -133,4 +239,30 @@ mod tests {
         assert_eq!(dimension.width, 10.0);
         assert_eq!(dimension.height, 20.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rect_intersection() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let overlap = rect.intersection(&Rect::new(50.0, 25.0, 100.0, 50.0)).unwrap();
+        assert_eq!((overlap.x, overlap.y, overlap.width, overlap.height), (50.0, 25.0, 50.0, 50.0));
+
+        assert!(rect.intersection(&Rect::new(100.0, 0.0, 10.0, 10.0)).is_none());
+    }
+
+    #[test]
+    fn test_transform() {
+        let transform = Transform::scale(2.0, 2.0).then(&Transform::translate(10.0, 20.0));
+        let coord = transform.apply(Coordinate::new(5.0, 5.0));
+        assert_eq!((coord.x, coord.y), (20.0, 30.0));
+
+        let inverse = transform.invert().unwrap();
+        let coord = inverse.apply(coord);
+        assert_eq!((coord.x, coord.y), (5.0, 5.0));
+        assert!(Transform::scale(0.0, 1.0).invert().is_none());
+
+        // A quarter turn maps the rectangle onto the negative x side
+        let rect = Transform::rotate(std::f64::consts::FRAC_PI_2).apply_rect(Rect::new(0.0, 0.0, 10.0, 20.0));
+        assert!((rect.x + 20.0).abs() < 1e-9 && rect.y.abs() < 1e-9);
+        assert!((rect.width - 20.0).abs() < 1e-9 && (rect.height - 10.0).abs() < 1e-9);
+    }
+}
@@ -99,7 +99,7 @@ impl MediaStore {
                     }
                 };
 
-                let media = Media::svg("gosub://data/svg", Svg::new(svg_tree));
+                let media = Media::svg("gosub://data/svg", Svg::from_data(svg_tree, data));
                 let media_id = *self.next_id.read().expect("Failed to lock next media ID");
                 *self.next_id.write().expect("Failed to lock next media ID") += 1;
 
@@ -154,7 +154,7 @@ impl MediaStore {
                     }
                 };
 
-                Media::svg(src, Svg::new(svg_tree))
+                Media::svg(src, Svg::from_data(svg_tree, &raw_data))
             }
             MediaType::Image => {
                 let img = match image::load_from_memory(&raw_data) {
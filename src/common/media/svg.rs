@@ -1,10 +1,14 @@
 use std::sync::{Arc, RwLock};
 use resvg::usvg;
 use crate::common::geo::Dimension;
+use crate::layouter::replaced::IntrinsicSize;
 
 #[derive(Clone)]
 pub struct Svg {
     pub tree: usvg::Tree,
+    /// Natural size of the document. usvg always gives the tree a size, even when the document has no width and
+    /// height, so this is taken from the attributes of the root element when the source is known.
+    pub intrinsic_size: IntrinsicSize,
     /// Rendered dimension of the rendered image
     pub rendered_dimension: Arc<RwLock<Dimension>>,
     /// Rendered image in the given dimension
@@ -14,7 +18,9 @@ pub struct Svg {
 impl Svg {
     #[allow(unused)]
     pub fn new(tree: usvg::Tree) -> Svg {
+        let size = tree.size();
         Svg {
+            intrinsic_size: IntrinsicSize::from_dimension(Dimension::new(size.width() as f64, size.height() as f64)),
             tree,
             rendered_dimension: Arc::new(RwLock::new(Dimension::ZERO)),
             rendered_data: Arc::new(RwLock::new(vec![])),
@@ -22,6 +28,17 @@ impl Svg {
     }
 }
 
+impl Svg {
+    /// Creates the SVG with the natural size from the source of the document
+    pub fn from_data(tree: usvg::Tree, data: &[u8]) -> Svg {
+        let mut svg = Svg::new(tree);
+        if let Some(intrinsic_size) = IntrinsicSize::from_svg_data(data) {
+            svg.intrinsic_size = intrinsic_size;
+        }
+        svg
+    }
+}
+
 impl std::fmt::Debug for Svg {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Svg")
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::{Arc, OnceLock, RwLock};
+use crate::common::hash::Sha256Hash;
 use crate::common::texture::{Texture, TextureId};
 
 pub static TEXTURE_STORE: OnceLock<RwLock<TextureStore>> = OnceLock::new();
@@ -12,6 +13,8 @@ pub fn get_texture_store() -> &'static RwLock<TextureStore> {
 /// Texture store stores all the textures. It can remove textures if needed (LRU / memory constraints for instance).
 pub struct TextureStore {
     textures: HashMap<TextureId, Arc<Texture>>,
+    /// Textures by the hash of their content, so the same content can share a single texture
+    cache: HashMap<Sha256Hash, TextureId>,
     next_id: RwLock<TextureId>,
 }
 
@@ -19,6 +22,7 @@ impl TextureStore {
     pub fn new() -> Self {
         Self {
             textures: HashMap::new(),
+            cache: HashMap::new(),
             next_id: RwLock::new(TextureId::new(0)),
         }
     }
@@ -46,9 +50,19 @@ impl TextureStore {
         self.textures.get(&texture_id).cloned()
     }
 
-    #[allow(unused)]
     pub fn remove(&mut self, texture_id: TextureId) {
         self.textures.remove(&texture_id);
+        self.cache.retain(|_, id| *id != texture_id);
+    }
+
+    /// Returns the texture that was stored for the given content hash
+    pub fn find_by_hash(&self, hash: &Sha256Hash) -> Option<TextureId> {
+        self.cache.get(hash).copied().filter(|texture_id| self.textures.contains_key(texture_id))
+    }
+
+    /// Stores the texture for the given content hash, so it can be found with `find_by_hash`
+    pub fn cache(&mut self, hash: Sha256Hash, texture_id: TextureId) {
+        self.cache.insert(hash, texture_id);
     }
 
     fn next_id(&self) -> TextureId {
@@ -2,7 +2,6 @@ use gtk4::cairo;
 use crate::common::browser_state::get_browser_state;
 use crate::compositor::cairo::compositor::cairo_compositor;
 use crate::compositor::Composable;
-use crate::layering::layer::LayerId;
 
 pub struct CairoCompositorConfig {
     pub cr: cairo::Context,
@@ -21,13 +20,8 @@ impl Composable for CairoCompositor {
         let binding = get_browser_state();
         let state = binding.read().expect("Failed to get browser state");
 
-        let mut layers = vec![];
-        if state.visible_layer_list[0] {
-            layers.push(LayerId::new(0));
-        }
-        if state.visible_layer_list[1] {
-            layers.push(LayerId::new(1));
-        }
+        // Layers are composited from bottom to top
+        let layers = state.visible_layers();
 
         cairo_compositor(&config.cr, layers);
     }
@@ -1,7 +1,7 @@
 use gtk4::cairo;
 use gtk4::cairo::ImageSurface;
 use crate::common::browser_state::get_browser_state;
-use crate::layering::layer::LayerId;
+use crate::layering::layer::{BlendMode, LayerId};
 use crate::common::get_texture_store;
 
 pub fn cairo_compositor(cr: &cairo::Context, layer_ids: Vec<LayerId>) {
@@ -14,9 +14,35 @@ pub fn compose_layer(cr: &cairo::Context, layer_id: LayerId) {
     let binding = get_browser_state();
     let state = binding.read().expect("Failed to get browser state");
 
-    let tile_ids = state.tile_list.read().expect("Failed to get tile list").get_intersecting_tiles(layer_id, state.viewport);
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return;
+    };
+
+    let properties = tile_list.read().expect("Failed to get tile list").layer_list.layer_properties(layer_id);
+    let tile_ids = tile_list.read().expect("Failed to get tile list").get_composite_tiles(layer_id, state.viewport);
+    if tile_ids.is_empty() {
+        return;
+    }
+
+    // The cairo context is in CSS pixels, just like the layer properties. Zooming scales all of them.
+    _ = cr.save();
+    cr.scale(state.zoom, state.zoom);
+    if let Some(clip) = properties.clip {
+        cr.rectangle(clip.x, clip.y, clip.width, clip.height);
+        cr.clip();
+    }
+
+    let t = properties.transform;
+    cr.transform(cairo::Matrix::new(t.a, t.b, t.c, t.d, t.e, t.f));
+
+    // Opacity and blending apply to the layer as a whole, so the tiles are drawn into a group first
+    if properties.is_isolated() {
+        cr.push_group();
+    }
+
     for tile_id in tile_ids {
-        let binding = state.tile_list.write().expect("Failed to get tile list");
+        let binding = tile_list.write().expect("Failed to get tile list");
         let Some(tile) = binding.get_tile(tile_id) else {
             log::warn!("Tile not found: {:?}", tile_id);
             continue;
@@ -44,14 +70,44 @@ pub fn compose_layer(cr: &cairo::Context, layer_id: LayerId) {
             texture.width as i32 * 4,
         ).expect("Failed to create image surface");
 
+        // The context of GTK is in logical (CSS) pixels and maps these onto device pixels by itself. Our
+        // texture is in device pixels, so we tell cairo its scale and place it in CSS pixels.
+        surface.set_device_scale(tile.scale, tile.scale);
         cr.rectangle(
             tile.rect.x,
             tile.rect.y,
-            tile.rect.height,
             tile.rect.width,
+            tile.rect.height,
         );
         _ = cr.set_source_surface(surface, tile.rect.x, tile.rect.y);
         _ = cr.fill();
     }
 
-}
\ No newline at end of file
+    if properties.is_isolated() {
+        _ = cr.pop_group_to_source();
+        cr.set_operator(to_cairo_operator(properties.blend_mode));
+        _ = cr.paint_with_alpha(properties.opacity as f64);
+    }
+    _ = cr.restore();
+}
+
+fn to_cairo_operator(blend_mode: BlendMode) -> cairo::Operator {
+    match blend_mode {
+        BlendMode::Normal => cairo::Operator::Over,
+        BlendMode::Multiply => cairo::Operator::Multiply,
+        BlendMode::Screen => cairo::Operator::Screen,
+        BlendMode::Overlay => cairo::Operator::Overlay,
+        BlendMode::Darken => cairo::Operator::Darken,
+        BlendMode::Lighten => cairo::Operator::Lighten,
+        BlendMode::ColorDodge => cairo::Operator::ColorDodge,
+        BlendMode::ColorBurn => cairo::Operator::ColorBurn,
+        BlendMode::HardLight => cairo::Operator::HardLight,
+        BlendMode::SoftLight => cairo::Operator::SoftLight,
+        BlendMode::Difference => cairo::Operator::Difference,
+        BlendMode::Exclusion => cairo::Operator::Exclusion,
+        BlendMode::Hue => cairo::Operator::HslHue,
+        BlendMode::Saturation => cairo::Operator::HslSaturation,
+        BlendMode::Color => cairo::Operator::HslColor,
+        BlendMode::Luminosity => cairo::Operator::HslLuminosity,
+    }
+}
@@ -1,7 +1,8 @@
-use skia_safe::{AlphaType, ColorType, Data, ISize, ImageInfo};
+use skia_safe::canvas::SaveLayerRec;
+use skia_safe::{AlphaType, ColorType, Data, ISize, ImageInfo, Matrix, Paint, Rect};
 use crate::common::browser_state::get_browser_state;
 use crate::common::get_texture_store;
-use crate::layering::layer::LayerId;
+use crate::layering::layer::{BlendMode, LayerId};
 
 pub fn skia_compositor(canvas: &skia_safe::Canvas, layer_ids: Vec<LayerId>) {
     for layer_id in layer_ids {
@@ -18,7 +19,40 @@ pub fn compose_layer(canvas: &skia_safe::canvas::Canvas, layer_id: LayerId) {
         return;
     };
 
-    let tile_ids = tile_list.read().expect("Failed to get tile list").get_intersecting_tiles(layer_id, state.viewport);
+    let properties = tile_list.read().expect("Failed to get tile list").layer_list.layer_properties(layer_id);
+    let tile_ids = tile_list.read().expect("Failed to get tile list").get_composite_tiles(layer_id, state.viewport);
+    if tile_ids.is_empty() {
+        return;
+    }
+
+    // Layer properties are in CSS pixels, while the tiles are drawn in device pixels (including the zoom)
+    let dpr = state.device_scale();
+    let save_count = canvas.save();
+    if let Some(clip) = properties.clip {
+        let clip_rect = Rect::from_xywh(
+            (clip.x * dpr) as f32,
+            (clip.y * dpr) as f32,
+            (clip.width * dpr) as f32,
+            (clip.height * dpr) as f32,
+        );
+        canvas.clip_rect(clip_rect, None, Some(true));
+    }
+
+    let t = properties.device_transform(dpr);
+    canvas.concat(&Matrix::new_all(
+        t.a as f32, t.c as f32, t.e as f32,
+        t.b as f32, t.d as f32, t.f as f32,
+        0.0, 0.0, 1.0,
+    ));
+
+    // Opacity and blending apply to the layer as a whole, so the tiles are drawn into an offscreen layer first
+    if properties.is_isolated() {
+        let mut paint = Paint::default();
+        paint.set_alpha_f(properties.opacity);
+        paint.set_blend_mode(to_skia_blend_mode(properties.blend_mode));
+        canvas.save_layer(&SaveLayerRec::default().paint(&paint));
+    }
+
     for tile_id in tile_ids {
         let binding = tile_list.write().expect("Failed to get tile list");
         let Some(tile) = binding.get_tile(tile_id) else {
@@ -54,11 +88,40 @@ pub fn compose_layer(canvas: &skia_safe::canvas::Canvas, layer_id: LayerId) {
             &image_info, &data, texture.width * 4
         ).unwrap();
 
+        // Textures are in device pixels of their own zoom level, so they are placed in those pixels and scaled
+        // when the tile is of another zoom level
+        let position = tile.device_position();
+        let tile_scale = (dpr / tile.scale) as f32;
+        canvas.save();
+        canvas.scale((tile_scale, tile_scale));
         canvas.draw_image(
             &img,
-            (tile.rect.x.round() as f32, tile.rect.y.round() as f32),
+            (position.x as f32, position.y as f32),
             None,
         );
+        canvas.restore();
     }
 
-}
\ No newline at end of file
+    canvas.restore_to_count(save_count);
+}
+
+fn to_skia_blend_mode(blend_mode: BlendMode) -> skia_safe::BlendMode {
+    match blend_mode {
+        BlendMode::Normal => skia_safe::BlendMode::SrcOver,
+        BlendMode::Multiply => skia_safe::BlendMode::Multiply,
+        BlendMode::Screen => skia_safe::BlendMode::Screen,
+        BlendMode::Overlay => skia_safe::BlendMode::Overlay,
+        BlendMode::Darken => skia_safe::BlendMode::Darken,
+        BlendMode::Lighten => skia_safe::BlendMode::Lighten,
+        BlendMode::ColorDodge => skia_safe::BlendMode::ColorDodge,
+        BlendMode::ColorBurn => skia_safe::BlendMode::ColorBurn,
+        BlendMode::HardLight => skia_safe::BlendMode::HardLight,
+        BlendMode::SoftLight => skia_safe::BlendMode::SoftLight,
+        BlendMode::Difference => skia_safe::BlendMode::Difference,
+        BlendMode::Exclusion => skia_safe::BlendMode::Exclusion,
+        BlendMode::Hue => skia_safe::BlendMode::Hue,
+        BlendMode::Saturation => skia_safe::BlendMode::Saturation,
+        BlendMode::Color => skia_safe::BlendMode::Color,
+        BlendMode::Luminosity => skia_safe::BlendMode::Luminosity,
+    }
+}
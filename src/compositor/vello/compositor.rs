@@ -1,8 +1,9 @@
 use vello::kurbo::Affine;
 use crate::common::browser_state::get_browser_state;
 use crate::common::get_texture_store;
-use crate::layering::layer::LayerId;
-use vello::peniko::{Blob, Image, ImageFormat};
+use crate::common::geo::Rect;
+use crate::layering::layer::{BlendMode, LayerId};
+use vello::peniko::{Blob, Image, ImageFormat, Mix};
 
 pub fn vello_compositor(layer_ids: Vec<LayerId>) -> vello::Scene {
     let mut scene = vello::Scene::new();
@@ -23,7 +24,39 @@ pub fn compose_layer(scene: &mut vello::Scene, layer_id: LayerId) {
         return;
     };
 
-    let tile_ids = tile_list.read().expect("Failed to get tile list").get_intersecting_tiles(layer_id, state.viewport);
+    let properties = tile_list.read().expect("Failed to get tile list").layer_list.layer_properties(layer_id);
+    let tile_ids = tile_list.read().expect("Failed to get tile list").get_composite_tiles(layer_id, state.viewport);
+    if tile_ids.is_empty() {
+        return;
+    }
+
+    // Layer properties are in CSS pixels, while the tiles are drawn in device pixels (including the zoom)
+    let dpr = state.device_scale();
+    let t = properties.device_transform(dpr);
+    let transform = Affine::new([t.a, t.b, t.c, t.d, t.e, t.f]);
+
+    // Opacity, blending and clipping apply to the layer as a whole, so the tiles are drawn into a vello layer
+    let is_layered = properties.is_isolated() || properties.clip.is_some();
+    if is_layered {
+        let clip = properties.clip.unwrap_or_else(|| {
+            let root = tile_list.read().expect("Failed to get tile list").layer_list.layout_tree.root_dimension;
+            properties.transform.apply_rect(Rect::new(0.0, 0.0, root.width, root.height))
+        });
+        let clip_shape = vello::kurbo::Rect::new(
+            clip.x * dpr,
+            clip.y * dpr,
+            (clip.x + clip.width) * dpr,
+            (clip.y + clip.height) * dpr,
+        );
+
+        let mix = match properties.blend_mode {
+            // Clipping only is cheaper than blending
+            BlendMode::Normal if properties.opacity >= 1.0 => Mix::Clip,
+            blend_mode => to_vello_mix(blend_mode),
+        };
+        scene.push_layer(mix, properties.opacity, Affine::IDENTITY, &clip_shape);
+    }
+
     for tile_id in tile_ids {
         let binding = tile_list.write().expect("Failed to get tile list");
         let Some(tile) = binding.get_tile(tile_id) else {
@@ -52,10 +85,37 @@ pub fn compose_layer(scene: &mut vello::Scene, layer_id: LayerId) {
             texture.height as u32,
         );
 
+        // Textures are in device pixels of their own zoom level, so they are placed in those pixels and scaled
+        // when the tile is of another zoom level
+        let position = tile.device_position();
         scene.draw_image(
             &surface,
-            Affine::translate((tile.rect.x.round(), tile.rect.y.round())),
+            transform * Affine::scale(dpr / tile.scale) * Affine::translate((position.x, position.y)),
         );
     }
 
-}
\ No newline at end of file
+    if is_layered {
+        scene.pop_layer();
+    }
+}
+
+fn to_vello_mix(blend_mode: BlendMode) -> Mix {
+    match blend_mode {
+        BlendMode::Normal => Mix::Normal,
+        BlendMode::Multiply => Mix::Multiply,
+        BlendMode::Screen => Mix::Screen,
+        BlendMode::Overlay => Mix::Overlay,
+        BlendMode::Darken => Mix::Darken,
+        BlendMode::Lighten => Mix::Lighten,
+        BlendMode::ColorDodge => Mix::ColorDodge,
+        BlendMode::ColorBurn => Mix::ColorBurn,
+        BlendMode::HardLight => Mix::HardLight,
+        BlendMode::SoftLight => Mix::SoftLight,
+        BlendMode::Difference => Mix::Difference,
+        BlendMode::Exclusion => Mix::Exclusion,
+        BlendMode::Hue => Mix::Hue,
+        BlendMode::Saturation => Mix::Saturation,
+        BlendMode::Color => Mix::Color,
+        BlendMode::Luminosity => Mix::Luminosity,
+    }
+}
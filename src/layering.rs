@@ -1 +1,3 @@
-pub mod layer;
\ No newline at end of file
+pub mod layer;
+pub mod policy;
+pub mod stacking;
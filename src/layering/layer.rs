@@ -1,6 +1,12 @@
 use std::collections::HashMap;
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock};
+use crate::animator::interpolate::parse_transform;
+use crate::common::document::node::{NodeId, NodeType};
+use crate::common::document::style::{StyleProperty, StyleValue};
+use crate::common::geo::{Rect, Transform};
+use crate::layering::policy::{DefaultLayerPolicy, LayerPolicy};
+use crate::layering::stacking::StackingContext;
 use crate::layouter::{LayoutElementId, LayoutTree};
 
 /// ID for layers
@@ -26,15 +32,97 @@ impl std::fmt::Display for LayerId {
 }
 
 
+/// How a layer is blended onto the layers below it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+/// Properties that the compositor applies when drawing the tiles of a layer. Changing these never requires the
+/// layer to be painted or rasterized again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayerProperties {
+    /// Opacity of the layer as a whole (0.0 - 1.0)
+    pub opacity: f32,
+    /// Transformation of the layer in CSS pixels
+    pub transform: Transform,
+    /// Clip rectangle in CSS pixels. The clip is not transformed, so the layer can move (or scroll) inside it.
+    pub clip: Option<Rect>,
+    /// Blend mode of the layer
+    pub blend_mode: BlendMode,
+}
+
+impl Default for LayerProperties {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            transform: Transform::IDENTITY,
+            clip: None,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
+impl LayerProperties {
+    /// Returns the part of the (untransformed) layer that is visible inside the viewport, or None when nothing
+    /// of the layer can be seen.
+    pub fn visible_rect(&self, viewport: Rect) -> Option<Rect> {
+        if self.opacity <= 0.0 {
+            return None;
+        }
+
+        let visible = match self.clip {
+            Some(clip) => viewport.intersection(&clip)?,
+            None => viewport,
+        };
+        if self.transform.is_identity() {
+            return Some(visible);
+        }
+
+        Some(self.transform.invert()?.apply_rect(visible))
+    }
+
+    /// Returns the transform for device pixels. Tiles are composited in device pixels, while the transform is
+    /// given in CSS pixels.
+    pub fn device_transform(&self, device_pixel_ratio: f64) -> Transform {
+        Transform::scale(1.0 / device_pixel_ratio, 1.0 / device_pixel_ratio)
+            .then(&self.transform)
+            .then(&Transform::scale(device_pixel_ratio, device_pixel_ratio))
+    }
+
+    /// Returns true when the layer must be composited in isolation (as a group), instead of drawing its tiles
+    /// directly onto the layers below it.
+    pub fn is_isolated(&self) -> bool {
+        self.opacity < 1.0 || self.blend_mode != BlendMode::Normal
+    }
+}
+
 #[derive(Clone)]
 pub struct Layer {
     /// Layer ID
     pub layer_id: LayerId,
-    /// Order of the layer
-    #[allow(unused)]
+    /// Order of the layer. Layers with a higher order are composited on top of layers with a lower order
     pub order: isize,
     /// Elements in this layer
-    pub elements: Vec<LayoutElementId>
+    pub elements: Vec<LayoutElementId>,
+    /// Compositing properties of this layer
+    pub properties: LayerProperties,
 }
 
 impl Layer {
@@ -42,7 +130,8 @@ impl Layer {
         Layer {
             layer_id,
             order,
-            elements: Vec::new()
+            elements: Vec::new(),
+            properties: LayerProperties::default(),
         }
     }
 
@@ -55,6 +144,7 @@ impl std::fmt::Debug for Layer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Layer")
             .field("elements", &self.elements)
+            .field("properties", &self.properties)
             .finish()
     }
 }
@@ -67,6 +157,12 @@ pub struct LayerList {
     pub layer_ids: RwLock<Vec<LayerId>>,
     /// List of actual layers
     pub layers: RwLock<HashMap<LayerId, Layer>>,
+    /// Elements that are promoted to their own layer, by their DOM node
+    promoted: HashMap<NodeId, (LayoutElementId, LayerId)>,
+    /// Layer each element is painted in
+    element_layers: HashMap<LayoutElementId, LayerId>,
+    /// Layers that continue a layer on top of a promoted layer, by the layer they continue
+    continuations: HashMap<LayerId, Vec<LayerId>>,
     /// Next layer ID
     next_layer_id: RwLock<LayerId>,
 }
@@ -81,24 +177,34 @@ impl std::fmt::Debug for LayerList {
 }
 
 impl LayerList {
+    /// Creates the layers for the layout tree, promoting elements with the default layer policy
     pub fn new(layout_tree: LayoutTree) -> LayerList {
+        Self::with_policy(layout_tree, &DefaultLayerPolicy::default())
+    }
+
+    /// Creates the layers for the layout tree, where the policy decides which elements get their own layer
+    pub fn with_policy(layout_tree: LayoutTree, policy: &dyn LayerPolicy) -> LayerList {
         let mut layer_list = LayerList {
             layout_tree: Arc::new(layout_tree),
             layers: RwLock::new(HashMap::new()),
             layer_ids: RwLock::new(Vec::new()),
+            promoted: HashMap::new(),
+            element_layers: HashMap::new(),
+            continuations: HashMap::new(),
             next_layer_id: RwLock::new(LayerId::new(0)),
         };
 
-        layer_list.generate_layers();
+        layer_list.generate_layers(policy);
         layer_list
     }
 
     /// @TODO: This must be done through rstar!
     /// Find the element at the given coordinates. It will return the given element if it is found or None otherwise
     pub fn find_element_at(&self, x: f64, y: f64) -> Option<LayoutElementId> {
-        // This assumes that the layers are ordered from top to bottom
-        for layer_id in self.layer_ids.read().expect("Failed to lock layer IDs").iter().rev() {
-            let binding = self.layers.read().expect("Failed to lock layers");
+        // Layers are checked from top to bottom, and the elements inside a layer are in painting order
+        let layer_ids = self.ordered_layer_ids();
+        let binding = self.layers.read().expect("Failed to lock layers");
+        for layer_id in layer_ids.iter().rev() {
             let Some(layer) = binding.get(layer_id) else {
               continue;
             };
@@ -120,6 +226,84 @@ impl LayerList {
         None
     }
 
+    /// Returns the layer the element is painted in
+    pub fn get_layer_for_element(&self, element_id: LayoutElementId) -> Option<LayerId> {
+        self.element_layers.get(&element_id).copied()
+    }
+
+    /// Returns all layer IDs, sorted on the order of the layers (from bottom to top). Layers with the same order
+    /// keep the order in which they are created.
+    pub fn ordered_layer_ids(&self) -> Vec<LayerId> {
+        let layers = self.layers.read().expect("Failed to lock layers");
+        let mut layer_ids = self.layer_ids.read().expect("Failed to lock layer IDs").clone();
+        layer_ids.sort_by_key(|layer_id| layers.get(layer_id).map_or(0, |layer| layer.order));
+        layer_ids
+    }
+
+    /// Returns the compositing properties of the layer
+    pub fn layer_properties(&self, layer_id: LayerId) -> LayerProperties {
+        let layers = self.layers.read().expect("Failed to lock layers");
+        layers.get(&layer_id).map(|layer| layer.properties).unwrap_or_default()
+    }
+
+    /// Sets the compositing properties of the layer. Only the compositor needs to run again afterward.
+    pub fn set_layer_properties(&self, layer_id: LayerId, properties: LayerProperties) {
+        let mut layers = self.layers.write().expect("Failed to lock layers");
+        match layers.get_mut(&layer_id) {
+            Some(layer) => layer.properties = properties,
+            None => log::warn!("Layer {} not found in HashMap", layer_id),
+        }
+    }
+
+    /// Returns the layer of the element when it is promoted to its own layer
+    pub fn promoted_layer(&self, dom_node_id: NodeId) -> Option<LayerId> {
+        self.promoted.get(&dom_node_id).map(|(_, layer_id)| *layer_id)
+    }
+
+    /// Returns the layer and the layers that continue it on top of other layers
+    fn with_continuations(&self, layer_id: LayerId) -> Vec<LayerId> {
+        let mut layer_ids = vec![layer_id];
+        layer_ids.extend(self.continuations.get(&layer_id).into_iter().flatten());
+        layer_ids
+    }
+
+    /// Sets the opacity of an element that has its own layer. Returns false when the element is not promoted, in
+    /// which case the opacity must be painted instead.
+    pub fn set_element_opacity(&self, dom_node_id: NodeId, opacity: f32) -> bool {
+        let Some(layer_id) = self.promoted_layer(dom_node_id) else {
+            return false;
+        };
+
+        for layer_id in self.with_continuations(layer_id) {
+            let properties = self.layer_properties(layer_id);
+            self.set_layer_properties(layer_id, LayerProperties { opacity, ..properties });
+        }
+        true
+    }
+
+    /// Sets the transform of an element that has its own layer. The transform is applied around the center of the
+    /// border box of the element (the default transform-origin). Returns false when the element is not promoted.
+    pub fn set_element_transform(&self, dom_node_id: NodeId, transform: Transform) -> bool {
+        let Some((element_id, layer_id)) = self.promoted.get(&dom_node_id).copied() else {
+            return false;
+        };
+        let Some(layout_element) = self.layout_tree.get_node_by_id(element_id) else {
+            return false;
+        };
+
+        let border_box = layout_element.box_model.border_box();
+        let (origin_x, origin_y) = (border_box.x + border_box.width / 2.0, border_box.y + border_box.height / 2.0);
+        let transform = Transform::translate(-origin_x, -origin_y)
+            .then(&transform)
+            .then(&Transform::translate(origin_x, origin_y));
+
+        for layer_id in self.with_continuations(layer_id) {
+            let properties = self.layer_properties(layer_id);
+            self.set_layer_properties(layer_id, LayerProperties { transform, ..properties });
+        }
+        true
+    }
+
     // Create a new layer to the list at the given order
     pub fn new_layer(&self, order: isize) -> LayerId {
         let layer = Layer::new(self.next_layer_id(), order);
@@ -149,52 +333,99 @@ impl LayerList {
         }
     }
 
-    fn generate_layers(&mut self) {
+    fn generate_layers(&mut self, policy: &dyn LayerPolicy) {
         self.layers.write().expect("Failed to lock layers").clear();
 
         let root_id = self.layout_tree.root_id;
         let default_layer_id = self.new_layer(0);
 
-        self.traverse(default_layer_id, root_id);
-    }
+        let mut parents = HashMap::new();
+        self.collect_parents(root_id, &mut parents);
 
-    fn traverse(&self, layer_id: LayerId, layout_element_node_id: LayoutElementId) {
-        let Some(layout_element) = self.layout_tree.get_node_by_id(layout_element_node_id) else {
-            return;
-        };
+        // Elements are added to their layers in painting order, as decided by the stacking contexts. An element
+        // that is promoted gets a new layer, ordered by its position in the painting order. All other elements end
+        // up in the layer of their parent. Parents are always painted before their children.
+        //
+        // Layers are created in painting order, so the last created layer is on top. An element that is painted
+        // after a promoted element it is not part of must be composited above it, so when the layer of its parent
+        // is not on top anymore, the layer is continued in a new layer on top. The next elements of the layer are
+        // added to this continuation as well.
+        let mut element_layers: HashMap<LayoutElementId, LayerId> = HashMap::new();
+        let mut top_layer_id = default_layer_id;
+        let mut continued_by: HashMap<LayerId, LayerId> = HashMap::new();
+        let mut continues: HashMap<LayerId, LayerId> = HashMap::new();
+        let stacking_context = StackingContext::from_layout_tree(&self.layout_tree);
+        for (paint_index, element_id) in stacking_context.paint_order().into_iter().enumerate() {
+            let Some(layout_element) = self.layout_tree.get_node_by_id(element_id) else {
+                continue;
+            };
 
-        let is_image = self.layout_tree.render_tree.doc
-            .get_node_by_id(layout_element.dom_node_id)
-            .and_then(|dom_node| match dom_node.node_type {
-                crate::common::document::node::NodeType::Element(ref element_data) => {
-                    Some(element_data.tag_name.eq_ignore_ascii_case("img"))
-                },
-                _ => None,
-            })
-            .unwrap_or(false);
-
-        // When we detect an image, we create a new layer for it
-        if is_image {
-            let image_layer_id = self.new_layer(1);
-            if let Some(mut layers) = self.get_layer_mut(image_layer_id) {
-                if let Some(image_layer) = layers.get_mut(&image_layer_id) {
-                    image_layer.add_element(layout_element.id);
-                } else {
-                    log::warn!("Image layer {} not found in HashMap", image_layer_id);
+            let is_promoted = element_id != root_id && self.layout_tree.render_tree.doc
+                .get_node_by_id(layout_element.dom_node_id)
+                .is_some_and(|dom_node| policy.promote(layout_element, dom_node));
+
+            let layer_id = if is_promoted {
+                let layer_id = self.new_layer(paint_index as isize);
+                self.promoted.insert(layout_element.dom_node_id, (element_id, layer_id));
+                top_layer_id = layer_id;
+                layer_id
+            } else {
+                let mut layer_id = parents.get(&element_id)
+                    .and_then(|parent_id| element_layers.get(parent_id))
+                    .copied()
+                    .unwrap_or(default_layer_id);
+                while let Some(continuation) = continued_by.get(&layer_id) {
+                    layer_id = *continuation;
                 }
-            }
-        } else {
+
+                if layer_id != top_layer_id {
+                    let continuation = self.new_layer(paint_index as isize);
+                    let origin = continues.get(&layer_id).copied().unwrap_or(layer_id);
+                    continued_by.insert(layer_id, continuation);
+                    continues.insert(continuation, origin);
+                    self.continuations.entry(origin).or_default().push(continuation);
+                    top_layer_id = continuation;
+                    layer_id = continuation;
+                }
+                layer_id
+            };
+
+            element_layers.insert(element_id, layer_id);
             if let Some(mut layers) = self.get_layer_mut(layer_id) {
                 if let Some(layer) = layers.get_mut(&layer_id) {
-                    layer.add_element(layout_element.id);
+                    layer.add_element(element_id);
                 } else {
                     log::warn!("Layer {} not found in HashMap", layer_id);
                 }
             }
         }
 
+        self.element_layers = element_layers;
+
+        // The opacity and transform of promoted elements are applied by the compositor
+        for &dom_node_id in self.promoted.keys() {
+            let Some(NodeType::Element(data)) = self.layout_tree.render_tree.doc.get_node_by_id(dom_node_id).map(|n| &n.node_type) else {
+                continue;
+            };
+            if let Some(StyleValue::Number(opacity)) = data.get_style(StyleProperty::Opacity) {
+                self.set_element_opacity(dom_node_id, *opacity);
+            }
+            if let Some(StyleValue::Keyword(value)) = data.get_style(StyleProperty::Transform) {
+                if let Some(transform) = parse_transform(value).filter(|t| !t.is_identity()) {
+                    self.set_element_transform(dom_node_id, transform);
+                }
+            }
+        }
+    }
+
+    fn collect_parents(&self, element_id: LayoutElementId, parents: &mut HashMap<LayoutElementId, LayoutElementId>) {
+        let Some(layout_element) = self.layout_tree.get_node_by_id(element_id) else {
+            return;
+        };
+
         for &child_id in &layout_element.children {
-            self.traverse(layer_id, child_id);
+            parents.insert(child_id, element_id);
+            self.collect_parents(child_id, parents);
         }
     }
 
@@ -204,4 +435,86 @@ impl LayerList {
         *nid += 1;
         id
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::style::{StylePropertyList, Unit};
+    use crate::common::geo::Dimension;
+    use crate::layouter::taffy::TaffyLayouter;
+    use crate::layouter::CanLayout;
+    use crate::rendertree_builder::RenderTree;
+
+    #[test]
+    fn test_visible_rect() {
+        let viewport = Rect::new(0.0, 100.0, 800.0, 600.0);
+
+        let properties = LayerProperties::default();
+        assert_eq!(properties.visible_rect(viewport), Some(viewport));
+
+        // Moving the layer down shows the part of the layer above the viewport
+        let properties = LayerProperties { transform: Transform::translate(0.0, 50.0), ..Default::default() };
+        assert_eq!(properties.visible_rect(viewport), Some(Rect::new(0.0, 50.0, 800.0, 600.0)));
+
+        let properties = LayerProperties { clip: Some(Rect::new(0.0, 0.0, 100.0, 200.0)), ..Default::default() };
+        assert_eq!(properties.visible_rect(viewport), Some(Rect::new(0.0, 100.0, 100.0, 100.0)));
+
+        let properties = LayerProperties { clip: Some(Rect::new(0.0, 0.0, 100.0, 50.0)), ..Default::default() };
+        assert_eq!(properties.visible_rect(viewport), None);
+
+        let properties = LayerProperties { opacity: 0.0, ..Default::default() };
+        assert_eq!(properties.visible_rect(viewport), None);
+    }
+
+    #[test]
+    fn test_layer_order_after_promoted_element() {
+        let styles = |props: &[(StyleProperty, StyleValue)]| {
+            let mut styles = StylePropertyList::new();
+            for (prop, value) in props {
+                styles.set_property(prop.clone(), value.clone());
+            }
+            styles
+        };
+        let px = |v: f32| StyleValue::Unit(v, Unit::Px);
+        let keyword = |v: &str| StyleValue::Keyword(v.to_string());
+
+        // A promoted element, followed by a sibling with a z-index and a sibling without
+        let mut doc = Document::new("https://example.com");
+        let root_id = doc.new_element(None, "DocumentRoot", None, false, None);
+        let page_id = doc.new_element(Some(root_id), "div", None, false, Some(styles(&[(StyleProperty::Width, px(1000.0))])));
+        doc.add_child(root_id, page_id);
+        let mut child = |props: &[(StyleProperty, StyleValue)]| {
+            let mut props = props.to_vec();
+            props.push((StyleProperty::Height, px(50.0)));
+            let node_id = doc.new_element(Some(page_id), "div", None, false, Some(styles(&props)));
+            doc.add_child(page_id, node_id);
+            node_id
+        };
+        let promoted_id = child(&[(StyleProperty::WillChange, keyword("transform"))]);
+        let z_index_id = child(&[(StyleProperty::Position, keyword("relative")), (StyleProperty::ZIndex, StyleValue::Number(10.0))]);
+        let static_id = child(&[]);
+        doc.set_root(root_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+        let layout_tree = TaffyLayouter::new().layout(render_tree, Some(Dimension::new(1000.0, 800.0)));
+        let layer_list = LayerList::new(layout_tree);
+
+        let layer_of = |dom_node_id: NodeId| {
+            let element = layer_list.layout_tree.arena.values().find(|e| e.dom_node_id == dom_node_id).unwrap();
+            layer_list.get_layer_for_element(element.id).unwrap()
+        };
+        let position = |layer_id: LayerId| layer_list.ordered_layer_ids().iter().position(|id| *id == layer_id).unwrap();
+
+        // Both siblings paint after the promoted element, so they are composited above it, in one layer on top
+        let promoted_layer = layer_list.promoted_layer(promoted_id).unwrap();
+        assert_eq!(layer_of(promoted_id), promoted_layer);
+        assert!(position(layer_of(z_index_id)) > position(promoted_layer));
+        assert!(position(layer_of(static_id)) > position(promoted_layer));
+        assert_eq!(layer_of(static_id), layer_of(z_index_id));
+
+        // The page itself is painted before the promoted element, in the bottom layer
+        assert_eq!(position(layer_of(page_id)), 0);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use crate::common::document::node::{ElementData, Node, NodeId, NodeType};
+use crate::common::document::style::{StyleProperty, StyleValue};
+use crate::layouter::LayoutElementNode;
+
+/// Decides which elements are promoted to their own compositor layer. A promoted element is placed in a new layer
+/// together with all its descendants (unless they are promoted themselves), so the compositor can move, fade or
+/// scroll it without repainting the layer below.
+pub trait LayerPolicy {
+    /// Returns true when the element must get its own layer
+    fn promote(&self, element: &LayoutElementNode, dom_node: &Node) -> bool;
+}
+
+/// Promotes elements the way browsers do: elements that are hinted with `will-change`, have an active animation
+/// or a transition of opacity or transform, have a fixed position, video and canvas elements, and large scroll containers.
+pub struct DefaultLayerPolicy {
+    /// Minimal area (in pixels) of a scroll container before it gets its own layer
+    pub min_scroll_area: f64,
+}
+
+impl Default for DefaultLayerPolicy {
+    fn default() -> Self {
+        Self {
+            min_scroll_area: 256.0 * 256.0,
+        }
+    }
+}
+
+impl LayerPolicy for DefaultLayerPolicy {
+    fn promote(&self, element: &LayoutElementNode, dom_node: &Node) -> bool {
+        let NodeType::Element(data) = &dom_node.node_type else {
+            return false;
+        };
+
+        if matches!(data.tag_name.to_ascii_lowercase().as_str(), "video" | "canvas") {
+            return true;
+        }
+
+        if keyword(data, StyleProperty::WillChange).is_some_and(|v| v != "auto") {
+            return true;
+        }
+        if keyword(data, StyleProperty::AnimationName).is_some_and(|v| v != "none") {
+            return true;
+        }
+        // Transitions of compositor friendly properties run on the layer
+        let transitions_layer = keyword(data, StyleProperty::TransitionProperty)
+            .is_some_and(|v| v.split(',').any(|p| matches!(p.trim(), "opacity" | "transform")));
+        if transitions_layer {
+            return true;
+        }
+        if keyword(data, StyleProperty::Position) == Some("fixed") {
+            return true;
+        }
+
+        let is_scrollable = [StyleProperty::OverflowX, StyleProperty::OverflowY]
+            .into_iter()
+            .any(|prop| matches!(keyword(data, prop), Some("scroll" | "auto")));
+        if is_scrollable {
+            let padding_box = element.box_model.padding_box();
+            return padding_box.width * padding_box.height >= self.min_scroll_area;
+        }
+
+        false
+    }
+}
+
+/// Debug policy that places every image in its own layer. This makes it easy to see layers in action, even on
+/// pages that would not have any layers otherwise.
+pub struct ImageLayerPolicy;
+
+impl LayerPolicy for ImageLayerPolicy {
+    fn promote(&self, _element: &LayoutElementNode, dom_node: &Node) -> bool {
+        match &dom_node.node_type {
+            NodeType::Element(data) => data.tag_name.eq_ignore_ascii_case("img"),
+            _ => false,
+        }
+    }
+}
+
+/// Promotes the elements that have a running animation or transition of opacity or transform, so the animator can
+/// apply these values to the layer. Unlike the default policy, this also finds `transition-property: all`.
+pub struct AnimatedLayerPolicy {
+    node_ids: HashSet<NodeId>,
+}
+
+impl AnimatedLayerPolicy {
+    pub fn new(node_ids: HashSet<NodeId>) -> Self {
+        Self { node_ids }
+    }
+}
+
+impl LayerPolicy for AnimatedLayerPolicy {
+    fn promote(&self, _element: &LayoutElementNode, dom_node: &Node) -> bool {
+        self.node_ids.contains(&dom_node.node_id)
+    }
+}
+
+/// Promotes an element when any of its policies promotes it
+pub struct CombinedLayerPolicy {
+    policies: Vec<Box<dyn LayerPolicy>>,
+}
+
+impl CombinedLayerPolicy {
+    pub fn new(policies: Vec<Box<dyn LayerPolicy>>) -> Self {
+        Self { policies }
+    }
+}
+
+impl LayerPolicy for CombinedLayerPolicy {
+    fn promote(&self, element: &LayoutElementNode, dom_node: &Node) -> bool {
+        self.policies.iter().any(|policy| policy.promote(element, dom_node))
+    }
+}
+
+fn keyword(data: &ElementData, prop: StyleProperty) -> Option<&str> {
+    match data.get_style(prop) {
+        Some(StyleValue::Keyword(value)) => Some(value.as_str()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::style::StylePropertyList;
+    use crate::common::geo::Rect;
+    use crate::layouter::box_model::BoxModel;
+    use crate::layouter::{ElementContext, LayoutElementId};
+    use crate::rendertree_builder::RenderNodeId;
+
+    fn element(tag_name: &str, styles: &[(StyleProperty, &str)], width: f64, height: f64) -> (LayoutElementNode, Node) {
+        let mut style = StylePropertyList::new();
+        for (prop, value) in styles {
+            style.set_property(prop.clone(), StyleValue::Keyword(value.to_string()));
+        }
+
+        let doc = Document::new("https://example.com");
+        let node = Node::new_element(&doc, None, tag_name.to_string(), None, false, Some(style));
+        let layout_element = LayoutElementNode {
+            id: LayoutElementId::new(1),
+            dom_node_id: node.node_id,
+            render_node_id: RenderNodeId::new(1),
+            children: vec![],
+            box_model: BoxModel { margin_box: Rect::new(0.0, 0.0, width, height), ..BoxModel::ZERO },
+            context: ElementContext::None,
+        };
+
+        (layout_element, node)
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let policy = DefaultLayerPolicy::default();
+        let promote = |(element, node): (LayoutElementNode, Node)| policy.promote(&element, &node);
+
+        assert!(!promote(element("div", &[], 100.0, 100.0)));
+        assert!(!promote(element("img", &[], 100.0, 100.0)));
+        assert!(promote(element("video", &[], 100.0, 100.0)));
+        assert!(promote(element("div", &[(StyleProperty::WillChange, "transform")], 100.0, 100.0)));
+        assert!(!promote(element("div", &[(StyleProperty::WillChange, "auto")], 100.0, 100.0)));
+        assert!(promote(element("div", &[(StyleProperty::AnimationName, "spin")], 100.0, 100.0)));
+        assert!(promote(element("div", &[(StyleProperty::TransitionProperty, "color, opacity")], 100.0, 100.0)));
+        assert!(!promote(element("div", &[(StyleProperty::TransitionProperty, "all")], 100.0, 100.0)));
+        assert!(promote(element("div", &[(StyleProperty::Position, "fixed")], 100.0, 100.0)));
+
+        // Only large scroll containers are promoted
+        assert!(!promote(element("div", &[(StyleProperty::OverflowY, "scroll")], 100.0, 100.0)));
+        assert!(promote(element("div", &[(StyleProperty::OverflowY, "scroll")], 1000.0, 500.0)));
+    }
+
+    #[test]
+    fn test_image_policy() {
+        let (image, node) = element("IMG", &[], 100.0, 100.0);
+        assert!(ImageLayerPolicy.promote(&image, &node));
+
+        let (div, node) = element("div", &[(StyleProperty::WillChange, "transform")], 100.0, 100.0);
+        assert!(!ImageLayerPolicy.promote(&div, &node));
+    }
+
+    #[test]
+    fn test_combined_policy() {
+        let (image, image_node) = element("img", &[], 100.0, 100.0);
+        let (div, div_node) = element("div", &[(StyleProperty::TransitionProperty, "all")], 100.0, 100.0);
+
+        let policy = |animated: HashSet<NodeId>| CombinedLayerPolicy::new(vec![
+            Box::new(ImageLayerPolicy),
+            Box::new(AnimatedLayerPolicy::new(animated)),
+        ]);
+        assert!(policy(HashSet::new()).promote(&image, &image_node));
+        assert!(!policy(HashSet::new()).promote(&div, &div_node));
+        assert!(policy(HashSet::from([div_node.node_id])).promote(&div, &div_node));
+    }
+}
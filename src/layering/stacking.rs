@@ -0,0 +1,316 @@
+use std::cmp::Ordering;
+use crate::common::document::node::NodeType;
+use crate::common::document::style::{Display, StyleProperty, StylePropertyList, StyleValue};
+use crate::layouter::{LayoutElementId, LayoutTree};
+
+// Stacking contexts decide the order in which elements are painted (CSS 2.1, appendix E). Within a stacking
+// context, elements are painted in the following order:
+//
+//   1. the element that creates the stacking context
+//   2. child stacking contexts with a negative z-index (most negative first)
+//   3. in-flow, non-positioned block-level descendants
+//   4. non-positioned floats
+//   5. in-flow, non-positioned inline-level descendants (including text)
+//   6. positioned descendants with z-index auto, and child stacking contexts with z-index 0, in tree order
+//   7. child stacking contexts with a positive z-index (lowest first)
+//
+// Floats and positioned elements with z-index auto are painted as if they created a stacking context, but their
+// positioned descendants and child stacking contexts are still part of the parent stacking context.
+
+/// How an element takes part in the painting order of its stacking context
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Participation {
+    Block,
+    Inline,
+    Float,
+    /// Positioned element with a z-index of auto
+    Positioned,
+    /// Element that creates a new stacking context with the given z-index
+    StackingContext(i32),
+}
+
+impl Participation {
+    /// Returns how an element with the given styles takes part in the painting order
+    pub fn from_styles(styles: &StylePropertyList) -> Participation {
+        let keyword = |prop: StyleProperty| match styles.get_property(prop) {
+            Some(StyleValue::Keyword(value)) => Some(value.as_str()),
+            _ => None,
+        };
+
+        let position = keyword(StyleProperty::Position).unwrap_or("static");
+        let is_positioned = position != "static";
+        let z_index = match styles.get_property(StyleProperty::ZIndex) {
+            Some(StyleValue::Number(z)) => Some(*z as i32),
+            _ => None,
+        };
+        let opacity = match styles.get_property(StyleProperty::Opacity) {
+            Some(StyleValue::Number(opacity)) => *opacity,
+            _ => 1.0,
+        };
+
+        let creates_context = (is_positioned && z_index.is_some())
+            || position == "fixed"
+            || position == "sticky"
+            || opacity < 1.0
+            || keyword(StyleProperty::Transform).is_some_and(|t| t != "none")
+            || keyword(StyleProperty::Isolation) == Some("isolate");
+
+        if creates_context {
+            return Participation::StackingContext(z_index.unwrap_or(0));
+        }
+        if is_positioned {
+            return Participation::Positioned;
+        }
+        if keyword(StyleProperty::Float).is_some_and(|f| f != "none") {
+            return Participation::Float;
+        }
+
+        match styles.get_property(StyleProperty::Display) {
+            Some(StyleValue::Display(Display::Inline | Display::InlineBlock)) => Participation::Inline,
+            _ => Participation::Block,
+        }
+    }
+}
+
+/// A stacking context with all the elements that are painted inside it, grouped by their painting phase
+#[derive(Clone, Debug)]
+pub struct StackingContext {
+    /// Element that creates the stacking context
+    pub element_id: LayoutElementId,
+    /// Z-index of the stacking context inside its parent
+    pub z_index: i32,
+    /// Child stacking contexts with a negative z-index, sorted on z-index
+    pub negative: Vec<StackingContext>,
+    /// In-flow, non-positioned block-level descendants
+    pub blocks: Vec<LayoutElementId>,
+    /// Non-positioned floats
+    pub floats: Vec<StackingContext>,
+    /// In-flow, non-positioned inline-level descendants
+    pub inlines: Vec<LayoutElementId>,
+    /// Positioned descendants with z-index auto and child stacking contexts with z-index 0, in tree order
+    pub positioned: Vec<StackingContext>,
+    /// Child stacking contexts with a positive z-index, sorted on z-index
+    pub positive: Vec<StackingContext>,
+}
+
+impl StackingContext {
+    fn new(element_id: LayoutElementId, z_index: i32) -> Self {
+        Self {
+            element_id,
+            z_index,
+            negative: Vec::new(),
+            blocks: Vec::new(),
+            floats: Vec::new(),
+            inlines: Vec::new(),
+            positioned: Vec::new(),
+            positive: Vec::new(),
+        }
+    }
+
+    /// Builds the root stacking context of the layout tree
+    pub fn from_layout_tree(layout_tree: &LayoutTree) -> Self {
+        let children = |id: LayoutElementId| {
+            layout_tree.get_node_by_id(id).map_or(Vec::new(), |element| element.children.clone())
+        };
+
+        let participation = |id: LayoutElementId| {
+            let dom_node = layout_tree.get_node_by_id(id)
+                .and_then(|element| layout_tree.render_tree.doc.get_node_by_id(element.dom_node_id));
+
+            // Text nodes copy the styles of their parent, so only elements are checked
+            match dom_node.map(|node| &node.node_type) {
+                Some(NodeType::Element(data)) => Participation::from_styles(&data.styles),
+                _ => Participation::Inline,
+            }
+        };
+
+        Self::build(layout_tree.root_id, &children, &participation)
+    }
+
+    /// Builds the root stacking context for the given root element. The children and participation functions
+    /// return the children of an element (in tree order) and how that element takes part in the painting order.
+    pub fn build<C, P>(root_id: LayoutElementId, children: &C, participation: &P) -> Self
+    where
+        C: Fn(LayoutElementId) -> Vec<LayoutElementId>,
+        P: Fn(LayoutElementId) -> Participation,
+    {
+        Self::build_context(root_id, 0, children, participation)
+    }
+
+    fn build_context<C, P>(element_id: LayoutElementId, z_index: i32, children: &C, participation: &P) -> Self
+    where
+        C: Fn(LayoutElementId) -> Vec<LayoutElementId>,
+        P: Fn(LayoutElementId) -> Participation,
+    {
+        let mut context = Self::new(element_id, z_index);
+        for child_id in children(element_id) {
+            Self::collect(child_id, &mut context, None, children, participation);
+        }
+
+        // Sorting is stable, so contexts with the same z-index stay in tree order
+        context.negative.sort_by_key(|c| c.z_index);
+        context.positive.sort_by_key(|c| c.z_index);
+
+        context
+    }
+
+    /// Adds the element and its descendants to the stacking context. The local context is the float or positioned
+    /// element the element resides in, which paints its own blocks and inlines. When not set, this is the
+    /// stacking context itself.
+    fn collect<C, P>(
+        element_id: LayoutElementId,
+        context: &mut StackingContext,
+        mut local: Option<&mut StackingContext>,
+        children: &C,
+        participation: &P,
+    ) where
+        C: Fn(LayoutElementId) -> Vec<LayoutElementId>,
+        P: Fn(LayoutElementId) -> Participation,
+    {
+        match participation(element_id) {
+            Participation::StackingContext(z_index) => {
+                let child = Self::build_context(element_id, z_index, children, participation);
+                match z_index.cmp(&0) {
+                    Ordering::Less => context.negative.push(child),
+                    Ordering::Equal => context.positioned.push(child),
+                    Ordering::Greater => context.positive.push(child),
+                }
+            }
+            Participation::Positioned => {
+                // Positioned descendants are added to the context while collecting, but must be painted after
+                // this element, so it is inserted at its position in tree order afterward.
+                let index = context.positioned.len();
+                let mut pseudo = Self::new(element_id, 0);
+                for child_id in children(element_id) {
+                    Self::collect(child_id, context, Some(&mut pseudo), children, participation);
+                }
+                context.positioned.insert(index, pseudo);
+            }
+            Participation::Float => {
+                let mut pseudo = Self::new(element_id, 0);
+                for child_id in children(element_id) {
+                    Self::collect(child_id, context, Some(&mut pseudo), children, participation);
+                }
+                local.unwrap_or(context).floats.push(pseudo);
+            }
+            Participation::Block => {
+                local.as_deref_mut().unwrap_or(&mut *context).blocks.push(element_id);
+                for child_id in children(element_id) {
+                    Self::collect(child_id, context, local.as_deref_mut(), children, participation);
+                }
+            }
+            Participation::Inline => {
+                local.as_deref_mut().unwrap_or(&mut *context).inlines.push(element_id);
+
+                for child_id in children(element_id) {
+                    Self::collect(child_id, context, local.as_deref_mut(), children, participation);
+                }
+            }
+        }
+    }
+
+    /// Returns all elements of the stacking context in the order they must be painted
+    pub fn paint_order(&self) -> Vec<LayoutElementId> {
+        let mut order = Vec::new();
+        self.append_paint_order(&mut order);
+        order
+    }
+
+    fn append_paint_order(&self, order: &mut Vec<LayoutElementId>) {
+        order.push(self.element_id);
+        for child in &self.negative {
+            child.append_paint_order(order);
+        }
+        order.extend(&self.blocks);
+        for child in &self.floats {
+            child.append_paint_order(order);
+        }
+        order.extend(&self.inlines);
+        for child in &self.positioned {
+            child.append_paint_order(order);
+        }
+        for child in &self.positive {
+            child.append_paint_order(order);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn id(val: u64) -> LayoutElementId {
+        LayoutElementId::new(val)
+    }
+
+    /// Builds a stacking context for a tree given as (element, parent, participation) in tree order
+    fn build(elements: &[(u64, Option<u64>, Participation)]) -> StackingContext {
+        let mut tree: HashMap<LayoutElementId, Vec<LayoutElementId>> = HashMap::new();
+        let mut participations = HashMap::new();
+        for (element, parent, participation) in elements {
+            if let Some(parent) = parent {
+                tree.entry(id(*parent)).or_default().push(id(*element));
+            }
+            participations.insert(id(*element), *participation);
+        }
+
+        let children = |e: LayoutElementId| tree.get(&e).cloned().unwrap_or_default();
+        let participation = |e: LayoutElementId| participations[&e];
+
+        StackingContext::build(id(elements[0].0), &children, &participation)
+    }
+
+    #[test]
+    fn test_paint_order() {
+        use Participation::*;
+
+        let context = build(&[
+            (0, None, Block),
+            (1, Some(0), StackingContext(2)),
+            (2, Some(0), Block),
+            (3, Some(2), Inline),
+            (4, Some(0), StackingContext(-1)),
+            (5, Some(0), Float),
+            (6, Some(0), Positioned),
+            (7, Some(6), StackingContext(1)),
+            (8, Some(6), Positioned),
+            (9, Some(0), StackingContext(0)),
+            (10, Some(9), StackingContext(-5)),
+        ]);
+
+        // Negative z first, then blocks, floats, inlines, positioned in tree order and positive z last. The
+        // z-index of 10 only counts inside the stacking context of 9.
+        assert_eq!(context.paint_order(), [0, 4, 2, 5, 3, 6, 8, 9, 10, 7, 1].map(id).to_vec());
+    }
+
+    #[test]
+    fn test_participation() {
+        let styles = |props: &[(StyleProperty, StyleValue)]| {
+            let mut styles = StylePropertyList::new();
+            for (prop, value) in props {
+                styles.set_property(prop.clone(), value.clone());
+            }
+            Participation::from_styles(&styles)
+        };
+        let keyword = |s: &str| StyleValue::Keyword(s.to_string());
+
+        assert_eq!(styles(&[]), Participation::Block);
+        assert_eq!(styles(&[(StyleProperty::Display, StyleValue::Display(Display::Inline))]), Participation::Inline);
+        assert_eq!(styles(&[(StyleProperty::Float, keyword("left"))]), Participation::Float);
+        assert_eq!(styles(&[(StyleProperty::Position, keyword("relative"))]), Participation::Positioned);
+
+        // z-index only applies to positioned elements
+        assert_eq!(styles(&[(StyleProperty::ZIndex, StyleValue::Number(3.0))]), Participation::Block);
+        assert_eq!(
+            styles(&[(StyleProperty::Position, keyword("absolute")), (StyleProperty::ZIndex, StyleValue::Number(-2.0))]),
+            Participation::StackingContext(-2)
+        );
+
+        assert_eq!(styles(&[(StyleProperty::Opacity, StyleValue::Number(0.5))]), Participation::StackingContext(0));
+        assert_eq!(styles(&[(StyleProperty::Transform, keyword("none"))]), Participation::Block);
+        assert_eq!(styles(&[(StyleProperty::Transform, keyword("rotate(45deg)"))]), Participation::StackingContext(0));
+        assert_eq!(styles(&[(StyleProperty::Isolation, keyword("isolate"))]), Participation::StackingContext(0));
+        assert_eq!(styles(&[(StyleProperty::Position, keyword("fixed"))]), Participation::StackingContext(0));
+    }
+}
@@ -1,21 +1,27 @@
 use std::collections::HashMap;
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock};
+use rstar::AABB;
 use rstar::primitives::GeomWithData;
 use crate::layouter::box_model::BoxModel;
 use crate::rendertree_builder::{RenderTree, RenderNodeId};
 use crate::common::document::node::{NodeId as DomNodeId, NodeId};
-use crate::common::geo::{Coordinate, Dimension};
+use crate::common::geo::{Coordinate, Dimension, Rect};
 use crate::common::media::MediaId;
-use crate::layouter::text::Alignment;
+use crate::layouter::text::{Alignment, TextDecoration, TextStyle, TextWrapping};
+use crate::common::document::style::{Direction, FontStyle};
+use crate::layouter::replaced::IntrinsicSize;
 
 pub mod taffy;
 pub mod text;
-mod box_model;
+pub mod replaced;
+pub mod regression;
+pub mod pagination;
+pub mod box_model;
 mod css_taffy_converter;
 
 /// ID's for layout elements
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct LayoutElementId(u64);
 
 impl LayoutElementId {
@@ -45,8 +51,10 @@ pub struct ElementContextText {
     pub font_family: String,
     /// Size of the font in pixels
     pub font_size: f64,
-    /// Weight (100-700) of the font
+    /// Weight (100-900) of the font
     pub font_weight: usize,
+    /// Normal, italic or oblique
+    pub font_style: FontStyle,
     /// Line height of the text. Most likely not needed anymore since we already calculated the text_offset
     pub line_height: f64,
     pub text: String,
@@ -54,6 +62,31 @@ pub struct ElementContextText {
     pub text_offset: Coordinate,
     /// Alignment of font
     pub alignment: Alignment,
+    /// Base direction of the text
+    pub direction: Direction,
+    /// How lines are wrapped and truncated
+    pub wrapping: TextWrapping,
+    /// Underline, overline and line-through of the text
+    pub decoration: TextDecoration,
+    /// Width of the content box the text is laid out in. The measure function uses the available width instead.
+    pub max_width: f64,
+}
+
+impl ElementContextText {
+    /// Returns the style the text is shaped with at its measured width
+    pub fn text_style(&self) -> TextStyle {
+        TextStyle {
+            font_family: self.font_family.clone(),
+            font_size: self.font_size,
+            font_weight: self.font_weight,
+            font_style: self.font_style,
+            line_height: self.line_height,
+            max_width: self.max_width,
+            alignment: self.alignment,
+            direction: self.direction,
+            wrapping: self.wrapping,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -64,8 +97,8 @@ pub struct ElementContextSvg {
     pub src: String,
     /// ID of the SVG inside the media store
     pub media_id: MediaId,
-    /// Dimension of the SVG. Can be Dimension::ZERO if not known yet
-    pub dimension: Dimension,
+    /// Natural size of the SVG, from its width, height and viewBox attributes
+    pub intrinsic_size: IntrinsicSize,
 }
 
 #[derive(Clone, Debug)]
@@ -76,8 +109,8 @@ pub struct ElementContextImage {
     pub src: String,
     /// ID of the image inside the image store
     pub media_id: MediaId,
-    /// Dimension of the image. Can be Dimension::ZERO if not known yet
-    pub dimension: Dimension,
+    /// Natural size of the image
+    pub intrinsic_size: IntrinsicSize,
 }
 
 /// Information about the given element that is needed for different phases of the rendering pipeline. For instance,
@@ -91,34 +124,21 @@ pub enum ElementContext {
 }
 
 impl ElementContext {
-    pub(crate) fn text(font_family: &str, font_size: f64, font_weight: usize, line_height: f64, alignment: Alignment, text: &str, node_id: DomNodeId, text_offset: Coordinate) -> ElementContext {
-        Self::Text(ElementContextText{
-            node_id,
-            font_family: font_family.to_string(),
-            font_size,
-            font_weight,
-            line_height,
-            alignment,
-            text: text.to_string(),
-            text_offset,
-        })
-    }
-
-    pub fn image(src: &str, media_id: MediaId, dimension: Dimension, node_id: DomNodeId) -> ElementContext {
+    pub fn image(src: &str, media_id: MediaId, intrinsic_size: IntrinsicSize, node_id: DomNodeId) -> ElementContext {
         Self::Image(ElementContextImage {
             node_id,
             src: src.to_string(),
             media_id,
-            dimension,
+            intrinsic_size,
         })
     }
 
-    pub fn svg(src: &str, media_id: MediaId, dimension: Dimension, node_id: DomNodeId) -> ElementContext {
+    pub fn svg(src: &str, media_id: MediaId, intrinsic_size: IntrinsicSize, node_id: DomNodeId) -> ElementContext {
         Self::Svg(ElementContextSvg {
             node_id,
             src: src.to_string(),
             media_id,
-            dimension,
+            intrinsic_size,
         })
     }
 
@@ -169,6 +189,23 @@ impl LayoutTree {
         *nid += 1;
         id
     }
+
+    /// Rebuilds the R* tree from the margin boxes of the elements. This must be done each time the box models of
+    /// the elements change.
+    pub fn rebuild_rstar_tree(&mut self) {
+        let elements = self.arena.values().map(|element| {
+            let rect = element.box_model.margin_box;
+            let rectangle = rstar::primitives::Rectangle::from_corners([rect.x, rect.y], [rect.x + rect.width, rect.y + rect.height]);
+            GeomWithData::new(rectangle, element.id)
+        }).collect();
+        self.rstar_tree = rstar::RTree::bulk_load(elements);
+    }
+
+    /// Returns the elements whose margin box intersects with the given rect
+    pub fn find_elements_in_rect(&self, rect: Rect) -> Vec<LayoutElementId> {
+        let envelope = AABB::from_corners([rect.x, rect.y], [rect.x + rect.width, rect.y + rect.height]);
+        self.rstar_tree.locate_in_envelope_intersecting(&envelope).map(|element| element.data).collect()
+    }
 }
 
 impl std::fmt::Debug for LayoutTree {
@@ -28,9 +28,22 @@ impl CssTaffyConverter {
             StyleValue::None => default,
             StyleValue::Display(_) => default,
             StyleValue::FontWeight(_) => default,
+            StyleValue::FontStyle(_) => default,
+            StyleValue::TextDecorationLine(_) => default,
+            StyleValue::TextDecorationStyle(_) => default,
             StyleValue::TextWrap(_) => default,
             StyleValue::Percentage(_) => default,
             StyleValue::TextAlign(_) => default,
+            StyleValue::Direction(_) => default,
+            StyleValue::UnicodeBidi(_) => default,
+            StyleValue::WordBreak(_) => default,
+            StyleValue::OverflowWrap(_) => default,
+            StyleValue::Hyphens(_) => default,
+            StyleValue::LineBreak(_) => default,
+            StyleValue::WhiteSpace(_) => default,
+            StyleValue::TextOverflow(_) => default,
+            StyleValue::PageBreak(_) => default,
+            StyleValue::AspectRatio(_) => default,
         }
     }
 
@@ -41,6 +54,7 @@ impl CssTaffyConverter {
 
         match *val {
             StyleValue::Number(num) => Some(num),
+            StyleValue::AspectRatio(aspect_ratio) => Some(aspect_ratio.ratio),
             _ => default,
         }
     }
@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use crate::common::document::document::Document;
+use crate::common::document::media_query::{MediaEnvironment, MediaType};
+use crate::common::document::node::NodeType;
+use crate::common::document::style::{PageBreak, StyleProperty, StylePropertyList, StyleValue, Unit};
+use crate::common::geo::{Coordinate, Dimension, Rect};
+use crate::layouter::box_model::Edges;
+use crate::layouter::taffy::TaffyLayouter;
+use crate::layouter::{CanLayout, LayoutElementId, LayoutTree};
+use crate::rendertree_builder::RenderTree;
+
+/// CSS pixels per millimeter (96 pixels per inch)
+const PX_PER_MM: f64 = 96.0 / 25.4;
+
+/// Breaks that are this close to the start of the page are ignored, so we never generate empty pages
+const EPSILON: f64 = 0.5;
+
+/// Size and margins of the pages a document is paginated into
+#[derive(Debug, Clone, Copy)]
+pub struct PageSetup {
+    /// Size of the page in CSS pixels
+    pub size: Dimension,
+    /// Margins of the page. The content is laid out inside these margins.
+    pub margin: Edges,
+}
+
+impl PageSetup {
+    pub const A4: Dimension = Dimension { width: 210.0 * PX_PER_MM, height: 297.0 * PX_PER_MM };
+    pub const LETTER: Dimension = Dimension { width: 816.0, height: 1056.0 };
+
+    pub fn new(size: Dimension) -> Self {
+        Self {
+            size,
+            margin: Edges { top: 0.0, right: 0.0, bottom: 0.0, left: 0.0 },
+        }
+    }
+
+    pub fn with_margin(mut self, margin: Edges) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Returns the page setup from the @page rules of the document. Anything the rules do not specify is taken
+    /// from the given default.
+    pub fn from_document(doc: &Document, default: PageSetup) -> Self {
+        let style = &doc.page_style;
+
+        let size = match style.get_property(StyleProperty::Size) {
+            Some(StyleValue::Keyword(size)) => parse_page_size(size).unwrap_or(default.size),
+            _ => default.size,
+        };
+
+        let margin = |prop: StyleProperty, default: f64| page_length(style, prop).unwrap_or(default);
+        Self {
+            size,
+            margin: Edges {
+                top: margin(StyleProperty::MarginTop, default.margin.top),
+                right: margin(StyleProperty::MarginRight, default.margin.right),
+                bottom: margin(StyleProperty::MarginBottom, default.margin.bottom),
+                left: margin(StyleProperty::MarginLeft, default.margin.left),
+            },
+        }
+    }
+
+    /// Area of the page the content is laid out in
+    pub fn content_box(&self) -> Rect {
+        Rect::new(
+            self.margin.left,
+            self.margin.top,
+            (self.size.width - self.margin.left - self.margin.right).max(0.0),
+            (self.size.height - self.margin.top - self.margin.bottom).max(0.0),
+        )
+    }
+}
+
+/// Vertical extent and break properties of an element, as far as pagination is concerned
+#[derive(Debug, Clone)]
+pub struct BreakBox {
+    pub top: f64,
+    pub bottom: f64,
+    pub before: PageBreak,
+    pub after: PageBreak,
+    pub inside: PageBreak,
+    pub children: Vec<BreakBox>,
+}
+
+impl BreakBox {
+    /// Creates the break box tree of the given layout element and its children
+    fn from_layout(layout_tree: &LayoutTree, element_id: LayoutElementId) -> Option<Self> {
+        let element = layout_tree.get_node_by_id(element_id)?;
+        let margin_box = element.box_model.margin_box;
+
+        let (mut before, mut after, inside) = match layout_tree.render_tree.doc.get_node_by_id(element.dom_node_id).map(|n| &n.node_type) {
+            Some(NodeType::Element(data)) => (
+                page_break(&data.styles, StyleProperty::BreakBefore),
+                page_break(&data.styles, StyleProperty::BreakAfter),
+                page_break(&data.styles, StyleProperty::BreakInside),
+            ),
+            _ => (PageBreak::Auto, PageBreak::Auto, PageBreak::Auto),
+        };
+
+        let children: Vec<BreakBox> = element.children.iter()
+            .filter_map(|child_id| Self::from_layout(layout_tree, *child_id))
+            .collect();
+
+        // A forced break before the first child or after the last child is a break before or after the parent
+        if children.first().is_some_and(|c| c.before == PageBreak::Page) {
+            before = PageBreak::Page;
+        }
+        if children.last().is_some_and(|c| c.after == PageBreak::Page) {
+            after = PageBreak::Page;
+        }
+
+        Some(Self {
+            top: margin_box.y,
+            bottom: margin_box.y + margin_box.height,
+            before,
+            after,
+            inside,
+            children,
+        })
+    }
+
+    fn height(&self) -> f64 {
+        self.bottom - self.top
+    }
+}
+
+/// Returns the vertical positions (in layout coordinates) where each page starts. The first page always starts
+/// at 0.
+///
+/// Elements without children (like text) are never split over pages, unless they are taller than a page. In that
+/// case they are sliced at the page boundaries, which can cut through a line of text.
+pub fn page_breaks(root: &BreakBox, page_height: f64) -> Vec<f64> {
+    let mut paginator = Paginator { page_height, breaks: vec![0.0] };
+    if page_height > 0.0 {
+        paginator.visit(root, None);
+    }
+    paginator.breaks
+}
+
+struct Paginator {
+    page_height: f64,
+    breaks: Vec<f64>,
+}
+
+impl Paginator {
+    fn page_start(&self) -> f64 {
+        *self.breaks.last().unwrap_or(&0.0)
+    }
+
+    fn page_end(&self) -> f64 {
+        self.page_start() + self.page_height
+    }
+
+    fn break_at(&mut self, y: f64) {
+        if y > self.page_start() + EPSILON {
+            self.breaks.push(y);
+        }
+    }
+
+    fn visit(&mut self, node: &BreakBox, prev: Option<&BreakBox>) {
+        let fits = node.bottom <= self.page_end() + EPSILON;
+        let monolithic = node.children.is_empty() || (node.inside == PageBreak::Avoid && node.height() <= self.page_height);
+
+        if !monolithic {
+            self.visit_children(&node.children);
+            return;
+        }
+
+        if !fits {
+            // Move the element to the next page. When a break between the element and its previous sibling
+            // should be avoided, the previous sibling moves along, as long as it does not start the page.
+            let avoid = node.before == PageBreak::Avoid || prev.is_some_and(|p| p.after == PageBreak::Avoid);
+            match prev {
+                Some(prev) if avoid && prev.top > self.page_start() + EPSILON => self.break_at(prev.top),
+                _ => self.break_at(node.top),
+            }
+
+            // Elements that are taller than a page are sliced
+            while node.bottom > self.page_end() + EPSILON {
+                let end = self.page_end();
+                self.breaks.push(end);
+            }
+        }
+
+        // Forced breaks win over break-inside: avoid
+        self.visit_children(&node.children);
+    }
+
+    fn visit_children(&mut self, children: &[BreakBox]) {
+        for (i, child) in children.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|j| &children[j]);
+            if child.before == PageBreak::Page || prev.is_some_and(|p| p.after == PageBreak::Page) {
+                self.break_at(child.top);
+            }
+            self.visit(child, prev);
+        }
+    }
+}
+
+/// Lays out the document for print on pages with the given setup, and returns a layout tree for each page. The
+/// print media styles are applied and the content is laid out at the width of the page area.
+pub fn layout_pages(doc: &Document, page: &PageSetup) -> Vec<LayoutTree> {
+    let content_box = page.content_box();
+
+    let mut doc = doc.clone();
+    doc.apply_media(&MediaEnvironment {
+        media_type: MediaType::Print,
+        ..MediaEnvironment::new(content_box.width, content_box.height)
+    });
+
+    let mut render_tree = RenderTree::new(Arc::new(doc));
+    render_tree.parse();
+
+    let mut layouter = TaffyLayouter::new();
+    let layout_tree = layouter.layout(render_tree, Some(content_box.dimension()));
+
+    paginate(&layout_tree, page)
+}
+
+/// Fragments a layout tree into pages. Each page gets its own layout tree with the elements that are (partly)
+/// visible on that page, positioned inside the page area. The root element covers the whole page.
+///
+/// Elements that are split over two pages are found in both trees, with their full box. They must be clipped to
+/// the page area when painted, otherwise they are visible in the page margins.
+pub fn paginate(layout_tree: &LayoutTree, page: &PageSetup) -> Vec<LayoutTree> {
+    let content_box = page.content_box();
+    let Some(root) = BreakBox::from_layout(layout_tree, layout_tree.root_id) else {
+        return vec![];
+    };
+
+    let breaks = page_breaks(&root, content_box.height);
+    let document_end = root.bottom.max(0.0);
+
+    breaks.iter().enumerate().map(|(i, &top)| {
+        let bottom = breaks.get(i + 1).copied().unwrap_or(document_end);
+        page_tree(layout_tree, page, top, bottom)
+    }).collect()
+}
+
+/// Returns the layout tree of a single page with the elements between top and bottom
+fn page_tree(layout_tree: &LayoutTree, page: &PageSetup, top: f64, bottom: f64) -> LayoutTree {
+    let content_box = page.content_box();
+    let offset = Coordinate::new(content_box.x, content_box.y - top);
+
+    let mut arena = HashMap::new();
+    copy_element(layout_tree, layout_tree.root_id, offset, top, bottom, &mut arena);
+
+    if let Some(root) = arena.get_mut(&layout_tree.root_id) {
+        root.box_model.margin_box = Rect::new(0.0, 0.0, page.size.width, page.size.height);
+        root.box_model.margin = Edges { top: 0.0, right: 0.0, bottom: 0.0, left: 0.0 };
+    }
+
+    let next_node_id = *layout_tree.next_node_id.read().expect("Failed to lock next node ID");
+    let mut page_tree = LayoutTree {
+        render_tree: layout_tree.render_tree.clone(),
+        arena,
+        root_id: layout_tree.root_id,
+        next_node_id: Arc::new(RwLock::new(next_node_id)),
+        root_dimension: page.size,
+        rstar_tree: rstar::RTree::new(),
+    };
+    page_tree.rebuild_rstar_tree();
+    page_tree
+}
+
+/// Copies the element and its children that are visible between top and bottom into the arena
+fn copy_element(
+    layout_tree: &LayoutTree,
+    element_id: LayoutElementId,
+    offset: Coordinate,
+    top: f64,
+    bottom: f64,
+    arena: &mut HashMap<LayoutElementId, crate::layouter::LayoutElementNode>,
+) {
+    let Some(element) = layout_tree.get_node_by_id(element_id) else {
+        return;
+    };
+
+    let visible = |id: &LayoutElementId| {
+        layout_tree.get_node_by_id(*id).is_some_and(|child| {
+            let margin_box = child.box_model.margin_box;
+            margin_box.y < bottom && margin_box.y + margin_box.height > top
+        })
+    };
+
+    let mut element = element.clone();
+    element.box_model.margin_box = element.box_model.margin_box.shift(offset);
+    element.children.retain(visible);
+
+    for child_id in &element.children {
+        copy_element(layout_tree, *child_id, offset, top, bottom, arena);
+    }
+    arena.insert(element_id, element);
+}
+
+fn page_break(styles: &StylePropertyList, prop: StyleProperty) -> PageBreak {
+    match styles.get_property(prop) {
+        Some(StyleValue::PageBreak(value)) => *value,
+        _ => PageBreak::Auto,
+    }
+}
+
+/// Returns a length from the @page rule in pixels
+fn page_length(style: &StylePropertyList, prop: StyleProperty) -> Option<f64> {
+    match style.get_property(prop)? {
+        StyleValue::Unit(value, Unit::Px) => Some(*value as f64),
+        StyleValue::Keyword(value) => parse_length(value),
+        _ => None,
+    }
+}
+
+/// Parses absolute lengths (px, mm, cm, in, pt, pc) into pixels
+fn parse_length(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let units = [("px", 1.0), ("mm", PX_PER_MM), ("cm", 10.0 * PX_PER_MM), ("in", 96.0), ("pt", 96.0 / 72.0), ("pc", 16.0)];
+    for (unit, factor) in units {
+        if let Some(number) = value.strip_suffix(unit) {
+            return number.trim().parse::<f64>().ok().map(|n| n * factor);
+        }
+    }
+
+    // Only zero can be written without a unit
+    value.parse::<f64>().ok().filter(|n| *n == 0.0)
+}
+
+/// Parses the size descriptor of an @page rule: `auto`, a page size name, one or two lengths, optionally with
+/// an orientation.
+fn parse_page_size(value: &str) -> Option<Dimension> {
+    let mut size = None;
+    let mut landscape = None;
+    let mut lengths = Vec::new();
+
+    for part in value.to_ascii_lowercase().split_whitespace() {
+        match part {
+            "auto" => return None,
+            "portrait" => landscape = Some(false),
+            "landscape" => landscape = Some(true),
+            "a5" => size = Some((148.0 * PX_PER_MM, 210.0 * PX_PER_MM)),
+            "a4" => size = Some((PageSetup::A4.width, PageSetup::A4.height)),
+            "a3" => size = Some((297.0 * PX_PER_MM, 420.0 * PX_PER_MM)),
+            "b5" => size = Some((176.0 * PX_PER_MM, 250.0 * PX_PER_MM)),
+            "b4" => size = Some((250.0 * PX_PER_MM, 353.0 * PX_PER_MM)),
+            "letter" => size = Some((PageSetup::LETTER.width, PageSetup::LETTER.height)),
+            "legal" => size = Some((816.0, 1344.0)),
+            "ledger" => size = Some((1056.0, 1632.0)),
+            _ => lengths.push(parse_length(part)?),
+        }
+    }
+
+    let (width, height) = match (size, lengths.as_slice()) {
+        (Some(size), []) => size,
+        (None, [side]) => (*side, *side),
+        (None, [width, height]) => (*width, *height),
+        // Orientation only, on the default page size
+        (None, []) => (PageSetup::A4.width, PageSetup::A4.height),
+        _ => return None,
+    };
+
+    // Named sizes are portrait, the orientation rotates them
+    match landscape {
+        Some(true) => Some(Dimension::new(width.max(height), width.min(height))),
+        Some(false) => Some(Dimension::new(width.min(height), width.max(height))),
+        None => Some(Dimension::new(width, height)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::style::Display;
+
+    fn block(top: f64, bottom: f64) -> BreakBox {
+        BreakBox {
+            top,
+            bottom,
+            before: PageBreak::Auto,
+            after: PageBreak::Auto,
+            inside: PageBreak::Auto,
+            children: vec![],
+        }
+    }
+
+    fn root(children: Vec<BreakBox>) -> BreakBox {
+        let bottom = children.last().map(|c| c.bottom).unwrap_or(0.0);
+        BreakBox { children, ..block(0.0, bottom) }
+    }
+
+    #[test]
+    fn test_page_breaks() {
+        // Everything fits on a single page
+        assert_eq!(page_breaks(&root(vec![block(0.0, 50.0), block(50.0, 100.0)]), 100.0), vec![0.0]);
+
+        // The second block does not fit, and is moved to the next page as a whole
+        assert_eq!(page_breaks(&root(vec![block(0.0, 60.0), block(60.0, 120.0)]), 100.0), vec![0.0, 60.0]);
+
+        // A block that is taller than a page is sliced
+        assert_eq!(page_breaks(&root(vec![block(0.0, 250.0)]), 100.0), vec![0.0, 100.0, 200.0]);
+
+        // Nothing to lay out on zero height pages
+        assert_eq!(page_breaks(&root(vec![block(0.0, 250.0)]), 0.0), vec![0.0]);
+    }
+
+    #[test]
+    fn test_forced_and_avoided_breaks() {
+        let forced = BreakBox { before: PageBreak::Page, ..block(20.0, 40.0) };
+        assert_eq!(page_breaks(&root(vec![block(0.0, 20.0), forced, block(40.0, 60.0)]), 100.0), vec![0.0, 20.0]);
+
+        let after = BreakBox { after: PageBreak::Page, ..block(0.0, 20.0) };
+        assert_eq!(page_breaks(&root(vec![after, block(20.0, 40.0)]), 100.0), vec![0.0, 20.0]);
+
+        // A heading that avoids a break after it moves along with the next block
+        let heading = BreakBox { after: PageBreak::Avoid, ..block(40.0, 60.0) };
+        assert_eq!(page_breaks(&root(vec![block(0.0, 40.0), heading, block(60.0, 120.0)]), 100.0), vec![0.0, 40.0]);
+
+        // break-inside: avoid keeps the children together
+        let mut section = block(50.0, 110.0);
+        section.inside = PageBreak::Avoid;
+        section.children = vec![block(50.0, 80.0), block(80.0, 110.0)];
+        assert_eq!(page_breaks(&root(vec![block(0.0, 50.0), section.clone()]), 100.0), vec![0.0, 50.0]);
+
+        section.inside = PageBreak::Auto;
+        assert_eq!(page_breaks(&root(vec![block(0.0, 50.0), section]), 100.0), vec![0.0, 80.0]);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let px = |v: f32| StyleValue::Unit(v, Unit::Px);
+        let block = || {
+            let mut styles = StylePropertyList::new();
+            styles.set_property(StyleProperty::Display, StyleValue::Display(Display::Block));
+            styles
+        };
+
+        let mut doc = Document::new("https://example.com");
+        let root_id = doc.new_element(None, "DocumentRoot", None, false, Some(block()));
+        let mut block_ids = vec![];
+        // The first block fits on the first page, the second is taller than a page and is sliced
+        for height in [60.0, 150.0] {
+            let mut styles = block();
+            styles.set_property(StyleProperty::Height, px(height));
+            let node_id = doc.new_element(Some(root_id), "div", None, false, Some(styles));
+            doc.add_child(root_id, node_id);
+            block_ids.push(node_id);
+        }
+        doc.set_root(root_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+        let layout_tree = TaffyLayouter::new().layout(render_tree, Some(Dimension::new(200.0, 100.0)));
+
+        let margin = Edges { top: 10.0, right: 10.0, bottom: 10.0, left: 10.0 };
+        let page = PageSetup::new(Dimension::new(220.0, 120.0)).with_margin(margin);
+        let pages = paginate(&layout_tree, &page);
+        assert_eq!(pages.len(), 3);
+
+        let element_of = |tree: &LayoutTree, dom_node_id| tree.arena.values().find(|e| e.dom_node_id == dom_node_id).map(|e| e.id);
+        let [first_id, sliced_id] = [block_ids[0], block_ids[1]];
+
+        // The tall block moves to the second page as a whole, and is sliced over the second and third page. It
+        // is positioned relative to the top of the page area on both.
+        assert!(element_of(&pages[0], first_id).is_some());
+        assert!(element_of(&pages[0], sliced_id).is_none());
+        for (page_tree, top) in pages[1..].iter().zip([10.0, -90.0]) {
+            assert!(element_of(page_tree, first_id).is_none());
+            let element_id = element_of(page_tree, sliced_id).unwrap();
+            let margin_box = page_tree.get_node_by_id(element_id).unwrap().box_model.margin_box;
+            assert_eq!(margin_box, Rect::new(10.0, top, 200.0, 150.0));
+        }
+
+        // The spatial index of each page tree is rebuilt for the positions on that page
+        let contains = |tree: &LayoutTree, area: Rect, dom_node_id| {
+            tree.find_elements_in_rect(area).contains(&element_of(tree, dom_node_id).unwrap())
+        };
+        let top = Rect::new(10.0, 10.0, 200.0, 20.0);
+        let bottom = Rect::new(10.0, 80.0, 200.0, 20.0);
+        assert!(contains(&pages[0], top, first_id));
+        assert!(!contains(&pages[0], bottom, first_id));
+        assert!(contains(&pages[1], bottom, sliced_id));
+        assert!(contains(&pages[2], top, sliced_id));
+        assert!(!contains(&pages[2], bottom, sliced_id));
+    }
+
+    #[test]
+    fn test_parse_page_size() {
+        let a4 = parse_page_size("A4").unwrap();
+        assert!((a4.width - 793.7).abs() < 0.1);
+        assert!((a4.height - 1122.5).abs() < 0.1);
+
+        assert_eq!(parse_page_size("letter landscape"), Some(Dimension::new(1056.0, 816.0)));
+        assert_eq!(parse_page_size("5in 4in"), Some(Dimension::new(480.0, 384.0)));
+        assert_eq!(parse_page_size("5in 4in portrait"), Some(Dimension::new(384.0, 480.0)));
+        assert_eq!(parse_page_size("100px"), Some(Dimension::new(100.0, 100.0)));
+        assert_eq!(parse_page_size("auto"), None);
+        assert_eq!(parse_page_size("a4 10px"), None);
+
+        assert_eq!(parse_length("2cm").map(|v| v.round()), Some(76.0));
+        assert_eq!(parse_length("72pt"), Some(96.0));
+        assert_eq!(parse_length("0"), Some(0.0));
+        assert_eq!(parse_length("10"), None);
+    }
+}
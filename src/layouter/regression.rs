@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use crate::common::document::node::{NodeId as DomNodeId, NodeType};
+use crate::common::document::parser::{document_from_json, CAPTURE_VIEWPORT};
+use crate::common::geo::Rect;
+use crate::layouter::taffy::TaffyLayouter;
+use crate::layouter::{CanLayout, LayoutElementNode, LayoutTree};
+use crate::rendertree_builder::RenderTree;
+
+/// Maximum difference (in pixels) between our layout and the reference layout for an element to be considered a
+/// match.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// Maximum difference of the x and y position
+    pub position: f64,
+    /// Maximum difference of the width and height
+    pub size: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { position: 1.0, size: 1.0 }
+    }
+}
+
+/// Difference between the reference box of an element and the border box we computed
+#[derive(Clone, Debug)]
+pub struct ElementDelta {
+    pub dom_node_id: DomNodeId,
+    /// Tag name of the element
+    pub tag: String,
+    /// Box as computed by the reference browser
+    pub expected: Rect,
+    /// Border box as computed by our layouter
+    pub actual: Rect,
+}
+
+impl ElementDelta {
+    /// Largest difference of the x and y position
+    pub fn position_delta(&self) -> f64 {
+        (self.actual.x - self.expected.x).abs().max((self.actual.y - self.expected.y).abs())
+    }
+
+    /// Largest difference of the width and height
+    pub fn size_delta(&self) -> f64 {
+        (self.actual.width - self.expected.width).abs().max((self.actual.height - self.expected.height).abs())
+    }
+
+    pub fn is_within(&self, thresholds: &Thresholds) -> bool {
+        self.position_delta() <= thresholds.position && self.size_delta() <= thresholds.size
+    }
+}
+
+/// Result of comparing the layout of a page against the reference layout
+#[derive(Clone, Debug)]
+pub struct RegressionReport {
+    /// Name of the page (normally the path of the JSON file)
+    pub page: String,
+    pub thresholds: Thresholds,
+    /// All elements that have a reference box and that we have laid out
+    pub deltas: Vec<ElementDelta>,
+    /// Elements that have a reference box, but that we did not lay out at all
+    pub missing: Vec<DomNodeId>,
+}
+
+impl RegressionReport {
+    /// Number of elements that are within the thresholds
+    pub fn matched(&self) -> usize {
+        self.deltas.iter().filter(|d| d.is_within(&self.thresholds)).count()
+    }
+
+    /// Number of elements that are compared, including the ones we did not lay out
+    pub fn total(&self) -> usize {
+        self.deltas.len() + self.missing.len()
+    }
+
+    /// Score of the page between 0.0 (nothing matches) and 1.0 (every element matches)
+    pub fn score(&self) -> f64 {
+        match self.total() {
+            0 => 1.0,
+            total => self.matched() as f64 / total as f64,
+        }
+    }
+
+    pub fn mean_position_delta(&self) -> f64 {
+        mean(self.deltas.iter().map(|d| d.position_delta()))
+    }
+
+    pub fn mean_size_delta(&self) -> f64 {
+        mean(self.deltas.iter().map(|d| d.size_delta()))
+    }
+
+    /// Returns the elements with the largest differences first
+    pub fn worst(&self, count: usize) -> Vec<&ElementDelta> {
+        let mut deltas: Vec<&ElementDelta> = self.deltas.iter().filter(|d| !d.is_within(&self.thresholds)).collect();
+        deltas.sort_by(|a, b| {
+            let a = a.position_delta() + a.size_delta();
+            let b = b.position_delta() + b.size_delta();
+            b.total_cmp(&a)
+        });
+        deltas.truncate(count);
+        deltas
+    }
+}
+
+impl Display for RegressionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}: score {:.1}% ({}/{} elements), missing: {}, mean delta position: {:.2}px size: {:.2}px",
+            self.page,
+            self.score() * 100.0,
+            self.matched(),
+            self.total(),
+            self.missing.len(),
+            self.mean_position_delta(),
+            self.mean_size_delta(),
+        )?;
+
+        for delta in self.worst(10) {
+            writeln!(
+                f,
+                "  <{}> {}: expected {:.1},{:.1} {:.1}x{:.1}, got {:.1},{:.1} {:.1}x{:.1}",
+                delta.tag,
+                delta.dom_node_id,
+                delta.expected.x,
+                delta.expected.y,
+                delta.expected.width,
+                delta.expected.height,
+                delta.actual.x,
+                delta.actual.y,
+                delta.actual.width,
+                delta.actual.height,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lays out the captured page at the given path and compares it against the bounding boxes the page was captured
+/// with.
+pub fn run_regression(path: &str, thresholds: Thresholds) -> RegressionReport {
+    let doc = document_from_json("https://example.com", path);
+    let viewport = doc.reference_viewport.unwrap_or(CAPTURE_VIEWPORT);
+
+    let mut render_tree = RenderTree::new(Arc::new(doc));
+    render_tree.parse();
+
+    let mut layouter = TaffyLayouter::new();
+    let layout_tree = layouter.layout(render_tree, Some(viewport));
+
+    compare_layout(path, &layout_tree, thresholds)
+}
+
+/// Compares the border boxes of the layout tree against the reference boxes of the document
+pub fn compare_layout(page: &str, layout_tree: &LayoutTree, thresholds: Thresholds) -> RegressionReport {
+    let doc = &layout_tree.render_tree.doc;
+
+    let mut deltas = Vec::new();
+    let mut missing = Vec::new();
+
+    let mut reference: Vec<_> = doc.reference_boxes.iter().collect();
+    reference.sort_by_key(|(node_id, _)| node_id.to_u64());
+
+    // Layout element of each DOM node. When a DOM node has more than one layout element, the first one is used.
+    let mut layout_elements: HashMap<DomNodeId, &LayoutElementNode> = HashMap::new();
+    for layout_node in layout_tree.arena.values() {
+        layout_elements.entry(layout_node.dom_node_id)
+            .and_modify(|existing| if layout_node.id < existing.id { *existing = layout_node })
+            .or_insert(layout_node);
+    }
+
+    for (node_id, expected) in reference {
+        let Some(layout_node) = layout_elements.get(node_id) else {
+            missing.push(*node_id);
+            continue;
+        };
+
+        let tag = match doc.get_node_by_id(*node_id).map(|n| &n.node_type) {
+            Some(NodeType::Element(data)) => data.tag_name.clone(),
+            _ => String::new(),
+        };
+
+        deltas.push(ElementDelta {
+            dom_node_id: *node_id,
+            tag,
+            expected: *expected,
+            actual: layout_node.box_model.border_box(),
+        });
+    }
+
+    RegressionReport {
+        page: page.to_string(),
+        thresholds,
+        deltas,
+        missing,
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    match count {
+        0 => 0.0,
+        count => sum / count as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(expected: Rect, actual: Rect) -> ElementDelta {
+        ElementDelta { dom_node_id: DomNodeId::new(1), tag: "div".to_string(), expected, actual }
+    }
+
+    #[test]
+    fn test_report_score() {
+        let report = RegressionReport {
+            page: "test".to_string(),
+            thresholds: Thresholds::default(),
+            deltas: vec![
+                delta(Rect::new(0.0, 0.0, 100.0, 100.0), Rect::new(0.5, 0.0, 100.0, 100.5)),
+                delta(Rect::new(0.0, 0.0, 100.0, 100.0), Rect::new(0.0, 10.0, 100.0, 100.0)),
+                delta(Rect::new(0.0, 0.0, 100.0, 100.0), Rect::new(0.0, 0.0, 50.0, 100.0)),
+            ],
+            missing: vec![DomNodeId::new(2)],
+        };
+
+        assert_eq!(report.matched(), 1);
+        assert_eq!(report.total(), 4);
+        assert_eq!(report.score(), 0.25);
+
+        let worst = report.worst(10);
+        assert_eq!(worst.len(), 2);
+        assert_eq!(worst[0].size_delta(), 50.0);
+        assert_eq!(worst[1].position_delta(), 10.0);
+    }
+}
@@ -0,0 +1,243 @@
+use resvg::usvg::roxmltree;
+use crate::common::document::style::AspectRatio;
+use crate::common::geo::Dimension;
+
+/// Default object size for replaced elements that have no natural dimensions at all (CSS Images 3, §4.1)
+pub const DEFAULT_OBJECT_SIZE: Dimension = Dimension { width: 300.0, height: 150.0 };
+
+/// Natural (intrinsic) sizing information of a replaced element like an image or an SVG. Not every replaced
+/// element has all of them: a bitmap image always has a width, height and ratio, but an SVG with only a viewBox
+/// has just a ratio, and an SVG without any attributes has nothing at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IntrinsicSize {
+    /// Natural width in pixels
+    pub width: Option<f64>,
+    /// Natural height in pixels
+    pub height: Option<f64>,
+    /// Natural aspect ratio (width / height)
+    pub ratio: Option<f64>,
+}
+
+impl IntrinsicSize {
+    pub const NONE: IntrinsicSize = IntrinsicSize { width: None, height: None, ratio: None };
+
+    /// Intrinsic size from a fully known dimension, like a decoded bitmap image
+    pub fn from_dimension(dimension: Dimension) -> Self {
+        Self {
+            width: Some(dimension.width),
+            height: Some(dimension.height),
+            ratio: ratio_of(dimension.width, dimension.height),
+        }
+    }
+
+    /// Intrinsic size of an SVG document based on the width, height and viewBox attributes of the root element.
+    /// Percentages (and missing attributes) do not give a natural dimension, in that case the ratio is taken from
+    /// the viewBox when available.
+    pub fn from_svg_attributes(width: Option<&str>, height: Option<&str>, view_box: Option<&str>) -> Self {
+        let width = width.and_then(parse_length);
+        let height = height.and_then(parse_length);
+        let view_box_ratio = view_box.and_then(parse_view_box).and_then(|vb| ratio_of(vb.width, vb.height));
+
+        let ratio = match (width, height) {
+            (Some(w), Some(h)) => ratio_of(w, h),
+            _ => view_box_ratio,
+        };
+
+        Self { width, height, ratio }
+    }
+
+    /// Intrinsic size of an SVG document from the attributes of the root element in its source. Returns None when
+    /// the source cannot be parsed, like compressed SVG documents.
+    pub fn from_svg_data(data: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(data).ok()?;
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+        let document = roxmltree::Document::parse_with_options(text, options).ok()?;
+        let root = document.root_element();
+
+        Some(Self::from_svg_attributes(root.attribute("width"), root.attribute("height"), root.attribute("viewBox")))
+    }
+
+    /// Returns the intrinsic size with the ratio of the aspect-ratio property. The natural ratio is replaced by it,
+    /// except for `aspect-ratio: auto && <ratio>`, where it is only used when there is no natural ratio.
+    pub fn with_aspect_ratio(self, aspect_ratio: Option<AspectRatio>) -> Self {
+        let Some(aspect_ratio) = aspect_ratio.filter(|a| a.ratio > 0.0) else {
+            return self;
+        };
+
+        let ratio = match (aspect_ratio.auto, self.ratio) {
+            (true, Some(natural)) => natural,
+            _ => aspect_ratio.ratio as f64,
+        };
+        Self { ratio: Some(ratio), ..self }
+    }
+}
+
+/// Computes the used size of a replaced element based on the (definite) width and height set by CSS, and the
+/// intrinsic size of the element. This follows the rules from CSS 2.2 §10.3.2 and §10.6.2 without the min/max
+/// constraints, as these are applied by the layout engine after measuring.
+pub fn compute_replaced_size(
+    known_width: Option<f64>,
+    known_height: Option<f64>,
+    intrinsic: IntrinsicSize,
+    available_width: Option<f64>,
+) -> Dimension {
+    let ratio = intrinsic.ratio;
+
+    match (known_width, known_height) {
+        (Some(width), Some(height)) => Dimension::new(width, height),
+        (Some(width), None) => {
+            let height = ratio.map(|r| width / r).or(intrinsic.height).unwrap_or(DEFAULT_OBJECT_SIZE.height);
+            Dimension::new(width, height)
+        }
+        (None, Some(height)) => {
+            let width = ratio.map(|r| height * r).or(intrinsic.width).unwrap_or(DEFAULT_OBJECT_SIZE.width);
+            Dimension::new(width, height)
+        }
+        (None, None) => match (intrinsic.width, intrinsic.height, ratio) {
+            (Some(width), Some(height), _) => Dimension::new(width, height),
+            (Some(width), None, Some(r)) => Dimension::new(width, width / r),
+            (None, Some(height), Some(r)) => Dimension::new(height * r, height),
+            (Some(width), None, None) => Dimension::new(width, DEFAULT_OBJECT_SIZE.height),
+            (None, Some(height), None) => Dimension::new(DEFAULT_OBJECT_SIZE.width, height),
+            (None, None, Some(r)) => {
+                // Only a ratio: fill the containing block if we know its width
+                let width = available_width.unwrap_or(DEFAULT_OBJECT_SIZE.width);
+                Dimension::new(width, width / r)
+            }
+            (None, None, None) => DEFAULT_OBJECT_SIZE,
+        },
+    }
+}
+
+/// Parses an absolute length as found in HTML and SVG width/height attributes ("100", "100px", "1.5in").
+/// Returns None for relative lengths like percentages or font-relative units.
+pub fn parse_length(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(split);
+
+    let num = num.parse::<f64>().ok()?;
+    if num < 0.0 {
+        return None;
+    }
+
+    let px = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "px" => num,
+        "in" => num * 96.0,
+        "cm" => num * 96.0 / 2.54,
+        "mm" => num * 96.0 / 25.4,
+        "pt" => num * 96.0 / 72.0,
+        "pc" => num * 16.0,
+        _ => return None,
+    };
+
+    Some(px)
+}
+
+/// Parses the width and height of a viewBox attribute ("min-x min-y width height")
+fn parse_view_box(value: &str) -> Option<Dimension> {
+    let parts: Vec<f64> = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    match parts.as_slice() {
+        [_, _, width, height] if *width > 0.0 && *height > 0.0 => Some(Dimension::new(*width, *height)),
+        _ => None,
+    }
+}
+
+fn ratio_of(width: f64, height: f64) -> Option<f64> {
+    if width > 0.0 && height > 0.0 {
+        Some(width / height)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_length() {
+        assert_eq!(parse_length("100"), Some(100.0));
+        assert_eq!(parse_length(" 12.5px "), Some(12.5));
+        assert_eq!(parse_length("1in"), Some(96.0));
+        assert_eq!(parse_length("50%"), None);
+        assert_eq!(parse_length("2em"), None);
+        assert_eq!(parse_length("-10"), None);
+        assert_eq!(parse_length(""), None);
+    }
+
+    #[test]
+    fn test_svg_attributes() {
+        let size = IntrinsicSize::from_svg_attributes(Some("200"), Some("100"), Some("0 0 10 10"));
+        assert_eq!(size.width, Some(200.0));
+        assert_eq!(size.height, Some(100.0));
+        assert_eq!(size.ratio, Some(2.0));
+
+        let size = IntrinsicSize::from_svg_attributes(None, None, Some("0,0,40,20"));
+        assert_eq!(size, IntrinsicSize { width: None, height: None, ratio: Some(2.0) });
+
+        let size = IntrinsicSize::from_svg_attributes(Some("100%"), None, None);
+        assert_eq!(size, IntrinsicSize::NONE);
+    }
+
+    #[test]
+    fn test_replaced_size_from_natural_size() {
+        let natural = IntrinsicSize::from_dimension(Dimension::new(400.0, 200.0));
+
+        assert_eq!(compute_replaced_size(None, None, natural, None), Dimension::new(400.0, 200.0));
+        assert_eq!(compute_replaced_size(Some(100.0), None, natural, None), Dimension::new(100.0, 50.0));
+        assert_eq!(compute_replaced_size(None, Some(100.0), natural, None), Dimension::new(200.0, 100.0));
+        assert_eq!(compute_replaced_size(Some(10.0), Some(20.0), natural, None), Dimension::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn test_replaced_size_partial_intrinsics() {
+        let ratio_only = IntrinsicSize { width: None, height: None, ratio: Some(2.0) };
+        assert_eq!(compute_replaced_size(None, None, ratio_only, Some(500.0)), Dimension::new(500.0, 250.0));
+        assert_eq!(compute_replaced_size(None, None, ratio_only, None), Dimension::new(300.0, 150.0));
+
+        let width_only = IntrinsicSize { width: Some(80.0), height: None, ratio: None };
+        assert_eq!(compute_replaced_size(None, None, width_only, None), Dimension::new(80.0, 150.0));
+
+        assert_eq!(compute_replaced_size(None, None, IntrinsicSize::NONE, None), DEFAULT_OBJECT_SIZE);
+        assert_eq!(compute_replaced_size(None, Some(30.0), IntrinsicSize::NONE, None), Dimension::new(300.0, 30.0));
+    }
+
+    #[test]
+    fn test_aspect_ratio() {
+        let auto = AspectRatio { auto: true, ratio: 1.5 };
+        let ratio = AspectRatio { auto: false, ratio: 1.5 };
+
+        let size = IntrinsicSize::NONE.with_aspect_ratio(Some(auto));
+        assert_eq!(size.ratio, Some(1.5));
+
+        // With auto, the natural ratio wins. Without, the natural ratio is replaced.
+        let natural = IntrinsicSize::from_dimension(Dimension::new(100.0, 100.0));
+        assert_eq!(natural.with_aspect_ratio(Some(auto)).ratio, Some(1.0));
+        assert_eq!(natural.with_aspect_ratio(Some(ratio)).ratio, Some(1.5));
+        assert_eq!(natural.with_aspect_ratio(None).ratio, Some(1.0));
+        assert_eq!(compute_replaced_size(Some(150.0), None, natural.with_aspect_ratio(Some(ratio)), None), Dimension::new(150.0, 100.0));
+    }
+
+    #[test]
+    fn test_svg_data() {
+        let data = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 20"><rect width="40" height="20"/></svg>"#;
+        let size = IntrinsicSize::from_svg_data(data).unwrap();
+        assert_eq!(size, IntrinsicSize { width: None, height: None, ratio: Some(2.0) });
+
+        // Only a ratio: the image fills the containing block instead of using a default size
+        assert_eq!(compute_replaced_size(None, None, size, Some(500.0)), Dimension::new(500.0, 250.0));
+
+        let data = br#"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="10"/>"#;
+        assert_eq!(IntrinsicSize::from_svg_data(data).unwrap().ratio, Some(3.0));
+        assert_eq!(IntrinsicSize::from_svg_data(&[0x1f, 0x8b, 0x08]), None);
+    }
+}
@@ -1,10 +1,14 @@
 use crate::common::document::node::{NodeId as DomNodeId, NodeType};
-use crate::common::document::style::{FontWeight, StyleProperty, StyleValue, TextAlign, Unit};
+use crate::common::document::document::Document;
+use crate::common::document::style::{Direction, FontStyle, StyleProperty, StyleValue, TextDecorationLine, TextDecorationStyle, UnicodeBidi, Unit, WordBreak, OverflowWrap, Hyphens, LineBreak, TextWrap, TextOverflow};
 use crate::common::geo::Coordinate;
 use crate::common::media::{Media, MediaId, MediaType};
 use crate::common::{geo, get_media_store};
 use crate::layouter::css_taffy_converter::CssTaffyConverter;
-use crate::layouter::text::{get_text_layout, Alignment};
+use crate::layouter::replaced::{compute_replaced_size, parse_length, IntrinsicSize};
+use crate::layouter::text::bidi::{apply_unicode_bidi, resolve_alignment};
+use crate::layouter::text::breaking::{apply_line_breaking, resolve_overflow_wrap};
+use crate::layouter::text::{get_text_layout, Alignment, TextDecoration, TextWrapping};
 use crate::layouter::{
     box_model, CanLayout, ElementContext, ElementContextImage, ElementContextSvg,
     ElementContextText, LayoutElementId, LayoutElementNode, LayoutTree,
@@ -38,48 +42,31 @@ pub enum TaffyContext {
 }
 
 impl TaffyContext {
-    fn text(
-        font_family: &str,
-        font_size: f64,
-        font_weight: usize,
-        line_height: f64,
-        alignment: Alignment,
-        text: &str,
-        node_id: DomNodeId,
-        text_offset: Coordinate,
-    ) -> TaffyContext {
-        TaffyContext::Text(ElementContextText {
-            node_id,
-            font_family: font_family.to_string(),
-            font_size,
-            font_weight,
-            line_height,
-            text: text.to_string(),
-            text_offset,
-            alignment,
-        })
-    }
-
     fn image(
         src: &str,
         media_id: MediaId,
-        dimension: geo::Dimension,
+        intrinsic_size: IntrinsicSize,
         node_id: DomNodeId,
     ) -> TaffyContext {
         TaffyContext::Image(ElementContextImage {
             node_id,
             src: src.to_string(),
             media_id,
-            dimension,
+            intrinsic_size,
         })
     }
 
-    fn svg(src: &str, media_id: MediaId, node_id: DomNodeId) -> TaffyContext {
+    fn svg(
+        src: &str,
+        media_id: MediaId,
+        intrinsic_size: IntrinsicSize,
+        node_id: DomNodeId,
+    ) -> TaffyContext {
         TaffyContext::Svg(ElementContextSvg {
             node_id,
             src: src.to_string(),
             media_id,
-            dimension: geo::Dimension::ZERO,
+            intrinsic_size,
         })
     }
 }
@@ -120,29 +107,14 @@ impl CanLayout for TaffyLayouter {
                 match v_nc {
                     // Calculate text node
                     Some(TaffyContext::Text(text_ctx)) => {
-                        let font_size = text_ctx.font_size;
-                        let font_weight = text_ctx.font_weight;
-                        let font_family = text_ctx.font_family.as_str();
-                        let text = text_ctx.text.as_str();
-                        let line_height = text_ctx.line_height;
-                        let alignment = text_ctx.alignment;
-
-                        let max_width = match v_as.width {
+                        text_ctx.max_width = match v_as.width {
                             AvailableSpace::Definite(width) => width as f64,
                             AvailableSpace::MaxContent => f64::MAX,
                             AvailableSpace::MinContent => 0.0,
                         };
 
                         // Calculate the text layout dimensions and return it to taffy
-                        let text_layout = get_text_layout(
-                            text,
-                            font_family,
-                            font_size,
-                            font_weight,
-                            line_height,
-                            max_width,
-                            alignment,
-                        );
+                        let text_layout = get_text_layout(&text_ctx.text, &text_ctx.text_style());
                         match text_layout {
                             Ok(text_layout) => Size {
                                 width: text_layout.width as f32,
@@ -151,7 +123,14 @@ impl CanLayout for TaffyLayouter {
                             Err(_) => Size::ZERO,
                         }
                     }
-                    _ => Size::ZERO,
+                    // Calculate replaced elements from their natural size
+                    Some(TaffyContext::Image(image_ctx)) => {
+                        measure_replaced(image_ctx.intrinsic_size, v_kd, v_as)
+                    }
+                    Some(TaffyContext::Svg(svg_ctx)) => {
+                        measure_replaced(svg_ctx.intrinsic_size, v_kd, v_as)
+                    }
+                    None => Size::ZERO,
                 }
             })
             .unwrap();
@@ -161,6 +140,7 @@ impl CanLayout for TaffyLayouter {
         // layout-engine agnostic.
         let root_id = layout_tree.root_id;
         self.populate_boxmodel(&mut layout_tree, root_id, Coordinate::ZERO);
+        layout_tree.rebuild_rstar_tree();
 
         // get dimension of the root node
         let root = layout_tree.get_node_by_id(root_id).unwrap();
@@ -185,21 +165,20 @@ impl TaffyLayouter {
 
         let el = layout_tree.get_node_by_id_mut(layout_node_id).unwrap();
         el.box_model = taffy_layout_to_boxmodel(&layout, offset);
+        // Text is painted in its final content box, which is not always the width it was last measured with
+        if let ElementContext::Text(text_ctx) = &mut el.context {
+            text_ctx.max_width = el.box_model.content_box().width;
+        }
         let child_ids = el.children.clone();
 
         for child_id in child_ids {
             self.populate_boxmodel(
                 layout_tree,
                 child_id,
+                // Taffy positions the children relative to the border box of their parent
                 Coordinate::new(
-                    offset.x
-                        + layout.location.x as f64
-                        + layout.padding.left as f64
-                        + layout.margin.left as f64,
-                    offset.y
-                        + layout.location.y as f64
-                        + layout.padding.top as f64
-                        + layout.margin.top as f64,
+                    offset.x + layout.location.x as f64,
+                    offset.y + layout.location.y as f64,
                 ),
             );
         }
@@ -271,6 +250,14 @@ impl TaffyLayouter {
 
                 // Check if element type is an image, if so, set the taffy context
                 if data.tag_name.eq_ignore_ascii_case("img") {
+                    // The width and height attributes are presentational hints, only used when CSS does not
+                    // set a size itself.
+                    apply_size_attributes(
+                        &mut taffy_style,
+                        data.get_attribute("width"),
+                        data.get_attribute("height"),
+                    );
+
                     let src = data.get_attribute("src").unwrap();
                     let src = to_absolute_url(src, base_url);
 
@@ -287,9 +274,12 @@ impl TaffyLayouter {
                     let binding = media_store.read().unwrap();
                     let media = binding.get(media_id, MediaType::Image);
                     taffy_context = match media.borrow() {
-                        Media::Svg(_) => {
-                            Some(TaffyContext::svg(src.as_str(), media_id, dom_node.node_id))
-                        }
+                        Media::Svg(media_svg) => Some(TaffyContext::svg(
+                            src.as_str(),
+                            media_id,
+                            media_svg.svg.intrinsic_size,
+                            dom_node.node_id,
+                        )),
                         Media::Image(media_image) => {
                             let dimension = geo::Dimension::new(
                                 media_image.image.width() as f64,
@@ -298,7 +288,7 @@ impl TaffyLayouter {
                             Some(TaffyContext::image(
                                 src.as_str(),
                                 media_id,
-                                dimension,
+                                IntrinsicSize::from_dimension(dimension),
                                 dom_node.node_id,
                             ))
                         }
@@ -315,9 +305,15 @@ impl TaffyLayouter {
                         .load_media_from_data(MediaType::Svg, inner_html.into_bytes().as_slice())
                     {
                         Ok(media_id) => {
+                            let intrinsic_size = IntrinsicSize::from_svg_attributes(
+                                data.get_attribute("width").map(|s| s.as_str()),
+                                data.get_attribute("height").map(|s| s.as_str()),
+                                data.get_attribute("viewBox").map(|s| s.as_str()),
+                            );
                             taffy_context = Some(TaffyContext::svg(
                                 "gosub://internal",
                                 media_id,
+                                intrinsic_size,
                                 dom_node.node_id,
                             ));
                         }
@@ -355,48 +351,88 @@ impl TaffyLayouter {
                     _ => {}
                 }
 
+                // Relative weights (bolder, lighter) are resolved against the weight of the parent element
                 let font_weight = match node_style.get_property(StyleProperty::FontWeight) {
-                    Some(StyleValue::FontWeight(weight)) => match weight {
-                        FontWeight::Normal => 400.0,
-                        FontWeight::Bold => 700.0,
-                        FontWeight::Number(value) => *value as f64,
-                        FontWeight::Bolder => {
-                            unimplemented!("FontWeight::Bolder is not implemented yet")
-                        }
-                        FontWeight::Lighter => {
-                            unimplemented!("FontWeight::Lighter is not implemented yet")
-                        }
-                    },
+                    Some(StyleValue::FontWeight(weight)) => weight.resolve(resolved_font_weight(
+                        &layout_tree.render_tree.doc,
+                        parent_node.and_then(|n| n.parent_id),
+                    )),
                     _ => 400.0,
                 };
 
-                let alignment = match node_style.get_property(StyleProperty::TextAlign) {
-                    Some(StyleValue::TextAlign(value)) => match value {
-                        TextAlign::Center => Alignment::Middle,
-                        TextAlign::Right => Alignment::Start,
-                        TextAlign::Left => Alignment::End,
-                        TextAlign::Justify => Alignment::Justified,
-                        TextAlign::Start => Alignment::Start,
-                        TextAlign::End => Alignment::Start,
-                        TextAlign::MatchParent => {
-                            unimplemented!("TextAlign::MatchParent is not implemented yet")
-                        }
-                        TextAlign::Initial => {
-                            unimplemented!("TextAlign::Initial is not implemented yet")
-                        }
-                        TextAlign::Inherit => {
-                            unimplemented!("TextAlign::Inherit is not implemented yet")
-                        }
-                        TextAlign::Revert => {
-                            unimplemented!("TextAlign::Revert is not implemented yet")
-                        }
-                        TextAlign::Unset => {
-                            unimplemented!("TextAlign::Unset is not implemented yet")
-                        }
+                let font_style = match node_style.get_property(StyleProperty::FontStyle) {
+                    Some(StyleValue::FontStyle(style)) => *style,
+                    _ => FontStyle::Normal,
+                };
+
+                let decoration = TextDecoration {
+                    line: match node_style.get_property(StyleProperty::TextDecorationLine) {
+                        Some(StyleValue::TextDecorationLine(line)) => *line,
+                        _ => TextDecorationLine::NONE,
+                    },
+                    style: match node_style.get_property(StyleProperty::TextDecorationStyle) {
+                        Some(StyleValue::TextDecorationStyle(style)) => *style,
+                        _ => TextDecorationStyle::Solid,
                     },
+                    color: match node_style.get_property(StyleProperty::TextDecorationColor) {
+                        Some(StyleValue::Color(color)) => Some(color.clone()),
+                        _ => None,
+                    },
+                };
+
+                let direction = match node_style.get_property(StyleProperty::Direction) {
+                    Some(StyleValue::Direction(direction)) => *direction,
+                    _ => Direction::Ltr,
+                };
+                let unicode_bidi = match node_style.get_property(StyleProperty::UnicodeBidi) {
+                    Some(StyleValue::UnicodeBidi(unicode_bidi)) => *unicode_bidi,
+                    _ => UnicodeBidi::Normal,
+                };
+
+                // Start and end alignment depend on the direction of the text. For plaintext, the direction is
+                // taken from the text itself, so we need to resolve that first.
+                let (_, direction) = apply_unicode_bidi(text, direction, unicode_bidi);
+                let alignment = match node_style.get_property(StyleProperty::TextAlign) {
+                    Some(StyleValue::TextAlign(value)) => resolve_alignment(value, direction),
                     _ => Alignment::Start,
                 };
 
+                let word_break = match node_style.get_property(StyleProperty::WordBreak) {
+                    Some(StyleValue::WordBreak(word_break)) => *word_break,
+                    _ => WordBreak::Normal,
+                };
+                let overflow_wrap = match node_style.get_property(StyleProperty::OverflowWrap) {
+                    Some(StyleValue::OverflowWrap(overflow_wrap)) => *overflow_wrap,
+                    _ => OverflowWrap::Normal,
+                };
+                let hyphens = match node_style.get_property(StyleProperty::Hyphens) {
+                    Some(StyleValue::Hyphens(hyphens)) => *hyphens,
+                    _ => Hyphens::Manual,
+                };
+                let line_break = match node_style.get_property(StyleProperty::LineBreak) {
+                    Some(StyleValue::LineBreak(line_break)) => *line_break,
+                    _ => LineBreak::Auto,
+                };
+
+                let nowrap = match node_style.get_property(StyleProperty::WhiteSpace) {
+                    Some(StyleValue::WhiteSpace(white_space)) => !white_space.wraps(),
+                    _ => false,
+                } || matches!(node_style.get_property(StyleProperty::TextWrap), Some(StyleValue::TextWrap(TextWrap::NoWrap)));
+
+                // text-overflow only applies to containers that do not show their overflow
+                let clips_overflow = matches!(
+                    node_style.get_property(StyleProperty::OverflowX),
+                    Some(StyleValue::Keyword(overflow)) if overflow != "visible"
+                );
+                let text_overflow = match node_style.get_property(StyleProperty::TextOverflow) {
+                    Some(StyleValue::TextOverflow(text_overflow)) if clips_overflow => *text_overflow,
+                    _ => TextOverflow::Clip,
+                };
+                let line_clamp = match node_style.get_property(StyleProperty::LineClamp) {
+                    Some(StyleValue::Number(lines)) if *lines >= 1.0 => Some(*lines as usize),
+                    _ => None,
+                };
+
                 let line_height = match node_style.get_property(StyleProperty::LineHeight) {
                     Some(StyleValue::Unit(value, unit)) => match unit {
                         Unit::Px => *value as f64,
@@ -417,17 +453,31 @@ impl TaffyLayouter {
                     // If we are in an inline container, we need to add a space between the text nodes
                     text = format!(" {}", text).clone()
                 }
+                let (text, _) = apply_unicode_bidi(&text, direction, unicode_bidi);
+                // Break opportunities that depend on the available width are added by the text backends
+                let text = apply_line_breaking(&text, word_break, line_break, hyphens);
+                let wrapping = TextWrapping {
+                    nowrap,
+                    overflow_wrap: resolve_overflow_wrap(word_break, overflow_wrap),
+                    text_overflow,
+                    line_clamp,
+                };
 
-                taffy_context = Some(TaffyContext::text(
-                    font_family.as_str(),
+                taffy_context = Some(TaffyContext::Text(ElementContextText {
+                    node_id: dom_node.node_id,
+                    font_family,
                     font_size,
-                    font_weight as usize,
+                    font_weight: font_weight as usize,
+                    font_style,
                     line_height,
-                    alignment,
-                    text.as_str(),
-                    dom_node.node_id,
+                    text,
                     text_offset,
-                ));
+                    alignment,
+                    direction,
+                    wrapping,
+                    decoration,
+                    max_width: 0.0,
+                }));
             }
             NodeType::Comment(_) => {
                 // No need to layout for comment nodes. In fact, they should have been removed already
@@ -437,6 +487,17 @@ impl TaffyLayouter {
         }
 
         // The context will be moved to the taffy tree, so we need to convert it before that happens.
+        // The aspect-ratio of replaced elements is applied to their natural size. Taffy resolves the known
+        // dimensions with the same ratio, so an `auto` ratio does not win from the natural ratio there.
+        if let NodeType::Element(data) = &dom_node.node_type {
+            if let Some(StyleValue::AspectRatio(aspect_ratio)) = data.get_style(StyleProperty::AspectRatio) {
+                if let Some(TaffyContext::Image(ElementContextImage { intrinsic_size, .. }) | TaffyContext::Svg(ElementContextSvg { intrinsic_size, .. })) = taffy_context.as_mut() {
+                    *intrinsic_size = intrinsic_size.with_aspect_ratio(Some(*aspect_ratio));
+                    taffy_style.aspect_ratio = intrinsic_size.ratio.map(|r| r as f32);
+                }
+            }
+        }
+
         let element_context = to_element_context(taffy_context.as_ref());
 
         let result = match taffy_context {
@@ -535,6 +596,23 @@ impl TaffyLayouter {
     }
 }
 
+/// Returns the numeric font weight of the given node. Relative weights are resolved against the ancestors.
+fn resolved_font_weight(doc: &Document, node_id: Option<DomNodeId>) -> f32 {
+    let Some(node) = node_id.and_then(|id| doc.get_node_by_id(id)) else {
+        return 400.0;
+    };
+
+    match &node.node_type {
+        NodeType::Element(data) => match data.get_style(StyleProperty::FontWeight) {
+            Some(StyleValue::FontWeight(weight)) => {
+                weight.resolve(resolved_font_weight(doc, node.parent_id))
+            }
+            _ => resolved_font_weight(doc, node.parent_id),
+        },
+        _ => resolved_font_weight(doc, node.parent_id),
+    }
+}
+
 fn to_absolute_url(uri: &str, base_uri: &str) -> String {
     if uri.starts_with("http://") || uri.starts_with("https://") {
         return uri.to_string();
@@ -558,32 +636,62 @@ fn to_absolute_url(uri: &str, base_uri: &str) -> String {
 /// and only ElementContext should be used.
 fn to_element_context(taffy_context: Option<&TaffyContext>) -> ElementContext {
     match taffy_context {
-        Some(TaffyContext::Text(text_ctx)) => ElementContext::text(
-            text_ctx.font_family.as_str(),
-            text_ctx.font_size,
-            text_ctx.font_weight,
-            text_ctx.line_height,
-            text_ctx.alignment,
-            text_ctx.text.as_str(),
-            text_ctx.node_id,
-            text_ctx.text_offset,
-        ),
+        Some(TaffyContext::Text(text_ctx)) => ElementContext::Text(text_ctx.clone()),
         Some(TaffyContext::Image(image_ctx)) => ElementContext::image(
             image_ctx.src.as_str(),
             image_ctx.media_id,
-            image_ctx.dimension.clone(),
+            image_ctx.intrinsic_size,
             image_ctx.node_id,
         ),
         Some(TaffyContext::Svg(svg_ctx)) => ElementContext::svg(
             svg_ctx.src.as_str(),
             svg_ctx.media_id,
-            svg_ctx.dimension,
+            svg_ctx.intrinsic_size,
             svg_ctx.node_id,
         ),
         None => ElementContext::None,
     }
 }
 
+/// Measures a replaced element (image or SVG). Taffy has already resolved the definite CSS sizes (including
+/// `aspect-ratio`) into the known dimensions, so we only need to fill in the missing ones from the natural size.
+fn measure_replaced(
+    intrinsic_size: IntrinsicSize,
+    known_dimensions: Size<Option<f32>>,
+    available_space: Size<AvailableSpace>,
+) -> Size<f32> {
+    let available_width = match available_space.width {
+        AvailableSpace::Definite(width) => Some(width as f64),
+        _ => None,
+    };
+
+    let size = compute_replaced_size(
+        known_dimensions.width.map(|w| w as f64),
+        known_dimensions.height.map(|h| h as f64),
+        intrinsic_size,
+        available_width,
+    );
+
+    Size {
+        width: size.width as f32,
+        height: size.height as f32,
+    }
+}
+
+/// Maps the HTML width and height attributes onto the style when the CSS size is auto
+fn apply_size_attributes(style: &mut Style, width: Option<&String>, height: Option<&String>) {
+    if style.size.width == Dimension::Auto {
+        if let Some(width) = width.and_then(|w| parse_length(w)) {
+            style.size.width = Dimension::Length(width as f32);
+        }
+    }
+    if style.size.height == Dimension::Auto {
+        if let Some(height) = height.and_then(|h| parse_length(h)) {
+            style.size.height = Dimension::Length(height as f32);
+        }
+    }
+}
+
 /// Returns true if there is a margin on the rect (basically, if the rect is non-zero)
 fn has_margin(src: Rect<LengthPercentageAuto>) -> bool {
     let is_zero = (src.top == LengthPercentageAuto::Length(0.0)
@@ -601,9 +709,10 @@ fn has_margin(src: Rect<LengthPercentageAuto>) -> bool {
 /// Converts a taffy layout to our own BoxModel structure
 pub fn taffy_layout_to_boxmodel(layout: &Layout, offset: Coordinate) -> box_model::BoxModel {
     box_model::BoxModel {
+        // The location of a taffy layout is the position of the border box
         margin_box: geo::Rect {
-            x: offset.x + layout.location.x as f64,
-            y: offset.y + layout.location.y as f64,
+            x: offset.x + layout.location.x as f64 - layout.margin.left as f64,
+            y: offset.y + layout.location.y as f64 - layout.margin.top as f64,
             width: layout.size.width as f64
                 + layout.margin.left as f64
                 + layout.margin.right as f64,
@@ -631,3 +740,74 @@ pub fn taffy_layout_to_boxmodel(layout: &Layout, offset: Coordinate) -> box_mode
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::style::{Display as CssDisplay, StylePropertyList};
+    use crate::layouter::CanLayout;
+    use taffy::Point;
+
+    #[test]
+    fn test_taffy_layout_to_boxmodel() {
+        let mut layout = Layout::new();
+        layout.location = Point { x: 10.0, y: 20.0 };
+        layout.size = Size { width: 100.0, height: 50.0 };
+        layout.margin = Rect { left: 5.0, right: 5.0, top: 5.0, bottom: 5.0 };
+        layout.border = Rect { left: 1.0, right: 1.0, top: 1.0, bottom: 1.0 };
+        layout.padding = Rect { left: 2.0, right: 2.0, top: 2.0, bottom: 2.0 };
+
+        // The location is the position of the border box, the margin box is around it
+        let box_model = taffy_layout_to_boxmodel(&layout, Coordinate::new(100.0, 200.0));
+        assert_eq!(box_model.margin_box, geo::Rect::new(105.0, 215.0, 110.0, 60.0));
+        assert_eq!(box_model.border_box(), geo::Rect::new(110.0, 220.0, 100.0, 50.0));
+        assert_eq!(box_model.content_box(), geo::Rect::new(113.0, 223.0, 94.0, 44.0));
+    }
+
+    #[test]
+    fn test_nested_positions() {
+        let px = |v: f32| StyleValue::Unit(v, Unit::Px);
+        let styles = |props: &[(StyleProperty, f32)]| {
+            let mut styles = StylePropertyList::new();
+            styles.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::Block));
+            styles.set_property(StyleProperty::BoxSizing, StyleValue::Keyword("content-box".to_string()));
+            for (prop, value) in props {
+                styles.set_property(prop.clone(), px(*value));
+            }
+            styles
+        };
+
+        let mut doc = Document::new("https://example.com");
+        let root_id = doc.new_element(None, "DocumentRoot", None, false, None);
+        let outer_id = doc.new_element(Some(root_id), "div", None, false, Some(styles(&[
+            (StyleProperty::Width, 200.0),
+            (StyleProperty::MarginTop, 8.0),
+            (StyleProperty::MarginLeft, 8.0),
+            (StyleProperty::PaddingTop, 10.0),
+            (StyleProperty::PaddingLeft, 10.0),
+        ])));
+        doc.add_child(root_id, outer_id);
+        let inner_id = doc.new_element(Some(outer_id), "div", None, false, Some(styles(&[
+            (StyleProperty::Height, 20.0),
+            (StyleProperty::MarginTop, 5.0),
+        ])));
+        doc.add_child(outer_id, inner_id);
+        doc.set_root(root_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+        let layout_tree = TaffyLayouter::new().layout(render_tree, Some(geo::Dimension::new(800.0, 600.0)));
+        let box_model = |dom_node_id| {
+            layout_tree.arena.values().find(|e| e.dom_node_id == dom_node_id).unwrap().box_model.clone()
+        };
+
+        // Margins and padding of the parent are counted once
+        let outer = box_model(outer_id);
+        assert_eq!(outer.margin_box, geo::Rect::new(0.0, 0.0, 218.0, 43.0));
+        assert_eq!(outer.border_box(), geo::Rect::new(8.0, 8.0, 210.0, 35.0));
+
+        let inner = box_model(inner_id);
+        assert_eq!(inner.margin_box, geo::Rect::new(18.0, 18.0, 200.0, 25.0));
+        assert_eq!(inner.border_box(), geo::Rect::new(18.0, 23.0, 200.0, 20.0));
+    }
+}
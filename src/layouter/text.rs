@@ -5,28 +5,121 @@ compile_error!("Either the 'text_parley' 'text_skia' or 'text_pango' feature mus
 pub mod parley;
 #[cfg(feature = "text_parley")]
 pub use crate::layouter::text::parley::get_text_layout;
+#[cfg(feature = "text_parley")]
+pub use crate::common::font::parley::layout_cache_stats as text_cache_stats;
 
 #[cfg(feature = "text_pango")]
 pub mod pango;
 #[cfg(feature = "text_pango")]
 pub use crate::layouter::text::pango::get_text_layout;
+#[cfg(feature = "text_pango")]
+pub use crate::common::font::pango::layout_cache_stats as text_cache_stats;
 
 
 #[cfg(feature = "text_skia")]
 pub mod skia;
 #[cfg(feature = "text_skia")]
 pub use crate::layouter::text::skia::get_text_layout;
+#[cfg(feature = "text_skia")]
+pub use crate::common::font::skia::paragraph_cache_stats as text_cache_stats;
 
+pub mod bidi;
+pub mod breaking;
+pub mod truncate;
+
+use std::hash::{Hash, Hasher};
+use crate::common::document::style::{Color as StyleColor, Direction, FontStyle, OverflowWrap, TextDecorationLine, TextDecorationStyle, TextOverflow};
 
 /// Text alignment
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub enum Alignment {
-    /// Alignment of text is at the start (depends on LTR)
+    /// Alignment of text is at the start (left for LTR, right for RTL)
     Start,
-    /// Alignment of text is at the end (depends on LTR)
+    /// Alignment of text is at the end (right for LTR, left for RTL)
     End,
     /// Alignment is centered
     Middle,
     /// alignment is justified (full column width)
     Justified,
-}
\ No newline at end of file
+}
+
+/// Decoration of a text, as computed from the text-decoration properties
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextDecoration {
+    /// Which lines to draw
+    pub line: TextDecorationLine,
+    /// Solid, double, dotted, dashed or wavy
+    pub style: TextDecorationStyle,
+    /// Color of the lines. When not set, the color of the text is used
+    pub color: Option<StyleColor>,
+}
+
+/// How the lines of a text are wrapped and truncated. These depend on the available width, so they are applied
+/// by the text backends when laying out the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TextWrapping {
+    /// Lines are only broken at forced line breaks (white-space: nowrap)
+    pub nowrap: bool,
+    /// Whether words that do not fit on a line may be broken
+    pub overflow_wrap: OverflowWrap,
+    /// Text that overflows its line is cut off, with an ellipsis when set
+    pub text_overflow: TextOverflow,
+    /// Maximum number of lines. The last line ends with an ellipsis when the text is clamped.
+    pub line_clamp: Option<usize>,
+}
+
+/// Everything that influences how a text is shaped and broken into lines. The layouter measures a text with its
+/// style, and the rasterizer shapes it again with the same style, so together with the text it is the key of the
+/// text layout caches.
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+    /// Font family (can be comma separated)
+    pub font_family: String,
+    /// Size of the font in pixels
+    pub font_size: f64,
+    /// Weight (100-900) of the font
+    pub font_weight: usize,
+    /// Normal, italic or oblique
+    pub font_style: FontStyle,
+    /// Height of each line
+    pub line_height: f64,
+    /// Width at which lines are wrapped
+    pub max_width: f64,
+    pub alignment: Alignment,
+    /// Base direction of the text
+    pub direction: Direction,
+    /// How lines are wrapped and truncated
+    pub wrapping: TextWrapping,
+}
+
+impl TextStyle {
+    /// Floating point values are compared as (f32) bits, so the style can be hashed, and the same values computed
+    /// by the layouter and the painter will match.
+    fn key(&self) -> (&str, u32, usize, FontStyle, u32, u32, Alignment, Direction, TextWrapping) {
+        (
+            self.font_family.as_str(),
+            (self.font_size as f32).to_bits(),
+            self.font_weight,
+            self.font_style,
+            (self.line_height as f32).to_bits(),
+            (self.max_width as f32).to_bits(),
+            self.alignment,
+            self.direction,
+            self.wrapping,
+        )
+    }
+}
+
+impl PartialEq for TextStyle {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for TextStyle {}
+
+impl Hash for TextStyle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::common::document::style::{Direction, TextAlign, UnicodeBidi};
+use crate::layouter::text::Alignment;
+
+/// Left-to-right mark
+pub const LRM: char = '\u{200E}';
+/// Right-to-left mark
+pub const RLM: char = '\u{200F}';
+const LRO: char = '\u{202D}';
+const RLO: char = '\u{202E}';
+const PDF: char = '\u{202C}';
+
+/// Returns the direction of the first strong character in the text (rule P2 of the Unicode bidi algorithm), or
+/// None when the text does not have any strong characters (only digits, punctuation, spaces etc).
+pub fn first_strong_direction(text: &str) -> Option<Direction> {
+    text.chars().find_map(|c| {
+        if is_strong_rtl(c) {
+            Some(Direction::Rtl)
+        } else if c.is_alphabetic() || c == LRM {
+            Some(Direction::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+/// Characters from right-to-left scripts (Hebrew, Arabic, Syriac, Thaana, N'Ko etc.)
+fn is_strong_rtl(c: char) -> bool {
+    matches!(c as u32,
+        0x0590..=0x08FF |
+        0xFB1D..=0xFDFF |
+        0xFE70..=0xFEFF |
+        0x10800..=0x10FFF |
+        0x1E800..=0x1EFFF
+    ) || c == RLM
+}
+
+/// Applies the unicode-bidi property to the text of an element. Returns the text with the needed bidi control
+/// characters, and the base direction the text must be laid out with.
+pub fn apply_unicode_bidi(text: &str, direction: Direction, unicode_bidi: UnicodeBidi) -> (String, Direction) {
+    match unicode_bidi {
+        // Every text is laid out as its own paragraph, so embedding and isolating only sets the base direction
+        UnicodeBidi::Normal | UnicodeBidi::Embed | UnicodeBidi::Isolate => (text.to_string(), direction),
+        // All characters are displayed in the order of the direction, regardless of their own direction
+        UnicodeBidi::BidiOverride | UnicodeBidi::IsolateOverride => {
+            let start = match direction {
+                Direction::Ltr => LRO,
+                Direction::Rtl => RLO,
+            };
+            (format!("{}{}{}", start, text, PDF), direction)
+        }
+        // The direction is taken from the text itself instead of the direction property
+        UnicodeBidi::Plaintext => (text.to_string(), first_strong_direction(text).unwrap_or(direction)),
+    }
+}
+
+/// Resolves the text-align property against the direction of the text. Physical alignments (left, right) become
+/// start or end, depending on the direction.
+pub fn resolve_alignment(text_align: &TextAlign, direction: Direction) -> Alignment {
+    match (text_align, direction) {
+        (TextAlign::Center, _) => Alignment::Middle,
+        (TextAlign::Justify, _) => Alignment::Justified,
+        (TextAlign::End, _) => Alignment::End,
+        (TextAlign::Left, Direction::Ltr) | (TextAlign::Right, Direction::Rtl) => Alignment::Start,
+        (TextAlign::Left, Direction::Rtl) | (TextAlign::Right, Direction::Ltr) => Alignment::End,
+        // Start, and all global values that are already resolved by the computed style
+        _ => Alignment::Start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_strong_direction() {
+        assert_eq!(first_strong_direction("hello"), Some(Direction::Ltr));
+        assert_eq!(first_strong_direction("123 שלום"), Some(Direction::Rtl));
+        assert_eq!(first_strong_direction("مرحبا world"), Some(Direction::Rtl));
+        assert_eq!(first_strong_direction("(123) !"), None);
+    }
+
+    #[test]
+    fn test_apply_unicode_bidi() {
+        let (text, dir) = apply_unicode_bidi("abc", Direction::Rtl, UnicodeBidi::Normal);
+        assert_eq!(text, "abc");
+        assert_eq!(dir, Direction::Rtl);
+
+        let (text, dir) = apply_unicode_bidi("abc", Direction::Rtl, UnicodeBidi::BidiOverride);
+        assert_eq!(text, "\u{202E}abc\u{202C}");
+        assert_eq!(dir, Direction::Rtl);
+
+        let (_, dir) = apply_unicode_bidi("שלום abc", Direction::Ltr, UnicodeBidi::Plaintext);
+        assert_eq!(dir, Direction::Rtl);
+        let (_, dir) = apply_unicode_bidi("123", Direction::Rtl, UnicodeBidi::Plaintext);
+        assert_eq!(dir, Direction::Rtl);
+    }
+
+    #[test]
+    fn test_resolve_alignment() {
+        assert_eq!(resolve_alignment(&TextAlign::Left, Direction::Ltr), Alignment::Start);
+        assert_eq!(resolve_alignment(&TextAlign::Right, Direction::Ltr), Alignment::End);
+        assert_eq!(resolve_alignment(&TextAlign::Left, Direction::Rtl), Alignment::End);
+        assert_eq!(resolve_alignment(&TextAlign::Right, Direction::Rtl), Alignment::Start);
+        assert_eq!(resolve_alignment(&TextAlign::Start, Direction::Rtl), Alignment::Start);
+        assert_eq!(resolve_alignment(&TextAlign::End, Direction::Ltr), Alignment::End);
+        assert_eq!(resolve_alignment(&TextAlign::Center, Direction::Rtl), Alignment::Middle);
+    }
+}
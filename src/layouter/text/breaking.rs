@@ -0,0 +1,159 @@
+use crate::common::document::style::{Hyphens, LineBreak, OverflowWrap, WordBreak};
+
+/// Zero width space: an invisible break opportunity
+pub const ZWSP: char = '\u{200B}';
+/// Word joiner: prevents a break between the characters around it
+const WORD_JOINER: char = '\u{2060}';
+/// Soft hyphen: a break opportunity that shows a hyphen when the line is broken there
+const SHY: char = '\u{00AD}';
+
+/// Applies the word-break, line-break and hyphens properties to the text of an element. The text backends only
+/// know the default line breaking rules, so we add (or remove) break opportunities with invisible control
+/// characters. The loose, normal and strict line-break values are left to the backend.
+///
+/// Automatic hyphenation needs dictionaries we don't have, so `hyphens: auto` behaves like `manual`.
+pub fn apply_line_breaking(text: &str, word_break: WordBreak, line_break: LineBreak, hyphens: Hyphens) -> String {
+    let text = match hyphens {
+        Hyphens::None => text.replace(SHY, ""),
+        Hyphens::Manual | Hyphens::Auto => text.to_string(),
+    };
+
+    if line_break == LineBreak::Anywhere {
+        // Every typographic character unit, including punctuation and preserved spaces
+        return insert_between(&text, ZWSP, |a, b| !a.is_whitespace() && !b.is_whitespace());
+    }
+
+    match word_break {
+        WordBreak::BreakAll => insert_between(&text, ZWSP, |a, b| a.is_alphanumeric() && b.is_alphanumeric()),
+        WordBreak::KeepAll => insert_between(&text, WORD_JOINER, |a, b| is_cjk(a) && is_cjk(b)),
+        WordBreak::Normal | WordBreak::BreakWord => text,
+    }
+}
+
+/// Returns the overflow-wrap that is used for the text. `word-break: break-word` is a legacy value that behaves
+/// like `overflow-wrap: anywhere`.
+pub fn resolve_overflow_wrap(word_break: WordBreak, overflow_wrap: OverflowWrap) -> OverflowWrap {
+    match word_break {
+        WordBreak::BreakWord => OverflowWrap::Anywhere,
+        _ => overflow_wrap,
+    }
+}
+
+/// Returns true when words that do not fit in the given width may be broken. Breaks from `break-word` are not
+/// taken into account for the min-content size, only the ones from `anywhere` are.
+pub fn breaks_overflowing_words(overflow_wrap: OverflowWrap, max_width: f64) -> bool {
+    match overflow_wrap {
+        OverflowWrap::Normal => false,
+        OverflowWrap::Anywhere => true,
+        OverflowWrap::BreakWord => max_width > 0.0,
+    }
+}
+
+/// Adds break opportunities to every word that is wider than the max width, so the backend can break these
+/// words anywhere. Words are measured on their own with the given function. Returns None when all words fit.
+pub fn break_overflowing_words(text: &str, max_width: f64, mut measure: impl FnMut(&str) -> f64) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut changed = false;
+
+    for (i, word) in text.split(' ').enumerate() {
+        if i > 0 {
+            result.push(' ');
+        }
+        if word.chars().nth(1).is_some() && measure(word) > max_width {
+            result.push_str(&insert_between(word, ZWSP, |a, b| !a.is_whitespace() && !b.is_whitespace()));
+            changed = true;
+        } else {
+            result.push_str(word);
+        }
+    }
+
+    changed.then_some(result)
+}
+
+/// Inserts the separator between every two characters that match the predicate. Nothing is inserted before
+/// combining marks, as they belong to the previous character.
+fn insert_between(text: &str, separator: char, predicate: impl Fn(char, char) -> bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+
+    for c in text.chars() {
+        if let Some(p) = prev {
+            if !is_combining_mark(c) && predicate(p, c) {
+                result.push(separator);
+            }
+        }
+        result.push(c);
+        prev = Some(c);
+    }
+
+    result
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F |
+        0x1AB0..=0x1AFF |
+        0x1DC0..=0x1DFF |
+        0x20D0..=0x20FF |
+        0xFE20..=0xFE2F
+    ) || c == '\u{200D}'
+}
+
+/// Han, Hiragana, Katakana and Hangul characters
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF |
+        0x3040..=0x30FF |
+        0x3400..=0x4DBF |
+        0x4E00..=0x9FFF |
+        0xAC00..=0xD7AF |
+        0xF900..=0xFAFF |
+        0x20000..=0x2FFFF
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_break_all() {
+        let text = apply_line_breaking("ab c.d", WordBreak::BreakAll, LineBreak::Auto, Hyphens::Manual);
+        assert_eq!(text, "a\u{200B}b c.d");
+
+        let text = apply_line_breaking("ab c.d", WordBreak::Normal, LineBreak::Anywhere, Hyphens::Manual);
+        assert_eq!(text, "a\u{200B}b c\u{200B}.\u{200B}d");
+    }
+
+    #[test]
+    fn test_keep_all_and_hyphens() {
+        let text = apply_line_breaking("日本 ab", WordBreak::KeepAll, LineBreak::Auto, Hyphens::Manual);
+        assert_eq!(text, "日\u{2060}本 ab");
+
+        let text = apply_line_breaking("hy\u{00AD}phen", WordBreak::Normal, LineBreak::Auto, Hyphens::None);
+        assert_eq!(text, "hyphen");
+        let text = apply_line_breaking("hy\u{00AD}phen", WordBreak::Normal, LineBreak::Auto, Hyphens::Auto);
+        assert_eq!(text, "hy\u{00AD}phen");
+    }
+
+    #[test]
+    fn test_break_overflowing_words() {
+        // Every character is 10 pixels wide
+        let measure = |word: &str| word.chars().count() as f64 * 10.0;
+
+        assert_eq!(break_overflowing_words("short words", 50.0, measure), None);
+        assert_eq!(
+            break_overflowing_words("a verylong word", 50.0, measure),
+            Some("a v\u{200B}e\u{200B}r\u{200B}y\u{200B}l\u{200B}o\u{200B}n\u{200B}g word".to_string())
+        );
+    }
+
+    #[test]
+    fn test_overflow_wrap() {
+        assert_eq!(resolve_overflow_wrap(WordBreak::BreakWord, OverflowWrap::Normal), OverflowWrap::Anywhere);
+        assert!(breaks_overflowing_words(OverflowWrap::Anywhere, 0.0));
+        assert!(!breaks_overflowing_words(OverflowWrap::BreakWord, 0.0));
+        assert!(breaks_overflowing_words(OverflowWrap::BreakWord, 100.0));
+        assert!(!breaks_overflowing_words(OverflowWrap::Normal, 100.0));
+    }
+}
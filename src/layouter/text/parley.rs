@@ -1,11 +1,11 @@
-use crate::layouter::text::Alignment;
+use crate::layouter::text::TextStyle;
 use std::fmt::Error;
 use crate::common::font::parley::get_parley_layout;
 use crate::common::geo::Dimension;
 
 
-pub fn get_text_layout(text: &str, font_family: &str, font_size: f64, _font_weight: usize, line_height: f64, max_width: f64, alignment: Alignment) -> Result<Dimension, Error> {
-    let layout = get_parley_layout(text, font_family, font_size, line_height, max_width, alignment);
+pub fn get_text_layout(text: &str, style: &TextStyle) -> Result<Dimension, Error> {
+    let layout = get_parley_layout(text, style);
 
     Ok(Dimension {
         width: layout.width() as f64,
@@ -0,0 +1,97 @@
+use std::ops::Range;
+use crate::common::document::style::TextOverflow;
+use crate::layouter::text::TextWrapping;
+
+/// Ellipsis that is added to truncated text
+pub const ELLIPSIS: &str = "\u{2026}";
+
+/// Returns the truncated text when the laid out text overflows because of text-overflow or line-clamp, or None
+/// when the text can be used as is. `lines` are the byte ranges of the lines in the laid out text, and `measure`
+/// returns the width of a text on a single (unwrapped) line.
+pub fn truncate_text(text: &str, wrapping: &TextWrapping, max_width: f64, lines: &[Range<usize>], mut measure: impl FnMut(&str) -> f64) -> Option<String> {
+    if let Some(max_lines) = wrapping.line_clamp.filter(|n| *n > 0) {
+        if lines.len() > max_lines {
+            let last_line = &lines[max_lines - 1];
+            if !text.is_char_boundary(last_line.start) || !text.is_char_boundary(last_line.end) {
+                return None;
+            }
+
+            let head = &text[..last_line.start];
+            let line = &text[last_line.clone()];
+            return Some(format!("{}{}", head, fit_with_ellipsis(line, max_width, measure)));
+        }
+    }
+
+    // At min-content size, the whole line is used, as there is no container to overflow yet
+    if max_width <= 0.0 {
+        return None;
+    }
+
+    // Only a single line can overflow its container, wrapped lines are broken before they overflow
+    if wrapping.nowrap && wrapping.text_overflow == TextOverflow::Ellipsis && lines.len() <= 1 && measure(text) > max_width {
+        return Some(fit_with_ellipsis(text, max_width, measure));
+    }
+
+    None
+}
+
+/// Returns the longest start of the line that, together with an ellipsis, fits in the max width. When not even a
+/// single character fits, only the ellipsis is returned.
+fn fit_with_ellipsis(line: &str, max_width: f64, mut measure: impl FnMut(&str) -> f64) -> String {
+    let boundaries: Vec<usize> = line.char_indices().map(|(i, _)| i).skip(1).chain(std::iter::once(line.len())).collect();
+    let candidate = |end: usize| format!("{}{}", line[..end].trim_end(), ELLIPSIS);
+
+    // Binary search for the number of characters we can keep
+    let mut low = 0;
+    let mut high = boundaries.len();
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if measure(&candidate(boundaries[mid - 1])) <= max_width {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    match low {
+        0 => ELLIPSIS.to_string(),
+        n => candidate(boundaries[n - 1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character is 10 pixels wide
+    fn measure(text: &str) -> f64 {
+        text.chars().count() as f64 * 10.0
+    }
+
+    fn nowrap_ellipsis() -> TextWrapping {
+        TextWrapping { nowrap: true, text_overflow: TextOverflow::Ellipsis, ..TextWrapping::default() }
+    }
+
+    #[test]
+    fn test_single_line_ellipsis() {
+        let wrapping = nowrap_ellipsis();
+
+        assert_eq!(truncate_text("hello world", &wrapping, 200.0, &[0..11], measure), None);
+        assert_eq!(truncate_text("hello world", &wrapping, 70.0, &[0..11], measure), Some("hello\u{2026}".to_string()));
+        assert_eq!(truncate_text("hello world", &wrapping, 5.0, &[0..11], measure), Some("\u{2026}".to_string()));
+
+        // Without ellipsis, the text is clipped by the container
+        let clip = TextWrapping { nowrap: true, ..TextWrapping::default() };
+        assert_eq!(truncate_text("hello world", &clip, 70.0, &[0..11], measure), None);
+    }
+
+    #[test]
+    fn test_line_clamp() {
+        let wrapping = TextWrapping { line_clamp: Some(2), ..TextWrapping::default() };
+        let text = "one two three four";
+        let lines = [0..8, 8..14, 14..18];
+
+        assert_eq!(truncate_text(text, &wrapping, 60.0, &lines, measure), Some("one two three\u{2026}".to_string()));
+        assert_eq!(truncate_text(text, &wrapping, 60.0, &lines[..2], measure), None);
+    }
+}
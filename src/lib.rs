@@ -9,4 +9,5 @@ pub mod tiler;
 pub mod painter;
 pub mod rasterizer;
 pub mod compositor;
+pub mod animator;
 pub mod common;
\ No newline at end of file
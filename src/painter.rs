@@ -1,4 +1,5 @@
 pub mod commands;
+pub mod display_list;
 
 use std::ops::AddAssign;
 use std::sync::Arc;
@@ -7,15 +8,16 @@ use crate::common::browser_state::{get_browser_state, BrowserState, WireframeSta
 use crate::common::document::node::{Node, NodeType};
 use crate::common::document::style::{StyleProperty, StyleValue, Color as StyleColor};
 use crate::layering::layer::LayerList;
-use crate::layouter::{ElementContext, LayoutElementNode};
+use crate::layouter::{ElementContext, LayoutElementId, LayoutElementNode};
 use crate::painter::commands::brush::Brush;
 use crate::painter::commands::color::Color;
+use crate::painter::commands::gradient::Gradient;
 use crate::painter::commands::rectangle::{Radius, Rectangle};
 use crate::painter::commands::PaintCommand;
 use crate::common::get_media_store;
 use crate::common::media::{Media, MediaType};
 use crate::painter::commands::border::{Border, BorderStyle};
-use crate::painter::commands::text::Text;
+use crate::painter::commands::text::{Text, TextDecoration};
 use crate::tiler::{Tile, TiledLayoutElement};
 
 /// Painter works with the layout tree and generates paint commands for the renderer. It does not
@@ -32,6 +34,17 @@ impl Painter {
         }
     }
 
+    /// Generates the paint commands of all elements in the tile. The commands are clipped to the tile, so an element
+    /// that spans many tiles only paints the part that is visible in each tile.
+    pub fn paint_tile(&self, tile: &mut Tile) {
+        for element in &mut tile.elements {
+            element.paint_commands = self.paint(element)
+                .iter()
+                .flat_map(|command| command.clip(tile.rect))
+                .collect();
+        }
+    }
+
     // Generate paint commands for the given tile
     pub fn paint(&self, element: &TiledLayoutElement) -> Vec<PaintCommand> {
         let mut commands = Vec::new();
@@ -58,18 +71,38 @@ impl Painter {
             }
             WireframeState::Both => {
                 // Paint both the wireframe and element
-                commands.extend(self.generate_element_commands(&layout_element, &dom_node));
+                commands.extend(self.retained_element_commands(&state, layout_element, dom_node).iter().cloned());
                 commands.extend(self.generate_wireframe_commands(&layout_element));
             }
             WireframeState::None => {
                 // Paint only the element. No debug/developer wireframe is needed.
-                commands.extend(self.generate_element_commands(&layout_element, &dom_node));
+                commands.extend(self.retained_element_commands(&state, layout_element, dom_node).iter().cloned());
             }
         }
 
         commands
     }
 
+    /// Generate the paint commands for the given element, without any debug or wireframe commands. This does not
+    /// depend on the browser state, so it can be used outside the browser, like when exporting to PDF.
+    pub fn paint_element(&self, element_id: LayoutElementId) -> Vec<PaintCommand> {
+        let Some(layout_element) = self.layer_list.layout_tree.get_node_by_id(element_id) else {
+            return Vec::new();
+        };
+        let Some(dom_node) = self.layer_list.layout_tree.render_tree.doc.get_node_by_id(layout_element.dom_node_id) else {
+            return Vec::new();
+        };
+
+        self.generate_element_commands(layout_element, dom_node)
+    }
+
+    /// Returns the paint commands of the element from the display list, so these are generated only once
+    fn retained_element_commands(&self, state: &BrowserState, layout_element: &LayoutElementNode, dom_node: &Node) -> Arc<Vec<PaintCommand>> {
+        state.display_list.get_or_generate(&self.layer_list.layout_tree, layout_element.id, || {
+            self.generate_element_commands(layout_element, dom_node)
+        })
+    }
+
     // Returns a brush for the color found in the given dom node
     fn get_brush(&self, node: &Node, css_prop: StyleProperty, default: Brush) -> Brush {
         let NodeType::Element(element_data) = &node.node_type else {
@@ -87,6 +120,17 @@ impl Painter {
         })
     }
 
+    // Returns the gradients found in the background-image of the given dom node, with the bottom layer first
+    fn get_background_gradients(&self, node: &Node) -> Vec<Gradient> {
+        let NodeType::Element(element_data) = &node.node_type else {
+            return vec![];
+        };
+        match element_data.get_style(StyleProperty::BackgroundImage) {
+            Some(StyleValue::Keyword(value)) => Gradient::parse_layers(value),
+            _ => vec![],
+        }
+    }
+
     // Returns a brush for the color found in the PARENT of the given dom node
     fn get_parent_brush(&self, node: &Node, css_prop: StyleProperty, default: Brush) -> Brush {
         let parent = match &node.parent_id {
@@ -148,9 +192,21 @@ impl Painter {
                     ctx.font_size,
                     ctx.font_weight,
                     ctx.line_height,
-                    brush,
+                    brush.clone(),
                     ctx.alignment,
-                );
+                )
+                .with_font_style(ctx.font_style)
+                .with_direction(ctx.direction)
+                .with_wrapping(ctx.wrapping)
+                .with_max_width(ctx.max_width);
+
+                // Decorations are painted in the color of the text, unless a decoration color is set
+                let decoration_brush = ctx.decoration.color.as_ref().map_or(brush, |c| Brush::solid(convert_css_color(c)));
+                let t = t.with_decoration(TextDecoration {
+                    line: ctx.decoration.line,
+                    style: ctx.decoration.style,
+                    brush: decoration_brush,
+                });
                 commands.push(PaintCommand::text(t));
 
                 // let border = Border::new(1.0, BorderStyle::Solid, Brush::Solid(Color::RED));
@@ -213,7 +269,22 @@ impl Painter {
                     );
                 }
 
-                commands.push(PaintCommand::rectangle(r));
+                // Gradients from the background-image are painted on top of the background color, and the border
+                // on top of the gradients
+                let gradients = self.get_background_gradients(dom_node);
+                if gradients.is_empty() {
+                    commands.push(PaintCommand::rectangle(r));
+                } else {
+                    let no_border = Border::new(0.0, BorderStyle::None, Brush::solid(Color::TRANSPARENT));
+                    commands.push(PaintCommand::rectangle(r.clone().with_border(no_border.clone())));
+
+                    let last = gradients.len() - 1;
+                    for (idx, gradient) in gradients.into_iter().enumerate() {
+                        let layer = r.clone().with_background(Brush::gradient(gradient));
+                        let layer = if idx == last { layer } else { layer.with_border(no_border.clone()) };
+                        commands.push(PaintCommand::rectangle(layer));
+                    }
+                }
             }
         }
 
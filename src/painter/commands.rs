@@ -1,4 +1,7 @@
+use crate::common::geo::{Coordinate, Rect};
 use crate::common::media::MediaId;
+use crate::painter::commands::border::BorderStyle;
+use crate::painter::commands::brush::Brush;
 use crate::painter::commands::rectangle::Rectangle;
 use crate::painter::commands::text::Text;
 
@@ -8,6 +11,7 @@ pub mod image;
 pub mod border;
 pub mod rectangle;
 pub mod brush;
+pub mod gradient;
 
 /// Generic that defines a top, right, bottom, and left value.
 #[derive(Clone, Debug)]
@@ -46,4 +50,198 @@ impl PaintCommand {
     pub fn rectangle(rectangle: Rectangle) -> Self {
         PaintCommand::Rectangle(rectangle)
     }
-}
\ No newline at end of file
+
+    /// Returns the same command, moved by the given offset
+    pub fn shift(&self, offset: Coordinate) -> PaintCommand {
+        match self {
+            PaintCommand::Text(text) => {
+                let mut text = text.clone();
+                text.rect = text.rect.shift(offset);
+                text.clip = text.clip.map(|clip| clip.shift(offset));
+                PaintCommand::Text(text)
+            }
+            PaintCommand::Rectangle(rectangle) => PaintCommand::Rectangle(rectangle.shift(offset)),
+            PaintCommand::Svg(svg) => PaintCommand::svg(svg.media_id, svg.rect.shift(offset)),
+        }
+    }
+
+    /// Returns true when the command does not paint any visible pixels
+    pub fn is_invisible(&self) -> bool {
+        let transparent = |brush: &Brush| matches!(brush, Brush::Solid(color) if color.a() == 0.0);
+
+        match self {
+            PaintCommand::Text(text) => text.text.trim().is_empty() && text.decoration.is_none(),
+            PaintCommand::Rectangle(rectangle) => {
+                let border = rectangle.border();
+                let no_border = border.width() == 0.0
+                    || matches!(border.style(), BorderStyle::None | BorderStyle::Hidden)
+                    || transparent(&border.brush());
+                no_border && rectangle.background().is_none_or(transparent)
+            }
+            PaintCommand::Svg(_) => false,
+        }
+    }
+
+    /// Returns the area that the command paints in
+    pub fn bounds(&self) -> Rect {
+        match self {
+            PaintCommand::Text(text) => {
+                // Glyphs and decorations can overhang the text rect a bit
+                grow(text.rect, text.font_size / 2.0)
+            }
+            PaintCommand::Rectangle(rectangle) => {
+                // Borders are stroked on the edge of the rectangle
+                grow(rectangle.rect(), rectangle.border().width() as f64 / 2.0)
+            }
+            PaintCommand::Svg(svg) => svg.rect.rect(),
+        }
+    }
+
+    /// Returns the commands needed to paint the part of this command that is inside the clip rect. Commands outside
+    /// the clip are dropped, and rectangles with a solid background and border are cut into the pieces that are
+    /// inside the clip. Other commands are returned as they are, as they are clipped by the rasterizer anyway.
+    pub fn clip(&self, clip: Rect) -> Vec<PaintCommand> {
+        let bounds = self.bounds();
+        if bounds.intersection(&clip).is_none() {
+            return vec![];
+        }
+        if contains(clip, bounds) {
+            return vec![self.clone()];
+        }
+
+        match self {
+            PaintCommand::Text(text) => {
+                // Text that is already clipped only keeps the part that is inside both clips
+                let visible = match text.clip {
+                    Some(text_clip) => match text_clip.intersection(&clip) {
+                        Some(visible) => visible,
+                        None => return vec![],
+                    },
+                    None => clip,
+                };
+                vec![PaintCommand::text(text.clone().with_clip(visible))]
+            }
+            PaintCommand::Rectangle(rectangle) => clip_rectangle(rectangle, clip).unwrap_or_else(|| vec![self.clone()]),
+            PaintCommand::Svg(_) => vec![self.clone()],
+        }
+    }
+}
+
+/// Cuts a rectangle into a background and border pieces that are inside the clip. Returns None when the rectangle
+/// cannot be cut, because it is rounded, or the pieces would not look the same as the whole.
+fn clip_rectangle(rectangle: &Rectangle, clip: Rect) -> Option<Vec<PaintCommand>> {
+    if rectangle.is_rounded() || rectangle.background().is_some_and(|b| !matches!(b, Brush::Solid(_))) {
+        return None;
+    }
+
+    let border = rectangle.border();
+    let border_width = match border.style() {
+        BorderStyle::None | BorderStyle::Hidden => 0.0,
+        BorderStyle::Solid if matches!(border.brush(), Brush::Solid(_)) => border.width() as f64,
+        _ => return None,
+    };
+
+    let mut commands = Vec::new();
+
+    let rect = rectangle.rect();
+    if let Some(background) = rectangle.background() {
+        if let Some(visible) = rect.intersection(&clip) {
+            commands.push(PaintCommand::rectangle(Rectangle::new(visible).with_background(background.clone())));
+        }
+    }
+
+    if border_width > 0.0 {
+        // The border is stroked on the edge, so half of it is outside the rectangle. The left and right sides do not
+        // overlap the top and bottom sides, so translucent borders do not get darker corners.
+        let (w, half) = (border_width, border_width / 2.0);
+        let sides = [
+            Rect::new(rect.x - half, rect.y - half, rect.width + w, w),
+            Rect::new(rect.x - half, rect.y + rect.height - half, rect.width + w, w),
+            Rect::new(rect.x - half, rect.y + half, w, rect.height - w),
+            Rect::new(rect.x + rect.width - half, rect.y + half, w, rect.height - w),
+        ];
+        for side in sides {
+            if let Some(visible) = side.intersection(&clip) {
+                commands.push(PaintCommand::rectangle(Rectangle::new(visible).with_background(border.brush())));
+            }
+        }
+    }
+
+    Some(commands)
+}
+
+fn grow(rect: Rect, amount: f64) -> Rect {
+    Rect::new(rect.x - amount, rect.y - amount, rect.width + amount * 2.0, rect.height + amount * 2.0)
+}
+
+/// Returns true when the inner rect is completely inside the outer rect
+fn contains(outer: Rect, inner: Rect) -> bool {
+    inner.x >= outer.x
+        && inner.y >= outer.y
+        && inner.x + inner.width <= outer.x + outer.width
+        && inner.y + inner.height <= outer.y + outer.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layouter::text::Alignment;
+    use crate::painter::commands::border::Border;
+    use crate::painter::commands::color::Color;
+
+    #[test]
+    fn test_clip_rectangle() {
+        let rectangle = Rectangle::new(Rect::new(0.0, 0.0, 1000.0, 1000.0))
+            .with_background(Brush::solid(Color::RED))
+            .with_border(Border::new(2.0, BorderStyle::Solid, Brush::solid(Color::BLACK)));
+        let command = PaintCommand::rectangle(rectangle);
+
+        // Outside the rectangle and its border
+        assert!(command.clip(Rect::new(2000.0, 0.0, 256.0, 256.0)).is_empty());
+
+        // The top left corner has the background, and the top and left border sides
+        let commands = command.clip(Rect::new(0.0, 0.0, 256.0, 256.0));
+        let rects: Vec<Rect> = commands.iter().map(|c| c.bounds()).collect();
+        assert_eq!(rects, vec![
+            Rect::new(0.0, 0.0, 256.0, 256.0),
+            Rect::new(0.0, 0.0, 256.0, 1.0),
+            Rect::new(0.0, 1.0, 1.0, 255.0),
+        ]);
+
+        // Somewhere in the middle, only the background is left
+        let commands = command.clip(Rect::new(256.0, 256.0, 256.0, 256.0));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].bounds(), Rect::new(256.0, 256.0, 256.0, 256.0));
+
+        // Rounded rectangles are not cut
+        let rounded = PaintCommand::rectangle(Rectangle::new(Rect::new(0.0, 0.0, 1000.0, 1000.0))
+            .with_background(Brush::solid(Color::RED))
+            .with_radius(rectangle::Radius::new(10.0)));
+        let commands = rounded.clip(Rect::new(0.0, 0.0, 256.0, 256.0));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].bounds(), Rect::new(0.0, 0.0, 1000.0, 1000.0));
+    }
+
+    #[test]
+    fn test_clip_text() {
+        let text = Text::new(Rect::new(0.0, 0.0, 200.0, 600.0), "text", "sans", 16.0, 400, 20.0, Brush::solid(Color::BLACK), Alignment::Start);
+        let command = PaintCommand::text(text);
+
+        assert!(command.clip(Rect::new(0.0, 1000.0, 256.0, 256.0)).is_empty());
+
+        let commands = command.clip(Rect::new(0.0, 256.0, 256.0, 256.0));
+        let PaintCommand::Text(text) = &commands[0] else {
+            panic!("Expected a text command");
+        };
+        assert_eq!(text.clip, Some(Rect::new(0.0, 256.0, 256.0, 256.0)));
+
+        // Clipped text is not drawn in a tile outside its clip
+        let clipped = PaintCommand::text(text.clone().with_clip(Rect::new(0.0, 0.0, 200.0, 100.0)));
+        assert!(clipped.clip(Rect::new(0.0, 256.0, 256.0, 256.0)).is_empty());
+        let commands = clipped.clip(Rect::new(0.0, 0.0, 100.0, 256.0));
+        let PaintCommand::Text(text) = &commands[0] else {
+            panic!("Expected a text command");
+        };
+        assert_eq!(text.clip, Some(Rect::new(0.0, 0.0, 100.0, 100.0)));
+    }
+}
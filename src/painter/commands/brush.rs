@@ -1,5 +1,6 @@
 use crate::common::media::MediaId;
 use crate::painter::commands::color::Color;
+use crate::painter::commands::gradient::Gradient;
 use crate::painter::commands::image::Image;
 
 #[derive(Clone, Debug)]
@@ -8,7 +9,8 @@ pub enum Brush {
     Solid(Color),
     /// Paint with an image. This allows us to display images
     Image(MediaId),
-    // Gradient(Gradient),
+    /// Paint with a linear, radial or conic gradient
+    Gradient(Gradient),
 }
 
 impl Brush {
@@ -20,7 +22,7 @@ impl Brush {
         Brush::Image(media_id)
     }
 
-    // pub fn gradient(gradient: Gradient) -> Self {
-    //     Brush::Gradient(gradient)
-    // }
+    pub fn gradient(gradient: Gradient) -> Self {
+        Brush::Gradient(gradient)
+    }
 }
@@ -89,17 +89,22 @@ impl Color {
 
     /// Converts a css color, or even #rrggbbaa to a Color
     pub fn from_css(css_color: &str) -> Self {
-        let Ok(ccp_color) = ccpColor::from_html(css_color) else {
+        Self::try_from_css(css_color).unwrap_or_else(|| {
             log::error!("Failed to parse css color: {}", css_color);
-            return Color::BLACK;
-        };
+            Color::BLACK
+        })
+    }
+
+    /// Converts a css color to a Color, or returns None when it is not a valid color
+    pub fn try_from_css(css_color: &str) -> Option<Self> {
+        let ccp_color = ccpColor::from_html(css_color).ok()?;
 
-        Self {
+        Some(Self {
             r: ccp_color.r,
             g: ccp_color.g,
             b: ccp_color.b,
             a: ccp_color.a,
-        }
+        })
     }
 }
 
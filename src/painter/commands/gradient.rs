@@ -0,0 +1,665 @@
+use std::f64::consts::PI;
+use crate::common::geo::{Coordinate, Rect};
+use crate::painter::commands::color::Color;
+
+/// A CSS gradient as found in `background-image`. The gradient is defined independent of the size of the element,
+/// and is resolved against the rect it is painted in by the rasterizer.
+#[derive(Clone, Debug)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+    /// True for the repeating-*-gradient variants
+    pub repeating: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GradientKind {
+    Linear(LinearDirection),
+    Radial { shape: RadialShape, size: RadialSize, center: Position },
+    /// Conic gradient starting at the given angle in degrees (0 points up, clockwise)
+    Conic { angle: f64, center: Position },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinearDirection {
+    /// Angle in degrees, 0 points up and 90 to the right
+    Angle(f64),
+    /// Towards a corner, like `to top right`. The angle depends on the size of the rect.
+    Corner { right: bool, bottom: bool },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadialShape {
+    Circle,
+    Ellipse,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RadialSize {
+    ClosestSide,
+    FarthestSide,
+    ClosestCorner,
+    FarthestCorner,
+    /// Explicit radius. A circle only uses the first one.
+    Explicit(LengthPercentage, LengthPercentage),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LengthPercentage {
+    Px(f64),
+    Percent(f64),
+}
+
+impl LengthPercentage {
+    fn resolve(&self, reference: f64) -> f64 {
+        match self {
+            LengthPercentage::Px(px) => *px,
+            LengthPercentage::Percent(percent) => reference * percent / 100.0,
+        }
+    }
+}
+
+/// Position of the center of a radial or conic gradient, relative to the top left of the rect
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub x: LengthPercentage,
+    pub y: LengthPercentage,
+}
+
+impl Position {
+    pub const CENTER: Position = Position { x: LengthPercentage::Percent(50.0), y: LengthPercentage::Percent(50.0) };
+}
+
+#[derive(Clone, Debug)]
+pub struct ColorStop {
+    pub color: Color,
+    /// Position of the stop. When None, stops are evenly spread between their neighbours.
+    pub position: Option<StopPosition>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopPosition {
+    /// Fraction of the gradient line, or of a full turn for conic gradients
+    Fraction(f64),
+    /// Pixels along the gradient line
+    Px(f64),
+}
+
+/// Gradient resolved against a rect, ready to be handed to a rasterizer. The stops are normalized to 0.0..1.0 and
+/// the geometry is adjusted so the first and last stop lie on the start and end of the gradient.
+#[derive(Clone, Debug)]
+pub struct ResolvedGradient {
+    pub kind: ResolvedKind,
+    pub stops: Vec<(f32, Color)>,
+    pub repeating: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum ResolvedKind {
+    Linear { start: Coordinate, end: Coordinate },
+    /// Radial gradient with circles around the center. Ellipses are circles scaled vertically by scale_y around the
+    /// center.
+    Radial { center: Coordinate, start_radius: f64, end_radius: f64, scale_y: f64 },
+    /// Conic gradient with angles in radians that run clockwise. The start and end angle are relative to the
+    /// rotation, which is the angle of the start of the turn, where 0 points to the right.
+    Conic { center: Coordinate, rotation: f64, start_angle: f64, end_angle: f64 },
+}
+
+impl Gradient {
+    pub fn new(kind: GradientKind, stops: Vec<ColorStop>) -> Self {
+        Gradient { kind, stops, repeating: false }
+    }
+
+    pub fn with_repeating(mut self, repeating: bool) -> Self {
+        self.repeating = repeating;
+        self
+    }
+
+    /// Parses a single `linear-gradient()`, `radial-gradient()` or `conic-gradient()` function, or one of their
+    /// repeating variants.
+    pub fn parse(value: &str) -> Option<Gradient> {
+        let value = value.trim();
+        let open = value.find('(')?;
+        let args = value.strip_suffix(')')?.get(open + 1..)?;
+        let name = value[..open].trim().to_ascii_lowercase();
+        let (name, repeating) = match name.strip_prefix("repeating-") {
+            Some(name) => (name, true),
+            None => (name.as_str(), false),
+        };
+
+        let mut parts = split_top_level(args, |c| c == ',');
+        if parts.is_empty() {
+            return None;
+        }
+
+        let (prelude, is_conic) = match name {
+            "linear-gradient" => (parse_linear(parts[0]), false),
+            "radial-gradient" => (parse_radial(parts[0]), false),
+            "conic-gradient" => (parse_conic(parts[0]), true),
+            _ => return None,
+        };
+        let kind = match prelude {
+            Some(kind) => {
+                parts.remove(0);
+                kind
+            }
+            None => match name {
+                "linear-gradient" => GradientKind::Linear(LinearDirection::Angle(180.0)),
+                "radial-gradient" => GradientKind::Radial { shape: RadialShape::Ellipse, size: RadialSize::FarthestCorner, center: Position::CENTER },
+                _ => GradientKind::Conic { angle: 0.0, center: Position::CENTER },
+            },
+        };
+
+        let mut stops = Vec::new();
+        for part in parts {
+            let tokens = split_top_level(part, char::is_whitespace);
+            let mut color = None;
+            let mut positions = Vec::new();
+            for token in tokens {
+                match parse_stop_position(token, is_conic) {
+                    Some(position) => positions.push(position),
+                    None if color.is_none() => color = Some(Color::try_from_css(token)?),
+                    None => return None,
+                }
+            }
+
+            // A position without a color is an interpolation hint, which we do not support and skip
+            let Some(color) = color else {
+                continue;
+            };
+            match positions.as_slice() {
+                [] => stops.push(ColorStop { color, position: None }),
+                [position] => stops.push(ColorStop { color, position: Some(*position) }),
+                [from, to] => {
+                    stops.push(ColorStop { color: color.clone(), position: Some(*from) });
+                    stops.push(ColorStop { color, position: Some(*to) });
+                }
+                _ => return None,
+            }
+        }
+
+        if stops.len() < 2 {
+            return None;
+        }
+
+        Some(Gradient { kind, stops, repeating })
+    }
+
+    /// Parses all gradients in a `background-image` value. Other images, like `url()`, are skipped. The gradients
+    /// are returned in paint order, so the bottom layer comes first.
+    pub fn parse_layers(value: &str) -> Vec<Gradient> {
+        let mut gradients: Vec<Gradient> = split_top_level(value, |c| c == ',')
+            .into_iter()
+            .filter_map(Gradient::parse)
+            .collect();
+        gradients.reverse();
+        gradients
+    }
+
+    /// Resolves the gradient against the rect it is painted in
+    pub fn resolve(&self, rect: Rect) -> ResolvedGradient {
+        let (kind, length) = match &self.kind {
+            GradientKind::Linear(direction) => {
+                let angle = match direction {
+                    LinearDirection::Angle(angle) => angle.to_radians(),
+                    LinearDirection::Corner { right, bottom } => {
+                        // The gradient line is perpendicular to the diagonal between the two other corners
+                        let dx = if *right { rect.height } else { -rect.height };
+                        let dy = if *bottom { rect.width } else { -rect.width };
+                        dx.atan2(-dy)
+                    }
+                };
+                let (dx, dy) = (angle.sin(), -angle.cos());
+                let length = (rect.width * dx).abs() + (rect.height * dy).abs();
+                let (cx, cy) = (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+                let start = Coordinate::new(cx - dx * length / 2.0, cy - dy * length / 2.0);
+                let end = Coordinate::new(cx + dx * length / 2.0, cy + dy * length / 2.0);
+                (ResolvedKind::Linear { start, end }, length)
+            }
+            GradientKind::Radial { shape, size, center } => {
+                let center = resolve_position(center, rect);
+                let (rx, ry) = radial_size(*shape, size, center, rect);
+                let (rx, ry) = (rx.max(0.01), ry.max(0.01));
+                let kind = ResolvedKind::Radial { center, start_radius: 0.0, end_radius: rx, scale_y: ry / rx };
+                (kind, rx)
+            }
+            GradientKind::Conic { angle, center } => {
+                let rotation = (angle - 90.0).to_radians();
+                let kind = ResolvedKind::Conic { center: resolve_position(center, rect), rotation, start_angle: 0.0, end_angle: 2.0 * PI };
+                (kind, 1.0)
+            }
+        };
+
+        if self.stops.is_empty() {
+            return ResolvedGradient { kind, stops: vec![], repeating: false };
+        }
+
+        let mut offsets = resolve_stop_offsets(&self.stops, length);
+        let mut repeating = self.repeating;
+
+        // Radial gradients cannot have a negative radius. A repeating gradient is shifted by whole periods, others
+        // are clamped at the center.
+        if matches!(kind, ResolvedKind::Radial { .. }) && offsets[0] < 0.0 {
+            let span = offsets[offsets.len() - 1] - offsets[0];
+            if repeating && span > f64::EPSILON {
+                let shift = (-offsets[0] / span).ceil() * span;
+                offsets.iter_mut().for_each(|offset| *offset += shift);
+            } else {
+                offsets.iter_mut().for_each(|offset| *offset = offset.max(0.0));
+            }
+        }
+
+        let first = offsets[0];
+        let last = offsets[offsets.len() - 1];
+        let span = last - first;
+
+        let kind = if span > f64::EPSILON {
+            offsets.iter_mut().for_each(|offset| *offset = (*offset - first) / span);
+            match kind {
+                ResolvedKind::Linear { start, end } => {
+                    let lerp = |t: f64| Coordinate::new(start.x + (end.x - start.x) * t, start.y + (end.y - start.y) * t);
+                    ResolvedKind::Linear { start: lerp(first), end: lerp(last) }
+                }
+                ResolvedKind::Radial { center, end_radius, scale_y, .. } => {
+                    ResolvedKind::Radial { center, start_radius: end_radius * first, end_radius: end_radius * last, scale_y }
+                }
+                ResolvedKind::Conic { center, rotation, start_angle, end_angle } => {
+                    let turn = end_angle - start_angle;
+                    ResolvedKind::Conic { center, rotation, start_angle: start_angle + turn * first, end_angle: start_angle + turn * last }
+                }
+            }
+        } else {
+            // All stops are at the same position, which gives a hard transition that cannot repeat
+            repeating = false;
+            offsets.iter_mut().for_each(|offset| *offset = offset.clamp(0.0, 1.0));
+            kind
+        };
+
+        ResolvedGradient {
+            kind,
+            stops: offsets.into_iter().zip(self.stops.iter()).map(|(offset, stop)| (offset as f32, stop.color.clone())).collect(),
+            repeating,
+        }
+    }
+}
+
+impl ResolvedGradient {
+    /// Returns the color at the given offset, for rasterizers that cannot paint the gradient themselves
+    pub fn color_at(&self, offset: f64) -> Color {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return Color::TRANSPARENT;
+        };
+
+        let offset = if self.repeating { offset.rem_euclid(1.0) } else { offset.clamp(0.0, 1.0) } as f32;
+        if offset <= first.0 {
+            return first.1.clone();
+        }
+
+        for pair in self.stops.windows(2) {
+            let ((from, from_color), (to, to_color)) = (&pair[0], &pair[1]);
+            if offset >= *to {
+                continue;
+            }
+            let t = if to > from { (offset - from) / (to - from) } else { 1.0 };
+            let lerp = |a: f32, b: f32| a + (b - a) * t;
+            return Color::from_rgba(
+                lerp(from_color.r(), to_color.r()),
+                lerp(from_color.g(), to_color.g()),
+                lerp(from_color.b(), to_color.b()),
+                lerp(from_color.a(), to_color.a()),
+            );
+        }
+
+        last.1.clone()
+    }
+}
+
+/// Converts the stop positions to offsets on the gradient line, where the gradient line has the given length in
+/// pixels. Missing positions are filled in as described in the CSS images spec: the first and last stop default to
+/// the start and end, other missing stops are evenly spread, and no stop can come before the one preceding it.
+fn resolve_stop_offsets(stops: &[ColorStop], length: f64) -> Vec<f64> {
+    let mut offsets: Vec<Option<f64>> = stops.iter().map(|stop| stop.position.map(|position| match position {
+        StopPosition::Fraction(fraction) => fraction,
+        StopPosition::Px(px) if length > 0.0 => px / length,
+        StopPosition::Px(_) => 0.0,
+    })).collect();
+
+    let last = offsets.len() - 1;
+    offsets[0].get_or_insert(0.0);
+    offsets[last].get_or_insert(1.0);
+
+    let mut max = f64::MIN;
+    for offset in offsets.iter_mut().flatten() {
+        max = max.max(*offset);
+        *offset = max;
+    }
+
+    let mut start = 0;
+    for idx in 1..offsets.len() {
+        let Some(to) = offsets[idx] else {
+            continue;
+        };
+        let from = offsets[start].unwrap_or(to);
+        let count = (idx - start) as f64;
+        for (n, offset) in offsets[start + 1..idx].iter_mut().enumerate() {
+            *offset = Some(from + (to - from) * (n + 1) as f64 / count);
+        }
+        start = idx;
+    }
+
+    offsets.into_iter().map(|offset| offset.unwrap_or(0.0)).collect()
+}
+
+fn resolve_position(position: &Position, rect: Rect) -> Coordinate {
+    Coordinate::new(rect.x + position.x.resolve(rect.width), rect.y + position.y.resolve(rect.height))
+}
+
+/// Returns the horizontal and vertical radius of a radial gradient
+fn radial_size(shape: RadialShape, size: &RadialSize, center: Coordinate, rect: Rect) -> (f64, f64) {
+    let left = center.x - rect.x;
+    let top = center.y - rect.y;
+    let right = rect.x + rect.width - center.x;
+    let bottom = rect.y + rect.height - center.y;
+
+    let (closest_x, closest_y) = (left.abs().min(right.abs()), top.abs().min(bottom.abs()));
+    let (farthest_x, farthest_y) = (left.abs().max(right.abs()), top.abs().max(bottom.abs()));
+
+    match (shape, size) {
+        (RadialShape::Circle, RadialSize::ClosestSide) => (closest_x.min(closest_y), closest_x.min(closest_y)),
+        (RadialShape::Circle, RadialSize::FarthestSide) => (farthest_x.max(farthest_y), farthest_x.max(farthest_y)),
+        (RadialShape::Circle, RadialSize::ClosestCorner) => (closest_x.hypot(closest_y), closest_x.hypot(closest_y)),
+        (RadialShape::Circle, RadialSize::FarthestCorner) => (farthest_x.hypot(farthest_y), farthest_x.hypot(farthest_y)),
+        (RadialShape::Circle, RadialSize::Explicit(radius, _)) => {
+            let radius = radius.resolve(rect.width);
+            (radius, radius)
+        }
+        (RadialShape::Ellipse, RadialSize::ClosestSide) => (closest_x, closest_y),
+        (RadialShape::Ellipse, RadialSize::FarthestSide) => (farthest_x, farthest_y),
+        // The ellipse through the corner has the same aspect ratio as the one for the matching side
+        (RadialShape::Ellipse, RadialSize::ClosestCorner) => (closest_x * 2f64.sqrt(), closest_y * 2f64.sqrt()),
+        (RadialShape::Ellipse, RadialSize::FarthestCorner) => (farthest_x * 2f64.sqrt(), farthest_y * 2f64.sqrt()),
+        (RadialShape::Ellipse, RadialSize::Explicit(rx, ry)) => (rx.resolve(rect.width), ry.resolve(rect.height)),
+    }
+}
+
+/// Parses the direction of a linear gradient, like `to right` or `45deg`
+fn parse_linear(prelude: &str) -> Option<GradientKind> {
+    let tokens = split_top_level(prelude, char::is_whitespace);
+    if tokens.first() != Some(&"to") {
+        let [angle] = tokens.as_slice() else {
+            return None;
+        };
+        return parse_angle(angle).map(|angle| GradientKind::Linear(LinearDirection::Angle(angle)));
+    }
+
+    let (mut horizontal, mut vertical) = (None, None);
+    for token in &tokens[1..] {
+        match *token {
+            "left" if horizontal.is_none() => horizontal = Some(false),
+            "right" if horizontal.is_none() => horizontal = Some(true),
+            "top" if vertical.is_none() => vertical = Some(false),
+            "bottom" if vertical.is_none() => vertical = Some(true),
+            _ => return None,
+        }
+    }
+
+    let direction = match (horizontal, vertical) {
+        (Some(right), Some(bottom)) => LinearDirection::Corner { right, bottom },
+        (Some(true), None) => LinearDirection::Angle(90.0),
+        (Some(false), None) => LinearDirection::Angle(270.0),
+        (None, Some(true)) => LinearDirection::Angle(180.0),
+        (None, Some(false)) => LinearDirection::Angle(0.0),
+        (None, None) => return None,
+    };
+    Some(GradientKind::Linear(direction))
+}
+
+/// Parses the shape, size and position of a radial gradient, like `circle closest-side at 20% 30%`
+fn parse_radial(prelude: &str) -> Option<GradientKind> {
+    let tokens = split_top_level(prelude, char::is_whitespace);
+    let (shape_tokens, center) = split_at_position(&tokens)?;
+
+    let mut shape = None;
+    let mut size = None;
+    let mut lengths = Vec::new();
+    for token in shape_tokens {
+        match *token {
+            "circle" if shape.is_none() => shape = Some(RadialShape::Circle),
+            "ellipse" if shape.is_none() => shape = Some(RadialShape::Ellipse),
+            "closest-side" if size.is_none() => size = Some(RadialSize::ClosestSide),
+            "farthest-side" if size.is_none() => size = Some(RadialSize::FarthestSide),
+            "closest-corner" if size.is_none() => size = Some(RadialSize::ClosestCorner),
+            "farthest-corner" if size.is_none() => size = Some(RadialSize::FarthestCorner),
+            _ => lengths.push(parse_length(token)?),
+        }
+    }
+
+    if size.is_some() && !lengths.is_empty() {
+        return None;
+    }
+    let (shape, size) = match (shape, lengths.as_slice()) {
+        (Some(RadialShape::Circle) | None, [radius]) => (RadialShape::Circle, RadialSize::Explicit(*radius, *radius)),
+        (Some(RadialShape::Ellipse) | None, [rx, ry]) => (RadialShape::Ellipse, RadialSize::Explicit(*rx, *ry)),
+        (shape, []) => (shape.unwrap_or(RadialShape::Ellipse), size.unwrap_or(RadialSize::FarthestCorner)),
+        _ => return None,
+    };
+
+    Some(GradientKind::Radial { shape, size, center })
+}
+
+/// Parses the start angle and position of a conic gradient, like `from 45deg at center`
+fn parse_conic(prelude: &str) -> Option<GradientKind> {
+    let tokens = split_top_level(prelude, char::is_whitespace);
+    let (angle_tokens, center) = split_at_position(&tokens)?;
+
+    let angle = match angle_tokens {
+        [] => 0.0,
+        ["from", angle] => parse_angle(angle)?,
+        _ => return None,
+    };
+
+    Some(GradientKind::Conic { angle, center })
+}
+
+/// Splits the tokens of a radial or conic prelude on `at`, and parses the position after it. Returns None when the
+/// tokens are empty, as then there is no prelude at all.
+fn split_at_position<'a>(tokens: &'a [&'a str]) -> Option<(&'a [&'a str], Position)> {
+    if tokens.is_empty() {
+        return None;
+    }
+
+    match tokens.iter().position(|token| *token == "at") {
+        Some(idx) => Some((&tokens[..idx], parse_position(&tokens[idx + 1..])?)),
+        None => Some((tokens, Position::CENTER)),
+    }
+}
+
+/// Parses a position of one or two keywords or lengths, like `center`, `top right` or `20% 30px`
+fn parse_position(tokens: &[&str]) -> Option<Position> {
+    let keyword = |token: &str| match token {
+        "left" | "top" => Some(LengthPercentage::Percent(0.0)),
+        "center" => Some(LengthPercentage::Percent(50.0)),
+        "right" | "bottom" => Some(LengthPercentage::Percent(100.0)),
+        _ => parse_length(token),
+    };
+
+    match tokens {
+        [token @ ("top" | "bottom")] => Some(Position { x: LengthPercentage::Percent(50.0), y: keyword(token)? }),
+        [token] => Some(Position { x: keyword(token)?, y: LengthPercentage::Percent(50.0) }),
+        [y @ ("top" | "bottom"), x] | [y, x @ ("left" | "right")] => Some(Position { x: keyword(x)?, y: keyword(y)? }),
+        [x, y] => Some(Position { x: keyword(x)?, y: keyword(y)? }),
+        _ => None,
+    }
+}
+
+fn parse_length(value: &str) -> Option<LengthPercentage> {
+    if let Some(percent) = value.strip_suffix('%') {
+        return percent.parse().ok().map(LengthPercentage::Percent);
+    }
+    if let Some(px) = value.strip_suffix("px") {
+        return px.parse().ok().map(LengthPercentage::Px);
+    }
+    (value == "0").then_some(LengthPercentage::Px(0.0))
+}
+
+/// Parses an angle in deg, grad, rad or turn, and returns it in degrees
+fn parse_angle(value: &str) -> Option<f64> {
+    let units: [(&str, f64); 4] = [("deg", 1.0), ("grad", 0.9), ("rad", 180.0 / PI), ("turn", 360.0)];
+    for (unit, factor) in units {
+        if let Some(number) = value.strip_suffix(unit) {
+            return number.parse::<f64>().ok().map(|number| number * factor);
+        }
+    }
+    (value == "0").then_some(0.0)
+}
+
+/// Parses the position of a color stop. Conic gradients take angles, the others lengths.
+fn parse_stop_position(value: &str, is_conic: bool) -> Option<StopPosition> {
+    if is_conic {
+        if let Some(angle) = parse_angle(value) {
+            return Some(StopPosition::Fraction(angle / 360.0));
+        }
+    }
+
+    match parse_length(value)? {
+        LengthPercentage::Percent(percent) => Some(StopPosition::Fraction(percent / 100.0)),
+        LengthPercentage::Px(px) if is_conic && px == 0.0 => Some(StopPosition::Fraction(0.0)),
+        LengthPercentage::Px(_) if is_conic => None,
+        LengthPercentage::Px(px) => Some(StopPosition::Px(px)),
+    }
+}
+
+/// Splits the value on the separator, but not inside parentheses like in `rgb(1, 2, 3)`. Empty parts are skipped.
+fn split_top_level(value: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if depth == 0 && is_separator(c) => {
+                parts.push(&value[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+
+    parts.into_iter().map(str::trim).filter(|part| !part.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(gradient: &ResolvedGradient) -> Vec<f32> {
+        gradient.stops.iter().map(|(offset, _)| *offset).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let gradient = Gradient::parse("linear-gradient(to right, rgb(255, 0, 0) 10%, blue 20px 40px, #00ff00)").unwrap();
+        assert_eq!(gradient.kind, GradientKind::Linear(LinearDirection::Angle(90.0)));
+        assert!(!gradient.repeating);
+        assert_eq!(gradient.stops.len(), 4);
+        assert_eq!(gradient.stops[0].color.r8(), 255);
+        assert_eq!(gradient.stops[0].position, Some(StopPosition::Fraction(0.1)));
+        assert_eq!(gradient.stops[2].position, Some(StopPosition::Px(40.0)));
+        assert_eq!(gradient.stops[3].position, None);
+        assert_eq!(gradient.stops[3].color.g8(), 255);
+
+        let gradient = Gradient::parse("repeating-radial-gradient(circle closest-side at top 30px, red, blue 20%)").unwrap();
+        assert!(gradient.repeating);
+        assert_eq!(gradient.kind, GradientKind::Radial {
+            shape: RadialShape::Circle,
+            size: RadialSize::ClosestSide,
+            center: Position { x: LengthPercentage::Px(30.0), y: LengthPercentage::Percent(0.0) },
+        });
+
+        let gradient = Gradient::parse("conic-gradient(from 0.25turn at 10% 20%, red, blue 90deg, green)").unwrap();
+        assert_eq!(gradient.kind, GradientKind::Conic {
+            angle: 90.0,
+            center: Position { x: LengthPercentage::Percent(10.0), y: LengthPercentage::Percent(20.0) },
+        });
+        assert_eq!(gradient.stops[1].position, Some(StopPosition::Fraction(0.25)));
+
+        // Without prelude, the defaults are used
+        let gradient = Gradient::parse("radial-gradient(red, blue)").unwrap();
+        assert_eq!(gradient.kind, GradientKind::Radial { shape: RadialShape::Ellipse, size: RadialSize::FarthestCorner, center: Position::CENTER });
+
+        assert!(Gradient::parse("linear-gradient(red)").is_none());
+        assert!(Gradient::parse("linear-gradient(to nowhere, red, blue)").is_none());
+        assert!(Gradient::parse("url(image.png)").is_none());
+    }
+
+    #[test]
+    fn test_parse_layers() {
+        let layers = Gradient::parse_layers("linear-gradient(red, blue), url(\"a.png\"), conic-gradient(red, blue)");
+        assert_eq!(layers.len(), 2);
+        assert!(matches!(layers[0].kind, GradientKind::Conic { .. }));
+        assert!(matches!(layers[1].kind, GradientKind::Linear(_)));
+
+        assert!(Gradient::parse_layers("none").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_linear() {
+        let rect = Rect::new(10.0, 20.0, 200.0, 100.0);
+
+        // Missing positions are spread evenly, and a stop can not come before the previous one
+        let gradient = Gradient::parse("linear-gradient(90deg, red, green, blue 50%, yellow 20px, black)").unwrap();
+        let resolved = gradient.resolve(rect);
+        assert_eq!(offsets(&resolved), vec![0.0, 0.25, 0.5, 0.5, 1.0]);
+        let ResolvedKind::Linear { start, end } = resolved.kind else { panic!("expected a linear gradient") };
+        assert_eq!((start.x, start.y), (10.0, 70.0));
+        assert_eq!((end.x, end.y), (210.0, 70.0));
+
+        // The lines perpendicular to a corner gradient go through the corners at the start and end
+        let gradient = Gradient::parse("linear-gradient(to bottom right, red, blue)").unwrap();
+        let ResolvedKind::Linear { start, end } = gradient.resolve(rect).kind else { panic!("expected a linear gradient") };
+        assert!(((start.x - 10.0) * 100.0 + (start.y - 20.0) * 200.0).abs() < 0.0001);
+        assert!(((end.x - 210.0) * 100.0 + (end.y - 120.0) * 200.0).abs() < 0.0001);
+
+        // The line of a repeating gradient only covers a single period
+        let gradient = Gradient::parse("repeating-linear-gradient(90deg, red 20px, blue 60px)").unwrap();
+        let resolved = gradient.resolve(rect);
+        assert!(resolved.repeating);
+        assert_eq!(offsets(&resolved), vec![0.0, 1.0]);
+        let ResolvedKind::Linear { start, end } = resolved.kind else { panic!("expected a linear gradient") };
+        assert_eq!((start.x, end.x), (30.0, 70.0));
+    }
+
+    #[test]
+    fn test_resolve_radial_and_conic() {
+        let rect = Rect::new(0.0, 0.0, 200.0, 100.0);
+
+        let gradient = Gradient::parse("radial-gradient(ellipse farthest-side at 50px 50%, red 25%, blue)").unwrap();
+        let ResolvedKind::Radial { center, start_radius, end_radius, scale_y } = gradient.resolve(rect).kind else {
+            panic!("expected a radial gradient")
+        };
+        assert_eq!((center.x, center.y), (50.0, 50.0));
+        assert_eq!((start_radius, end_radius), (37.5, 150.0));
+        assert_eq!(scale_y, 50.0 / 150.0);
+
+        // Repeating radial gradients are shifted by whole periods to stay outside of the center
+        let gradient = Gradient::parse("repeating-radial-gradient(circle 100px, red -30px, blue 20px)").unwrap();
+        let ResolvedKind::Radial { start_radius, end_radius, .. } = gradient.resolve(rect).kind else {
+            panic!("expected a radial gradient")
+        };
+        assert!((start_radius - 20.0).abs() < 0.0001 && (end_radius - 70.0).abs() < 0.0001);
+
+        let gradient = Gradient::parse("conic-gradient(from 90deg, red 0.25turn, blue)").unwrap();
+        let resolved = gradient.resolve(rect);
+        assert_eq!(offsets(&resolved), vec![0.0, 1.0]);
+        assert_eq!(resolved.color_at(-1.0).r8(), 255);
+        assert_eq!((resolved.color_at(0.5).r8(), resolved.color_at(0.5).b8()), (127, 127));
+        assert_eq!(resolved.color_at(2.0).b8(), 255);
+        let ResolvedKind::Conic { center, rotation, start_angle, end_angle } = resolved.kind else { panic!("expected a conic gradient") };
+        assert_eq!((center.x, center.y), (100.0, 50.0));
+        assert_eq!(rotation, 0.0);
+        assert!((start_angle - PI / 2.0).abs() < 0.0001);
+        assert!((end_angle - 2.0 * PI).abs() < 0.0001);
+    }
+}
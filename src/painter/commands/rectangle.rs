@@ -1,4 +1,4 @@
-use crate::common::geo::Rect;
+use crate::common::geo::{Coordinate, Rect};
 use crate::painter::commands::border::Border;
 use crate::painter::commands::brush::Brush;
 
@@ -79,11 +79,24 @@ impl Rectangle {
         self
     }
 
+    pub fn without_background(mut self) -> Self {
+        self.background = None;
+        self
+    }
+
     pub fn with_border(mut self, border: Border) -> Self {
         self.border = border;
         self
     }
 
+    /// Returns the same rectangle, moved by the given offset
+    pub fn shift(&self, offset: Coordinate) -> Self {
+        Rectangle {
+            rect: self.rect.shift(offset),
+            ..self.clone()
+        }
+    }
+
     pub fn rect(&self) -> Rect {
         self.rect
     }
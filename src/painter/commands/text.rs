@@ -1,5 +1,6 @@
+use crate::common::document::style::{Direction, FontStyle, TextDecorationLine, TextDecorationStyle};
 use crate::common::geo::Rect;
-use crate::layouter::text::Alignment;
+use crate::layouter::text::{Alignment, TextStyle, TextWrapping};
 use crate::painter::commands::brush::Brush;
 
 #[derive(Clone, Debug)]
@@ -10,8 +11,10 @@ pub struct Text {
     pub font_family: String,
     /// Size of font in pixels
     pub font_size: f64,
-    /// Weight of the font 100-700
+    /// Weight of the font 100-900
     pub font_weight: usize,
+    /// Normal, italic or oblique
+    pub font_style: FontStyle,
     /// Height of each line (line-spacing)
     pub line_height: f64,
     /// Actual text
@@ -19,7 +22,18 @@ pub struct Text {
     /// Brush to paint the text with
     pub brush: Brush,
     /// Text alignment
-    pub alignment: Alignment
+    pub alignment: Alignment,
+    /// Base direction of the text
+    pub direction: Direction,
+    /// How lines are wrapped and truncated
+    pub wrapping: TextWrapping,
+    /// Width the text is laid out with. This is the width the layouter has measured the text with, which can
+    /// differ from the width of the rect.
+    pub max_width: f64,
+    /// Lines to draw over, under or through the text
+    pub decoration: Option<TextDecoration>,
+    /// Only the glyph runs inside this rect need to be drawn. Used when the text is spread over multiple tiles.
+    pub clip: Option<Rect>,
 }
 
 impl Text {
@@ -29,11 +43,170 @@ impl Text {
             font_family: font_family.to_string(),
             font_size,
             font_weight,
+            font_style: FontStyle::Normal,
             line_height,
             text: text.to_string(),
             brush,
             alignment,
+            direction: Direction::Ltr,
+            wrapping: TextWrapping::default(),
+            max_width: rect.width,
+            decoration: None,
+            clip: None,
         }
     }
+
+    pub fn with_font_style(mut self, font_style: FontStyle) -> Self {
+        self.font_style = font_style;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_wrapping(mut self, wrapping: TextWrapping) -> Self {
+        self.wrapping = wrapping;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f64) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    pub fn with_clip(mut self, clip: Rect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Returns the style the text is shaped with. This is the same style the layouter has measured the text with.
+    pub fn text_style(&self) -> TextStyle {
+        TextStyle {
+            font_family: self.font_family.clone(),
+            font_size: self.font_size,
+            font_weight: self.font_weight,
+            font_style: self.font_style,
+            line_height: self.line_height,
+            max_width: self.max_width,
+            alignment: self.alignment,
+            direction: self.direction,
+            wrapping: self.wrapping,
+        }
+    }
+
+    pub fn with_decoration(mut self, decoration: TextDecoration) -> Self {
+        if !decoration.line.is_none() {
+            self.decoration = Some(decoration);
+        }
+        self
+    }
+}
+
+/// Decoration lines of a text, with the brush to paint them with
+#[derive(Clone, Debug)]
+pub struct TextDecoration {
+    pub line: TextDecorationLine,
+    pub style: TextDecorationStyle,
+    pub brush: Brush,
+}
+
+/// Metrics of a single line (or run) of shaped text, as reported by the text backend. All values are in pixels,
+/// offsets are measured downwards from the baseline.
+#[derive(Clone, Copy, Debug)]
+pub struct TextLineMetrics {
+    /// Start of the line
+    pub x: f64,
+    /// Width of the line
+    pub width: f64,
+    /// Position of the baseline
+    pub baseline: f64,
+    /// Height of the font above the baseline
+    pub ascent: f64,
+    pub underline_offset: f64,
+    pub underline_thickness: f64,
+    /// Normally negative, as the strikethrough is above the baseline
+    pub strikethrough_offset: f64,
+    pub strikethrough_thickness: f64,
 }
 
+impl TextLineMetrics {
+    /// Metrics based on the font size only. Used when the backend cannot provide the metrics from the font.
+    pub fn from_font_size(x: f64, width: f64, baseline: f64, font_size: f64) -> Self {
+        let thickness = (font_size / 16.0).max(1.0);
+        Self {
+            x,
+            width,
+            baseline,
+            ascent: font_size * 0.8,
+            underline_offset: font_size * 0.1,
+            underline_thickness: thickness,
+            strikethrough_offset: -font_size * 0.3,
+            strikethrough_thickness: thickness,
+        }
+    }
+}
+
+/// A single decoration line to paint. `y` is the center of the line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecorationLine {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub thickness: f64,
+}
+
+/// Returns the lines that should be painted for the given decoration on a line of text. Double decorations
+/// result in two lines, the dotted, dashed and wavy styles must be applied by the rasterizer with
+/// `dash_pattern()` and `wavy_points()`.
+pub fn decoration_lines(decoration: &TextDecoration, metrics: &TextLineMetrics) -> Vec<DecorationLine> {
+    let mut lines = Vec::new();
+
+    let mut push = |y: f64, thickness: f64, direction: f64| {
+        lines.push(DecorationLine { x: metrics.x, y, width: metrics.width, thickness });
+        if decoration.style == TextDecorationStyle::Double {
+            // The second line is drawn away from the text
+            lines.push(DecorationLine { x: metrics.x, y: y + direction * thickness * 2.0, width: metrics.width, thickness });
+        }
+    };
+
+    if decoration.line.underline {
+        push(metrics.baseline + metrics.underline_offset + metrics.underline_thickness / 2.0, metrics.underline_thickness, 1.0);
+    }
+    if decoration.line.overline {
+        push(metrics.baseline - metrics.ascent + metrics.underline_thickness / 2.0, metrics.underline_thickness, -1.0);
+    }
+    if decoration.line.line_through {
+        push(metrics.baseline + metrics.strikethrough_offset + metrics.strikethrough_thickness / 2.0, metrics.strikethrough_thickness, 1.0);
+    }
+
+    lines
+}
+
+/// Returns the dash intervals (on, off) for dotted and dashed decorations
+pub fn dash_pattern(style: TextDecorationStyle, thickness: f64) -> Option<[f64; 2]> {
+    match style {
+        TextDecorationStyle::Dotted => Some([thickness, thickness]),
+        TextDecorationStyle::Dashed => Some([thickness * 3.0, thickness * 3.0]),
+        _ => None,
+    }
+}
+
+/// Returns the points of a zigzag polyline that approximates a wavy decoration line
+pub fn wavy_points(line: &DecorationLine) -> Vec<(f64, f64)> {
+    let amplitude = line.thickness * 1.5;
+    let step = amplitude * 2.0;
+
+    let mut points = Vec::new();
+    let mut x = line.x;
+    let mut up = true;
+    while x < line.x + line.width {
+        points.push((x, if up { line.y - amplitude } else { line.y + amplitude }));
+        x += step;
+        up = !up;
+    }
+    points.push((line.x + line.width, line.y));
+
+    points
+}
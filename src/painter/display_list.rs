@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use crate::common::document::node::{Node, NodeType};
+use crate::common::document::style::StylePropertyList;
+use crate::common::hash::{hash_from_string, Sha256Hash};
+use crate::layouter::{LayoutElementId, LayoutTree};
+use crate::painter::commands::PaintCommand;
+
+/// Retained paint commands of the layout elements. The commands of an element are generated once and reused for
+/// every tile the element is painted on, also when the tiles are created again after scrolling or a change of the
+/// tile size. The commands are in layer coordinates and do not contain debug overlays like wireframes.
+#[derive(Default)]
+pub struct DisplayList {
+    items: RwLock<HashMap<LayoutElementId, DisplayItem>>,
+}
+
+struct DisplayItem {
+    /// Fingerprint of the geometry and styles the commands are generated from
+    fingerprint: Sha256Hash,
+    commands: Arc<Vec<PaintCommand>>,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the retained commands of the element, or generates and retains them when there are none
+    pub fn get_or_generate<F: FnOnce() -> Vec<PaintCommand>>(&self, layout_tree: &LayoutTree, element_id: LayoutElementId, generate: F) -> Arc<Vec<PaintCommand>> {
+        if let Some(commands) = self.get(element_id) {
+            return commands;
+        }
+
+        let commands = Arc::new(generate());
+        if let Some(fingerprint) = fingerprint(layout_tree, element_id) {
+            let mut items = self.items.write().expect("Failed to lock display list");
+            items.insert(element_id, DisplayItem { fingerprint, commands: commands.clone() });
+        }
+        commands
+    }
+
+    /// Returns the retained commands of the element
+    pub fn get(&self, element_id: LayoutElementId) -> Option<Arc<Vec<PaintCommand>>> {
+        let items = self.items.read().expect("Failed to lock display list");
+        items.get(&element_id).map(|item| item.commands.clone())
+    }
+
+    /// Drops the commands of the element, so they are generated again the next time it is painted
+    pub fn invalidate(&self, element_id: LayoutElementId) {
+        self.items.write().expect("Failed to lock display list").remove(&element_id);
+    }
+
+    pub fn clear(&self) {
+        self.items.write().expect("Failed to lock display list").clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.read().expect("Failed to lock display list").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keeps only the commands of elements whose geometry and styles are the same in the given layout tree. Called
+    /// when the layout tree is replaced, like after a reflow.
+    pub fn retain_unchanged(&self, layout_tree: &LayoutTree) {
+        let mut items = self.items.write().expect("Failed to lock display list");
+        items.retain(|element_id, item| fingerprint(layout_tree, *element_id) == Some(item.fingerprint));
+    }
+}
+
+/// Fingerprint of everything the paint commands of an element depend on: its box model and layout context, the
+/// styles of its DOM node, and the styles of the parent node, as text is painted in the color of its parent.
+fn fingerprint(layout_tree: &LayoutTree, element_id: LayoutElementId) -> Option<Sha256Hash> {
+    let element = layout_tree.get_node_by_id(element_id)?;
+    let doc = &layout_tree.render_tree.doc;
+    let node = doc.get_node_by_id(element.dom_node_id)?;
+    let parent = node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id));
+
+    let mut content = format!("{:?}\n{:?}\n{:?}\n", element.dom_node_id, element.box_model, element.context);
+    content.push_str(&node_fingerprint(node));
+    if let Some(parent) = parent {
+        content.push_str(&node_fingerprint(parent));
+    }
+
+    Some(hash_from_string(&content))
+}
+
+fn node_fingerprint(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Element(data) => format!("<{}>{}", data.tag_name, styles_fingerprint(&data.styles)),
+        NodeType::Text(text, styles) => format!("{:?}{}", text, styles_fingerprint(styles)),
+        NodeType::Comment(_) => String::new(),
+    }
+}
+
+/// Styles are stored in a hashmap, so they are sorted to get the same fingerprint for the same styles
+fn styles_fingerprint(styles: &StylePropertyList) -> String {
+    let mut entries: Vec<String> = styles.properties.iter()
+        .map(|(prop, value)| format!("{:?}={:?}", prop, value))
+        .collect();
+    entries.sort();
+    entries.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::style::{StyleProperty, StyleValue, Unit};
+    use crate::common::geo::Dimension;
+    use crate::layouter::taffy::TaffyLayouter;
+    use crate::layouter::CanLayout;
+    use crate::rendertree_builder::RenderTree;
+
+    /// Lays out a 1000x50 page with two divs next to each other, where the second one has the given width
+    fn layout(width: f32) -> LayoutTree {
+        let styles = |width: f32, height: f32| {
+            let mut styles = StylePropertyList::new();
+            styles.set_property(StyleProperty::Width, StyleValue::Unit(width, Unit::Px));
+            styles.set_property(StyleProperty::Height, StyleValue::Unit(height, Unit::Px));
+            styles
+        };
+
+        let mut doc = Document::new("https://example.com");
+        let root_id = doc.new_element(None, "DocumentRoot", None, false, Some(styles(1000.0, 50.0)));
+        for width in [100.0, width] {
+            let div_id = doc.new_element(Some(root_id), "div", None, false, Some(styles(width, 50.0)));
+            doc.add_child(root_id, div_id);
+        }
+        doc.set_root(root_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+        TaffyLayouter::new().layout(render_tree, Some(Dimension::new(1000.0, 800.0)))
+    }
+
+    #[test]
+    fn test_retain_unchanged() {
+        let layout_tree = layout(100.0);
+        let display_list = DisplayList::new();
+        let element_ids: Vec<LayoutElementId> = layout_tree.arena.keys().copied().collect();
+
+        let mut generated = 0;
+        for element_id in &element_ids {
+            display_list.get_or_generate(&layout_tree, *element_id, || { generated += 1; vec![] });
+        }
+        assert_eq!(generated, element_ids.len());
+
+        // Already retained commands are not generated again
+        display_list.get_or_generate(&layout_tree, element_ids[1], || { generated += 1; vec![] });
+        assert_eq!(generated, element_ids.len());
+
+        // The same layout keeps everything
+        display_list.retain_unchanged(&layout(100.0));
+        assert_eq!(display_list.len(), element_ids.len());
+
+        // Only the element with a different size is dropped
+        display_list.retain_unchanged(&layout(200.0));
+        assert_eq!(display_list.len(), element_ids.len() - 1);
+
+        display_list.invalidate(element_ids[0]);
+        assert!(display_list.get(element_ids[0]).is_none());
+    }
+}
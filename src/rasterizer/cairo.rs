@@ -8,6 +8,7 @@ use crate::tiler::Tile;
 
 mod rectangle;
 mod brush;
+mod svg;
 mod text;
 
 pub struct CairoRasterizer {}
@@ -20,7 +21,11 @@ impl CairoRasterizer {
 
 impl Rasterable for CairoRasterizer {
     fn rasterize(&self, tile: &Tile) -> TextureId {
-        let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, tile.rect.width as i32, tile.rect.height as i32).expect("Failed to create image surface");
+        // The surface is in device pixels. With the device scale set, cairo maps the CSS pixels of the paint
+        // commands onto it.
+        let texture_size = tile.texture_dimension();
+        let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, texture_size.width as i32, texture_size.height as i32).expect("Failed to create image surface");
+        surface.set_device_scale(tile.scale, tile.scale);
 
         {
             // Each tile has a number of elements which have paint commands. We need to execute these paint commands in order
@@ -1,9 +1,13 @@
-use gtk4::cairo::Context;
+use std::f64::consts::PI;
+use gtk4::cairo::{Context, Extend, LinearGradient, Matrix, Mesh, MeshCorner, RadialGradient};
 use gtk4::gdk_pixbuf::{Colorspace, Pixbuf};
 use gtk4::glib::Bytes;
 use gtk4::prelude::GdkCairoContextExt;
-use crate::common::geo::Rect;
+use crate::common::geo::{Coordinate, Rect};
+use crate::common::get_media_store;
 use crate::painter::commands::brush::Brush;
+use crate::painter::commands::color::Color;
+use crate::painter::commands::gradient::{Gradient, ResolvedGradient, ResolvedKind};
 
 // Sets the given brush to the context. In case of an image brush, rect defines the scale size of the image.
 pub fn set_brush(cr: &Context, brush: &Brush, rect: Rect) {
@@ -11,17 +15,22 @@ pub fn set_brush(cr: &Context, brush: &Brush, rect: Rect) {
         Brush::Solid(color) => {
             cr.set_source_rgba(color.r() as f64, color.g() as f64, color.b() as f64, color.a() as f64);
         }
-        Brush::Image(img) => {
+        Brush::Image(media_id) => {
             // If the rect has no width or height, we do not need to draw the image. So we can leave the brush as-is.
             if rect.width == 0.0 || rect.height == 0.0 {
                 return;
             }
 
-            let bytes = Bytes::from(img.data());
-            let pixbuf = Pixbuf::from_bytes(&bytes, Colorspace::Rgb, true, 8, img.width() as i32, img.height() as i32, img.width() as i32 * 4);
+            let binding = get_media_store();
+            let media_store = binding.read().expect("Failed to get media store");
+            let media = media_store.get_image(*media_id);
+            let (width, height) = (media.image.width(), media.image.height());
 
-            let scale_x = rect.width / img.width() as f64;
-            let scale_y = rect.height / img.height() as f64;
+            let bytes = Bytes::from_owned(media.image.to_vec());
+            let pixbuf = Pixbuf::from_bytes(&bytes, Colorspace::Rgb, true, 8, width as i32, height as i32, width as i32 * 4);
+
+            let scale_x = rect.width / width as f64;
+            let scale_y = rect.height / height as f64;
 
             // Create a scaled version of the image. This does not really sound like a good idea, but i have to find better ways to deal
             // with scaled images.
@@ -35,5 +44,102 @@ pub fn set_brush(cr: &Context, brush: &Brush, rect: Rect) {
 
             cr.set_source_pixbuf(&scaled_pixbuf, rect.x, rect.y);
         }
+        Brush::Gradient(gradient) => set_gradient(cr, gradient, rect),
+    }
+}
+
+fn set_gradient(cr: &Context, gradient: &Gradient, rect: Rect) {
+    let resolved = gradient.resolve(rect);
+    let extend = if resolved.repeating { Extend::Repeat } else { Extend::Pad };
+
+    let result = match resolved.kind {
+        ResolvedKind::Linear { start, end } => {
+            let pattern = LinearGradient::new(start.x, start.y, end.x, end.y);
+            for (offset, color) in &resolved.stops {
+                pattern.add_color_stop_rgba(*offset as f64, color.r() as f64, color.g() as f64, color.b() as f64, color.a() as f64);
+            }
+            pattern.set_extend(extend);
+            cr.set_source(&pattern)
+        }
+        ResolvedKind::Radial { center, start_radius, end_radius, scale_y } => {
+            let pattern = RadialGradient::new(center.x, center.y, start_radius, center.x, center.y, end_radius);
+            for (offset, color) in &resolved.stops {
+                pattern.add_color_stop_rgba(*offset as f64, color.r() as f64, color.g() as f64, color.b() as f64, color.a() as f64);
+            }
+            pattern.set_extend(extend);
+            // Ellipses are circles that are scaled vertically around the center. The pattern matrix maps user space
+            // to pattern space, so it holds the inverse scale.
+            pattern.set_matrix(Matrix::new(1.0, 0.0, 0.0, 1.0 / scale_y, 0.0, center.y - center.y / scale_y));
+            cr.set_source(&pattern)
+        }
+        ResolvedKind::Conic { center, rotation, start_angle, end_angle } => {
+            let pattern = create_conic_mesh(&resolved, center, rotation, start_angle, end_angle, rect);
+            cr.set_source(&pattern)
+        }
+    };
+
+    if let Err(e) = result {
+        log::warn!("Failed to set gradient brush: {}", e);
+    }
+}
+
+/// Number of slices of a full turn when a conic gradient is approximated with a mesh
+const CONIC_SLICES: usize = 64;
+
+// Cairo has no conic gradients, so we approximate them with a mesh of slices around the center. The slices are cut
+// at every color stop, so hard transitions stay sharp.
+fn create_conic_mesh(resolved: &ResolvedGradient, center: Coordinate, rotation: f64, start_angle: f64, end_angle: f64, rect: Rect) -> Mesh {
+    // The slices must reach beyond every corner of the rect
+    let radius = [(rect.x, rect.y), (rect.x + rect.width, rect.y), (rect.x, rect.y + rect.height), (rect.x + rect.width, rect.y + rect.height)]
+        .iter()
+        .map(|(x, y)| (x - center.x).hypot(y - center.y))
+        .fold(0.0, f64::max) * 1.01 + 1.0;
+
+    let span = end_angle - start_angle;
+    let mut cuts: Vec<f64> = (0..=CONIC_SLICES).map(|idx| 2.0 * PI * idx as f64 / CONIC_SLICES as f64).collect();
+    if span > 0.0 {
+        let periods = if resolved.repeating { ((2.0 * PI / span).ceil() as i64).min(1024) } else { 0 };
+        let first_period = if resolved.repeating { (-start_angle / span).floor() as i64 } else { 0 };
+        for period in first_period..=first_period + periods {
+            for (offset, _) in &resolved.stops {
+                let angle = start_angle + span * (period as f64 + *offset as f64);
+                if angle > 0.0 && angle < 2.0 * PI {
+                    cuts.push(angle);
+                }
+            }
+        }
     }
+    cuts.sort_by(f64::total_cmp);
+    cuts.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+
+    let offset_at = |angle: f64| if span > 0.0 { (angle - start_angle) / span } else { 0.0 };
+    let point_at = |angle: f64| (center.x + radius * (rotation + angle).cos(), center.y + radius * (rotation + angle).sin());
+    let set_color = |mesh: &Mesh, corner: MeshCorner, color: &Color| {
+        mesh.set_corner_color_rgba(corner, color.r() as f64, color.g() as f64, color.b() as f64, color.a() as f64);
+    };
+
+    let mesh = Mesh::new();
+    for pair in cuts.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        // The colors are taken just inside the slice, so a stop on the cut does not bleed into it
+        let epsilon = (to - from) * 1e-4;
+        let from_color = resolved.color_at(offset_at(from + epsilon));
+        let to_color = resolved.color_at(offset_at(to - epsilon));
+
+        let (from_x, from_y) = point_at(from);
+        let (to_x, to_y) = point_at(to);
+
+        mesh.begin_patch();
+        mesh.move_to(center.x, center.y);
+        mesh.line_to(from_x, from_y);
+        mesh.line_to(to_x, to_y);
+        mesh.line_to(center.x, center.y);
+        set_color(&mesh, MeshCorner::MeshCorner0, &from_color);
+        set_color(&mesh, MeshCorner::MeshCorner1, &from_color);
+        set_color(&mesh, MeshCorner::MeshCorner2, &to_color);
+        set_color(&mesh, MeshCorner::MeshCorner3, &to_color);
+        mesh.end_patch();
+    }
+
+    mesh
 }
\ No newline at end of file
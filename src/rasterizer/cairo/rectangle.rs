@@ -1,6 +1,7 @@
+use std::f64::consts::PI;
 use gtk4::cairo::Context;
 use crate::painter::commands::border::BorderStyle;
-use crate::painter::commands::rectangle::Rectangle;
+use crate::painter::commands::rectangle::{Radius, Rectangle};
 use crate::rasterizer::cairo::brush::set_brush;
 use crate::tiler::Tile;
 
@@ -92,26 +93,42 @@ pub(crate) fn do_paint_rectangle(cr: &Context, tile: &Tile, rectangle: &Rectangl
 
 /// Creates a cairo rectangle with either sharp or rounded corners. Does not fill or stroke the path.
 fn setup_rectangle_path(cr: &Context, rect: &Rectangle) {
-    let (r_tl, r_tr, r_br, r_bl) = rect.radius();
-
-    if r_tl == 0.0 && r_tr == 0.0 && r_br == 0.0 && r_bl == 0.0 {
-        cr.rectangle(rect.rect().x, rect.rect().y, rect.rect().width, rect.rect().height);
+    let r = rect.rect();
+    if !rect.is_rounded() {
+        cr.rectangle(r.x, r.y, r.width, r.height);
         return;
     }
 
-    cr.move_to(rect.rect().x + r_tl, rect.rect().y);
+    let (r_tl, r_tr, r_br, r_bl) = rect.radius();
 
-    cr.line_to(rect.rect().x + rect.rect().width - r_tr, rect.rect().y);
-    cr.arc(rect.rect().x + rect.rect().width - r_tr, rect.rect().y + r_tr, r_tr, -0.5 * std::f64::consts::PI, 0.0);
+    cr.move_to(r.x + r_tl.x, r.y);
 
-    cr.line_to(rect.rect().x + rect.rect().width, rect.rect().y + rect.rect().height - r_br);
-    cr.arc(rect.rect().x + rect.rect().width - r_br, rect.rect().y + rect.rect().height - r_br, r_br, 0.0, 0.5 * std::f64::consts::PI);
+    cr.line_to(r.x + r.width - r_tr.x, r.y);
+    corner_arc(cr, r.x + r.width - r_tr.x, r.y + r_tr.y, r_tr, -0.5 * PI, 0.0);
 
-    cr.line_to(rect.rect().x + r_bl, rect.rect().y + rect.rect().height);
-    cr.arc(rect.rect().x + r_bl, rect.rect().y + rect.rect().height - r_bl, r_bl, 0.5 * std::f64::consts::PI, std::f64::consts::PI);
+    cr.line_to(r.x + r.width, r.y + r.height - r_br.y);
+    corner_arc(cr, r.x + r.width - r_br.x, r.y + r.height - r_br.y, r_br, 0.0, 0.5 * PI);
 
-    cr.line_to(rect.rect().x, rect.rect().y + r_tl);
-    cr.arc(rect.rect().x + r_tl, rect.rect().y + r_tl, r_tl, std::f64::consts::PI, 1.5 * std::f64::consts::PI);
+    cr.line_to(r.x + r_bl.x, r.y + r.height);
+    corner_arc(cr, r.x + r_bl.x, r.y + r.height - r_bl.y, r_bl, 0.5 * PI, PI);
+
+    cr.line_to(r.x, r.y + r_tl.y);
+    corner_arc(cr, r.x + r_tl.x, r.y + r_tl.y, r_tl, PI, 1.5 * PI);
 
     cr.close_path();
-}
\ No newline at end of file
+}
+
+/// Adds an elliptical corner around the given center to the path. Cairo only draws circular arcs, so the arc is
+/// drawn on a unit circle that is scaled to the radii of the corner.
+fn corner_arc(cr: &Context, cx: f64, cy: f64, radius: Radius, angle1: f64, angle2: f64) {
+    if radius.x <= 0.0 || radius.y <= 0.0 {
+        cr.line_to(cx, cy);
+        return;
+    }
+
+    let matrix = cr.matrix();
+    cr.translate(cx, cy);
+    cr.scale(radius.x, radius.y);
+    cr.arc(0.0, 0.0, 1.0, angle1, angle2);
+    cr.set_matrix(matrix);
+}
@@ -0,0 +1,47 @@
+use gtk4::cairo::{Context, Format, ImageSurface};
+use resvg::usvg::Transform;
+use crate::common::get_media_store;
+use crate::painter::commands::PaintSvg;
+use crate::tiler::Tile;
+
+/// Renders the SVG at the size of its rect, and paints it onto the tile
+pub(crate) fn do_paint_svg(cr: &Context, tile: &Tile, command: &PaintSvg) {
+    let rect = command.rect.rect();
+    // The SVG is rendered in device pixels, so it stays sharp on HiDPI screens
+    let width = (rect.width * tile.scale).ceil() as u32;
+    let height = (rect.height * tile.scale).ceil() as u32;
+    let Some(mut pixmap) = resvg::tiny_skia::Pixmap::new(width, height) else {
+        return;
+    };
+
+    let binding = get_media_store().read().unwrap();
+    let media = binding.get_svg(command.media_id);
+    let size = media.svg.tree.size();
+    let transform = Transform::from_scale(width as f32 / size.width(), height as f32 / size.height());
+    resvg::render(&media.svg.tree, transform, &mut pixmap.as_mut());
+    drop(binding);
+
+    // Both are premultiplied, but tiny-skia stores RGBA, while cairo stores ARGB as native-endian words
+    let mut data = pixmap.take();
+    for pixel in data.chunks_exact_mut(4) {
+        let argb = u32::from_be_bytes([pixel[3], pixel[0], pixel[1], pixel[2]]);
+        pixel.copy_from_slice(&argb.to_ne_bytes());
+    }
+
+    let Ok(surface) = ImageSurface::create_for_data(data, Format::ARgb32, width as i32, height as i32, width as i32 * 4) else {
+        log::warn!("Failed to create surface for SVG: {:?}", command.media_id);
+        return;
+    };
+    surface.set_device_scale(tile.scale, tile.scale);
+
+    _ = cr.save();
+
+    // Translate the context to the tile's position and clip it.
+    cr.translate(-tile.rect.x, -tile.rect.y);
+    cr.rectangle(tile.rect.x, tile.rect.y, tile.rect.width, tile.rect.height);
+    cr.clip();
+
+    _ = cr.set_source_surface(&surface, rect.x, rect.y);
+    _ = cr.paint();
+    _ = cr.restore();
+}
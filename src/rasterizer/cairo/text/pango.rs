@@ -1,14 +1,17 @@
 use gtk4::cairo::{Context, Error, Format, ImageSurface};
-use gtk4::pango::SCALE;
-use pangocairo::functions::{context_set_resolution, create_layout};
-use pangocairo::pango::FontDescription;
-use crate::painter::commands::text::Text;
+use gtk4::pango::{Layout, SCALE};
+use crate::common::document::style::TextDecorationStyle;
+use crate::painter::commands::text::{dash_pattern, decoration_lines, wavy_points, Text, TextDecoration, TextLineMetrics};
 use crate::rasterizer::cairo::brush::set_brush;
 use crate::tiler::Tile;
-use crate::common::font::pango::{find_available_font, to_pango_weight};
+use crate::common::font::pango::get_pango_layout;
+use crate::common::geo::Rect;
 
 pub(crate) fn do_paint_text(cr: &Context, tile: &Tile, cmd: &Text) -> Result<(), Error> {
-    let surface = create_text_layout(cmd)?;
+    let Some(region) = visible_region(cmd) else {
+        return Ok(());
+    };
+    let surface = create_text_layout(cmd, region)?;
 
     // Save the context state. This allows us to do clipping and translation without worrying about
     // the state of the context.
@@ -20,39 +23,118 @@ pub(crate) fn do_paint_text(cr: &Context, tile: &Tile, cmd: &Text) -> Result<(),
     cr.clip();
 
     cr.move_to(cmd.rect.x, cmd.rect.y);
-    cr.set_source_surface(&surface, cmd.rect.x, cmd.rect.y)?;
+    cr.set_source_surface(&surface, cmd.rect.x + region.x, cmd.rect.y + region.y)?;
     cr.paint()?;
     cr.restore()?;
 
     Ok(())
 }
 
-fn create_text_layout(cmd: &Text) -> Result<ImageSurface, Error> {
-    let surface = ImageSurface::create(Format::ARgb32, cmd.rect.width as i32, cmd.rect.height as i32)?;
-    let cr = Context::new(&surface)?;
-    let layout = create_layout(&cr);
-
-    // @TODO: I need to set the DPI resolution to 72dpi, otherwise the text will be too large
-    context_set_resolution(&layout.context(), 72.0);
+/// Returns the part of the text that is inside its clip, relative to the text rect. When the text is spread over
+/// multiple tiles, only this part is rendered.
+fn visible_region(cmd: &Text) -> Option<Rect> {
+    let text_rect = Rect::new(0.0, 0.0, cmd.rect.width, cmd.rect.height);
+    match cmd.clip {
+        Some(clip) => Rect::new(clip.x - cmd.rect.x, clip.y - cmd.rect.y, clip.width, clip.height).intersection(&text_rect),
+        None => Some(text_rect),
+    }
+}
 
-    let selected_family = find_available_font(cmd.font_family.as_str(), &layout.context());
-    let mut font_desc = FontDescription::new();
-    font_desc.set_family(&selected_family);
-    font_desc.set_size((cmd.font_size * SCALE as f64) as i32);
-    font_desc.set_weight(to_pango_weight(cmd.font_weight));
-    layout.set_font_description(Some(&font_desc));
+/// Returns true when the line between top and bottom (relative to the text rect) is inside the region
+fn line_visible(top: f64, bottom: f64, region: Rect) -> bool {
+    bottom >= region.y && top <= region.y + region.height
+}
 
-    layout.set_text(cmd.text.as_str());
-    layout.set_width((cmd.rect.width * SCALE as f64) as i32);
-    layout.set_wrap(gtk4::pango::WrapMode::Word);
+fn create_text_layout(cmd: &Text, region: Rect) -> Result<ImageSurface, Error> {
+    let surface = ImageSurface::create(Format::ARgb32, region.width.ceil() as i32, region.height.ceil() as i32)?;
+    let cr = Context::new(&surface)?;
+    cr.translate(-region.x, -region.y);
 
-    layout.set_spacing(0);
-    layout.set_line_spacing(0.0);
+    // This is the same layout the layouter has measured, so it is already shaped
+    let layout = get_pango_layout(cmd.text.as_str(), &cmd.text_style())?;
+    pangocairo::functions::update_layout(&cr, &layout);
 
+    // Only the lines inside the region are drawn
     set_brush(&cr, &cmd.brush, cmd.rect);
-    cr.move_to(0.0, 0.0);
-    pangocairo::functions::show_layout(&cr, &layout);
+    let scale = SCALE as f64;
+    let mut iter = layout.iter();
+    loop {
+        let (_, logical) = iter.line_extents();
+        let top = logical.y() as f64 / scale;
+        if line_visible(top, top + logical.height() as f64 / scale, region) {
+            if let Some(line) = iter.line_readonly() {
+                cr.move_to(logical.x() as f64 / scale, iter.baseline() as f64 / scale);
+                pangocairo::functions::show_layout_line(&cr, &line);
+            }
+        }
+
+        if !iter.next_line() {
+            break;
+        }
+    }
+
+    if let Some(decoration) = &cmd.decoration {
+        paint_decorations(&cr, &layout, cmd, decoration, region)?;
+    }
 
     Ok(surface)
 }
 
+fn paint_decorations(cr: &Context, layout: &Layout, cmd: &Text, decoration: &TextDecoration, region: Rect) -> Result<(), Error> {
+    let scale = SCALE as f64;
+    let font_metrics = layout.context().metrics(layout.font_description().as_ref(), None);
+
+    set_brush(cr, &decoration.brush, cmd.rect);
+
+    let mut iter = layout.iter();
+    loop {
+        let (_, logical) = iter.line_extents();
+        let top = logical.y() as f64 / scale;
+        if !line_visible(top, top + logical.height() as f64 / scale, region) {
+            if !iter.next_line() {
+                break;
+            }
+            continue;
+        }
+
+        // Pango positions point upwards from the baseline, ours point downwards
+        let metrics = TextLineMetrics {
+            x: logical.x() as f64 / scale,
+            width: logical.width() as f64 / scale,
+            baseline: iter.baseline() as f64 / scale,
+            ascent: font_metrics.ascent() as f64 / scale,
+            underline_offset: -font_metrics.underline_position() as f64 / scale,
+            underline_thickness: font_metrics.underline_thickness() as f64 / scale,
+            strikethrough_offset: -font_metrics.strikethrough_position() as f64 / scale,
+            strikethrough_thickness: font_metrics.strikethrough_thickness() as f64 / scale,
+        };
+
+        for line in decoration_lines(decoration, &metrics) {
+            cr.set_line_width(line.thickness);
+            match dash_pattern(decoration.style, line.thickness) {
+                Some(pattern) => cr.set_dash(&pattern, 0.0),
+                None => cr.set_dash(&[], 0.0),
+            }
+
+            if decoration.style == TextDecorationStyle::Wavy {
+                for (i, (x, y)) in wavy_points(&line).into_iter().enumerate() {
+                    if i == 0 {
+                        cr.move_to(x, y);
+                    } else {
+                        cr.line_to(x, y);
+                    }
+                }
+            } else {
+                cr.move_to(line.x, line.y);
+                cr.line_to(line.x + line.width, line.y);
+            }
+            cr.stroke()?;
+        }
+
+        if !iter.next_line() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -8,6 +8,7 @@ mod rectangle;
 mod paint;
 mod text;
 mod svg;
+pub mod pdf;
 
 pub struct SkiaRasterizer;
 
@@ -19,8 +20,10 @@ impl SkiaRasterizer {
 
 impl Rasterable for SkiaRasterizer {
     fn rasterize(&self, tile: &Tile) -> TextureId {
-        let width = tile.rect.width as u32;
-        let height = tile.rect.height as u32;
+        // The surface is in device pixels, the paint commands are in CSS pixels
+        let texture_size = tile.texture_dimension();
+        let width = texture_size.width as u32;
+        let height = texture_size.height as u32;
 
         let mut surface = skia_safe::surfaces::raster_n32_premul(
             skia_safe::ISize::new(width as i32, height as i32),
@@ -32,16 +35,17 @@ impl Rasterable for SkiaRasterizer {
             None,
             None,
         );
+        canvas.scale((tile.scale as f32, tile.scale as f32));
         canvas.translate((-tile.rect.x as f32, -tile.rect.y as f32));
 
         for element in &tile.elements {
             for command in &element.paint_commands {
                 match command {
                     PaintCommand::Rectangle(command) => {
-                        rectangle::do_paint_rectangle(canvas, &tile, &command);
+                        rectangle::do_paint_rectangle(canvas, &command);
                     }
                     PaintCommand::Text(command) => {
-                        match text::do_paint_text(canvas, &tile, &command) {
+                        match text::do_paint_text(canvas, &command) {
                             Ok(_) => {}
                             Err(e) => {
                                 println!("Failed to paint text: {:?}", e);
@@ -49,7 +53,7 @@ impl Rasterable for SkiaRasterizer {
                         }
                     }
                     PaintCommand::Svg(command) => {
-                        svg::do_paint_svg(canvas, &tile, command.media_id, &command.rect);
+                        svg::do_paint_svg(canvas, command.media_id, &command.rect);
                     }
                 }
             }
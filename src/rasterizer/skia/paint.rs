@@ -1,7 +1,9 @@
-use skia_safe::{image_filters, AlphaType, Color4f, ColorSpace, ColorType, Data, ISize, ImageInfo, Paint as SkiaPaint};
-use crate::common::geo::Dimension;
+use skia_safe::{image_filters, AlphaType, Color4f, ColorSpace, ColorType, Data, ISize, ImageInfo, Matrix, Paint as SkiaPaint, Point, Shader, TileMode};
+use crate::common::geo::{Dimension, Rect};
 use crate::common::get_media_store;
 use crate::painter::commands::brush::Brush;
+use crate::painter::commands::color::Color;
+use crate::painter::commands::gradient::{Gradient, ResolvedKind};
 
 // Instead of sending a (skia) Paint object, we encapsulate this, as we might need to store additional information
 // in case of an image paint.
@@ -13,6 +15,7 @@ pub struct ImagePaint {
 pub enum Paint {
     Solid(SkiaPaint),
     Image(ImagePaint),
+    Gradient(SkiaPaint),
 }
 
 impl Paint {
@@ -20,6 +23,7 @@ impl Paint {
         match self {
             Paint::Solid(p) => p,
             Paint::Image(p) => &p.paint,
+            Paint::Gradient(p) => p,
         }
     }
 
@@ -27,17 +31,26 @@ impl Paint {
         match self {
             Paint::Solid(p) => p,
             Paint::Image(p) => &mut p.paint,
+            Paint::Gradient(p) => p,
         }
     }
 }
 
-pub fn create_paint(brush: &Brush) -> Paint {
+// Creates a paint for the given brush. The rect is the area that is painted, which is needed to resolve gradients.
+pub fn create_paint(brush: &Brush, rect: Rect) -> Paint {
     match brush {
         Brush::Solid(color) => {
-            // Note: bgra instead of rgba.. Although i'm not sure why, as this does not seem the documented order
-            let paint = SkiaPaint::new(Color4f::new(color.b(), color.g(), color.r(), color.a()), &ColorSpace::new_srgb());
+            let paint = SkiaPaint::new(convert_color(color), &ColorSpace::new_srgb());
             Paint::Solid(paint)
         }
+        Brush::Gradient(gradient) => {
+            let mut paint = SkiaPaint::default();
+            match create_gradient_shader(gradient, rect) {
+                Some(shader) => { paint.set_shader(shader); }
+                None => log::warn!("Failed to create gradient shader: {:?}", gradient),
+            }
+            Paint::Gradient(paint)
+        }
         Brush::Image(media_id) => {
             let binding = get_media_store();
             let media_store = binding.read().expect("Failed to get image store");
@@ -69,4 +82,46 @@ pub fn create_paint(brush: &Brush) -> Paint {
             })
         }
     }
-}
\ No newline at end of file
+}
+
+fn create_gradient_shader(gradient: &Gradient, rect: Rect) -> Option<Shader> {
+    let resolved = gradient.resolve(rect);
+
+    let colors: Vec<Color4f> = resolved.stops.iter().map(|(_, color)| convert_color(color)).collect();
+    let positions: Vec<f32> = resolved.stops.iter().map(|(offset, _)| *offset).collect();
+    let colors = (colors.as_slice(), ColorSpace::new_srgb());
+    let mode = if resolved.repeating { TileMode::Repeat } else { TileMode::Clamp };
+
+    match resolved.kind {
+        ResolvedKind::Linear { start, end } => {
+            let points = (Point::new(start.x as f32, start.y as f32), Point::new(end.x as f32, end.y as f32));
+            Shader::linear_gradient(points, colors, positions.as_slice(), mode, None, None)
+        }
+        ResolvedKind::Radial { center, start_radius, end_radius, scale_y } => {
+            // Ellipses are circles that are scaled vertically around the center
+            let center = Point::new(center.x as f32, center.y as f32);
+            let mut matrix = Matrix::default();
+            matrix.set_scale((1.0, scale_y as f32), center);
+
+            Shader::two_point_conical_gradient(
+                center, start_radius as f32,
+                center, end_radius as f32,
+                colors, positions.as_slice(), mode, None, &matrix,
+            )
+        }
+        ResolvedKind::Conic { center, rotation, start_angle, end_angle } => {
+            // Sweep gradients always start at the right, so they are rotated to the start of the turn
+            let center = Point::new(center.x as f32, center.y as f32);
+            let mut matrix = Matrix::default();
+            matrix.set_rotate(rotation.to_degrees() as f32, center);
+
+            let angles = (start_angle.to_degrees() as f32, end_angle.to_degrees() as f32);
+            Shader::sweep_gradient(center, colors, positions.as_slice(), mode, angles, None, &matrix)
+        }
+    }
+}
+
+// Note: bgra instead of rgba.. Although i'm not sure why, as this does not seem the documented order
+fn convert_color(color: &Color) -> Color4f {
+    Color4f::new(color.b(), color.g(), color.r(), color.a())
+}
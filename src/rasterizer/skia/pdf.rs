@@ -0,0 +1,174 @@
+use std::io::Write;
+use std::sync::Arc;
+use resvg::usvg;
+use skia_safe::{images, pdf, AlphaType, ColorType, Data, FontMgr, ISize, ImageInfo, Vector};
+use crate::common::document::document::Document;
+use crate::common::geo::Rect;
+use crate::common::get_media_store;
+use crate::common::media::MediaId;
+use crate::layering::layer::LayerList;
+use crate::layouter::pagination::{layout_pages, PageSetup};
+use crate::layouter::LayoutTree;
+use crate::painter::commands::brush::Brush;
+use crate::painter::commands::rectangle::Rectangle;
+use crate::painter::commands::PaintCommand;
+use crate::painter::Painter;
+use crate::common::font::skia::{get_painted_skia_paragraph, paint_skia_decorations};
+use crate::painter::commands::text::Text;
+use crate::rasterizer::skia::paint::create_paint;
+use crate::rasterizer::skia::{rectangle, svg};
+
+// The PDF export does not rasterize tiles. Instead, the paint commands of each page are replayed onto the canvas
+// of skia's PDF backend, so text stays text (with embedded font subsets), and SVGs stay vectors.
+
+/// CSS pixels are 1/96 inch, PDF points are 1/72 inch
+const PT_PER_PX: f32 = 72.0 / 96.0;
+
+/// Paginates the document with the given page setup and writes it as PDF
+pub fn document_to_pdf(doc: &Document, page: &PageSetup, title: &str, writer: &mut impl Write) {
+    let pages = layout_pages(doc, page);
+    export_pdf(pages, page, title, writer);
+}
+
+/// Writes the given pages (as returned by the paginator for the page setup) as PDF. Each layout tree becomes a
+/// single page with the size of its root. Elements that are split over two pages are clipped to the page area.
+pub fn export_pdf(pages: Vec<LayoutTree>, page: &PageSetup, title: &str, writer: &mut impl Write) {
+    let metadata = pdf::Metadata {
+        title: title.to_string(),
+        creator: "gosub pipeline".to_string(),
+        ..Default::default()
+    };
+
+    let mut document = pdf::new_document(writer, Some(&metadata));
+    let content_box = page.content_box();
+    for page_tree in pages {
+        let size = page_tree.root_dimension;
+        let layer_list = Arc::new(LayerList::new(page_tree));
+
+        let mut on_page = document.begin_page((size.width as f32 * PT_PER_PX, size.height as f32 * PT_PER_PX), None);
+        let canvas = on_page.canvas();
+        // Everything is painted in CSS pixels
+        canvas.scale((PT_PER_PX, PT_PER_PX));
+        let clip = skia_safe::Rect::from_xywh(content_box.x as f32, content_box.y as f32, content_box.width as f32, content_box.height as f32);
+        canvas.clip_rect(clip, None, Some(true));
+        paint_page(canvas, layer_list, content_box);
+
+        document = on_page.end_page();
+    }
+    document.close();
+}
+
+/// Paints all elements of the page, layer by layer, in the order of the layers
+fn paint_page(canvas: &skia_safe::Canvas, layer_list: Arc<LayerList>, content_box: Rect) {
+    let painter = Painter::new(layer_list.clone());
+
+    let layer_ids = layer_list.ordered_layer_ids();
+    let layers = layer_list.layers.read().expect("Failed to lock layers");
+    for layer_id in layer_ids {
+        let Some(layer) = layers.get(&layer_id) else {
+            continue;
+        };
+
+        for element_id in &layer.elements {
+            // Commands outside the page area are dropped, instead of being written to the PDF invisibly
+            for command in painter.paint_element(*element_id).iter().flat_map(|command| command.clip(content_box)) {
+                paint_command(canvas, &command);
+            }
+        }
+    }
+}
+
+fn paint_command(canvas: &skia_safe::Canvas, command: &PaintCommand) {
+    match command {
+        PaintCommand::Rectangle(command) => match command.background() {
+            // Images are embedded as images, instead of the image filter the rasterizer uses, which the PDF
+            // backend would rasterize again.
+            Some(Brush::Image(media_id)) => {
+                paint_image(canvas, *media_id, command);
+                rectangle::do_paint_rectangle(canvas, &command.clone().without_background());
+            }
+            _ => rectangle::do_paint_rectangle(canvas, command),
+        },
+        PaintCommand::Text(command) => paint_text(canvas, command),
+        PaintCommand::Svg(command) => {
+            if !paint_svg_vector(canvas, command.media_id, &command.rect) {
+                svg::do_paint_svg(canvas, command.media_id, &command.rect);
+            }
+        }
+    }
+}
+
+/// Draws the text with the brush as the paint of the glyphs. The rasterizer paints text into a separate layer
+/// instead, which the PDF backend would turn into a transparency group, or even rasterize.
+fn paint_text(canvas: &skia_safe::Canvas, cmd: &Text) {
+    let paint = create_paint(&cmd.brush, cmd.rect);
+    let paragraph = get_painted_skia_paragraph(cmd.text.as_str(), &cmd.text_style(), paint.paint());
+
+    let origin = (cmd.rect.x as f32, cmd.rect.y as f32);
+    paragraph.paint(canvas, origin);
+
+    if let Some(decoration) = &cmd.decoration {
+        let decoration_paint = create_paint(&decoration.brush, cmd.rect);
+        paint_skia_decorations(canvas, &paragraph, origin, decoration, cmd.font_size, decoration_paint.paint());
+    }
+}
+
+/// Draws the image stretched over the rectangle, clipped to its rounded corners
+fn paint_image(canvas: &skia_safe::Canvas, media_id: MediaId, rect: &Rectangle) {
+    let binding = get_media_store();
+    let media_store = binding.read().expect("Failed to get media store");
+    let media = media_store.get_image(media_id);
+
+    let img_info = ImageInfo::new(
+        ISize::new(media.image.width() as i32, media.image.height() as i32),
+        ColorType::RGBA8888,
+        AlphaType::Premul,
+        None,
+    );
+    let data = Data::new_copy(media.image.to_vec().as_slice());
+    let Some(image) = images::raster_from_data(&img_info, data, img_info.width() as usize * 4) else {
+        log::warn!("Failed to create image for media: {:?}", media_id);
+        return;
+    };
+
+    let r = rect.rect();
+    let dst = skia_safe::Rect::from_xywh(r.x as f32, r.y as f32, r.width as f32, r.height as f32);
+
+    canvas.save();
+    if rect.is_rounded() {
+        let (tl, tr, br, bl) = rect.radius();
+        let radii = [tl, tr, br, bl].map(|r| Vector::new(r.x as f32, r.y as f32));
+        canvas.clip_rrect(skia_safe::RRect::new_rect_radii(dst, &radii), None, Some(true));
+    }
+    canvas.draw_image_rect(&image, None, dst, &skia_safe::Paint::default());
+    canvas.restore();
+}
+
+/// Draws the SVG as vectors, scaled to the rectangle. Returns false when skia cannot load the SVG, in which case
+/// it should be drawn as an image.
+fn paint_svg_vector(canvas: &skia_safe::Canvas, media_id: MediaId, rect: &Rectangle) -> bool {
+    let binding = get_media_store().read().expect("Failed to get media store");
+    let media = binding.get_svg(media_id);
+
+    // Our SVG tree is already parsed by usvg, which writes it back as a simplified SVG that skia can read
+    let source = media.svg.tree.to_string(&usvg::WriteOptions::default());
+    let Ok(mut dom) = skia_safe::svg::Dom::from_str(source, FontMgr::default()) else {
+        log::warn!("Failed to load svg as vectors: {:?}", media_id);
+        return false;
+    };
+
+    let size = media.svg.tree.size();
+    if size.width() <= 0.0 || size.height() <= 0.0 {
+        return true;
+    }
+    dom.set_container_size((size.width(), size.height()));
+
+    let r = rect.rect();
+    canvas.save();
+    canvas.translate((r.x as f32, r.y as f32));
+    canvas.scale((r.width as f32 / size.width(), r.height as f32 / size.height()));
+    dom.render(canvas);
+    canvas.restore();
+
+    true
+}
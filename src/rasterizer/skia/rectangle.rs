@@ -4,14 +4,13 @@ use crate::common::geo::Rect;
 use crate::painter::commands::border::BorderStyle;
 use crate::painter::commands::rectangle::Rectangle;
 use crate::rasterizer::skia::paint::{create_paint, Paint};
-use crate::tiler::Tile;
 
-pub(crate) fn do_paint_rectangle(canvas: &skia_safe::Canvas, _tile: &Tile, rect: &Rectangle) {
+pub(crate) fn do_paint_rectangle(canvas: &skia_safe::Canvas, rect: &Rectangle) {
     // Draw background (if any background brush is defined)
     match rect.background() {
         Some(brush) => {
             let shape = create_rect_shape(rect);
-            let mut skia_paint = create_paint(brush);
+            let mut skia_paint = create_paint(brush, rect.rect());
             skia_paint.paint_mut().set_style(skia_safe::PaintStyle::Fill);
 
             shape.draw(canvas, &skia_paint);
@@ -38,7 +37,7 @@ pub(crate) fn do_paint_rectangle(canvas: &skia_safe::Canvas, _tile: &Tile, rect:
 }
 
 fn draw_single_border(canvas: &skia_safe::Canvas, rect: &Rectangle, dashes: Vec<f64>) {
-    let mut skia_paint = create_paint(&rect.border().brush());
+    let mut skia_paint = create_paint(&rect.border().brush(), rect.rect());
     skia_paint.paint_mut().set_style(skia_safe::PaintStyle::Stroke);
     skia_paint.paint_mut().set_stroke_width(rect.border().width());
     if !dashes.is_empty() {
@@ -51,7 +50,7 @@ fn draw_single_border(canvas: &skia_safe::Canvas, rect: &Rectangle, dashes: Vec<
 }
 
 fn draw_double_border(canvas: &skia_safe::Canvas, rect: &Rectangle, dashes: Vec<f64>) {
-    let mut skia_paint = create_paint(&rect.border().brush());
+    let mut skia_paint = create_paint(&rect.border().brush(), rect.rect());
     skia_paint.paint_mut().set_stroke(true);
     skia_paint.paint_mut().set_stroke_width(rect.border().width());
     skia_paint.paint_mut().set_stroke_cap(skia_safe::PaintCap::Round);
@@ -86,7 +85,7 @@ fn draw_double_border(canvas: &skia_safe::Canvas, rect: &Rectangle, dashes: Vec<
         rect.rect().height - width as f64 - gap_size
     ));
     let shape = create_rect_shape(&inner_border_rect);
-    let skia_paint = create_paint(&rect.border().brush());
+    let skia_paint = create_paint(&rect.border().brush(), rect.rect());
     shape.draw(canvas, &skia_paint);
 }
 
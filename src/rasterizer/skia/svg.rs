@@ -1,7 +1,6 @@
 use crate::common::get_media_store;
 use crate::common::media::MediaId;
 use crate::painter::commands::rectangle::Rectangle;
-use crate::tiler::Tile;
 use resvg::usvg::Transform;
 use skia_safe::{images, AlphaType, ColorType, Data, ISize, ImageInfo};
 
@@ -14,7 +13,6 @@ use skia_safe::{images, AlphaType, ColorType, Data, ISize, ImageInfo};
 
 pub(crate) fn do_paint_svg(
     canvas: &skia_safe::Canvas,
-    _tile: &Tile,
     media_id: MediaId,
     rect: &Rectangle,
 ) {
@@ -1,22 +1,47 @@
 use std::fmt::Error;
+use skia_safe::BlendMode;
+use skia_safe::canvas::SaveLayerRec;
 use crate::painter::commands::text::Text;
-use crate::common::font::skia::get_skia_paragraph;
+use crate::common::font::skia::{get_cached_skia_paragraph, paint_skia_decorations};
 use crate::rasterizer::skia::paint::create_paint;
-use crate::tiler::Tile;
 
-pub fn do_paint_text(canvas: &skia_safe::Canvas, _tile: &Tile, cmd: &Text) -> Result<(), Error> {
-    let skia_paint = create_paint(&cmd.brush);
-    let paragraph = get_skia_paragraph(
-        cmd.text.as_str(),
-        cmd.font_family.as_str(),
-        cmd.font_size,
-        cmd.line_height,
-        cmd.rect.width,
-        cmd.alignment,
-        Some(skia_paint.paint()),
-    );
+pub fn do_paint_text(canvas: &skia_safe::Canvas, cmd: &Text) -> Result<(), Error> {
+    // This is the same paragraph the layouter has measured, so it is already shaped
+    let paragraph = get_cached_skia_paragraph(cmd.text.as_str(), &cmd.text_style());
 
-    paragraph.paint(canvas, (cmd.rect.x as f32, cmd.rect.y as f32));
+    // The cached paragraph has no paint of its own. We paint the glyphs into a separate layer, and fill that
+    // layer with our brush, keeping only the pixels where the glyphs are. The layer only covers the text (glyphs
+    // can overhang their box a bit), so the cost depends on the size of the text, not on the size of the canvas.
+    let margin = (cmd.font_size / 2.0) as f32;
+    let mut bounds = skia_safe::Rect::from_xywh(cmd.rect.x as f32, cmd.rect.y as f32, cmd.rect.width as f32, cmd.rect.height as f32)
+        .with_outset((margin, margin));
+
+    // When the text is spread over multiple tiles, only the part inside the clip is drawn. Skia skips the lines
+    // outside the clip rect.
+    canvas.save();
+    if let Some(clip) = cmd.clip {
+        let clip = skia_safe::Rect::from_xywh(clip.x as f32, clip.y as f32, clip.width as f32, clip.height as f32);
+        if !bounds.intersect(clip) {
+            canvas.restore();
+            return Ok(());
+        }
+        canvas.clip_rect(bounds, None, None);
+    }
+
+    canvas.save_layer(&SaveLayerRec::default().bounds(&bounds));
+    let origin = (cmd.rect.x as f32, cmd.rect.y as f32);
+    paragraph.paint(canvas, origin);
+
+    let mut skia_paint = create_paint(&cmd.brush, cmd.rect);
+    skia_paint.paint_mut().set_blend_mode(BlendMode::SrcIn);
+    canvas.draw_rect(bounds, skia_paint.paint());
+    canvas.restore();
+
+    if let Some(decoration) = &cmd.decoration {
+        let decoration_paint = create_paint(&decoration.brush, cmd.rect);
+        paint_skia_decorations(canvas, &paragraph, origin, decoration, cmd.font_size, decoration_paint.paint());
+    }
+    canvas.restore();
 
     Ok(())
-}
\ No newline at end of file
+}
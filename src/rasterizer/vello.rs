@@ -5,7 +5,6 @@ use vello::peniko::{Color, Mix};
 use vello::{AaConfig, Renderer, Scene};
 use vello::kurbo::{Affine, Rect, Vec2};
 use vello::wgpu::{Device, Queue, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
-use crate::common::geo::Dimension;
 use crate::rasterizer::Rasterable;
 use crate::common::texture::TextureId;
 use crate::common::get_texture_store;
@@ -36,11 +35,11 @@ impl Rasterable for VelloRasterizer<'_> {
     fn rasterize(&self, tile: &Tile) -> TextureId {
         let mut scene = Scene::new();
 
-        let tile_size = Dimension::new(tile.rect.width, tile.rect.height);
+        let texture_size = tile.texture_dimension();
 
         // Painting commands are in absolute coordinates, so we need to clip the scene to the tile's rect
         // so only things on this tile gets painted.
-        let clip = Rect::new(0.0, 0.0, tile_size.width, tile_size.height);
+        let clip = Rect::new(0.0, 0.0, texture_size.width, texture_size.height);
         scene.push_layer(Mix::Clip, 1.0, Affine::IDENTITY, &clip);
 
         // let shape = Rect::new(10.0, 10.0, 20.0, 20.0);
@@ -48,8 +47,9 @@ impl Rasterable for VelloRasterizer<'_> {
         // scene.fill(Fill::NonZero, Affine::IDENTITY, &brush, None, &shape);
 
         // Vello does not allow us to transform the scene so we can use relative coordinates (ie: 0,0 is the top left of the tile)
-        // So we need to render each element by adding the transform manually
-        let affine = Affine::translate(Vec2::new(-tile.rect.x, -tile.rect.y));
+        // So we need to render each element by adding the transform manually. The texture is in device pixels,
+        // so the CSS pixels of the paint commands are scaled as well.
+        let affine = Affine::scale(tile.scale) * Affine::translate(Vec2::new(-tile.rect.x, -tile.rect.y));
 
         for element in &tile.elements {
             for command in &element.paint_commands {
@@ -61,7 +61,7 @@ impl Rasterable for VelloRasterizer<'_> {
                         rectangle::do_paint_rectangle(&mut scene, &command, affine);
                     }
                     PaintCommand::Text(command) => {
-                        match do_paint_text(&mut scene, &command, tile_size, affine) {
+                        match do_paint_text(&mut scene, &command, texture_size, affine) {
                             Ok(_) => {}
                             Err(e) => {
                                 println!("Failed to paint text: {:?}", e);
@@ -74,12 +74,12 @@ impl Rasterable for VelloRasterizer<'_> {
 
         scene.pop_layer();
 
-        let texture = create_offscreen_texture(&self.device, tile_size.width as u32, tile_size.height as u32);
+        let texture = create_offscreen_texture(&self.device, texture_size.width as u32, texture_size.height as u32);
 
         let render_params = vello::RenderParams {
             base_color: Color::new([0.0, 0.0, 0.0, 0.0]),   // Transparent texture
-            width: tile.rect.width as u32,
-            height: tile.rect.height as u32,
+            width: texture_size.width as u32,
+            height: texture_size.height as u32,
             antialiasing_method: AaConfig::Msaa16,
         };
 
@@ -91,11 +91,11 @@ impl Rasterable for VelloRasterizer<'_> {
             &render_params,
         ).unwrap();
 
-        let texture_data = read_texture_to_image(&self.device, &self.queue, &texture, tile_size.width as u32, tile_size.height as u32, tile.id);
+        let texture_data = read_texture_to_image(&self.device, &self.queue, &texture, texture_size.width as u32, texture_size.height as u32, tile.id);
 
         let binding = get_texture_store();
         let mut texture_store = binding.write().expect("Failed to get texture store");
-        let texture_id = texture_store.add(tile_size.width as usize, tile_size.height as usize, texture_data.to_vec());
+        let texture_id = texture_store.add(texture_size.width as usize, texture_size.height as usize, texture_data.to_vec());
 
         texture_id
     }
@@ -1,15 +1,17 @@
-use vello::peniko::{Blob, Brush as VelloBrush};
-use vello::peniko::color::{AlphaColor, Rgba8};
+use vello::kurbo::Affine;
+use vello::peniko::{Blob, Brush as VelloBrush, Extend, Gradient as PenikoGradient};
+use vello::peniko::color::{AlphaColor, Rgba8, Srgb};
 use crate::common::geo::Rect;
 use crate::painter::commands::brush::Brush;
 use vello::peniko::Image as PenikoImage;
 use crate::common::get_media_store;
+use crate::painter::commands::color::Color;
+use crate::painter::commands::gradient::ResolvedKind;
 
-pub fn set_brush(brush: &Brush, _rect: Rect) -> VelloBrush {
+pub fn set_brush(brush: &Brush, rect: Rect) -> VelloBrush {
     match brush {
         Brush::Solid(color) => {
-            let c = Rgba8::from_u8_array([color.r8(), color.g8(), color.b8(), color.a8()]);
-            VelloBrush::Solid(AlphaColor::from(c))
+            VelloBrush::Solid(convert_color(color))
         }
         Brush::Image(media_id) => {
             let binding = get_media_store();
@@ -23,5 +25,53 @@ pub fn set_brush(brush: &Brush, _rect: Rect) -> VelloBrush {
                 media.image.height(),
             ))
         }
+        Brush::Gradient(gradient) => {
+            let resolved = gradient.resolve(rect);
+
+            let vello_gradient = match resolved.kind {
+                ResolvedKind::Linear { start, end } => {
+                    PenikoGradient::new_linear((start.x, start.y), (end.x, end.y))
+                }
+                ResolvedKind::Radial { center, start_radius, end_radius, .. } => {
+                    PenikoGradient::new_two_point_radial((center.x, center.y), start_radius as f32, (center.x, center.y), end_radius as f32)
+                }
+                ResolvedKind::Conic { center, start_angle, end_angle, .. } => {
+                    PenikoGradient::new_sweep((center.x, center.y), start_angle as f32, end_angle as f32)
+                }
+            };
+
+            let stops: Vec<(f32, AlphaColor<Srgb>)> = resolved.stops.iter()
+                .map(|(offset, color)| (*offset, convert_color(color)))
+                .collect();
+            let extend = if resolved.repeating { Extend::Repeat } else { Extend::Pad };
+
+            VelloBrush::Gradient(vello_gradient.with_extend(extend).with_stops(stops.as_slice()))
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Returns the transformation that must be applied to the brush. Vello only has circular radial gradients, so an
+/// elliptical gradient is a circle that is scaled vertically around its center. Sweep gradients always start at the
+/// right, so conic gradients are rotated to their start angle.
+pub fn brush_transform(brush: &Brush, rect: Rect) -> Option<Affine> {
+    let Brush::Gradient(gradient) = brush else {
+        return None;
+    };
+
+    match gradient.resolve(rect).kind {
+        ResolvedKind::Radial { center, scale_y, .. } if scale_y != 1.0 => Some(
+            Affine::translate((center.x, center.y))
+                * Affine::scale_non_uniform(1.0, scale_y)
+                * Affine::translate((-center.x, -center.y))
+        ),
+        ResolvedKind::Conic { center, rotation, .. } if rotation != 0.0 => {
+            Some(Affine::rotate_about(rotation, (center.x, center.y).into()))
+        }
+        _ => None,
+    }
+}
+
+fn convert_color(color: &Color) -> AlphaColor<Srgb> {
+    let c = Rgba8::from_u8_array([color.r8(), color.g8(), color.b8(), color.a8()]);
+    AlphaColor::from(c)
+}
@@ -3,7 +3,7 @@ use vello::kurbo::{Affine, PathEl, Point, Rect, RoundedRect, Shape};
 use vello::peniko::{Fill};
 use crate::painter::commands::border::BorderStyle;
 use crate::painter::commands::rectangle::Rectangle;
-use crate::rasterizer::vello::brush::set_brush;
+use crate::rasterizer::vello::brush::{brush_transform, set_brush};
 
 pub(crate) fn do_paint_rectangle(scene: &mut vello::Scene, rect: &Rectangle, affine: Affine) {
     // Draw background (if any background brush is defined)
@@ -16,7 +16,7 @@ pub(crate) fn do_paint_rectangle(scene: &mut vello::Scene, rect: &Rectangle, aff
                 Fill::NonZero,
                 affine,
                 &vello_brush,
-                None,
+                brush_transform(brush, rect.rect()),
                 &vello_rect,
             );
         }
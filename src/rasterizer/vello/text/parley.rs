@@ -1,23 +1,47 @@
 use std::fmt::Error;
 use vello::Scene;
-use crate::painter::commands::text::Text;
+use crate::common::document::style::TextDecorationStyle;
+use crate::painter::commands::text::{dash_pattern, decoration_lines, wavy_points, Text, TextDecoration, TextLineMetrics};
 use crate::tiler::Tile;
 use crate::common::font::parley::get_parley_layout;
 use parley::layout::{GlyphRun, PositionedLayoutItem};
-use vello::kurbo::Affine;
+use vello::kurbo::{Affine, BezPath, Line, Stroke};
 use vello::peniko::Fill;
 use crate::common::geo::{Dimension, Rect};
 use crate::painter::commands::brush::Brush;
 use crate::rasterizer::vello::brush::set_brush;
 
-pub fn do_paint_text(scene: &mut Scene,  cmd: &Text, _tile_size: Dimension, affine: Affine) -> Result<(), Error> {
-    let layout = get_parley_layout(cmd.text.as_str(), cmd.font_family.as_str(), cmd.font_size, cmd.line_height, cmd.rect.width, cmd.alignment);
+pub fn do_paint_text(scene: &mut Scene,  cmd: &Text, _texture_size: Dimension, affine: Affine) -> Result<(), Error> {
+    let layout = get_parley_layout(cmd.text.as_str(), &cmd.text_style());
+
+    // Glyphs can overhang their run a bit, so runs just outside the clip are drawn as well
+    let margin = cmd.font_size / 2.0;
 
     for line in layout.lines() {
+        if let Some(clip) = cmd.clip {
+            let metrics = line.metrics();
+            let top = cmd.rect.y + metrics.min_coord as f64 - margin;
+            let bottom = cmd.rect.y + metrics.max_coord as f64 + margin;
+            if bottom < clip.y || top > clip.y + clip.height {
+                continue;
+            }
+        }
+
         for item in line.items() {
             match item {
                 PositionedLayoutItem::GlyphRun(glyph_run) => {
-                    render_glyph_run(scene, glyph_run, &cmd.brush, &cmd.rect, affine);
+                    if let Some(clip) = cmd.clip {
+                        let left = cmd.rect.x + glyph_run.offset() as f64 - margin;
+                        let right = cmd.rect.x + (glyph_run.offset() + glyph_run.advance()) as f64 + margin;
+                        if right < clip.x || left > clip.x + clip.width {
+                            continue;
+                        }
+                    }
+
+                    render_glyph_run(scene, glyph_run.clone(), &cmd.brush, &cmd.rect, affine);
+                    if let Some(decoration) = &cmd.decoration {
+                        render_decoration(scene, &glyph_run, decoration, &cmd.rect, affine);
+                    }
                 }
                 PositionedLayoutItem::InlineBox(_inline_box) => {
                     todo!("Inline boxes are not supported yet");
@@ -32,8 +56,6 @@ pub fn do_paint_text(scene: &mut Scene,  cmd: &Text, _tile_size: Dimension, affi
 fn render_glyph_run(scene: &mut Scene, glyph_run: GlyphRun<[u8;4]>, brush: &Brush, rect: &Rect, affine: Affine) {
     let vello_brush = set_brush(brush, *rect);
 
-    // @TODO: we need font decorations like underline, strike through, maybe sub sup?
-
     let mut x = glyph_run.offset() + rect.x as f32;
     let y = glyph_run.baseline() + rect.y as f32;
     let run = glyph_run.run();
@@ -65,6 +87,45 @@ fn render_glyph_run(scene: &mut Scene, glyph_run: GlyphRun<[u8;4]>, brush: &Brus
                 }
             })
         );
+}
+
+fn render_decoration(scene: &mut Scene, glyph_run: &GlyphRun<[u8; 4]>, decoration: &TextDecoration, rect: &Rect, affine: Affine) {
+    let vello_brush = set_brush(&decoration.brush, *rect);
 
-        // @TODO: Do strike through
+    // Parley offsets point upwards from the baseline, ours point downwards
+    let run_metrics = glyph_run.run().metrics();
+    let metrics = TextLineMetrics {
+        x: rect.x + glyph_run.offset() as f64,
+        width: glyph_run.advance() as f64,
+        baseline: rect.y + glyph_run.baseline() as f64,
+        ascent: run_metrics.ascent as f64,
+        underline_offset: -run_metrics.underline_offset as f64,
+        underline_thickness: run_metrics.underline_size as f64,
+        strikethrough_offset: -run_metrics.strikethrough_offset as f64,
+        strikethrough_thickness: run_metrics.strikethrough_size as f64,
+    };
+
+    for line in decoration_lines(decoration, &metrics) {
+        let stroke = Stroke::new(line.thickness);
+
+        if decoration.style == TextDecorationStyle::Wavy {
+            let mut path = BezPath::new();
+            for (i, (x, y)) in wavy_points(&line).into_iter().enumerate() {
+                if i == 0 {
+                    path.move_to((x, y));
+                } else {
+                    path.line_to((x, y));
+                }
+            }
+            scene.stroke(&stroke, affine, &vello_brush, None, &path);
+            continue;
+        }
+
+        let stroke = match dash_pattern(decoration.style, line.thickness) {
+            Some(pattern) => stroke.with_dashes(0.0, pattern),
+            None => stroke,
+        };
+        let shape = Line::new((line.x, line.y), (line.x + line.width, line.y));
+        scene.stroke(&stroke, affine, &vello_brush, None, &shape);
+    }
 }
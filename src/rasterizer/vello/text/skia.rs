@@ -1,28 +1,38 @@
 use std::fmt::Error;
-use skia_safe::Vector;
+use skia_safe::{Color4f, Matrix, Paint};
 use vello::kurbo::Affine;
 use vello::peniko::Blob;
 use vello::Scene;
 use crate::painter::commands::text::Text;
-use crate::common::font::skia::get_skia_paragraph;
+use crate::common::font::skia::{get_cached_skia_paragraph, paint_skia_decorations};
+use crate::painter::commands::brush::Brush;
 use crate::common::geo::Dimension;
 
-pub fn do_paint_text(scene: &mut Scene, cmd: &Text, tile_size: Dimension, affine: Affine) -> Result<(), Error> {
-    let paragraph = get_skia_paragraph(cmd.text.as_str(), cmd.font_family.as_str(), cmd.font_size, cmd.line_height, cmd.rect.width, cmd.alignment, None);
+pub fn do_paint_text(scene: &mut Scene, cmd: &Text, texture_size: Dimension, affine: Affine) -> Result<(), Error> {
+    let paragraph = get_cached_skia_paragraph(cmd.text.as_str(), &cmd.text_style());
 
     // Create a (skia) surface to render onto
     // @TODO: THIS IS CPU, NOT GPU!
-    let mut surface = skia_safe::surfaces::raster_n32_premul((tile_size.width as i32, tile_size.height as i32)).unwrap();
-    let mut canvas = surface.canvas();
+    let mut surface = skia_safe::surfaces::raster_n32_premul((texture_size.width as i32, texture_size.height as i32)).unwrap();
+    let canvas = surface.canvas();
 
-    // Clip and translate the canvas so only our tile is painted and 0.0 coordinate is the top left of the tile
-    canvas.clip_rect(skia_safe::Rect::new(0.0,0.0, tile_size.width as f32, tile_size.height as f32), None, None);
-    let transform = affine.translation();
-    canvas.translate(Vector::new(transform.x as f32, transform.y as f32));
+    // Clip to the texture, and use the same transform as the other commands, so 0.0 coordinate is the top left
+    // of the tile and the CSS pixels are scaled to the device pixels of the texture.
+    canvas.clip_rect(skia_safe::Rect::new(0.0,0.0, texture_size.width as f32, texture_size.height as f32), None, None);
+    let [a, b, c, d, e, f] = affine.as_coeffs();
+    canvas.concat(&Matrix::new_all(a as f32, c as f32, e as f32, b as f32, d as f32, f as f32, 0.0, 0.0, 1.0));
 
     canvas.clear(skia_safe::Color::TRANSPARENT);
-    // paragraph.paint(&mut canvas, (-(transform.x - cmd.rect.x) as f32, -(transform.y - cmd.rect.y) as f32));
-    paragraph.paint(&mut canvas, (cmd.rect.x as f32, cmd.rect.y as f32));
+    paragraph.paint(canvas, (cmd.rect.x as f32, cmd.rect.y as f32));
+
+    if let Some(decoration) = &cmd.decoration {
+        // Only solid colors are supported for decorations here. Note the bgra order of the skia surface.
+        let paint = match &decoration.brush {
+            Brush::Solid(c) => Paint::new(Color4f::new(c.b(), c.g(), c.r(), c.a()), None),
+            _ => Paint::default(),
+        };
+        paint_skia_decorations(&canvas, &paragraph, (cmd.rect.x as f32, cmd.rect.y as f32), decoration, cmd.font_size, &paint);
+    }
 
     // let img = surface.image_snapshot();
     // let data = img.encode_to_data(skia_safe::EncodedImageFormat::PNG).unwrap();
@@ -35,7 +45,7 @@ pub fn do_paint_text(scene: &mut Scene, cmd: &Text, tile_size: Dimension, affine
     let peek = canvas.peek_pixels().unwrap();
     let pixels = peek.bytes().unwrap().to_vec();
     let blob = Blob::from(pixels);
-    let mut img = vello::peniko::Image::new(blob, vello::peniko::ImageFormat::Rgba8, tile_size.width as u32, tile_size.height as u32);
+    let mut img = vello::peniko::Image::new(blob, vello::peniko::ImageFormat::Rgba8, texture_size.width as u32, texture_size.height as u32);
     img.quality = vello::peniko::ImageQuality::High;
     scene.draw_image(&img, Affine::IDENTITY);
 
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+pub mod scheduler;
+
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock};
 use rstar::AABB;
 use rstar::primitives::GeomWithData;
 use crate::common::geo::{Coordinate, Dimension, Rect};
+use crate::common::get_texture_store;
+use crate::common::hash::{hash_from_string, Sha256Hash};
 use crate::layering::layer::{LayerId, LayerList};
 use crate::layouter::{LayoutElementId, LayoutElementNode};
 use crate::painter::commands::PaintCommand;
@@ -114,6 +118,8 @@ pub enum TileState {
     Dirty,
     /// Tile texture cannot be rendered by this backend
     Unrenderable,
+    /// Tile has nothing visible to paint, so it has no texture and is skipped by the compositor
+    Empty,
 }
 
 /// Single tile in the tile list. It contains a list of elements that are laid out in the tile and
@@ -134,30 +140,102 @@ pub struct Tile {
     pub state: TileState,
     // Position and dimension of the tile in the layer
     pub rect: Rect,
+    /// Zoom level of the tile. The tile covers default_tile_dimension / 2^level CSS pixels.
+    pub level: i32,
+    /// Number of device pixels per CSS pixel the texture of this tile is rendered at
+    pub scale: f64,
 }
 
+impl Tile {
+    /// Dimension of the texture of this tile in device pixels
+    pub fn texture_dimension(&self) -> Dimension {
+        Dimension::new((self.rect.width * self.scale).ceil(), (self.rect.height * self.scale).ceil())
+    }
+
+    /// Position of the tile in the layer in device pixels
+    pub fn device_position(&self) -> Coordinate {
+        Coordinate::new((self.rect.x * self.scale).round(), (self.rect.y * self.scale).round())
+    }
+
+    /// Returns true when none of the paint commands of the tile paint anything visible
+    pub fn is_empty(&self) -> bool {
+        self.elements.iter()
+            .flat_map(|element| &element.paint_commands)
+            .all(|command| command.is_invisible())
+    }
+
+    /// Hash of the texture size and the paint commands relative to the tile. Tiles with the same hash render to the
+    /// same texture.
+    pub fn content_hash(&self) -> Sha256Hash {
+        let dimension = self.texture_dimension();
+        let mut content = format!("{}x{}@{}", dimension.width, dimension.height, self.scale);
+
+        let offset = Coordinate::new(-self.rect.x, -self.rect.y);
+        for element in &self.elements {
+            for command in &element.paint_commands {
+                content.push_str(&format!("\n{:?}", command.shift(offset)));
+            }
+        }
+
+        hash_from_string(&content)
+    }
+}
+
+/// Element in a layer together with its position in the painting order of that layer
+type PaintedElement = (usize, LayoutElementId);
+
 /// Each layer has a list of tiles. Each tile has a list of elements that are laid out in that tile.
 #[derive(Debug, Clone)]
 pub struct TileLayer {
     // Layer ID of this layer
     pub layer_id: LayerId,
-    // List of tiles inside this layer
+    // List of tiles inside this layer (of all zoom levels). Only tiles with content are created, when they are needed.
     pub tiles: Vec<TileId>,
-    /// R* tree for fast spatial queries of tiles inside this layer
+    /// Tile grid of each zoom level
+    levels: HashMap<i32, TileLevel>,
+    /// R* tree of the elements in this layer with their position in the painting order of the layer
+    content: rstar::RTree<GeomWithData<rstar::primitives::Rectangle<[f64; 2]>, PaintedElement>>,
+}
+
+/// The tiles of a layer at a single zoom level
+#[derive(Debug, Clone, Default)]
+struct TileLevel {
+    /// R* tree for fast spatial queries of tiles inside this level
     rstar_tree: rstar::RTree<GeomWithData<rstar::primitives::Rectangle<[f64; 2]>, TileId>>,
+    /// Tiles by their grid cell (column, row)
+    cells: HashMap<(u32, u32), TileId>,
+    /// Grid cells that have been checked for content, whether they got a tile or not
+    visited: HashSet<(u32, u32)>,
 }
 
 impl TileLayer {
-    // Find all tile ids in this layer that intersects with the given rect
-    pub fn intersects_with(&self, rect: Rect) -> Vec<TileId> {
-        self.rstar_tree
-            .locate_in_envelope_intersecting(&AABB::from_corners(
-                [rect.x, rect.y],
-                [rect.x + rect.width, rect.y + rect.height]
-            ))
+    // Find all tile ids of the zoom level in this layer that intersects with the given rect
+    pub fn intersects_with(&self, level: i32, rect: Rect) -> Vec<TileId> {
+        let Some(tile_level) = self.levels.get(&level) else {
+            return vec![];
+        };
+
+        tile_level.rstar_tree
+            .locate_in_envelope_intersecting(&to_aabb(rect))
             .map(|x| x.data)
             .collect()
     }
+
+    // Find all tile ids of all zoom levels in this layer that intersects with the given rect
+    pub fn intersects_with_any_level(&self, rect: Rect) -> Vec<TileId> {
+        self.levels.values()
+            .flat_map(|tile_level| tile_level.rstar_tree.locate_in_envelope_intersecting(&to_aabb(rect)))
+            .map(|x| x.data)
+            .collect()
+    }
+}
+
+fn to_aabb(rect: Rect) -> AABB<[f64; 2]> {
+    AABB::from_corners([rect.x, rect.y], [rect.x + rect.width, rect.y + rect.height])
+}
+
+fn to_rectangle(rect: Rect) -> rstar::primitives::Rectangle<[f64; 2]> {
+    rstar::primitives::Rectangle::from_corners([rect.x, rect.y], [rect.x + rect.width, rect.y + rect.height])
 }
 
 /// Main list of tiles per layer.
@@ -171,10 +249,21 @@ pub struct TileList {
 
     /// Arena of layout nodes
     pub arena : HashMap<TileId, Tile>,
+    /// Tiles (of all zoom levels) that show (part of) each element
+    element_tiles: HashMap<LayoutElementId, Vec<TileId>>,
     /// Next node ID
     next_node_id: Arc<RwLock<TileId>>,
 
+    /// Dimension of the tiles in CSS pixels
     pub default_tile_dimension: Dimension,
+    /// Number of device pixels per CSS pixel. Layout is done in CSS pixels, but the tiles are rendered at
+    /// device resolution.
+    pub device_pixel_ratio: f64,
+    /// Current zoom factor. Tiles are rendered at the zoom level (a power of two) that is at least as sharp.
+    pub zoom: f64,
+    /// Zoom level that is shown (scaled) until the tiles of the current zoom level are rendered. None when there is
+    /// nothing to fall back to.
+    fallback_level: Option<i32>,
 }
 
 impl Debug for TileList {
@@ -184,23 +273,44 @@ impl Debug for TileList {
             .field("arena", &self.arena)
             .field("next_node_id", &self.next_node_id)
             .field("default_tile_dimension", &self.default_tile_dimension)
+            .field("device_pixel_ratio", &self.device_pixel_ratio)
+            .field("zoom", &self.zoom)
             .finish()
     }
 }
 
 impl TileList {
+    /// Returns the tiles (of all zoom levels) that show (part of) the element
     pub fn get_tiles_for_element(&self, element_id: LayoutElementId) -> Vec<TileId> {
-        let mut matching_tiles = vec![];
+        self.element_tiles.get(&element_id).cloned().unwrap_or_default()
+    }
 
-        for tile in self.arena.values() {
-            for element in &tile.elements {
-                if element.id == element_id {
-                    matching_tiles.push(tile.id);
-                }
+    /// Returns the topmost element at the given coordinates, or None when there is no element
+    pub fn find_element_at(&self, x: f64, y: f64) -> Option<LayoutElementId> {
+        // Layers are checked from top to bottom, and the elements inside a layer from the last painted one
+        for layer_id in self.layer_list.ordered_layer_ids().iter().rev() {
+            let Some(tile_layer) = self.tiles.get(layer_id) else {
+                continue;
+            };
+
+            let found = tile_layer.content
+                .locate_all_at_point(&[x, y])
+                .map(|item| item.data)
+                .filter(|(_, element_id)| {
+                    // The right and bottom edges are not part of the element
+                    self.layer_list.layout_tree.get_node_by_id(*element_id).is_some_and(|element| {
+                        let margin_box = element.box_model.margin_box;
+                        x < margin_box.x + margin_box.width && y < margin_box.y + margin_box.height
+                    })
+                })
+                .max_by_key(|(paint_index, _)| *paint_index);
+
+            if let Some((_, element_id)) = found {
+                return Some(element_id);
             }
         }
 
-        matching_tiles
+        None
     }
 
     pub fn invalidate_all(&mut self) {
@@ -214,6 +324,177 @@ impl TileList {
         tile.state = TileState::Dirty;
     }
 
+    /// Marks the tiles (of all zoom levels) of the layer that overlap with the given rect as dirty, so only these
+    /// are painted and rasterized again.
+    pub fn invalidate_rect(&mut self, layer_id: LayerId, rect: Rect) {
+        let Some(tile_layer) = self.tiles.get(&layer_id) else {
+            return;
+        };
+
+        for tile_id in tile_layer.intersects_with_any_level(rect) {
+            let Some(tile) = self.arena.get_mut(&tile_id) else {
+                continue;
+            };
+            // Tiles that only touch the rect are not affected
+            if tile.rect.intersection(&rect).is_some() {
+                tile.state = TileState::Dirty;
+            }
+        }
+    }
+
+    /// Marks the tiles that show (part of) the element as dirty
+    pub fn invalidate_element(&mut self, element_id: LayoutElementId) {
+        let Some(tile_ids) = self.element_tiles.get(&element_id) else {
+            return;
+        };
+
+        for tile_id in tile_ids {
+            if let Some(tile) = self.arena.get_mut(tile_id) {
+                tile.state = TileState::Dirty;
+            }
+        }
+    }
+
+    /// Sets the device pixel ratio the tiles are rendered at. All tiles are invalidated when it changes, as
+    /// their textures have the wrong resolution.
+    pub fn set_device_pixel_ratio(&mut self, device_pixel_ratio: f64) {
+        if self.device_pixel_ratio == device_pixel_ratio {
+            return;
+        }
+
+        self.device_pixel_ratio = device_pixel_ratio;
+        for tile in self.arena.values_mut() {
+            tile.scale = device_pixel_ratio * level_scale(tile.level);
+            tile.state = TileState::Dirty;
+        }
+    }
+
+    /// Sets the zoom factor. The tiles of the previous zoom level are kept as fallback, so the compositor can show
+    /// them (scaled) until the tiles of the new zoom level are rendered. Tiles of all other zoom levels are removed.
+    pub fn set_zoom(&mut self, zoom: f64) {
+        let previous_level = self.zoom_level();
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+
+        let level = self.zoom_level();
+        if level == previous_level {
+            return;
+        }
+
+        // When nothing was rendered at the previous level yet (zooming quickly), we keep the older fallback
+        let has_textures = self.arena.values().any(|tile| tile.level == previous_level && tile.texture_id.is_some());
+        if has_textures || self.fallback_level.is_none() {
+            self.fallback_level = Some(previous_level);
+        }
+
+        let fallback_level = self.fallback_level;
+        self.evict_levels(|l| l != level && Some(l) != fallback_level);
+    }
+
+    /// Removes the tiles of the fallback zoom level once the visible tiles of the current zoom level are rendered
+    /// in all given layers, so the compositor no longer needs them.
+    pub fn evict_fallback_level(&mut self, layer_ids: &[LayerId], viewport: Rect) {
+        let Some(fallback_level) = self.fallback_level else {
+            return;
+        };
+
+        let level = self.zoom_level();
+        let rendered = layer_ids.iter().all(|layer_id| {
+            let Some(tile_layer) = self.tiles.get(layer_id) else {
+                return true;
+            };
+            match self.layer_list.layer_properties(*layer_id).visible_rect(viewport) {
+                Some(visible) => self.gaps(tile_layer, level, visible).is_empty(),
+                None => true,
+            }
+        });
+        if !rendered {
+            return;
+        }
+
+        self.fallback_level = None;
+        self.evict_levels(|l| l == fallback_level);
+    }
+
+    /// Removes the tiles of the zoom levels for which `evict` returns true, together with their textures
+    fn evict_levels(&mut self, evict: impl Fn(i32) -> bool) {
+        let evicted: Vec<Tile> = self.arena.values().filter(|tile| evict(tile.level)).cloned().collect();
+        if evicted.is_empty() {
+            return;
+        }
+
+        for tile_layer in self.tiles.values_mut() {
+            tile_layer.levels.retain(|level, _| !evict(*level));
+        }
+        for tile in &evicted {
+            self.arena.remove(&tile.id);
+        }
+        for tile_layer in self.tiles.values_mut() {
+            tile_layer.tiles.retain(|tile_id| self.arena.contains_key(tile_id));
+        }
+        for tile_ids in self.element_tiles.values_mut() {
+            tile_ids.retain(|tile_id| self.arena.contains_key(tile_id));
+        }
+        self.element_tiles.retain(|_, tile_ids| !tile_ids.is_empty());
+
+        // Tiles with the same content share their texture, so textures that are still used by other tiles stay
+        let in_use: HashSet<TextureId> = self.arena.values().filter_map(|tile| tile.texture_id).collect();
+        let mut texture_store = get_texture_store().write().expect("Failed to get texture store");
+        for texture_id in evicted.iter().filter_map(|tile| tile.texture_id) {
+            if !in_use.contains(&texture_id) {
+                texture_store.remove(texture_id);
+            }
+        }
+    }
+
+    /// Returns the zoom level tiles are currently rendered at
+    pub fn zoom_level(&self) -> i32 {
+        zoom_level(self.zoom)
+    }
+
+    /// Rasterizes the painted tile with the given function, and marks it clean. Tiles that have nothing visible to
+    /// paint become empty without a texture, and tiles with the same content as an earlier rasterized tile share its
+    /// texture, so neither is rasterized. The previous texture of the tile is removed when no other tile uses it.
+    pub fn rasterize_tile<F: FnOnce(&Tile) -> TextureId>(&mut self, tile_id: TileId, rasterize: F) {
+        let Some(tile) = self.arena.get_mut(&tile_id) else {
+            log::warn!("Tile not found: {:?}", tile_id);
+            return;
+        };
+        let previous = tile.texture_id;
+
+        if tile.is_empty() {
+            tile.texture_id = None;
+            tile.state = TileState::Empty;
+        } else {
+            let hash = tile.content_hash();
+            let binding = get_texture_store();
+            let cached = binding.read().expect("Failed to get texture store").find_by_hash(&hash);
+            let texture_id = match cached {
+                Some(texture_id) => texture_id,
+                None => {
+                    let texture_id = rasterize(tile);
+                    binding.write().expect("Failed to get texture store").cache(hash, texture_id);
+                    texture_id
+                }
+            };
+
+            tile.texture_id = Some(texture_id);
+            tile.state = TileState::Clean;
+        }
+
+        if let Some(texture_id) = previous {
+            self.release_texture(texture_id);
+        }
+    }
+
+    /// Removes the texture from the texture store, unless it is still used by a tile
+    fn release_texture(&self, texture_id: TextureId) {
+        if self.arena.values().any(|tile| tile.texture_id == Some(texture_id)) {
+            return;
+        }
+
+        get_texture_store().write().expect("Failed to get texture store").remove(texture_id);
+    }
+
     pub fn get_tile_mut(&mut self, tile_id: TileId) -> Option<&mut Tile> {
         self.arena.get_mut(&tile_id)
     }
@@ -223,14 +504,124 @@ impl TileList {
         self.arena.get(&tile_id)
     }
 
-    /// Return all the tiles for the specific layer that intersects with the given viewport
+    /// Return all the tiles of the current zoom level for the specific layer that intersects with the given viewport
     pub fn get_intersecting_tiles(&self, layer_id: LayerId, viewport: Rect) -> Vec<TileId> {
         let Some(tile_layer) = self.tiles.get(&layer_id) else {
             return vec![];
         };
 
-        tile_layer.intersects_with(viewport)
+        tile_layer.intersects_with(self.zoom_level(), viewport)
     }
+
+    /// Returns the tiles of the layer that are visible in the viewport after the layer is transformed and clipped
+    /// by the compositor.
+    pub fn get_visible_tiles(&self, layer_id: LayerId, viewport: Rect) -> Vec<TileId> {
+        let properties = self.layer_list.layer_properties(layer_id);
+        match properties.visible_rect(viewport) {
+            Some(rect) => self.get_intersecting_tiles(layer_id, rect),
+            None => vec![],
+        }
+    }
+
+    /// Returns the tiles the compositor draws for the layer, in drawing order. Parts of the viewport that do not
+    /// have a rendered tile at the current zoom level yet are covered by rendered tiles of other zoom levels,
+    /// which the compositor scales. These are drawn first, with the zoom level closest to the current one last.
+    pub fn get_composite_tiles(&self, layer_id: LayerId, viewport: Rect) -> Vec<TileId> {
+        let Some(tile_layer) = self.tiles.get(&layer_id) else {
+            return vec![];
+        };
+        let Some(visible) = self.layer_list.layer_properties(layer_id).visible_rect(viewport) else {
+            return vec![];
+        };
+
+        let has_texture = |tile_id: &TileId| self.arena.get(tile_id).is_some_and(|tile| tile.texture_id.is_some());
+
+        let level = self.zoom_level();
+        let gaps = self.gaps(tile_layer, level, visible);
+
+        let mut other_levels: Vec<i32> = tile_layer.levels.keys().copied().filter(|l| *l != level).collect();
+        other_levels.sort_by_key(|l| std::cmp::Reverse((l - level).abs()));
+
+        let mut tile_ids: Vec<TileId> = Vec::new();
+        for other_level in other_levels {
+            for gap in &gaps {
+                for tile_id in tile_layer.intersects_with(other_level, *gap) {
+                    // Tiles that only touch the gap don't cover any of it
+                    let covers = self.arena.get(&tile_id).is_some_and(|tile| tile.rect.intersection(gap).is_some());
+                    if covers && has_texture(&tile_id) && !tile_ids.contains(&tile_id) {
+                        tile_ids.push(tile_id);
+                    }
+                }
+            }
+        }
+
+        tile_ids.extend(tile_layer.intersects_with(level, visible).into_iter().filter(has_texture));
+        tile_ids
+    }
+
+    /// Returns the rects of the grid cells of the zoom level inside the visible rect that don't have a rendered tile.
+    /// Cells that are known to be empty don't need anything.
+    fn gaps(&self, tile_layer: &TileLayer, level: i32, visible: Rect) -> Vec<Rect> {
+        let is_rendered = |tile_id: &TileId| self.arena.get(tile_id)
+            .is_some_and(|tile| tile.texture_id.is_some() || tile.state == TileState::Empty);
+
+        let tile_level = tile_layer.levels.get(&level);
+        self.cells(level, visible)
+            .into_iter()
+            .filter(|(cell, _)| match tile_level {
+                Some(tile_level) => match tile_level.cells.get(cell) {
+                    Some(tile_id) => !is_rendered(tile_id),
+                    None => !tile_level.visited.contains(cell),
+                },
+                None => true,
+            })
+            .map(|(_, rect)| rect)
+            .collect()
+    }
+
+    /// Returns the grid cells (column, row) of the zoom level with their rect that intersect with the given rect
+    fn cells(&self, level: i32, rect: Rect) -> Vec<((u32, u32), Rect)> {
+        let bounds = Rect::new(
+            0.0,
+            0.0,
+            self.layer_list.layout_tree.root_dimension.width,
+            self.layer_list.layout_tree.root_dimension.height,
+        );
+        let Some(rect) = rect.intersection(&bounds) else {
+            return vec![];
+        };
+
+        let tile_width = self.default_tile_dimension.width / level_scale(level);
+        let tile_height = self.default_tile_dimension.height / level_scale(level);
+        let first_col = (rect.x / tile_width).floor() as u32;
+        let first_row = (rect.y / tile_height).floor() as u32;
+        let last_col = ((rect.x + rect.width) / tile_width).ceil() as u32;
+        let last_row = ((rect.y + rect.height) / tile_height).ceil() as u32;
+
+        let mut cells = Vec::new();
+        for row in first_row..last_row {
+            for col in first_col..last_col {
+                let cell_rect = Rect::new(col as f64 * tile_width, row as f64 * tile_height, tile_width, tile_height);
+                cells.push(((col, row), cell_rect));
+            }
+        }
+        cells
+    }
+}
+
+/// Zoom factors outside this range are clamped
+pub const MIN_ZOOM: f64 = 0.25;
+pub const MAX_ZOOM: f64 = 8.0;
+
+/// Returns the zoom level for the zoom factor: the smallest power of two that is at least the zoom factor
+fn zoom_level(zoom: f64) -> i32 {
+    // The epsilon keeps zoom factors that are (nearly) a power of two on their own level
+    (zoom.log2() - 1e-6).ceil() as i32
+}
+
+/// Returns the number of tile pixels per CSS pixel of the zoom level, relative to the device pixel ratio
+fn level_scale(level: i32) -> f64 {
+    2f64.powi(level)
 }
 
 impl TileList {
@@ -239,114 +630,141 @@ impl TileList {
             layer_list: Arc::new(layer_list),
             tiles: HashMap::new(),
             arena: HashMap::new(),
+            element_tiles: HashMap::new(),
             next_node_id: Arc::new(RwLock::new(TileId::new(0))),
             default_tile_dimension: dimension,
+            device_pixel_ratio: 1.0,
+            zoom: 1.0,
+            fallback_level: None,
         }
     }
 
-    // @TODO: Optimize: remove all tiles that are empty
+    /// Prepares the tiling of all layers. No tiles are created yet: these are created on demand by
+    /// `ensure_tiles`, and only where a layer has content, so empty parts of a layer never allocate a tile.
     pub fn generate(&mut self) {
-        let rows = (self.layer_list.layout_tree.root_dimension.height / self.default_tile_dimension.height).ceil() as usize;
-        let cols = (self.layer_list.layout_tree.root_dimension.width / self.default_tile_dimension.width).ceil() as usize;
-
-        let mut layer_list = self.layer_list.layers.read().unwrap();
-
-        // iterate each layer
-        for layer_id in self.layer_list.layer_ids.read().unwrap().iter() {
-            // Each layer gets a list of tiles (rows * cols). They are stored in the arena.
-            let mut tile_ids = Vec::with_capacity(rows * cols);
-
-            // Generate tiles for this layer
-            for y in 0..rows {
-                for x in 0..cols {
-
-                    let tile_id = self.next_node_id();
-                    let tile = Tile {
-                        id: tile_id,
-                        layer_id: *layer_id,
-                        state: TileState::Dirty,
-                        elements: Vec::new(),
-                        texture_id: None,
-                        rect: Rect::new(
-                            x as f64 * self.default_tile_dimension.width,
-                            y as f64 * self.default_tile_dimension.height,
-                            self.default_tile_dimension.width,
-                            self.default_tile_dimension.height,
-                        ),
-                    };
-
-                    self.arena.insert(tile_id, tile);
-                    tile_ids.push(tile_id);
-                }
+        self.tiles.clear();
+        self.arena.clear();
+        self.element_tiles.clear();
+        self.fallback_level = None;
+
+        let layers = self.layer_list.layers.read().expect("Failed to lock layers");
+        for layer_id in self.layer_list.layer_ids.read().expect("Failed to lock layer IDs").iter() {
+            let Some(layer) = layers.get(layer_id) else {
+                continue;
+            };
+
+            let mut content = Vec::with_capacity(layer.elements.len());
+            for (paint_index, &element_id) in layer.elements.iter().enumerate() {
+                let Some(element) = self.layer_list.layout_tree.get_node_by_id(element_id) else {
+                    log::warn!("Warning: Element {:?} not found in layout tree!", element_id);
+                    continue;
+                };
+                content.push(GeomWithData::new(to_rectangle(element.box_model.margin_box), (paint_index, element_id)));
             }
 
-            let rtree_data: Vec<_> = tile_ids.iter().map(|tile_id| {
-                let tile = self.arena.get(tile_id).unwrap();
-                GeomWithData::new(
-                    rstar::primitives::Rectangle::from_corners(
-                        [tile.rect.x, tile.rect.y],
-                        [tile.rect.x + tile.rect.width, tile.rect.y + tile.rect.height]
-                    ),
-                    *tile_id
-                )
-            }).collect();
-
-            // Add all remaining tiles to the tile layer
-            let tile_layer = TileLayer {
+            self.tiles.insert(*layer_id, TileLayer {
                 layer_id: *layer_id,
-                tiles: tile_ids.clone(),
-                rstar_tree: rstar::RTree::bulk_load(rtree_data),
-            };
-            self.tiles.insert(*layer_id, tile_layer);
+                tiles: Vec::new(),
+                levels: HashMap::new(),
+                content: rstar::RTree::bulk_load(content),
+            });
+        }
+    }
+
+    /// Creates the tiles of the layer at the current zoom level that intersect with the given rect and have any
+    /// content. Tiles that already exist are kept as they are.
+    pub fn ensure_tiles(&mut self, layer_id: LayerId, rect: Rect) {
+        let level = self.zoom_level();
+        let Some(tile_layer) = self.tiles.get(&layer_id) else {
+            return;
+        };
 
-            // Get elements in this layer
-            let Some(layer) = layer_list.get(&layer_id) else {
+        let mut new_tiles = Vec::new();
+        for (cell, tile_rect) in self.cells(level, rect) {
+            if tile_layer.levels.get(&level).is_some_and(|l| l.visited.contains(&cell)) {
                 continue;
-            };
+            }
+            new_tiles.push((cell, tile_rect, self.tiled_elements(tile_layer, tile_rect)));
+        }
+
+        for (cell, tile_rect, elements) in new_tiles {
+            let tile_id = if elements.is_empty() { None } else { Some(self.next_node_id()) };
+            let tile_layer = self.tiles.get_mut(&layer_id).expect("Tile layer disappeared");
+            let tile_level = tile_layer.levels.entry(level).or_default();
+            tile_level.visited.insert(cell);
 
-            let Some(tile_layer) = self.tiles.get(&layer_id) else {
+            let Some(tile_id) = tile_id else {
                 continue;
             };
+            tile_level.cells.insert(cell, tile_id);
+            tile_level.rstar_tree.insert(GeomWithData::new(to_rectangle(tile_rect), tile_id));
+            tile_layer.tiles.push(tile_id);
 
-            // iterate each element in the layer
-            for &element_id in &layer.elements {
-                // Get element
-                let Some(element) = self.layer_list.layout_tree.get_node_by_id(element_id) else {
-                    log::warn!("Warning: Element {:?} not found in layout tree!", element_id);
-                    continue;
-                };
-                let margin_box = element.box_model.margin_box;
-
-                // Find all tile_ids that contain this element
-                let matching_tile_ids = tile_layer.intersects_with(margin_box);
-                for tile_id in &matching_tile_ids {
-                    let tile = self.arena.get_mut(&tile_id).unwrap();
-
-                    let position = Coordinate::new(
-                        tile.rect.x.max(margin_box.x) - margin_box.x,
-                        tile.rect.y.max(margin_box.y) - margin_box.y
-                    );
-
-                    let dimension = Rect::new(
-                        margin_box.x.max(tile.rect.x) - tile.rect.x,
-                        margin_box.y.max(tile.rect.y) - tile.rect.y,
-                        (tile.rect.x + tile.rect.width).min(margin_box.x + margin_box.width) - tile.rect.x.max(margin_box.x),
-                        (tile.rect.y + tile.rect.height).min(margin_box.y + margin_box.height) - tile.rect.y.max(margin_box.y),
-                    );
-
-                    let tiled_element = TiledLayoutElement {
-                        id: element_id,
-                        rect: dimension,
-                        position,
-                        paint_commands: vec![],
-                    };
-
-                    tile.elements.push(tiled_element);
-                }
+            for element in &elements {
+                self.element_tiles.entry(element.id).or_default().push(tile_id);
             }
+
+            self.arena.insert(tile_id, Tile {
+                id: tile_id,
+                layer_id,
+                state: TileState::Dirty,
+                elements,
+                texture_id: None,
+                rect: tile_rect,
+                level,
+                scale: self.device_pixel_ratio * level_scale(level),
+            });
         }
     }
 
+    /// Creates the tiles of the layer that are visible in the viewport, or are at most one tile away from it, so
+    /// the tiles are ready before they scroll into view.
+    pub fn ensure_visible_tiles(&mut self, layer_id: LayerId, viewport: Rect) {
+        let Some(visible) = self.layer_list.layer_properties(layer_id).visible_rect(viewport) else {
+            return;
+        };
+
+        let margin = Dimension::new(
+            self.default_tile_dimension.width / level_scale(self.zoom_level()),
+            self.default_tile_dimension.height / level_scale(self.zoom_level()),
+        );
+        let rect = Rect::new(
+            visible.x - margin.width,
+            visible.y - margin.height,
+            visible.width + margin.width * 2.0,
+            visible.height + margin.height * 2.0,
+        );
+        self.ensure_tiles(layer_id, rect);
+    }
+
+    /// Returns the (partial) elements of the layer that are found in the given tile rect
+    fn tiled_elements(&self, tile_layer: &TileLayer, tile_rect: Rect) -> Vec<TiledLayoutElement> {
+        let mut elements: Vec<(usize, LayoutElementId, Rect)> = tile_layer.content
+            .locate_in_envelope_intersecting(&to_aabb(tile_rect))
+            .filter_map(|item| {
+                let (paint_index, element_id) = item.data;
+                let element = self.layer_list.layout_tree.get_node_by_id(element_id)?;
+                Some((paint_index, element_id, element.box_model.margin_box))
+            })
+            .collect();
+
+        // Elements must stay in the painting order of the layer
+        elements.sort_by_key(|(paint_index, _, _)| *paint_index);
+
+        elements.into_iter()
+            .filter_map(|(_, element_id, margin_box)| {
+                // Elements that only touch the edge of the tile are not part of it
+                let visible = margin_box.intersection(&tile_rect)?;
+                Some(TiledLayoutElement {
+                    id: element_id,
+                    rect: Rect::new(visible.x - tile_rect.x, visible.y - tile_rect.y, visible.width, visible.height),
+                    position: Coordinate::new(visible.x - margin_box.x, visible.y - margin_box.y),
+                    paint_commands: vec![],
+                })
+            })
+            .collect()
+    }
+
     pub fn print_list(&self) {
         println!("Generated tilelist:");
         for (layer_id, tile_layer) in self.tiles.iter() {
@@ -366,3 +784,328 @@ impl TileList {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::style::{StyleProperty, StylePropertyList, StyleValue, Unit};
+    use crate::layouter::taffy::TaffyLayouter;
+    use crate::layouter::CanLayout;
+    use crate::rendertree_builder::RenderTree;
+    use crate::painter::commands::brush::Brush;
+    use crate::painter::commands::color::Color;
+    use crate::painter::commands::rectangle::Rectangle;
+
+    /// Lays out a 1000x3000 page with a 50x50 element that has its own layer
+    pub(crate) fn tile_list() -> TileList {
+        let styles = |props: &[(StyleProperty, StyleValue)]| {
+            let mut styles = StylePropertyList::new();
+            for (prop, value) in props {
+                styles.set_property(prop.clone(), value.clone());
+            }
+            styles
+        };
+        let px = |v: f32| StyleValue::Unit(v, Unit::Px);
+
+        let mut doc = Document::new("https://example.com");
+        let root_id = doc.new_element(None, "DocumentRoot", None, false, None);
+        let page_id = doc.new_element(Some(root_id), "div", None, false, Some(styles(&[
+            (StyleProperty::Width, px(1000.0)),
+            (StyleProperty::Height, px(3000.0)),
+        ])));
+        doc.add_child(root_id, page_id);
+        let box_id = doc.new_element(Some(page_id), "div", None, false, Some(styles(&[
+            (StyleProperty::Width, px(50.0)),
+            (StyleProperty::Height, px(50.0)),
+            (StyleProperty::WillChange, StyleValue::Keyword("transform".to_string())),
+        ])));
+        doc.add_child(page_id, box_id);
+        doc.set_root(root_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+        let layout_tree = TaffyLayouter::new().layout(render_tree, Some(Dimension::new(1000.0, 800.0)));
+
+        let mut tile_list = TileList::new(LayerList::new(layout_tree), Dimension::new(256.0, 256.0));
+        tile_list.generate();
+        tile_list
+    }
+
+    #[test]
+    fn test_lazy_tiles() {
+        let mut tile_list = tile_list();
+        assert!(tile_list.arena.is_empty());
+
+        let layer_ids = tile_list.layer_list.ordered_layer_ids();
+        assert_eq!(layer_ids.len(), 2);
+
+        // Only the tiles in (and near) the viewport are created
+        let viewport = Rect::new(0.0, 0.0, 1000.0, 800.0);
+        tile_list.ensure_visible_tiles(layer_ids[0], viewport);
+        assert_eq!(tile_list.tiles[&layer_ids[0]].tiles.len(), 4 * 5);
+        assert_eq!(tile_list.get_visible_tiles(layer_ids[0], viewport).len(), 4 * 4);
+
+        // Tiles that already exist are not created again
+        tile_list.ensure_visible_tiles(layer_ids[0], viewport);
+        assert_eq!(tile_list.tiles[&layer_ids[0]].tiles.len(), 4 * 5);
+
+        // The layer with the small element only gets a single tile
+        tile_list.ensure_visible_tiles(layer_ids[1], viewport);
+        let tile_ids = tile_list.get_visible_tiles(layer_ids[1], viewport);
+        assert_eq!(tile_ids.len(), 1);
+        let tile = tile_list.get_tile(tile_ids[0]).unwrap();
+        assert_eq!(tile.elements.len(), 1);
+        assert_eq!(tile.elements[0].rect, Rect::new(0.0, 0.0, 50.0, 50.0));
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut tile_list = tile_list();
+        let layer_ids = tile_list.layer_list.ordered_layer_ids();
+        let viewport = Rect::new(0.0, 0.0, 1000.0, 800.0);
+        for layer_id in &layer_ids {
+            tile_list.ensure_visible_tiles(*layer_id, viewport);
+        }
+        for tile in tile_list.arena.values_mut() {
+            tile.state = TileState::Clean;
+        }
+        let dirty = |tile_list: &TileList| tile_list.arena.values().filter(|t| t.state == TileState::Dirty).count();
+
+        // Only the tiles that overlap with the rect, not the ones touching it
+        tile_list.invalidate_rect(layer_ids[0], Rect::new(0.0, 0.0, 300.0, 256.0));
+        assert_eq!(dirty(&tile_list), 2);
+
+        // The element with its own layer only dirties the tile of that layer
+        let element_id = tile_list.layer_list.layers.read().unwrap()[&layer_ids[1]].elements[0];
+        tile_list.invalidate_element(element_id);
+        assert_eq!(dirty(&tile_list), 3);
+    }
+
+    #[test]
+    fn test_element_index() {
+        let mut tile_list = tile_list();
+        let layer_ids = tile_list.layer_list.ordered_layer_ids();
+        let viewport = Rect::new(0.0, 0.0, 1000.0, 800.0);
+        for zoom in [1.0, 2.0] {
+            tile_list.set_zoom(zoom);
+            for layer_id in &layer_ids {
+                tile_list.ensure_visible_tiles(*layer_id, viewport);
+            }
+        }
+
+        // The index matches the elements found in the tiles
+        let element_ids: HashSet<LayoutElementId> = tile_list.arena.values()
+            .flat_map(|tile| tile.elements.iter().map(|e| e.id))
+            .collect();
+        for element_id in element_ids {
+            let mut expected: Vec<TileId> = tile_list.arena.values()
+                .filter(|tile| tile.elements.iter().any(|e| e.id == element_id))
+                .map(|tile| tile.id)
+                .collect();
+            let mut found = tile_list.get_tiles_for_element(element_id);
+            expected.sort_by_key(|id| id.0);
+            found.sort_by_key(|id| id.0);
+            assert_eq!(found, expected);
+        }
+
+        // The element with its own layer is on top, but does not include its right edge
+        let element_id = tile_list.layer_list.layers.read().unwrap()[&layer_ids[1]].elements[0];
+        assert_eq!(tile_list.layer_list.get_layer_for_element(element_id), Some(layer_ids[1]));
+        assert_eq!(tile_list.get_tiles_for_element(element_id).len(), 2);
+        assert_eq!(tile_list.find_element_at(10.0, 10.0), Some(element_id));
+        let below = tile_list.find_element_at(50.0, 10.0).unwrap();
+        assert_eq!(tile_list.layer_list.get_layer_for_element(below), Some(layer_ids[0]));
+
+        // Generating the tiles again starts with an empty index
+        tile_list.generate();
+        assert!(tile_list.get_tiles_for_element(element_id).is_empty());
+    }
+
+    #[test]
+    fn test_rasterize_tile() {
+        let mut tile_list = tile_list();
+        let layer_ids = tile_list.layer_list.ordered_layer_ids();
+        let viewport = Rect::new(0.0, 0.0, 1000.0, 800.0);
+        tile_list.ensure_visible_tiles(layer_ids[0], viewport);
+        let tile_ids = tile_list.get_visible_tiles(layer_ids[0], viewport);
+
+        // Two tiles get the same command relative to the tile, the third one only a transparent background
+        let color = Color::from_rgb8(12, 34, 56);
+        for (idx, tile_id) in tile_ids[..3].iter().enumerate() {
+            let tile = tile_list.get_tile_mut(*tile_id).unwrap();
+            let (brush, rect) = match idx {
+                2 => (Brush::solid(Color::TRANSPARENT), tile.rect),
+                _ => (Brush::solid(color.clone()), Rect::new(tile.rect.x + 10.0, tile.rect.y + 10.0, 20.0, 20.0)),
+            };
+            tile.elements[0].paint_commands = vec![PaintCommand::rectangle(Rectangle::new(rect).with_background(brush))];
+        }
+
+        let mut rasterized = 0;
+        let mut rasterize = |_: &Tile| {
+            rasterized += 1;
+            get_texture_store().write().unwrap().add(1, 1, vec![0; 4])
+        };
+        for tile_id in &tile_ids[..3] {
+            tile_list.rasterize_tile(*tile_id, &mut rasterize);
+        }
+
+        assert_eq!(rasterized, 1);
+        let tiles: Vec<&Tile> = tile_ids[..3].iter().map(|id| tile_list.get_tile(*id).unwrap()).collect();
+        assert_eq!(tiles[0].state, TileState::Clean);
+        assert_eq!(tiles[0].texture_id, tiles[1].texture_id);
+        assert_eq!(tiles[2].state, TileState::Empty);
+        assert_eq!(tiles[2].texture_id, None);
+
+        // Empty tiles are not composited
+        let composited = tile_list.get_composite_tiles(layer_ids[0], viewport);
+        assert_eq!(composited.len(), 2);
+        assert!(!composited.contains(&tile_ids[2]));
+    }
+
+    #[test]
+    fn test_rasterize_dirty_tile() {
+        let mut tile_list = tile_list();
+        let layer_id = tile_list.layer_list.ordered_layer_ids()[0];
+        let viewport = Rect::new(0.0, 0.0, 1000.0, 800.0);
+        tile_list.ensure_visible_tiles(layer_id, viewport);
+        let tile_id = tile_list.get_visible_tiles(layer_id, viewport)[0];
+
+        // The texture store is shared with other tests, so we only look at the textures created here
+        let mut created = vec![];
+        for idx in 0..3u8 {
+            let tile = tile_list.get_tile_mut(tile_id).unwrap();
+            let rect = Rect::new(tile.rect.x, tile.rect.y, 20.0, 20.0);
+            let brush = Brush::solid(Color::from_rgb8(idx, 200, 100));
+            tile.elements[0].paint_commands = vec![PaintCommand::rectangle(Rectangle::new(rect).with_background(brush))];
+            tile_list.invalidate_tile(tile_id);
+
+            tile_list.rasterize_tile(tile_id, |_| {
+                let texture_id = get_texture_store().write().unwrap().add(1, 1, vec![0; 4]);
+                created.push(texture_id);
+                texture_id
+            });
+        }
+
+        // Only the texture of the last content is kept
+        let store = get_texture_store().read().unwrap();
+        let stored: Vec<TextureId> = created.iter().copied().filter(|id| store.has(*id)).collect();
+        assert_eq!(created.len(), 3);
+        assert_eq!(stored, vec![created[2]]);
+        assert_eq!(tile_list.get_tile(tile_id).unwrap().texture_id, Some(created[2]));
+        drop(store);
+
+        // A tile that becomes empty releases its texture as well
+        let tile = tile_list.get_tile_mut(tile_id).unwrap();
+        tile.elements.iter_mut().for_each(|element| element.paint_commands.clear());
+        tile_list.rasterize_tile(tile_id, |_| unreachable!());
+        assert_eq!(tile_list.get_tile(tile_id).unwrap().state, TileState::Empty);
+        assert!(!get_texture_store().read().unwrap().has(created[2]));
+    }
+
+    #[test]
+    fn test_zoom_levels() {
+        assert_eq!(zoom_level(1.0), 0);
+        assert_eq!(zoom_level(1.25), 1);
+        assert_eq!(zoom_level(2.0), 1);
+        assert_eq!(zoom_level(0.5), -1);
+        assert_eq!(zoom_level(0.3), -1);
+
+        let mut tile_list = tile_list();
+        let layer_id = tile_list.layer_list.ordered_layer_ids()[0];
+        let viewport = Rect::new(0.0, 0.0, 500.0, 400.0);
+
+        tile_list.ensure_visible_tiles(layer_id, viewport);
+        let tile_ids = tile_list.get_visible_tiles(layer_id, viewport);
+        for tile_id in &tile_ids {
+            tile_list.get_tile_mut(*tile_id).unwrap().texture_id = Some(TextureId::new(1));
+        }
+
+        // Zoomed in, tiles cover half the CSS pixels at twice the resolution
+        tile_list.set_zoom(2.0);
+        let viewport = Rect::new(0.0, 0.0, 250.0, 200.0);
+        tile_list.ensure_visible_tiles(layer_id, viewport);
+        let sharp_ids = tile_list.get_visible_tiles(layer_id, viewport);
+        assert_eq!(sharp_ids.len(), 4);
+        let sharp = tile_list.get_tile(sharp_ids[0]).unwrap();
+        assert_eq!((sharp.rect.width, sharp.rect.height), (128.0, 128.0));
+        assert_eq!(sharp.scale, 2.0);
+        assert_eq!(sharp.texture_dimension(), Dimension::new(256.0, 256.0));
+
+        // Until the sharp tiles are rendered, the compositor uses the tiles of zoom level 0
+        let composite = tile_list.get_composite_tiles(layer_id, viewport);
+        assert_eq!(composite.len(), 1);
+        let fallback = tile_list.get_tile(composite[0]).unwrap();
+        assert_eq!((fallback.level, fallback.rect), (0, Rect::new(0.0, 0.0, 256.0, 256.0)));
+
+        for tile_id in &sharp_ids {
+            tile_list.get_tile_mut(*tile_id).unwrap().texture_id = Some(TextureId::new(2));
+        }
+        assert_eq!(tile_list.get_composite_tiles(layer_id, viewport), sharp_ids);
+    }
+
+    #[test]
+    fn test_evict_zoom_levels() {
+        let mut tile_list = tile_list();
+        let layer_id = tile_list.layer_list.ordered_layer_ids()[0];
+        let viewport = Rect::new(0.0, 0.0, 250.0, 200.0);
+
+        let render_level = |tile_list: &mut TileList, zoom: f64| {
+            tile_list.set_zoom(zoom);
+            tile_list.ensure_visible_tiles(layer_id, viewport);
+            let tile_ids = tile_list.get_visible_tiles(layer_id, viewport);
+            let texture_id = get_texture_store().write().unwrap().add(1, 1, vec![0; 4]);
+            for tile_id in &tile_ids {
+                tile_list.get_tile_mut(*tile_id).unwrap().texture_id = Some(texture_id);
+            }
+            (tile_ids, texture_id)
+        };
+        let levels = |tile_list: &TileList| {
+            let mut levels: Vec<i32> = tile_list.arena.values().map(|tile| tile.level).collect();
+            levels.sort();
+            levels.dedup();
+            levels
+        };
+
+        let (_, level0_texture) = render_level(&mut tile_list, 1.0);
+        render_level(&mut tile_list, 2.0);
+        assert_eq!(levels(&tile_list), vec![0, 1]);
+
+        // Zooming again removes the oldest level, the previous level is kept as fallback
+        tile_list.set_zoom(4.0);
+        tile_list.ensure_visible_tiles(layer_id, viewport);
+        assert_eq!(levels(&tile_list), vec![1, 2]);
+        assert!(!get_texture_store().read().unwrap().has(level0_texture));
+
+        // The fallback is kept until the visible tiles of the current level are rendered
+        tile_list.evict_fallback_level(&[layer_id], viewport);
+        assert_eq!(levels(&tile_list), vec![1, 2]);
+
+        let (tile_ids, _) = render_level(&mut tile_list, 4.0);
+        tile_list.evict_fallback_level(&[layer_id], viewport);
+        assert_eq!(levels(&tile_list), vec![2]);
+        assert_eq!(tile_list.get_composite_tiles(layer_id, viewport), tile_ids);
+        assert!(tile_list.get_tiles_for_element(tile_list.get_tile(tile_ids[0]).unwrap().elements[0].id)
+            .iter().all(|tile_id| tile_list.get_tile(*tile_id).is_some()));
+    }
+
+    #[test]
+    fn test_tile_device_pixels() {
+        let mut tile = Tile {
+            id: TileId::new(1),
+            layer_id: LayerId::new(0),
+            elements: vec![],
+            texture_id: None,
+            state: TileState::Dirty,
+            rect: Rect::new(256.0, 512.0, 256.0, 256.0),
+            level: 0,
+            scale: 1.0,
+        };
+        assert_eq!(tile.texture_dimension(), Dimension::new(256.0, 256.0));
+        assert_eq!((tile.device_position().x, tile.device_position().y), (256.0, 512.0));
+
+        tile.scale = 1.5;
+        assert_eq!(tile.texture_dimension(), Dimension::new(384.0, 384.0));
+        assert_eq!((tile.device_position().x, tile.device_position().y), (384.0, 768.0));
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::{Duration, Instant};
+use crate::common::geo::{Coordinate, Rect};
+use crate::layering::layer::LayerId;
+use crate::tiler::{TileId, TileList, TileState};
+
+/// Default margin in CSS pixels around the viewport of which tiles are prefetched
+pub const DEFAULT_PREFETCH_MARGIN: f64 = 256.0;
+
+/// A dirty tile that needs to be painted and rasterized
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileTask {
+    /// Layer on which the tile lives
+    pub layer_id: LayerId,
+    /// Tile to paint and rasterize
+    pub tile_id: TileId,
+    /// True when the tile is (partially) visible in the viewport, false when it is prefetched
+    pub visible: bool,
+}
+
+/// Decides which dirty tiles are painted and rasterized, and in which order. Visible tiles come first, closest to the
+/// center of the viewport first. Then the offscreen tiles within the prefetch margin, with the tiles in the direction
+/// the user is scrolling to first.
+#[derive(Clone, Debug)]
+pub struct TileScheduler {
+    /// Margin in CSS pixels around the viewport of which tiles are prefetched
+    prefetch_margin: f64,
+    /// Time that can be spent on tiles in a single frame. When None, all tiles are processed.
+    frame_budget: Option<Duration>,
+    /// Viewport of the previous schedule, used to find the scroll direction
+    last_viewport: Option<Rect>,
+    /// Direction of the last scroll movement
+    direction: Coordinate,
+}
+
+impl Default for TileScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TileScheduler {
+    pub fn new() -> Self {
+        Self {
+            prefetch_margin: DEFAULT_PREFETCH_MARGIN,
+            frame_budget: None,
+            last_viewport: None,
+            direction: Coordinate::new(0.0, 0.0),
+        }
+    }
+
+    /// Sets the margin in CSS pixels around the viewport of which tiles are prefetched. A margin of 0 disables
+    /// prefetching.
+    pub fn with_prefetch_margin(mut self, margin: f64) -> Self {
+        self.prefetch_margin = margin.max(0.0);
+        self
+    }
+
+    /// Sets the time that can be spent on tiles in a single frame
+    pub fn with_frame_budget(mut self, budget: Duration) -> Self {
+        self.frame_budget = Some(budget);
+        self
+    }
+
+    /// Creates the tiles of the layers around the viewport and returns the dirty ones in the order they should be
+    /// painted and rasterized. The tiles of the previous zoom level are dropped once the tiles that replace them
+    /// have been rendered in an earlier frame.
+    pub fn schedule(&mut self, tile_list: &mut TileList, layer_ids: &[LayerId], viewport: Rect) -> Vec<TileTask> {
+        tile_list.evict_fallback_level(layer_ids, viewport);
+
+        if let Some(last) = self.last_viewport {
+            let (dx, dy) = (viewport.x - last.x, viewport.y - last.y);
+            // When the viewport does not move, we keep prefetching in the direction of the last scroll
+            if dx != 0.0 || dy != 0.0 {
+                self.direction = Coordinate::new(dx, dy);
+            }
+        }
+        self.last_viewport = Some(viewport);
+
+        let prefetch_viewport = Rect::new(
+            viewport.x - self.prefetch_margin,
+            viewport.y - self.prefetch_margin,
+            viewport.width + self.prefetch_margin * 2.0,
+            viewport.height + self.prefetch_margin * 2.0,
+        );
+
+        let mut tasks = Vec::new();
+        for &layer_id in layer_ids {
+            let properties = tile_list.layer_list.layer_properties(layer_id);
+            let Some(visible_rect) = properties.visible_rect(viewport) else {
+                continue;
+            };
+            let Some(prefetch_rect) = properties.visible_rect(prefetch_viewport) else {
+                continue;
+            };
+
+            tile_list.ensure_tiles(layer_id, prefetch_rect);
+
+            for tile_id in tile_list.get_intersecting_tiles(layer_id, prefetch_rect) {
+                let Some(tile) = tile_list.get_tile(tile_id) else {
+                    log::warn!("Tile not found: {:?}", tile_id);
+                    continue;
+                };
+                if tile.state != TileState::Dirty {
+                    continue;
+                }
+
+                let visible = tile.rect.intersection(&visible_rect).is_some();
+                let (priority, distance) = if visible {
+                    (0, center_distance(tile.rect, visible_rect))
+                } else {
+                    let ahead = self.is_ahead(tile.rect, visible_rect);
+                    (if ahead { 1 } else { 2 }, gap_distance(tile.rect, visible_rect))
+                };
+                tasks.push((priority, distance, TileTask { layer_id, tile_id, visible }));
+            }
+        }
+
+        tasks.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        tasks.into_iter().map(|(_, _, task)| task).collect()
+    }
+
+    /// Runs the work for the tasks in order until the frame budget is spent, and returns the tasks that are left for
+    /// the next frame. At least one task is run, so every frame makes progress.
+    pub fn run<F: FnMut(&TileTask)>(&self, tasks: Vec<TileTask>, mut work: F) -> Vec<TileTask> {
+        let start = Instant::now();
+        for (idx, task) in tasks.iter().enumerate() {
+            if idx > 0 && self.frame_budget.is_some_and(|budget| start.elapsed() >= budget) {
+                return tasks[idx..].to_vec();
+            }
+            work(task);
+        }
+        vec![]
+    }
+
+    /// Returns true when the tile lies in the direction the user is scrolling to
+    fn is_ahead(&self, tile_rect: Rect, visible_rect: Rect) -> bool {
+        let (tx, ty) = center(tile_rect);
+        let (vx, vy) = center(visible_rect);
+        (tx - vx) * self.direction.x + (ty - vy) * self.direction.y > 0.0
+    }
+}
+
+fn center(rect: Rect) -> (f64, f64) {
+    (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0)
+}
+
+/// Distance between the centers of both rects
+fn center_distance(a: Rect, b: Rect) -> f64 {
+    let (ax, ay) = center(a);
+    let (bx, by) = center(b);
+    (ax - bx).hypot(ay - by)
+}
+
+/// Distance between the edges of both rects, 0 when they overlap or touch
+fn gap_distance(a: Rect, b: Rect) -> f64 {
+    let dx = (b.x - (a.x + a.width)).max(a.x - (b.x + b.width)).max(0.0);
+    let dy = (b.y - (a.y + a.height)).max(a.y - (b.y + b.height)).max(0.0);
+    dx.hypot(dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiler::tests::tile_list;
+
+    #[test]
+    fn test_schedule_order() {
+        let mut tile_list = tile_list();
+        let layer_ids = tile_list.layer_list.ordered_layer_ids();
+        let mut scheduler = TileScheduler::new().with_prefetch_margin(256.0);
+
+        let viewport = Rect::new(0.0, 512.0, 1000.0, 512.0);
+        let tasks = scheduler.schedule(&mut tile_list, &layer_ids[..1], viewport);
+
+        // 4 columns of visible tiles, followed by a row of tiles above and below the viewport
+        assert_eq!(tasks.len(), 4 * 4);
+        assert!(tasks[..8].iter().all(|t| t.visible));
+        assert!(tasks[8..].iter().all(|t| !t.visible));
+
+        // Closest to the center of the viewport first
+        let first = tile_list.get_tile(tasks[0].tile_id).unwrap();
+        assert!(first.rect.x == 256.0 || first.rect.x == 512.0);
+
+        // Scrolling down prefetches the tiles below the viewport first
+        let viewport = Rect::new(0.0, 768.0, 1000.0, 512.0);
+        let tasks = scheduler.schedule(&mut tile_list, &layer_ids[..1], viewport);
+        let prefetch = tasks.iter().find(|t| !t.visible).unwrap();
+        assert!(tile_list.get_tile(prefetch.tile_id).unwrap().rect.y >= 1280.0);
+
+        // Scrolling back up prefetches the tiles above the viewport first
+        let viewport = Rect::new(0.0, 512.0, 1000.0, 512.0);
+        let tasks = scheduler.schedule(&mut tile_list, &layer_ids[..1], viewport);
+        let prefetch = tasks.iter().find(|t| !t.visible).unwrap();
+        assert!(tile_list.get_tile(prefetch.tile_id).unwrap().rect.y < 512.0);
+    }
+
+    #[test]
+    fn test_frame_budget() {
+        let mut tile_list = tile_list();
+        let layer_ids = tile_list.layer_list.ordered_layer_ids();
+        let viewport = Rect::new(0.0, 0.0, 1000.0, 800.0);
+
+        // Without a budget, everything is processed
+        let mut scheduler = TileScheduler::new();
+        let tasks = scheduler.schedule(&mut tile_list, &layer_ids, viewport);
+        let count = tasks.len();
+        let mut done = 0;
+        assert!(scheduler.run(tasks, |_| done += 1).is_empty());
+        assert_eq!(done, count);
+
+        // An exhausted budget still processes a single tile each frame
+        let mut scheduler = TileScheduler::new().with_frame_budget(Duration::ZERO);
+        let tasks = scheduler.schedule(&mut tile_list, &layer_ids, viewport);
+        let mut done = 0;
+        let left = scheduler.run(tasks, |_| done += 1);
+        assert_eq!(done, 1);
+        assert_eq!(left.len(), count - 1);
+    }
+}
@@ -0,0 +1,61 @@
+//! Compares our layout against the layout of Chromium. Every JSON file in `tests/layout` is a page with the bounding
+//! boxes of its elements, in the format of `soupertoo.py --bbox`. Run with
+//! `cargo test --test layout_regression -- --nocapture` to see the report of each page.
+//!
+//! The score of each page is compared against `tests/layout/baseline.json`, so a layout change fails the test when
+//! a page moves further away from Chromium. When a page improves, raise its score in the baseline.
+//!
+//! `blocks.json` is `blocks.html` written down by hand as `soupertoo.py --bbox` captures it: the computed styles and
+//! the boxes Chromium gives for that markup. `tests/layout/shifted` has the same page with every box moved, which
+//! must fail against the baseline.
+
+use std::collections::HashMap;
+use poc_pipeline::layouter::regression::{run_regression, Thresholds};
+
+const LAYOUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/layout");
+
+fn baseline() -> HashMap<String, f64> {
+    let baseline = std::fs::read_to_string(format!("{}/baseline.json", LAYOUT_DIR)).expect("Failed to read baseline");
+    serde_json::from_str(&baseline).expect("Failed to parse baseline")
+}
+
+/// Runs the regression for every page in the directory, and returns the pages that score below their baseline
+fn regressed_pages(dir: &str, baseline: &HashMap<String, f64>) -> Vec<String> {
+    let mut pages: Vec<_> = std::fs::read_dir(dir)
+        .expect("Failed to read layout regression directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|path| path.file_name().is_some_and(|name| name != "baseline.json"))
+        .collect();
+    pages.sort();
+    assert!(!pages.is_empty(), "No pages found in {}", dir);
+
+    let mut regressed = Vec::new();
+    for page in pages {
+        let name = page.file_name().unwrap().to_string_lossy().to_string();
+        let report = run_regression(page.to_str().unwrap(), Thresholds::default());
+        print!("{}", report);
+
+        match baseline.get(&name) {
+            Some(min_score) if report.score() < *min_score => regressed.push(format!("{} ({:.3} < {:.3})", name, report.score(), min_score)),
+            Some(min_score) if report.score() > *min_score => println!("{} improved, its baseline can be raised to {:.3}", name, report.score()),
+            Some(_) => {}
+            None => println!("{} has no baseline yet, add it with a score of {:.3}", name, report.score()),
+        }
+    }
+
+    regressed
+}
+
+#[test]
+fn layout_regression() {
+    let regressed = regressed_pages(LAYOUT_DIR, &baseline());
+    assert!(regressed.is_empty(), "Layout regressed for: {:?}", regressed);
+}
+
+#[test]
+fn shifted_layout_fails() {
+    let regressed = regressed_pages(&format!("{}/shifted", LAYOUT_DIR), &baseline());
+    assert_eq!(regressed.len(), 1, "The shifted page must score below the baseline");
+    assert!(regressed[0].starts_with("blocks.json"));
+}
@@ -5,13 +5,21 @@ from bs4 import BeautifulSoup, Comment
 from playwright.async_api import async_playwright
 
 
-async def fetch_and_parse_html(url):
-    """Fetch the HTML page, resolve styles, and return its JSON DOM structure."""
+VIEWPORT = {"width": 1280, "height": 1144}
+
+
+async def fetch_and_parse_html(url, with_bbox=False):
+    """Fetch the HTML page, resolve styles, and return its JSON DOM structure. When with_bbox is set, each
+    rendered element also gets its bounding box (in page coordinates) as computed by the browser.
+
+    Styles from @media rules are not part of the computed styles. Instead, each element gets the media styles
+    that apply to it, so the viewport of the renderer decides which of them are used. Each element also gets the
+    specificity and source order of the declarations it has itself, so the media styles can be cascaded with them."""
     async with async_playwright() as p:
         browser = await p.chromium.launch(headless=True)  # Run headless browser
         page = await browser.new_page()
 
-        await page.set_viewport_size({"width": 1280, "height": 1144})
+        await page.set_viewport_size(VIEWPORT)
         await page.goto(url, wait_until="domcontentloaded")
 
         # Get the fully rendered HTML
@@ -19,7 +27,21 @@ async def fetch_and_parse_html(url):
 
         # Extract computed styles for each node
         computed_styles_script = """
-        (function() {
+        (function(withBbox) {
+            function getBbox(element) {
+                // Elements that are not rendered (display: none) have no boxes at all
+                if (element.getClientRects().length === 0) {
+                    return null;
+                }
+                let rect = element.getBoundingClientRect();
+                return {
+                    x: rect.x + window.scrollX,
+                    y: rect.y + window.scrollY,
+                    width: rect.width,
+                    height: rect.height
+                };
+            }
+
             function getStyles(element) {
                 let computedStyle = window.getComputedStyle(element);
                 let styles = {};
@@ -30,6 +52,266 @@ async def fetch_and_parse_html(url):
                 return styles;
             }
 
+            // Returns the declarations of all @page rules without a page selector, later rules win
+            function collectPageStyles() {
+                let styles = {};
+                for (let sheet of document.styleSheets) {
+                    try {
+                        for (let rule of sheet.cssRules) {
+                            if (rule instanceof CSSPageRule && !rule.selectorText) {
+                                for (let i = 0; i < rule.style.length; i++) {
+                                    let prop = rule.style[i];
+                                    styles[prop] = rule.style.getPropertyValue(prop);
+                                }
+                            }
+                        }
+                    } catch (e) {
+                        // Cross-origin stylesheets cannot be read
+                    }
+                }
+                return styles;
+            }
+
+            // Returns the keyframes of all @keyframes rules by animation name, with offsets from 0.0 to 1.0
+            function collectKeyframes() {
+                let keyframes = {};
+
+                function walk(ruleList) {
+                    for (let rule of ruleList) {
+                        if (rule instanceof CSSMediaRule) {
+                            walk(rule.cssRules);
+                        } else if (rule instanceof CSSKeyframesRule) {
+                            let frames = [];
+                            for (let keyframe of rule.cssRules) {
+                                let styles = {};
+                                for (let i = 0; i < keyframe.style.length; i++) {
+                                    let prop = keyframe.style[i];
+                                    styles[prop] = keyframe.style.getPropertyValue(prop);
+                                }
+                                // A keyframe can have multiple offsets, like "0%, 50%"
+                                for (let key of keyframe.keyText.split(",")) {
+                                    key = key.trim();
+                                    let offset = key === "from" ? 0 : key === "to" ? 1 : parseFloat(key) / 100;
+                                    frames.push({ offset: offset, styles: styles });
+                                }
+                            }
+                            keyframes[rule.name] = frames;
+                        }
+                    }
+                }
+
+                for (let sheet of document.styleSheets) {
+                    try {
+                        walk(sheet.cssRules);
+                    } catch (e) {
+                        // Cross-origin stylesheets cannot be read
+                    }
+                }
+                return keyframes;
+            }
+
+            // Returns the specificity [ids, classes, types] of a single (complex) selector. Good enough for the
+            // selectors found in practice: :is(), :not() and :has() count their most specific argument, and
+            // :where() counts nothing.
+            function specificity(selector) {
+                let result = [0, 0, 0];
+                let skipName = i => {
+                    while (i < selector.length && /[a-zA-Z0-9_-]/.test(selector[i])) i++;
+                    return i;
+                };
+                let i = 0;
+                while (i < selector.length) {
+                    let c = selector[i];
+                    if (c === "#") {
+                        result[0]++;
+                        i = skipName(i + 1);
+                    } else if (c === ".") {
+                        result[1]++;
+                        i = skipName(i + 1);
+                    } else if (c === "[") {
+                        result[1]++;
+                        let end = selector.indexOf("]", i);
+                        i = end < 0 ? selector.length : end + 1;
+                    } else if (c === ":") {
+                        let pseudoElement = selector[i + 1] === ":";
+                        let start = pseudoElement ? i + 2 : i + 1;
+                        let end = skipName(start);
+                        let name = selector.slice(start, end).toLowerCase();
+                        if (selector[end] === "(") {
+                            let depth = 0;
+                            let close = end;
+                            for (; close < selector.length; close++) {
+                                if (selector[close] === "(") depth++;
+                                if (selector[close] === ")" && --depth === 0) break;
+                            }
+                            if (["is", "not", "has", "matches"].includes(name)) {
+                                let max = maxSpecificity(splitSelectors(selector.slice(end + 1, close)));
+                                result = result.map((count, idx) => count + max[idx]);
+                            } else if (name !== "where") {
+                                result[pseudoElement ? 2 : 1]++;
+                            }
+                            i = close + 1;
+                        } else {
+                            let legacy = ["before", "after", "first-line", "first-letter"].includes(name);
+                            result[pseudoElement || legacy ? 2 : 1]++;
+                            i = end;
+                        }
+                    } else if (/[a-zA-Z0-9_-]/.test(c)) {
+                        result[2]++;
+                        i = skipName(i);
+                    } else {
+                        // Universal selector, combinators and whitespace
+                        i++;
+                    }
+                }
+                return result;
+            }
+
+            function compareSpecificity(a, b) {
+                return a[0] - b[0] || a[1] - b[1] || a[2] - b[2];
+            }
+
+            function maxSpecificity(selectors) {
+                return selectors.map(specificity).reduce((max, s) => compareSpecificity(s, max) > 0 ? s : max, [0, 0, 0]);
+            }
+
+            // Splits a selector list on the commas that are not inside parentheses
+            function splitSelectors(selectorText) {
+                let selectors = [];
+                let depth = 0;
+                let start = 0;
+                for (let i = 0; i < selectorText.length; i++) {
+                    let c = selectorText[i];
+                    if (c === "(") depth++;
+                    if (c === ")") depth--;
+                    if (c === "," && depth === 0) {
+                        selectors.push(selectorText.slice(start, i).trim());
+                        start = i + 1;
+                    }
+                }
+                selectors.push(selectorText.slice(start).trim());
+                return selectors;
+            }
+
+            // Returns the specificity of the most specific selector of the rule that matches the element, or null when
+            // the rule does not match
+            function matchSpecificity(element, rule) {
+                let matching = rule.selectors.filter(selector => {
+                    try {
+                        return element.matches(selector);
+                    } catch (e) {
+                        // Selectors with pseudo-elements cannot be matched against an element
+                        return false;
+                    }
+                });
+                return matching.length > 0 ? maxSpecificity(matching) : null;
+            }
+
+            // Returns all style rules in source order, split into the rules that always apply and the rules that only
+            // apply under a media query. Declarations of a rule are split into normal and important ones, as these
+            // have a different place in the cascade.
+            function collectStyleRules() {
+                let result = { base: [], rules: [], disable: [] };
+                let order = 0;
+
+                function addRule(rule, media) {
+                    order++;
+                    let declarations = { normal: {}, important: {} };
+                    for (let i = 0; i < rule.style.length; i++) {
+                        let prop = rule.style[i];
+                        let priority = rule.style.getPropertyPriority(prop) === "important" ? "important" : "normal";
+                        declarations[priority][prop] = rule.style.getPropertyValue(prop);
+                    }
+                    for (let priority of ["normal", "important"]) {
+                        let styles = declarations[priority];
+                        if (Object.keys(styles).length === 0) {
+                            continue;
+                        }
+                        let entry = {
+                            selectors: splitSelectors(rule.selectorText),
+                            styles: styles,
+                            important: priority === "important",
+                            order: order,
+                        };
+                        if (media) {
+                            entry.media = media;
+                            result.rules.push(entry);
+                        } else {
+                            result.base.push(entry);
+                        }
+                    }
+                }
+
+                function walk(ruleList, media) {
+                    for (let rule of ruleList) {
+                        if (rule instanceof CSSMediaRule) {
+                            let text = media ? media + " and " + rule.media.mediaText : rule.media.mediaText;
+                            result.disable.push(rule.media);
+                            walk(rule.cssRules, text);
+                        } else if (rule instanceof CSSStyleRule) {
+                            addRule(rule, media);
+                        }
+                    }
+                }
+
+                for (let sheet of document.styleSheets) {
+                    let media = sheet.media.mediaText;
+                    try {
+                        walk(sheet.cssRules, media);
+                    } catch (e) {
+                        // Cross-origin stylesheets cannot be read
+                        continue;
+                    }
+                    if (media) {
+                        result.disable.push(sheet.media);
+                    }
+                }
+                return result;
+            }
+
+            function cascadeOrder(rule, specificity) {
+                return { important: rule.important, specificity: specificity, order: rule.order };
+            }
+
+            function isLater(a, b) {
+                return (a.important - b.important) || ((a.inline || false) - (b.inline || false))
+                    || compareSpecificity(a.specificity, b.specificity) || (a.order - b.order);
+            }
+
+            // Returns the cascade order of the declaration that wins for each property the element declares itself,
+            // so the media styles can be placed in the same cascade
+            function getCascade(element) {
+                let cascade = {};
+                let declare = (prop, order) => {
+                    if (!cascade[prop] || isLater(order, cascade[prop]) >= 0) {
+                        cascade[prop] = order;
+                    }
+                };
+                for (let rule of styleRules.base) {
+                    let specificity = matchSpecificity(element, rule);
+                    if (specificity) {
+                        Object.keys(rule.styles).forEach(prop => declare(prop, cascadeOrder(rule, specificity)));
+                    }
+                }
+                for (let i = 0; i < element.style.length; i++) {
+                    let prop = element.style[i];
+                    let important = element.style.getPropertyPriority(prop) === "important";
+                    declare(prop, { important: important, inline: true, specificity: [0, 0, 0], order: 0 });
+                }
+                return cascade;
+            }
+
+            function getMediaStyles(element) {
+                let mediaStyles = [];
+                for (let rule of styleRules.rules) {
+                    let specificity = matchSpecificity(element, rule);
+                    if (specificity) {
+                        mediaStyles.push({ media: rule.media, styles: rule.styles, ...cascadeOrder(rule, specificity) });
+                    }
+                }
+                return mediaStyles;
+            }
+
             function extractDOMTree(element) {
                 if (element.nodeType === Node.COMMENT_NODE) {
                     return { comment: element.nodeValue.trim() };
@@ -47,31 +329,76 @@ async def fetch_and_parse_html(url):
                     }
                 }
 
-                return {
+                let node = {
                     tag: element.tagName.toLowerCase(),
                     self_closing: element.childNodes.length === 0,
                     attributes: Object.fromEntries([...element.attributes].map(attr => [attr.name, attr.value])),
                     styles: getStyles(element),
                     children: children
                 };
+                let mediaStyles = getMediaStyles(element);
+                if (mediaStyles.length > 0) {
+                    node.media_styles = mediaStyles;
+                }
+                node.cascade = getCascade(element);
+                let bbox = boxes.get(element);
+                if (bbox) {
+                    node.bbox = bbox;
+                }
+                return node;
             }
 
-            return extractDOMTree(document.documentElement);
-        })();
+            // Boxes are taken with all media rules active, as this is the layout of the page in the viewport
+            let boxes = new Map();
+            if (withBbox) {
+                for (let element of document.querySelectorAll("*")) {
+                    let bbox = getBbox(element);
+                    if (bbox) {
+                        boxes.set(element, bbox);
+                    }
+                }
+            }
+
+            // Running animations and transitions would end up in the computed styles, so they are cancelled first
+            document.getAnimations().forEach(animation => animation.cancel());
+
+            // Computed styles are taken without any media rules, so they are the base the media styles apply on
+            let styleRules = collectStyleRules();
+            let mediaTexts = styleRules.disable.map(media => media.mediaText);
+            styleRules.disable.forEach(media => media.mediaText = "not all");
+
+            let tree = extractDOMTree(document.documentElement);
+
+            styleRules.disable.forEach((media, i) => media.mediaText = mediaTexts[i]);
+            return { tree: tree, page: collectPageStyles(), keyframes: collectKeyframes() };
+        })
         """
 
-        dom_tree = await page.evaluate(computed_styles_script)
+        result = await page.evaluate(computed_styles_script, with_bbox)
 
         await browser.close()
-        return {"tag": "DocumentRoot", "attributes": {}, "styles": {}, "children": [dom_tree]}
+
+        return {
+            "tag": "DocumentRoot",
+            "attributes": {},
+            "styles": {},
+            "children": [result["tree"]],
+            "viewport": VIEWPORT,
+            "page": result["page"],
+            "keyframes": result["keyframes"],
+        }
 
 
 async def main():
-    if len(sys.argv) != 2:
-        print("Usage: souper.py <url>")
+    args = sys.argv[1:]
+    with_bbox = "--bbox" in args
+    args = [arg for arg in args if arg != "--bbox"]
+
+    if len(args) != 1:
+        print("Usage: souper.py [--bbox] <url>")
         sys.exit(1)
 
-    dom_tree = await fetch_and_parse_html(sys.argv[1])
+    dom_tree = await fetch_and_parse_html(args[0], with_bbox)
 
     # Save to JSON file
     with open("output.json", "w", encoding="utf-8") as f: